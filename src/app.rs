@@ -1,24 +1,81 @@
 use std::time::Instant;
 
 use eframe::egui::{self, Color32, Context, Key, Modifiers, RichText, Sense};
+use rayon::prelude::*;
 use rfd::FileDialog;
 
-use crate::image_io;
-use crate::palette::Palette;
+use crate::batch::{self, BatchFormat, BatchJob, BatchMsg};
+use crate::cameo::{self, CameoOptions};
+use crate::color_match::{self, QuantizeCache};
+use crate::contact_sheet::{self, SheetOptions};
+use crate::filters::{self, Filter, FilterInput};
+use crate::hook;
+use crate::i18n::{self, format_number, tr, Language};
+use crate::image_io::{self, Resample};
+use crate::io_task::{self, IoDone, IoTask, LoadedShp};
+use crate::keymap::{Action, KeyChord};
+use crate::mix::{self, IdScheme, MixFile, NameDb};
+use crate::palette::{self, ColorBlindMode, Palette, PaletteAdjust, PaletteRole, PaletteRoles, PaletteSort};
+use crate::pixel_font::{self, TextFont, TextParams};
+use crate::project::{EditorState, Project};
+use crate::script;
 
-use crate::shp::SHP;
+use crate::selection::{SelectOp, Selection};
+use crate::sequences::{self, Sequence};
+use crate::settings::{CanvasBackground, LastSession, PaletteSwitch, Settings};
+use crate::shp::{Compression, EmptyFrames, LazyShp, PixelScaler, RetargetAnchor, ShadowParams, TweenMode, LAZY_THRESHOLD, SHP};
+use crate::stats::{self, SessionStats};
+use crate::texture_cache::{FrameTexture, PlaybackTextures};
+use crate::undo::{FramePixels, History, Patch};
+use crate::update::{self, ReleaseInfo};
+use crate::watch::FileWatch;
 
 // 内置字体：构建时打包 wqy-microhei.ttc
 const EMBED_WQY_MICROHEI: &[u8] = include_bytes!("../wqy-microhei.ttc");
+// 可导入的图片扩展名，与 image_io::load_rgba_frames 支持的一致
+const IMAGE_EXTS: [&str; 5] = ["png", "jpg", "jpeg", "gif", "apng"];
+// 画笔 HUD 在最后一次调整后保留的时长
+const BRUSH_HUD_TIME: std::time::Duration = std::time::Duration::from_millis(1200);
+// 调色板中“定位索引”闪烁的时长
+const PAL_FLASH_TIME: std::time::Duration = std::time::Duration::from_millis(1500);
+// 用户调色板文件夹在调色板菜单中的分组名
+const USER_PAL_GROUP: &str = "User";
+// 固定调色板的快捷键：数字键 1–9
+const PIN_KEYS: [Key; 9] = [Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5, Key::Num6, Key::Num7, Key::Num8, Key::Num9];
+const MAX_PINNED_KEYS: usize = PIN_KEYS.len();
+// 生成朝向时可选的源帧朝向：北、东、南、西，自北逆时针的方位角
+const FACING_SOURCES: [f32; 4] = [0.0, 270.0, 180.0, 90.0];
+// 调色板下“最近使用”记录的索引个数
+const RECENT_COLORS: usize = 16;
 
 pub struct MixApp {
+    // 后台线程完成任务后用它唤醒界面
+    egui_ctx: Context,
+    // 正在后台进行的打开/保存/解码；期间显示遮罩并阻止编辑
+    io_task: Option<IoTask>,
+    // 只读快速查看模式（--view）：仅播放与切换调色板，不含任何编辑状态
+    pub view_only: bool,
+    // 查看模式下按需解码的超大 SHP（与 shp 互斥）
+    pub lazy_shp: Option<LazyShp>,
     pub palette: Palette,
     pub shp: Option<SHP>,
     // UI state
     pub brush_index: u8,
+    // 副颜色：右键绘制时使用，X 与主颜色互换；draw_index 为当前这一笔实际使用的索引
+    pub brush_index2: u8,
+    draw_index: u8,
+    // 最近绘制用过的索引，最新的在前
+    recent_colors: Vec<u8>,
+    // 画布右键菜单：弹出位置（屏幕）与对应的画布像素；调色板面板中短暂闪烁标出的索引
+    canvas_menu: Option<(egui::Pos2, (i32, i32))>,
+    pal_flash: Option<(u8, Instant)>,
     pub tool: Tool,
     pub scale: f32,
     pub brush_size: u32,
+    pub brush_shape: BrushShape,
+    pub custom_brush: Option<BrushStamp>,
+    // 画笔 HUD 显示截止时间
+    pub brush_hud_until: Option<Instant>,
     // 绘图状态
     pub drawing: bool,
     pub draw_start: Option<egui::Pos2>,
@@ -39,19 +96,281 @@ pub struct MixApp {
     pub import_pos: egui::Pos2,
     pub import_scale: f32,
     pub import_angle_deg: f32,
+    pub import_resample: Resample,
     pub import_armed: bool,
+    pub quant_cache: QuantizeCache,
+    // 高分辨率渲染图：固定前先按整数倍面积平均缩小，可选锐化
+    pub import_downscale: u32,
+    pub import_sharpen: f32,
+    // 没有 alpha 的渲染图：固定前把接近背景色的像素抠成透明
+    pub import_chroma: bool,
+    pub import_chroma_color: [u8; 3],
+    pub import_chroma_tol: u8,
+    // 导入时 alpha 低于阈值的像素丢弃；可选将半透明像素与帧内已有内容混合后再量化
+    pub import_alpha_min: u8,
+    pub import_blend: bool,
+    // 导入 Gizmo 预览：显示量化结果（否则显示原图）；缓存 (参数键, 纹理, 旋转后左上角的前移量)
+    pub import_show_quantized: bool,
+    import_preview: Option<(u64, egui::TextureHandle, (i32, i32))>,
     // grouped palettes by folder
     pub grouped_pals: Vec<(String, Vec<(String, Palette)>)>,
     pub dirty: bool,
     pub show_exit_confirm: bool,
-    // 撤销/重做
-    pub undo_stack: Vec<Vec<u8>>, // 当前帧历史
-    pub redo_stack: Vec<Vec<u8>>, // 当前帧重做
+    // 调色板预览墙：当前帧在所有内置PAL下的缩略图
+    pub show_pal_gallery: bool,
+    pub pal_gallery_cache: Option<(u64, Vec<egui::TextureHandle>)>,
+    // HUD 遮挡区域叠加
+    pub show_safe_area: bool,
+    pub safe_area: SafeArea,
+    // 透视辅助线
+    pub show_guides: bool,
+    pub guides: Guides,
+    // 画布按各帧对齐偏移显示
+    pub show_offsets: bool,
+    // 色盲模拟预览
+    pub color_blind: ColorBlindMode,
+    // 画布纹理缓存（局部更新）
+    pub canvas_tex: Option<FrameTexture>,
+    // 播放时的逐帧纹理缓存，循环播放不再每帧重建纹理；停止播放时释放
+    play_tex: PlaybackTextures,
+    // 替换颜色对话框
+    pub show_replace_dialog: bool,
+    pub replace_from: u8,
+    pub replace_to: u8,
+    pub replace_scope: FrameScope,
+    // 转换到另一调色板：目标调色板、是否只列出用到的索引
+    pub show_pal_convert: bool,
+    pal_convert_target: Option<(String, Palette)>,
+    pal_convert_used_only: bool,
+    // 打开对话框时统计的各索引像素数（所有帧）
+    pal_convert_usage: Vec<usize>,
+    // 生成阴影帧：阴影参数与写入方式
+    pub show_shadow_dialog: bool,
+    pub shadow_params: ShadowParams,
+    // true：把前半帧的阴影写入后半帧；false：为全部帧生成阴影并追加到末尾
+    pub shadow_overwrite: bool,
+    // 调整调色板（色相/饱和度/亮度等）：对话框与当前参数
+    pub show_pal_adjust: bool,
+    pub pal_adjust: PaletteAdjust,
+    // 打开调整对话框时的原调色板：预览基于它计算，取消时恢复
+    pal_adjust_orig: Option<Palette>,
+    // 调色板对比/合并：A 为当前调色板，B 为对比对象；复制用的索引范围
+    pub show_pal_diff: bool,
+    pal_diff_b: Option<(String, Palette)>,
+    pub pal_diff_range: (u8, u8),
+    // 整理调色板顺序：新位置 → 原索引；排序依据、是否保留特殊索引、正在拖动的位置
+    pub show_pal_sort: bool,
+    pal_sort_order: Vec<u8>,
+    pub pal_sort_by: PaletteSort,
+    pub pal_sort_keep_special: bool,
+    pal_sort_drag: Option<usize>,
+    // 画布尺寸：新宽高与内容锚点
+    pub show_canvas_size: bool,
+    pub canvas_new_w: u32,
+    pub canvas_new_h: u32,
+    // 锚点：(列, 行)，0=左/上，1=中，2=右/下
+    pub canvas_anchor: (u8, u8),
+    // 重定画布向导：新尺寸、按美术类型选的对齐方式、内容底边留白
+    pub show_retarget: bool,
+    pub retarget_w: u32,
+    pub retarget_h: u32,
+    pub retarget_anchor: RetargetAnchor,
+    pub retarget_margin: u32,
+    // 缩放全部帧：新宽高、是否保持比例与缩放算法
+    pub show_scale_dialog: bool,
+    pub scale_new_w: u32,
+    pub scale_new_h: u32,
+    pub scale_keep_aspect: bool,
+    pub scaler: PixelScaler,
+    // 补间：在两个选中帧之间插入的帧数与方式
+    pub show_tween_dialog: bool,
+    pub tween_steps: usize,
+    pub tween_mode: TweenMode,
+    // 生成朝向：朝向数、是否处理全部帧、源帧朝向（FACING_SOURCES 下标）、顺时针排列、平滑旋转
+    pub show_facings_dialog: bool,
+    pub facings_count: usize,
+    pub facings_all: bool,
+    pub facings_source: usize,
+    pub facings_clockwise: bool,
+    pub facings_smooth: bool,
+    // 重复帧分析：打开窗口或点“重新分析”时的结果（相同帧分组、共用数据可省的字节数）
+    pub show_dedup: bool,
+    dedup_groups: Vec<Vec<usize>>,
+    dedup_saving: usize,
+    // 描边：颜色索引、内/外侧、是否含对角、作用范围
+    pub show_outline_dialog: bool,
+    pub outline_index: u8,
+    pub outline_outside: bool,
+    pub outline_diagonal: bool,
+    pub outline_scope: FrameScope,
+    // 平移全部帧：位移量与边缘处理（环绕 / 限制在画布内）
+    pub show_shift_dialog: bool,
+    pub shift_dx: i32,
+    pub shift_dy: i32,
+    pub shift_wrap: bool,
+    // 生成侧边栏图标：图标调色板、参数与预览 (参数键, 纹理)
+    pub show_cameo_dialog: bool,
+    cameo_pal: Option<(String, Palette)>,
+    pub cameo_opts: CameoOptions,
+    cameo_preview: Option<(u64, egui::TextureHandle)>,
+    // 滤镜：已注册的滤镜及各自参数、当前选中项、范围；参数每改一次代数加一，预览按 (键, 纹理) 缓存
+    pub show_filter_dialog: bool,
+    filters: Vec<Box<dyn Filter>>,
+    filter_idx: usize,
+    pub filter_scope: FrameScope,
+    filter_gen: u64,
+    filter_preview: Option<(u64, egui::TextureHandle)>,
+    // 颜色用量面板：统计范围（false 当前帧 / true 整个文件）与画布上高亮的索引
+    pub show_color_usage: bool,
+    pub color_usage_all: bool,
+    pub usage_highlight: Option<u8>,
+    // 小地图：放大后画布超出可视区时显示；拖动小地图时下一帧要设置的滚动位置，以及上一帧小地图所在区域
+    pub show_minimap: bool,
+    canvas_scroll_to: Option<egui::Vec2>,
+    minimap_rect: Option<egui::Rect>,
+    // 当前文档关联的调色板组（如 unittem/unitsno/unitdes），快捷键在其间轮换；打开/新建文档时清空
+    doc_pals: Vec<(String, Palette)>,
+    // 与另一个 SHP 对比：对比文档 (文件名, SHP)、缩放、是否叠加差异，以及按帧缓存的 (键, [A, B, 差异] 纹理)
+    pub show_compare: bool,
+    compare_doc: Option<(String, SHP)>,
+    pub compare_zoom: f32,
+    pub compare_overlay: bool,
+    compare_tex: Option<(u64, [egui::TextureHandle; 3])>,
+    // 监视打开的 SHP/PAL 文件（设置中开启）；PAL 路径为最近从文件加载的调色板；有未保存修改时外部改动待确认
+    watch: Option<FileWatch>,
+    pal_path: Option<std::path::PathBuf>,
+    watch_conflict: bool,
+    // 保存前的体积报告：各帧在两种编码下的数据字节数（未压缩, RLE0）
+    pub show_save_report: bool,
+    save_sizes: Vec<(usize, usize)>,
+    // 多帧导入（GIF/APNG/序列图）：读取后等待在对话框中选择与现有帧的对应方式
+    frame_import: Option<FrameImport>,
+    // 命名动画序列：随 SHP 保存到旁注 JSON，选中后限定播放与序列导出的范围
+    sequences: Vec<Sequence>,
+    pub show_sequences: bool,
+    // 文字工具：参数、放置位置（画布像素，文字左上角；None 表示尚未放置）、拖动时指针相对文字左上角的偏移
+    pub text_params: TextParams,
+    text_pos: Option<egui::Pos2>,
+    text_grab: egui::Vec2,
+    text_font_data: Option<Vec<u8>>,
+    text_preview: Option<TextPreview>,
+    // 铅笔/橡皮：当前一笔的落点记录，以及像素完美模式开关
+    stroke: Stroke,
+    pub pixel_perfect: bool,
+    // 数位板压感：窗口系统以触控力度上报笔压时，按笔压缩放铅笔/橡皮大小
+    pub pressure_size: bool,
+    pen_pressure: Option<f32>,
+    // 环绕绘制：越过画布边缘的笔画、形状和填充从对边继续，用于无缝平铺的地形/覆盖物
+    pub wrap_draw: bool,
+    // 填充与魔棒按 8 连通（对角相邻也算连通）
+    pub fill_diagonal: bool,
+    // 填充方式与两色抖动时另一颜色的占比（%）
+    pub fill_pattern: FillPattern,
+    pub fill_ratio: u8,
+    // 3×3 平铺预览窗口及其纹理缓存 (帧内容键, 纹理)
+    pub show_tile_preview: bool,
+    pub tile_zoom: f32,
+    tile_preview: Option<(u64, egui::TextureHandle)>,
+    // 游戏比例预览：整数倍率、CRT 扫描线及强度、纹理缓存 (参数键, 纹理)
+    pub show_game_preview: bool,
+    pub game_zoom: u32,
+    pub game_crt: bool,
+    pub game_crt_strength: f32,
+    game_preview: Option<(u64, egui::TextureHandle)>,
+    // 帧属性检查器窗口
+    pub show_frame_inspector: bool,
+    // 双调色板对比：B 调色板、分割线位置（0–1）、是否并排、缩放倍数，以及 (键, [A, B] 纹理)
+    pub show_pal_split: bool,
+    pal_split_b: Option<(String, Palette)>,
+    pub pal_split_pos: f32,
+    pub pal_split_side: bool,
+    pub pal_split_zoom: u32,
+    pal_split_tex: Option<(u64, [egui::TextureHandle; 2])>,
+    // 从内置调色板菜单选中、等待确认切换方式的调色板
+    pal_switch_pending: Option<(String, Palette)>,
+    pal_switch_remember: bool,
+    // 参考背景图（如地形截图）：显示在精灵后方代替棋盘格，位置以画布像素计
+    pub bg_image: Option<egui::TextureHandle>,
+    pub show_bg_image: bool,
+    pub bg_pos: egui::Vec2,
+    // 系统剪贴板（首次使用时创建；Linux 下需常驻才能继续提供已复制的图像）
+    pub clipboard: Option<arboard::Clipboard>,
+    // 时间轴缩略图：按帧缓存 (内容键, 纹理)，以及多选的帧集合
+    pub thumb_cache: Vec<Option<(u64, egui::TextureHandle)>>,
+    pub selected_frames: std::collections::BTreeSet<usize>,
+    pub timeline_last_frame: usize,
+    // 像素选区（矩形/套索/魔棒），跨帧保留；绘图工具只作用于选区内
+    pub selection: Option<Selection>,
+    lasso_pts: Vec<(f32, f32)>,
+    sel_move: Option<SelMove>,
+    clip_base: Option<Vec<u8>>,
+    pub replace_in_selection: bool,
+    // 撤销/重做：像素、偏移、调色板与文档结构的补丁历史
+    pub history: History,
     pub max_undo_steps: usize,
-    // 撤销历史所属的帧锚点：当当前帧变化时清空历史，避免跨帧污染
-    pub undo_frame_anchor: Option<usize>,
+    // 持久化设置（快捷键等）
+    pub settings: Settings,
+    pub show_keymap_window: bool,
+    pub keymap_capture: Option<Action>,
+    // 导出序列的文件名前缀（允许中文）
+    pub show_delta_export: bool,
+    pub export_prefix: String,
+    // 联系表导出：窗口开关与排版参数
+    pub show_contact_sheet: bool,
+    pub contact_sheet: SheetOptions,
+    // 残影叠加导出：首/末帧不透明度与取帧间隔
+    pub show_ghost_export: bool,
+    pub ghost_first_alpha: f32,
+    pub ghost_last_alpha: f32,
+    pub ghost_step: usize,
+    // 批量转换：输入/输出文件夹、调色板与格式；后台任务的通道、取消标记、进度 (已处理, 总数, 当前文件) 与日志
+    pub show_batch: bool,
+    batch_input: Option<std::path::PathBuf>,
+    batch_output: Option<std::path::PathBuf>,
+    batch_pal: Option<(String, Palette)>,
+    batch_format: BatchFormat,
+    batch_rx: Option<std::sync::mpsc::Receiver<BatchMsg>>,
+    batch_cancel: Option<std::sync::Arc<std::sync::atomic::AtomicBool>>,
+    batch_progress: (usize, usize, String),
+    batch_log: Vec<String>,
+    // 当前文档路径（打开/保存后记录）
+    pub current_path: Option<std::path::PathBuf>,
+    // 当前文档对应的 .lvshp 工程文件；打开/新建 SHP 时清空
+    project_path: Option<std::path::PathBuf>,
+    // 启动恢复上次会话时，SHP 在后台读取完成后要回到的 (帧, 缩放)
+    restore_view: Option<(usize, f32)>,
+    // 使用统计
+    pub session: SessionStats,
+    pub show_stats_window: bool,
+    pub show_hook_window: bool,
+    // 脚本控制台：编辑中的脚本、对应的 .rhai 文件与最近一次运行的输出
+    pub show_script_console: bool,
+    script_source: String,
+    script_path: Option<std::path::PathBuf>,
+    script_log: Vec<String>,
+    // 形状预览叠加纹理及其参数（工具、端点、填充、画笔大小、颜色），参数不变时复用
+    shape_preview: Option<(ShapePreviewKey, egui::TextureHandle)>,
+    pub show_pal_roles: bool,
+    // 调色板格子：边长、是否叠加索引编号、是否标出当前帧用到的索引
+    pub pal_cell: f32,
+    pub pal_show_numbers: bool,
+    pub pal_show_used: bool,
+    pub show_update_window: bool,
+    update_rx: Option<std::sync::mpsc::Receiver<Result<ReleaseInfo, String>>>,
+    update_result: Option<Result<ReleaseInfo, String>>,
+    pub show_mix_window: bool,
+    pub mix: Option<MixFile>,
+    pub mix_search: String,
+    pub mix_selected: Option<u32>,
+    pub mix_new_id: String,
+    // 当前 SHP 来自已打开 MIX 的哪个条目（写回用）
+    pub mix_source: Option<u32>,
+    pub mix_names: NameDb,
+    pub mix_scheme: IdScheme,
 }
 
+type ShapePreviewKey = (Tool, (i32, i32, i32, i32), bool, u32, Color32, bool);
+
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub enum Tool {
     Pencil,
@@ -60,6 +379,344 @@ pub enum Tool {
     Rectangle,
     Circle,
     Fill,
+    SelectRect,
+    Lasso,
+    Wand,
+    Text,
+}
+
+/// 文字工具的栅格化结果缓存：参数与颜色不变时复用掩码和预览纹理
+struct TextPreview {
+    key: (TextParams, u8),
+    mask: Option<(u32, u32, Vec<bool>)>,
+    tex: Option<egui::TextureHandle>,
+}
+
+/// 铅笔/橡皮的当前一笔：是否像素完美、是否环绕、上一落点，以及像素完美模式下最近几个落点和它们原来的索引
+#[derive(Default)]
+struct Stroke {
+    perfect: bool,
+    wrap: bool,
+    last: Option<(i32, i32)>,
+    trail: Vec<(i32, i32, u8)>,
+}
+
+/// 正在拖动的选区内容：按下时记录原帧与清空选区后的底图，拖动中每次由两者重新合成
+struct SelMove {
+    start: (i32, i32),
+    offset: (i32, i32),
+    src: Vec<u8>,
+    base: Vec<u8>,
+    mask: Selection,
+}
+
+impl SelMove {
+    // 选区内的透明像素不覆盖目标位置
+    fn render(&self, pixels: &mut [u8], transparent: u8) {
+        pixels.copy_from_slice(&self.base);
+        let (w, h) = (self.mask.width as i32, self.mask.height as i32);
+        for (i, _) in self.mask.mask.iter().enumerate().filter(|(_, m)| **m) {
+            let v = self.src[i];
+            if v == transparent { continue; }
+            let (x, y) = (i as i32 % w + self.offset.0, i as i32 / w + self.offset.1);
+            if x >= 0 && y >= 0 && x < w && y < h { pixels[(y * w + x) as usize] = v; }
+        }
+    }
+}
+
+/// 铅笔/橡皮使用的笔刷形状
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum BrushShape {
+    Circle,
+    Square,
+    Diagonal,
+    Custom,
+}
+
+/// 自定义笔刷：mask 为 true 的位置着色，以中心对齐光标。
+/// 由选区生成的笔刷带各点原有索引（colors），铅笔按原索引盖章；PNG 笔刷为 1-bit，用当前颜色
+#[derive(Clone)]
+pub struct BrushStamp {
+    pub width: u32,
+    pub height: u32,
+    pub mask: Vec<bool>,
+    pub colors: Option<Vec<u8>>,
+}
+
+/// 一次着色所需的笔刷参数（形状、大小、自定义笔刷）
+#[derive(Copy, Clone)]
+pub struct Brush<'a> {
+    pub shape: BrushShape,
+    pub size: u32,
+    pub custom: Option<&'a BrushStamp>,
+}
+
+impl BrushStamp {
+    /// 从 PNG 读取：不透明(alpha>=128)且偏暗(亮度<128)的像素视为笔刷点
+    pub fn load_png(path: &std::path::Path) -> Result<Self, String> {
+        let img = image::open(path).map_err(|e| e.to_string())?.to_rgba8();
+        let (width, height) = img.dimensions();
+        if width == 0 || height == 0 || width > 256 || height > 256 { return Err(tr("笔刷尺寸需在 1..=256 之间").into()); }
+        let mask = img.pixels().map(|p| {
+            let luma = (p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000;
+            p[3] >= 128 && luma < 128
+        }).collect::<Vec<_>>();
+        if !mask.iter().any(|&b| b) { return Err(tr("笔刷图片中没有有效像素（需不透明的深色像素）").into()); }
+        Ok(Self { width, height, mask, colors: None })
+    }
+
+    /// 从帧的选区生成：裁到选区内非透明像素的包围盒，透明索引处不着色
+    pub fn from_selection(shp: &SHP, frame: usize, sel: &Selection) -> Result<Self, String> {
+        let px = &shp.frames[frame].pixels;
+        let keep: Vec<u8> = px.iter().zip(&sel.mask).map(|(&p, &m)| if m { p } else { shp.transparent }).collect();
+        let Some((x0, y0, width, height)) = crate::shp::content_rect(&keep, shp.width, shp.transparent) else {
+            return Err(tr("选区内没有非透明像素").into());
+        };
+        if width > 256 || height > 256 { return Err(tr("笔刷尺寸需在 1..=256 之间").into()); }
+        let colors: Vec<u8> = (0..height).flat_map(|y| (0..width).map(move |x| ((y0 + y) * shp.width + x0 + x) as usize))
+            .map(|i| if sel.mask[i] { px[i] } else { shp.transparent }).collect();
+        let mask = colors.iter().map(|&c| c != shp.transparent).collect();
+        Ok(Self { width, height, mask, colors: Some(colors) })
+    }
+}
+
+/// 填充工具的着色方式：单色、主副两色按比例有序抖动、或平铺自定义笔刷
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum FillPattern {
+    Solid,
+    Dither,
+    Stamp,
+}
+
+impl FillPattern {
+    pub const ALL: [FillPattern; 3] = [FillPattern::Solid, FillPattern::Dither, FillPattern::Stamp];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FillPattern::Solid => tr("单色"),
+            FillPattern::Dither => tr("两色抖动"),
+            FillPattern::Stamp => tr("笔刷图案"),
+        }
+    }
+}
+
+// 4×4 有序抖动阈值
+const BAYER4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// 一次填充实际使用的颜色来源；按画布绝对坐标取色，相邻区域分别填充时图案能接上
+#[derive(Copy, Clone)]
+pub enum Fill<'a> {
+    Solid(u8),
+    /// (颜色, 另一颜色, 另一颜色占比 0..=100)
+    Dither(u8, u8, u8),
+    /// 平铺笔刷；PNG 笔刷的着色点用该颜色，笔刷空白处保持原样
+    Stamp(&'a BrushStamp, u8),
+}
+
+impl<'a> Fill<'a> {
+    /// `color` 为这次填充的颜色，`other` 为另一个（主/副）颜色；选了图案但没有自定义笔刷时退回单色
+    pub fn new(pattern: FillPattern, color: u8, other: u8, ratio: u8, stamp: Option<&'a BrushStamp>) -> Self {
+        match (pattern, stamp) {
+            (FillPattern::Dither, _) => Fill::Dither(color, other, ratio),
+            (FillPattern::Stamp, Some(st)) => Fill::Stamp(st, color),
+            _ => Fill::Solid(color),
+        }
+    }
+
+    /// (x, y) 处应填的索引；None 表示保持原样
+    pub fn color_at(&self, x: u32, y: u32) -> Option<u8> {
+        match *self {
+            Fill::Solid(c) => Some(c),
+            Fill::Dither(a, b, ratio) => {
+                let t = BAYER4[(y % 4) as usize][(x % 4) as usize] as u32;
+                Some(if t * 100 < ratio as u32 * 16 { b } else { a })
+            }
+            Fill::Stamp(st, c) => {
+                let i = ((y % st.height) * st.width + x % st.width) as usize;
+                st.mask[i].then(|| st.colors.as_ref().map_or(c, |cs| cs[i]))
+            }
+        }
+    }
+}
+
+/// 游戏内 HUD 遮挡区域（相对画布中心，单位：像素）：血条 + 选择框四角
+#[derive(Copy, Clone, PartialEq)]
+pub struct SafeArea {
+    pub bar_y: i32,
+    pub bar_w: u32,
+    pub bar_h: u32,
+    pub box_w: u32,
+    pub box_h: u32,
+}
+
+impl SafeArea {
+    /// 常见单位类型的近似值：步兵血条短、位置低；载具血条更宽
+    pub const INFANTRY: SafeArea = SafeArea { bar_y: -24, bar_w: 16, bar_h: 4, box_w: 20, box_h: 30 };
+    pub const VEHICLE: SafeArea = SafeArea { bar_y: -28, bar_w: 34, bar_h: 4, box_w: 40, box_h: 36 };
+
+    /// 在画布上绘制遮挡区域：`rect` 为画布屏幕矩形，`scale` 为缩放
+    fn paint(&self, painter: &egui::Painter, rect: egui::Rect, scale: f32) {
+        let fill = Color32::from_rgba_unmultiplied(255, 60, 60, 70);
+        let stroke = egui::Stroke::new(1.0, Color32::from_rgb(255, 80, 80));
+        let c = rect.center();
+        let bar = egui::Rect::from_min_size(
+            egui::pos2(c.x - self.bar_w as f32 * scale / 2.0, c.y + self.bar_y as f32 * scale),
+            egui::vec2(self.bar_w as f32 * scale, self.bar_h as f32 * scale),
+        );
+        painter.rect_filled(bar, 0.0, fill);
+        painter.rect_stroke(bar, 0.0, stroke);
+        let bx = egui::Rect::from_center_size(c, egui::vec2(self.box_w as f32 * scale, self.box_h as f32 * scale));
+        let len = 4.0 * scale;
+        for (corner, dx, dy) in [(bx.left_top(), 1.0, 1.0), (bx.right_top(), -1.0, 1.0), (bx.left_bottom(), 1.0, -1.0), (bx.right_bottom(), -1.0, -1.0)] {
+            painter.line_segment([corner, corner + egui::vec2(len * dx, 0.0)], stroke);
+            painter.line_segment([corner, corner + egui::vec2(0.0, len * dy)], stroke);
+        }
+    }
+}
+
+/// 透视辅助线类型
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum GuideMode {
+    /// 固定等角线：2:1 斜率（TS/RA2 地形与建筑的标准角度）
+    Isometric,
+    /// 两点透视：从两个消失点放射，外加经过两点的地平线
+    TwoPoint,
+}
+
+/// 画布辅助线设置（画布像素坐标，消失点可位于画布外）
+#[derive(Copy, Clone, PartialEq)]
+pub struct Guides {
+    pub mode: GuideMode,
+    pub spacing: u32,
+    pub vp1: egui::Pos2,
+    pub vp2: egui::Pos2,
+    pub rays: u32,
+}
+
+impl Guides {
+    fn new() -> Self {
+        Self { mode: GuideMode::Isometric, spacing: 8, vp1: egui::pos2(-200.0, 40.0), vp2: egui::pos2(400.0, 40.0), rays: 16 }
+    }
+
+    /// 在画布上绘制辅助线：`rect` 为画布屏幕矩形，`scale` 为缩放
+    fn paint(&self, painter: &egui::Painter, rect: egui::Rect, scale: f32) {
+        let painter = painter.with_clip_rect(rect);
+        let stroke = egui::Stroke::new(1.0, Color32::from_rgba_unmultiplied(80, 200, 255, 110));
+        let to_screen = |p: egui::Pos2| rect.min + p.to_vec2() * scale;
+        match self.mode {
+            GuideMode::Isometric => {
+                // 以画布中心为基准，按垂直间距铺两组 ±1/2 斜率的平行线
+                let (w, h) = (rect.width() / scale, rect.height() / scale);
+                let c = egui::pos2(w / 2.0, h / 2.0);
+                let step = self.spacing.max(1) as f32;
+                let n = ((w / 2.0 + h) / step).ceil() as i32 + 1;
+                for slope in [0.5f32, -0.5] {
+                    for k in -n..=n {
+                        let y0 = c.y + k as f32 * step - slope * c.x;
+                        painter.line_segment([to_screen(egui::pos2(0.0, y0)), to_screen(egui::pos2(w, y0 + slope * w))], stroke);
+                    }
+                }
+            }
+            GuideMode::TwoPoint => {
+                let horizon = egui::Stroke::new(1.0, Color32::from_rgba_unmultiplied(255, 200, 80, 160));
+                let (a, b) = (to_screen(self.vp1), to_screen(self.vp2));
+                let dir = (b - a).normalized() * (rect.width() + rect.height()) * 4.0;
+                if dir.is_finite() { painter.line_segment([a - dir, b + dir], horizon); }
+                for vp in [a, b] {
+                    // 只在能覆盖画布的角度区间内均匀放射
+                    let reach = [rect.left_top(), rect.right_top(), rect.right_bottom(), rect.left_bottom()]
+                        .iter().map(|p| p.distance(vp)).fold(0.0f32, f32::max);
+                    let base = (rect.center() - vp).angle();
+                    let (lo, hi) = if rect.contains(vp) {
+                        (-std::f32::consts::PI, std::f32::consts::PI)
+                    } else {
+                        let rel = |p: egui::Pos2| {
+                            let d = (p - vp).angle() - base;
+                            (d + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI
+                        };
+                        let angles = [rel(rect.left_top()), rel(rect.right_top()), rel(rect.right_bottom()), rel(rect.left_bottom())];
+                        (angles.iter().copied().fold(f32::MAX, f32::min), angles.iter().copied().fold(f32::MIN, f32::max))
+                    };
+                    let n = self.rays.max(2);
+                    for i in 0..n {
+                        let t = base + lo + (hi - lo) * i as f32 / (n - 1) as f32;
+                        painter.line_segment([vp, vp + egui::Vec2::angled(t) * reach], stroke);
+                    }
+                    painter.circle_stroke(vp, 4.0, horizon);
+                }
+            }
+        }
+    }
+}
+
+/// 批量操作的帧范围
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum FrameScope {
+    Current,
+    Selected,
+    All,
+}
+
+/// 多帧图片导入时源帧与 SHP 帧的对应方式
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FrameImportMode {
+    Append,
+    Overwrite,
+    OverwriteSkipExtra,
+    LoopFill,
+}
+
+impl FrameImportMode {
+    pub const ALL: [FrameImportMode; 4] = [FrameImportMode::Append, FrameImportMode::Overwrite, FrameImportMode::OverwriteSkipExtra, FrameImportMode::LoopFill];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FrameImportMode::Append => tr("全部追加为新帧"),
+            FrameImportMode::Overwrite => tr("从起始帧覆盖，超出部分追加"),
+            FrameImportMode::OverwriteSkipExtra => tr("从起始帧覆盖，超出部分丢弃"),
+            FrameImportMode::LoopFill => tr("循环填满起始帧之后的所有帧"),
+        }
+    }
+
+    /// (目标帧, 源帧) 列表；目标帧不小于 `dst` 的为追加的新帧
+    pub fn plan(self, src: usize, dst: usize, start: usize) -> Vec<(usize, usize)> {
+        match self {
+            FrameImportMode::Append => (0..src).map(|k| (dst + k, k)).collect(),
+            FrameImportMode::Overwrite => (0..src).map(|k| (start + k, k)).collect(),
+            FrameImportMode::OverwriteSkipExtra => (0..src).map(|k| (start + k, k)).take_while(|&(t, _)| t < dst).collect(),
+            FrameImportMode::LoopFill if src == 0 => Vec::new(),
+            FrameImportMode::LoopFill => (start..dst).map(|t| (t, (t - start) % src)).collect(),
+        }
+    }
+}
+
+/// 待确认的多帧导入：源帧、帧延时与对应方式
+pub struct FrameImport {
+    pub path: std::path::PathBuf,
+    pub frames: Vec<image::RgbaImage>,
+    pub delays: Vec<u32>,
+    pub mode: FrameImportMode,
+    pub start: usize,
+}
+
+/// 播放到范围端点后的行为
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LoopMode {
+    Loop,
+    PingPong,
+    Once,
+}
+
+impl LoopMode {
+    pub const ALL: [LoopMode; 3] = [LoopMode::Loop, LoopMode::PingPong, LoopMode::Once];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LoopMode::Loop => tr("循环"),
+            LoopMode::PingPong => tr("往返"),
+            LoopMode::Once => tr("播放一次"),
+        }
+    }
 }
 
 pub struct PreviewState {
@@ -68,6 +725,11 @@ pub struct PreviewState {
     pub ms_per_frame: u64,
     pub last_tick: Instant,
     pub accumulator_ms: u64,
+    pub loop_mode: LoopMode,
+    /// 播放范围（闭区间）；None 表示全部帧
+    pub range: Option<(usize, usize)>,
+    /// 倒放；往返模式下到达端点时自动翻转
+    pub backwards: bool,
 }
 
 impl PreviewState {
@@ -78,6 +740,59 @@ impl PreviewState {
             ms_per_frame: 150,
             last_tick: Instant::now(),
             accumulator_ms: 0,
+            loop_mode: LoopMode::Loop,
+            range: None,
+            backwards: false,
+        }
+    }
+
+    /// 实际生效的播放范围，已夹到帧数以内
+    pub fn bounds(&self, frame_count: usize) -> (usize, usize) {
+        let last = frame_count.saturating_sub(1);
+        match self.range {
+            Some((a, b)) => { let (a, b) = (a.min(last), b.min(last)); (a.min(b), a.max(b)) }
+            None => (0, last),
+        }
+    }
+
+    /// 开始/暂停；“播放一次”已停在终点时从头开始
+    pub fn toggle(&mut self, frame_count: usize) {
+        self.playing = !self.playing;
+        self.last_tick = Instant::now();
+        self.accumulator_ms = 0;
+        if self.playing && self.loop_mode == LoopMode::Once {
+            let (lo, hi) = self.bounds(frame_count);
+            let end = if self.backwards { lo } else { hi };
+            if self.current_frame == end { self.current_frame = if self.backwards { hi } else { lo }; }
+        }
+    }
+
+    /// 相对当前帧跳转，越界时停在首/末帧
+    pub fn jump(&mut self, delta: isize, frame_count: usize) {
+        if frame_count == 0 { return; }
+        self.current_frame = self.current_frame.saturating_add_signed(delta).min(frame_count - 1);
+    }
+
+    /// 按方向前进一帧；范围外先跳到范围起点
+    fn step(&mut self, frame_count: usize) {
+        let (lo, hi) = self.bounds(frame_count);
+        let cur = self.current_frame;
+        if cur < lo || cur > hi {
+            self.current_frame = if self.backwards { hi } else { lo };
+            return;
+        }
+        let at_end = if self.backwards { cur == lo } else { cur == hi };
+        if !at_end {
+            self.current_frame = if self.backwards { cur - 1 } else { cur + 1 };
+            return;
+        }
+        match self.loop_mode {
+            LoopMode::Loop => self.current_frame = if self.backwards { hi } else { lo },
+            LoopMode::PingPong => {
+                self.backwards = !self.backwards;
+                if lo < hi { self.current_frame = if self.backwards { cur - 1 } else { cur + 1 }; }
+            }
+            LoopMode::Once => self.playing = false,
         }
     }
 
@@ -88,30 +803,58 @@ impl PreviewState {
         self.last_tick = now;
         self.accumulator_ms = self.accumulator_ms.saturating_add(dt.as_millis() as u64);
         let mut advanced = 0usize;
-        while self.accumulator_ms >= self.ms_per_frame {
+        while self.playing && self.accumulator_ms >= self.ms_per_frame {
             self.accumulator_ms -= self.ms_per_frame;
-            self.current_frame = (self.current_frame + 1) % frame_count;
+            self.step(frame_count);
             advanced += 1;
         }
         if advanced > 0 { Some(self.current_frame) } else { None }
     }
 }
 
+// MIX 窗口中的操作（参数为条目 ID）
+#[derive(Clone, Copy)]
+enum MixAction {
+    OpenShp(u32),
+    ReplaceWithShp(u32),
+    ReplaceWithFile(u32),
+    Extract(u32),
+    Delete(u32),
+    AddShp(u32),
+    AddFile(u32),
+    Save,
+    SaveAs,
+    LoadNames,
+    ExtractPalettes,
+}
+
 impl MixApp {
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
+    pub fn new(cc: &eframe::CreationContext<'_>, view_only: bool, open_paths: Vec<std::path::PathBuf>) -> Self {
         setup_fonts(&cc.egui_ctx);
         setup_theme(&cc.egui_ctx);
         // load embedded or filesystem palettes
         let (grouped, flat): (Vec<(String, Vec<(String, Palette)>)>, Vec<(String, Palette)>) = load_embedded_palettes();
         let default_pal = flat.first().map(|(_, p)| p.clone()).unwrap_or_else(Palette::default_grayscale);
 
-        Self {
+        let mut app = Self {
+            egui_ctx: cc.egui_ctx.clone(),
+            io_task: None,
+            view_only,
+            lazy_shp: None,
             palette: default_pal,
             shp: None,
             brush_index: 1,
+            brush_index2: 0,
+            draw_index: 1,
+            recent_colors: Vec::new(),
+            canvas_menu: None,
+            pal_flash: None,
             tool: Tool::Pencil,
             scale: 4.0,
             brush_size: 1,
+            brush_hud_until: None,
+            brush_shape: BrushShape::Circle,
+            custom_brush: None,
             drawing: false,
             draw_start: None,
             draw_end: None,
@@ -129,68 +872,276 @@ impl MixApp {
             import_pos: egui::pos2(0.0, 0.0),
             import_scale: 1.0,
             import_angle_deg: 0.0,
+            import_resample: Resample::Nearest,
+            import_downscale: 1,
+            import_sharpen: 0.0,
+            import_chroma: false,
+            import_chroma_color: [255, 0, 255],
+            import_chroma_tol: 32,
+            import_alpha_min: 8,
+            import_blend: false,
             import_armed: false,
+            quant_cache: QuantizeCache::new(16),
+            import_show_quantized: true,
+            import_preview: None,
             grouped_pals: grouped,
             dirty: false,
             show_exit_confirm: false,
-            undo_stack: Vec::new(),
-            redo_stack: Vec::new(),
-            max_undo_steps: 100,
-            undo_frame_anchor: None,
+            show_pal_gallery: false,
+            show_safe_area: false,
+            color_blind: ColorBlindMode::None,
+            canvas_tex: None,
+            play_tex: PlaybackTextures::default(),
+            show_replace_dialog: false,
+            show_pal_convert: false,
+            show_shadow_dialog: false,
+            show_pal_adjust: false,
+            pal_adjust: PaletteAdjust::default(),
+            pal_adjust_orig: None,
+            show_pal_diff: false,
+            show_pal_sort: false,
+            pal_sort_order: Vec::new(),
+            pal_sort_by: PaletteSort::Hue,
+            pal_sort_keep_special: true,
+            pal_sort_drag: None,
+            pal_diff_b: None,
+            pal_diff_range: (0, 255),
+            show_canvas_size: false,
+            canvas_new_w: 0,
+            canvas_new_h: 0,
+            canvas_anchor: (1, 1),
+            show_retarget: false,
+            retarget_w: 0,
+            retarget_h: 0,
+            retarget_anchor: RetargetAnchor::ContentBottom,
+            retarget_margin: 0,
+            show_scale_dialog: false,
+            scale_new_w: 0,
+            scale_new_h: 0,
+            scale_keep_aspect: true,
+            scaler: PixelScaler::Nearest,
+            shadow_params: ShadowParams { index: 1, shear: 0.5, squash: 0.5, offset_x: 0, offset_y: 0 },
+            shadow_overwrite: false,
+            show_tween_dialog: false,
+            tween_steps: 3,
+            tween_mode: TweenMode::CrossFade,
+            show_facings_dialog: false,
+            facings_count: 8,
+            facings_all: false,
+            facings_source: 0,
+            facings_clockwise: false,
+            facings_smooth: false,
+            show_dedup: false,
+            dedup_groups: Vec::new(),
+            dedup_saving: 0,
+            show_outline_dialog: false,
+            outline_index: 0,
+            outline_outside: true,
+            outline_diagonal: false,
+            outline_scope: FrameScope::Current,
+            show_shift_dialog: false,
+            shift_dx: 0,
+            shift_dy: 0,
+            shift_wrap: false,
+            show_cameo_dialog: false,
+            cameo_pal: None,
+            cameo_opts: CameoOptions::default(),
+            cameo_preview: None,
+            show_filter_dialog: false,
+            filters: filters::registry(),
+            filter_idx: 0,
+            filter_scope: FrameScope::Current,
+            filter_gen: 0,
+            filter_preview: None,
+            show_color_usage: false,
+            color_usage_all: false,
+            usage_highlight: None,
+            show_minimap: true,
+            doc_pals: Vec::new(),
+            show_compare: false,
+            compare_doc: None,
+            compare_zoom: 2.0,
+            compare_overlay: true,
+            compare_tex: None,
+            watch: None,
+            pal_path: None,
+            watch_conflict: false,
+            show_save_report: false,
+            frame_import: None,
+            save_sizes: Vec::new(),
+            sequences: Vec::new(),
+            show_sequences: false,
+            canvas_scroll_to: None,
+            minimap_rect: None,
+            text_params: TextParams::default(),
+            stroke: Stroke::default(),
+            pixel_perfect: false,
+            pressure_size: false,
+            pen_pressure: None,
+            wrap_draw: false,
+            fill_diagonal: false,
+            fill_pattern: FillPattern::Solid,
+            fill_ratio: 50,
+            show_tile_preview: false,
+            tile_zoom: 1.0,
+            tile_preview: None,
+            show_game_preview: false,
+            game_zoom: 1,
+            game_crt: false,
+            game_crt_strength: 0.5,
+            show_frame_inspector: false,
+            show_pal_split: false,
+            pal_split_b: None,
+            pal_split_pos: 0.5,
+            pal_split_side: false,
+            pal_split_zoom: 2,
+            pal_split_tex: None,
+            game_preview: None,
+            text_pos: None,
+            text_grab: egui::Vec2::ZERO,
+            text_font_data: None,
+            text_preview: None,
+            pal_convert_target: None,
+            pal_convert_used_only: true,
+            pal_convert_usage: Vec::new(),
+            pal_switch_pending: None,
+            pal_switch_remember: false,
+            replace_from: 0,
+            replace_to: 0,
+            replace_scope: FrameScope::Current,
+            selection: None,
+            lasso_pts: Vec::new(),
+            sel_move: None,
+            clip_base: None,
+            replace_in_selection: false,
+            safe_area: SafeArea::VEHICLE,
+            show_guides: false,
+            show_offsets: false,
+            bg_image: None,
+            show_bg_image: true,
+            bg_pos: egui::Vec2::ZERO,
+            clipboard: None,
+            guides: Guides::new(),
+            pal_gallery_cache: None,
+            thumb_cache: Vec::new(),
+            selected_frames: std::collections::BTreeSet::new(),
+            timeline_last_frame: usize::MAX,
+            history: History::default(),
+            max_undo_steps: 500,
+            settings: Settings::load(),
+            show_keymap_window: false,
+            show_delta_export: false,
+            show_contact_sheet: false,
+            contact_sheet: SheetOptions { columns: 6, rows: 5, cell: 96, title: String::new(), notes: String::new() },
+            show_ghost_export: false,
+            ghost_first_alpha: 0.15,
+            ghost_last_alpha: 1.0,
+            ghost_step: 1,
+            show_batch: false,
+            batch_input: None,
+            batch_output: None,
+            batch_pal: None,
+            batch_format: BatchFormat::Png,
+            batch_rx: None,
+            batch_cancel: None,
+            batch_progress: (0, 0, String::new()),
+            batch_log: Vec::new(),
+            export_prefix: "delta_".into(),
+            keymap_capture: None,
+            current_path: None,
+            project_path: None,
+            restore_view: None,
+            session: SessionStats::new(String::new()),
+            show_stats_window: false,
+            show_hook_window: false,
+            show_script_console: false,
+            script_source: script::example(),
+            script_path: None,
+            script_log: Vec::new(),
+            shape_preview: None,
+            show_pal_roles: true,
+            pal_cell: 14.0,
+            pal_show_numbers: false,
+            pal_show_used: false,
+            show_update_window: false,
+            show_mix_window: false,
+            mix: None,
+            mix_search: String::new(),
+            mix_selected: None,
+            mix_new_id: String::new(),
+            mix_source: None,
+            mix_names: NameDb::default(),
+            mix_scheme: IdScheme::Ts,
+            update_rx: None,
+            update_result: None,
+        };
+        if app.settings.check_updates && !view_only { app.update_rx = Some(update::spawn_check()); }
+        for p in &app.settings.mix_name_lists {
+            if let Ok(bytes) = std::fs::read(p) { app.mix_names.load_bytes(&bytes); }
         }
+        app.load_user_palettes();
+        // 按顺序处理，便于 `lvjiaShpEdit unit.shp unittem.pal` 这类组合
+        for path in &open_paths { app.open_path(path); }
+        // 没有指定文件时按设置恢复上次的文档
+        if open_paths.is_empty() && !view_only && app.settings.restore_session
+            && let Some(last) = app.settings.last_session.clone() { app.restore_session(last); }
+        app
     }
 
     // 撤销/重做
-    #[allow(dead_code)]
-    fn save_undo_state_for_frame(&mut self, frame_idx: usize) {
-        if let Some(shp) = &self.shp {
-            let data = shp.frames[frame_idx].pixels.clone();
-            self.undo_stack.push(data);
-            if self.undo_stack.len() > self.max_undo_steps { self.undo_stack.remove(0); }
-            self.redo_stack.clear();
-        }
+    fn push_undo(&mut self, patch: Patch) {
+        let fi = self.preview.current_frame;
+        self.history.push(fi, patch, self.max_undo_steps, self.shp.as_ref());
+    }
+
+    // 多帧操作前记录这些帧的像素
+    fn save_undo_frames(&mut self, frames: &[usize]) {
+        let Some(shp) = &self.shp else { return; };
+        let data = frames.iter().filter(|&&i| i < shp.frames.len()).map(|&i| (i, FramePixels::Full(shp.frames[i].pixels.clone()))).collect();
+        self.push_undo(Patch::Pixels(data));
+    }
+
+    fn save_undo_offsets(&mut self, frames: &[usize]) {
+        let Some(shp) = &self.shp else { return; };
+        let data = frames.iter().filter(|&&i| i < shp.frames.len()).map(|&i| (i, shp.frames[i].offset)).collect();
+        self.push_undo(Patch::Offsets(data));
+    }
+
+    fn save_undo_palette(&mut self) {
+        self.push_undo(Patch::Palette(Box::new(self.palette.clone()), self.current_pal_name.clone()));
+    }
+
+    // 结构性操作（帧增删、画布尺寸等）前记录整份文档；with_palette 时连同调色板
+    fn save_undo_document(&mut self, with_palette: bool) {
+        let Some(shp) = &self.shp else { return; };
+        let pal = with_palette.then(|| (Box::new(self.palette.clone()), self.current_pal_name.clone()));
+        self.push_undo(Patch::Document(Box::new(shp.clone()), pal));
     }
 
     fn undo(&mut self) {
-        if let Some(shp) = &mut self.shp {
-            let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
-            // 帧锚点校验：若已切换帧，清空历史避免跨帧污染
-            if self.undo_frame_anchor.map_or(false, |a| a != fi) {
-                self.undo_stack.clear();
-                self.redo_stack.clear();
-                self.undo_frame_anchor = Some(fi);
-                self.status = "已切换帧，撤销历史已清空".to_owned();
-                return;
-            }
-            if let Some(prev) = self.undo_stack.pop() {
-                let cur = std::mem::replace(&mut shp.frames[fi].pixels, prev);
-                self.redo_stack.push(cur);
-                self.dirty = true;
-                self.status = "已撤销".to_owned();
-            }
+        match self.history.undo(&mut self.shp, &mut self.palette, &mut self.current_pal_name) {
+            Some(fi) => { self.after_history_step(fi); self.status = tr("已撤销").to_owned(); }
+            None => self.status = tr("没有可撤销的操作").to_owned(),
         }
     }
 
     fn redo(&mut self) {
-        if let Some(shp) = &mut self.shp {
-            let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
-            // 帧锚点校验：若已切换帧，清空历史避免跨帧污染
-            if self.undo_frame_anchor.map_or(false, |a| a != fi) {
-                self.undo_stack.clear();
-                self.redo_stack.clear();
-                self.undo_frame_anchor = Some(fi);
-                self.status = "已切换帧，重做历史已清空".to_owned();
-                return;
-            }
-            if let Some(next_) = self.redo_stack.pop() {
-                let cur = std::mem::replace(&mut shp.frames[fi].pixels, next_);
-                self.undo_stack.push(cur);
-                self.dirty = true;
-                self.status = "已重做".to_owned();
-            }
+        match self.history.redo(&mut self.shp, &mut self.palette, &mut self.current_pal_name) {
+            Some(fi) => { self.after_history_step(fi); self.status = tr("已重做").to_owned(); }
+            None => self.status = tr("没有可重做的操作").to_owned(),
         }
     }
 
+    // 撤销/重做后跳回该步所属的帧；结构可能已变，帧选择与预览缓存一并校正
+    fn after_history_step(&mut self, fi: usize) {
+        let count = self.shp.as_ref().map_or(0, |s| s.frames.len());
+        self.preview.current_frame = fi.min(count.saturating_sub(1));
+        self.selected_frames.retain(|&i| i < count);
+        self.shape_preview = None;
+        self.sel_move = None;
+        self.dirty = true;
+    }
+
     // ===== 画图算法（在不修改SHP的前提下）=====
     fn frame_set_pixel(shp: &mut SHP, frame_idx: usize, x: i32, y: i32, color: u8) {
         if frame_idx >= shp.frames.len() { return; }
@@ -201,26 +1152,35 @@ impl MixApp {
         shp.frames[frame_idx].pixels[i] = color;
     }
 
-    fn frame_get_pixel(shp: &SHP, frame_idx: usize, x: i32, y: i32) -> u8 {
-        if x < 0 || y < 0 { return 0; }
-        let (x, y) = (x as u32, y as u32);
-        if frame_idx >= shp.frames.len() || x >= shp.width || y >= shp.height { return 0; }
-        let i = (y * shp.width + x) as usize;
-        shp.frames[frame_idx].pixels[i]
+    fn draw_line_on_frame(shp: &mut SHP, fi: usize, x0: i32, y0: i32, x1: i32, y1: i32, color: u8) {
+        for (x, y) in line_points(x0, y0, x1, y1) { Self::frame_set_pixel(shp, fi, x, y, color); }
     }
 
-    fn draw_line_on_frame(shp: &mut SHP, fi: usize, mut x0: i32, mut y0: i32, x1: i32, y1: i32, color: u8) {
-        let dx = (x1 - x0).abs();
-        let sx = if x0 < x1 { 1 } else { -1 };
-        let dy = -(y1 - y0).abs();
-        let sy = if y0 < y1 { 1 } else { -1 };
-        let mut err = dx + dy;
-        loop {
-            Self::frame_set_pixel(shp, fi, x0, y0, color);
-            if x0 == x1 && y0 == y1 { break; }
-            let e2 = 2 * err;
-            if e2 >= dy { err += dy; x0 += sx; }
-            if e2 <= dx { err += dx; y0 += sy; }
+    // 铅笔/橡皮笔画：从上一落点到 (x, y) 逐点盖章，快速移动时不留空隙。
+    // 像素完美模式（1 像素画笔）下，相邻三点成 L 形时把拐角处的点还原，去掉双像素台阶
+    fn stroke_to(shp: &mut SHP, fi: usize, stroke: &mut Stroke, x: i32, y: i32, brush: Brush, color: u8) {
+        let pts: Vec<(i32, i32)> = match stroke.last {
+            Some((lx, ly)) => line_points(lx, ly, x, y).into_iter().skip(1).collect(),
+            None => vec![(x, y)],
+        };
+        stroke.last = Some((x, y));
+        for (px, py) in pts {
+            if !stroke.perfect {
+                for (dx, dy) in wrap_offsets(shp.width, shp.height, stroke.wrap) { Self::stamp_brush_on_frame(shp, fi, px + dx, py + dy, brush, color); }
+                continue;
+            }
+            let (px, py) = if stroke.wrap { (px.rem_euclid(shp.width as i32), py.rem_euclid(shp.height as i32)) } else { (px, py) };
+            if px < 0 || py < 0 || px as u32 >= shp.width || py as u32 >= shp.height { stroke.trail.clear(); continue; }
+            let i = (py as u32 * shp.width + px as u32) as usize;
+            stroke.trail.push((px, py, shp.frames[fi].pixels[i]));
+            shp.frames[fi].pixels[i] = color;
+            if let [.., a, b, c] = stroke.trail[..]
+                && (a.0 == b.0 || a.1 == b.1) && (c.0 == b.0 || c.1 == b.1) && a.0 != c.0 && a.1 != c.1
+            {
+                Self::frame_set_pixel(shp, fi, b.0, b.1, b.2);
+                stroke.trail.remove(stroke.trail.len() - 2);
+            }
+            if stroke.trail.len() > 3 { stroke.trail.remove(0); }
         }
     }
 
@@ -272,205 +1232,3958 @@ impl MixApp {
         Self::fill_circle_on_frame(shp, fi, cx, cy, radius.max(1), color);
     }
 
-    fn flood_fill_on_frame(shp: &mut SHP, fi: usize, x: i32, y: i32, new_color: u8) {
+    // 按当前笔刷形状着色；自定义笔刷未加载时退回圆形
+    fn stamp_brush_on_frame(shp: &mut SHP, fi: usize, cx: i32, cy: i32, brush: Brush, color: u8) {
+        let size = brush.size;
+        match (brush.shape, brush.custom) {
+            (BrushShape::Square, _) => {
+                // 偶数尺寸时向右下多出一格，与常见像素画工具一致
+                let s = size.max(1) as i32;
+                let lx = cx - (s - 1) / 2; let ty = cy - (s - 1) / 2;
+                Self::fill_rect_on_frame(shp, fi, lx, ty, lx + s - 1, ty + s - 1, color);
+            }
+            (BrushShape::Diagonal, _) => {
+                // 斜线笔刷：左下到右上的 45° 线段，长度为 size
+                let s = size.max(1) as i32;
+                let lx = cx - (s - 1) / 2; let by = cy + (s - 1) / 2;
+                Self::draw_line_on_frame(shp, fi, lx, by, lx + s - 1, by - (s - 1), color);
+            }
+            (BrushShape::Custom, Some(stamp)) => {
+                let ox = cx - (stamp.width as i32) / 2; let oy = cy - (stamp.height as i32) / 2;
+                // 带索引的笔刷：铅笔按原索引盖章，橡皮（透明色）仍按形状擦除
+                let colors = stamp.colors.as_ref().filter(|_| color != shp.transparent);
+                for y in 0..stamp.height { for x in 0..stamp.width {
+                    let i = (y * stamp.width + x) as usize;
+                    if stamp.mask[i] { Self::frame_set_pixel(shp, fi, ox + x as i32, oy + y as i32, colors.map_or(color, |c| c[i])); }
+                }}
+            }
+            _ => Self::stamp_disc_on_frame(shp, fi, cx, cy, size, color),
+        }
+    }
+
+    // 直线/矩形/圆：提交与预览共用同一光栅化，保证所见即所得。
+    // 描边按画笔大小加粗（圆形笔刷），填充形状不受画笔大小影响
+    fn draw_shape_on_frame(shp: &mut SHP, fi: usize, tool: Tool, (x0, y0, x1, y1): (i32, i32, i32, i32), fill: bool, size: u32, color: u8) {
+        let radius = || (((x1-x0)*(x1-x0) + (y1-y0)*(y1-y0)) as f32).sqrt() as i32;
+        if fill {
+            match tool {
+                Tool::Rectangle => return Self::fill_rect_on_frame(shp, fi, x0, y0, x1, y1, color),
+                Tool::Circle => return Self::fill_circle_on_frame(shp, fi, x0, y0, radius(), color),
+                _ => {}
+            }
+        }
+        // 先画 1px 轮廓到临时蒙版，再在每个轮廓点盖一次笔刷
+        let thin = size <= 1;
+        let mut mask = if thin { None } else { Some(SHP::new(shp.width, shp.height, 1)) };
+        let (target, tfi, tcolor) = match mask.as_mut() { Some(m) => (m, 0, 1), None => (&mut *shp, fi, color) };
+        match tool {
+            Tool::Line => Self::draw_line_on_frame(target, tfi, x0, y0, x1, y1, tcolor),
+            Tool::Rectangle => Self::draw_rect_on_frame(target, tfi, x0, y0, x1, y1, tcolor),
+            Tool::Circle => Self::draw_circle_on_frame(target, tfi, x0, y0, radius(), tcolor),
+            _ => {}
+        }
+        if let Some(m) = mask {
+            let w = shp.width as usize;
+            for (i, _) in m.frames[0].pixels.iter().enumerate().filter(|(_, p)| **p != 0) {
+                Self::stamp_disc_on_frame(shp, fi, (i % w) as i32, (i / w) as i32, size, color);
+            }
+        }
+    }
+
+    // 形状预览叠加层：把待提交的形状（环绕时为各平移副本）光栅化为与画布同尺寸的 RGBA 图像
+    fn shape_preview_image(width: u32, height: u32, tool: Tool, shapes: &[(i32, i32, i32, i32)], fill: bool, size: u32, color: Color32) -> egui::ColorImage {
+        let mut scratch = SHP::new(width, height, 1);
+        for &pts in shapes { Self::draw_shape_on_frame(&mut scratch, 0, tool, pts, fill, size, 1); }
+        let pixels: Vec<Color32> = scratch.frames[0].pixels.iter()
+            .map(|&p| if p != 0 { color } else { Color32::TRANSPARENT })
+            .collect();
+        egui::ColorImage { size: [width as usize, height as usize], pixels }
+    }
+
+    fn flood_fill_on_frame(shp: &mut SHP, fi: usize, x: i32, y: i32, fill: Fill, wrap: bool, diagonal: bool) {
         if fi >= shp.frames.len() { return; }
         let w = shp.width as i32; let h = shp.height as i32;
-        let target = Self::frame_get_pixel(shp, fi, x, y);
-        if target == new_color { return; }
-        let mut stack = vec![(x, y)];
-        while let Some((px, py)) = stack.pop() {
-            if px < 0 || py < 0 || px >= w || py >= h { continue; }
-            if Self::frame_get_pixel(shp, fi, px, py) != target { continue; }
-            Self::frame_set_pixel(shp, fi, px, py, new_color);
-            stack.push((px-1, py)); stack.push((px+1, py));
-            stack.push((px, py-1)); stack.push((px, py+1));
+        if x < 0 || y < 0 || x >= w || y >= h { return; }
+        let pixels = &mut shp.frames[fi].pixels;
+        let region = crate::selection::flood_region(pixels, shp.width, shp.height, x, y, wrap, diagonal);
+        for (i, (p, m)) in pixels.iter_mut().zip(region).enumerate() {
+            if m && let Some(c) = fill.color_at(i as u32 % shp.width, i as u32 / shp.width) { *p = c; }
         }
     }
 
     
 
     pub fn ui_menu(&mut self, ui: &mut egui::Ui, ctx: &Context) {
-        ui.menu_button("文件", |ui| {
-            if ui.button("新建 SHP...").clicked() { ui.close_menu(); self.show_new_dialog = true; }
-            if ui.button("打开 SHP...").clicked() {
+        let mut switch_to: Option<(String, Palette)> = None;
+        let mut builtin_pick: Option<(String, Palette)> = None;
+        let mut pin_toggle: Option<(String, String)> = None;
+        ui.menu_button(tr("文件"), |ui| {
+            if ui.button(tr("新建 SHP...")).clicked() { ui.close_menu(); self.show_new_dialog = true; }
+            if ui.button(tr("打开 SHP...")).clicked() {
                 ui.close_menu();
                 self.action_open_shp();
             }
-            if ui.button("保存 SHP...").clicked() {
+            if ui.button(tr("保存 SHP...")).clicked() {
                 ui.close_menu();
                 self.action_save_shp();
             }
+            if ui.button(tr("打开工程...")).on_hover_text(tr("打开 .lvshp 工程：帧、调色板、对齐偏移、序列与编辑状态")).clicked() {
+                ui.close_menu();
+                self.action_open_project();
+            }
+            if ui.add_enabled(self.shp.is_some(), egui::Button::new(tr("保存工程..."))).clicked() {
+                ui.close_menu();
+                self.action_save_project();
+            }
+            if ui.button(tr("打开 MIX...")).clicked() {
+                ui.close_menu();
+                self.action_open_mix();
+            }
+            if ui.add_enabled(self.shp.is_some(), egui::Button::new(tr("与另一个 SHP 对比..."))).clicked() {
+                ui.close_menu();
+                self.action_open_compare();
+            }
+            if self.mix.is_some() && ui.button(tr("MIX 归档窗口")).clicked() {
+                ui.close_menu();
+                self.show_mix_window = true;
+            }
             ui.separator();
-            ui.menu_button("选择内置PAL", |ui| {
+            ui.menu_button(tr("选择内置PAL"), |ui| {
+                let pinned = self.pinned_palettes();
+                for (i, (_, name, pal)) in pinned.iter().enumerate() {
+                    let label = if i < MAX_PINNED_KEYS { format!("{}  {}", i + 1, name) } else { name.clone() };
+                    if ui.selectable_label(self.current_pal_name == *name, label).clicked() {
+                        builtin_pick = Some((name.clone(), pal.clone()));
+                        ui.close_menu();
+                    }
+                }
+                if !pinned.is_empty() { ui.separator(); }
                 for (group, items) in &self.grouped_pals {
                     ui.menu_button(group, |ui| {
                         for (name, pal) in items {
-                            if ui.selectable_label(self.current_pal_name==*name, name).clicked() {
-                                self.palette = pal.clone();
-                                self.current_pal_name = name.clone();
-                                self.dirty = true; // 切换调色板会影响显示，标记为需要保存
+                            let key = (group.clone(), name.clone());
+                            let is_pinned = self.settings.pinned_palettes.contains(&key);
+                            let resp = ui.selectable_label(self.current_pal_name==*name, name);
+                            if resp.clicked() {
+                                builtin_pick = Some((name.clone(), pal.clone()));
                                 ui.close_menu();
                             }
+                            resp.on_hover_text(tr("右键固定到菜单顶部")).context_menu(|ui| {
+                                if ui.button(if is_pinned { tr("取消固定") } else { tr("固定到顶部") }).clicked() {
+                                    pin_toggle = Some(key.clone());
+                                    ui.close_menu();
+                                }
+                            });
+                        }
+                    });
+                }
+            });
+            if ui.button(tr("添加调色板文件夹...")).on_hover_text(tr("扫描文件夹及其子文件夹中的 .pal，加入「User」分组，下次启动自动加载")).clicked() {
+                ui.close_menu();
+                self.action_add_palette_folder();
+            }
+            if let Some(dir) = self.settings.palette_folder.clone() {
+                ui.menu_button(tr("调色板文件夹"), |ui| {
+                    ui.weak(&dir);
+                    if ui.button(tr("重新扫描")).clicked() {
+                        ui.close_menu();
+                        let n = self.load_user_palettes();
+                        self.status = trf!("已从调色板文件夹加载 {} 个调色板", n);
+                    }
+                    if ui.button(tr("移除")).clicked() {
+                        ui.close_menu();
+                        self.settings.palette_folder = None;
+                        self.grouped_pals.retain(|(g, _)| g != USER_PAL_GROUP);
+                        self.save_settings();
+                    }
+                });
+            }
+            ui.menu_button(tr("文档调色板组"), |ui| {
+                if self.doc_pals.is_empty() { ui.weak(tr("（空）")); }
+                let mut remove = None;
+                for (i, (name, pal)) in self.doc_pals.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui.selectable_label(self.current_pal_name == *name, name).clicked() {
+                            switch_to = Some((name.clone(), pal.clone()));
+                            ui.close_menu();
                         }
+                        if ui.small_button("×").on_hover_text(tr("移出调色板组")).clicked() { remove = Some(i); }
                     });
                 }
+                if let Some(i) = remove { self.doc_pals.remove(i); }
+                ui.separator();
+                let has_current = self.doc_pals.iter().any(|(n, _)| *n == self.current_pal_name);
+                if ui.add_enabled(!has_current, egui::Button::new(tr("加入当前调色板"))).clicked() {
+                    self.doc_pals.push((self.current_pal_name.clone(), self.palette.clone()));
+                }
+                ui.weak(trf!("快捷键 {} 在组内轮换", self.settings.keymap.chord_text(Action::CyclePalette)));
             });
-            if ui.button("打开 PAL...").clicked() {
+            if ui.button(tr("打开 PAL...")).clicked() {
                 ui.close_menu();
                 self.action_open_pal();
             }
-            if ui.button("保存 PAL...").clicked() {
+            if ui.button(tr("保存 PAL...")).clicked() {
                 ui.close_menu();
                 self.action_save_pal();
             }
             ui.separator();
-            if ui.button("导入图片为帧 (PNG/JPG/GIF/APNG)...").clicked() {
+            if ui.button(tr("导入图片为帧 (PNG/JPG/GIF/APNG)...")).clicked() {
                 ui.close_menu();
                 self.action_import_image(ctx);
             }
-            if ui.button("导出当前帧为 PNG...").clicked() {
+            if ui.button(tr("导入动画帧序列 (GIF/APNG)...")).clicked() {
+                ui.close_menu();
+                self.action_import_frames();
+            }
+            if ui.button(tr("导出当前帧为 PNG...")).clicked() {
                 ui.close_menu();
                 self.action_export_png();
             }
-        });
-
-        ui.menu_button("预览", |ui| {
-            if ui.button(if self.preview.playing { "暂停" } else { "播放" }).clicked() {
-                self.preview.playing = !self.preview.playing;
-                self.preview.last_tick = Instant::now();
+            if ui.button(tr("导出全部帧为 PCX 序列...")).on_hover_text(tr("每帧一个 8 位 PCX，内嵌当前调色板，按 XCC 习惯命名为“名称 0000.pcx”；限定了播放范围（序列）时只导出该范围")).clicked() {
                 ui.close_menu();
+                self.action_export_pcx();
             }
-            ui.add(egui::Slider::new(&mut self.preview.ms_per_frame, 30..=500).text("间隔ms"));
-        });
-
-        // 顶部不再放工具菜单，遵循“左侧工具箱”设计
-
-        ui.separator();
-        ui.label(RichText::new(&self.status).color(Color32::LIGHT_GRAY));
-    }
-
-    fn action_new_shp(&mut self) {
-        // 简化：固定弹窗交互改为默认值；后续补对话框
-        let width = 128u32;
-        let height = 128u32;
-        let frames = 8usize;
-        self.shp = Some(SHP::new(width, height, frames));
-        self.preview.current_frame = 0;
-        self.status = format!("已新建 SHP: {}x{}, 帧数 {}", width, height, frames);
-        // 新建后复位编辑状态，避免历史遗留
-        self.dirty = false; // 新建文件，清除dirty标记
-        self.import_img = None;
-        self.import_armed = false;
-        self.undo_stack.clear();
-        self.redo_stack.clear();
-        self.undo_frame_anchor = Some(0);
-        self.preview.playing = false;
-    }
-
-    fn action_open_shp(&mut self) {
-        if let Some(path) = FileDialog::new().add_filter("SHP", &["shp"]).pick_file() {
-            match std::fs::read(&path) {
-                Ok(bytes) => match SHP::load(&bytes) {
-                    Ok(shp) => { 
-                        self.shp = Some(shp); 
-                        self.status = format!("已加载 SHP: {}", path.display()); 
-                        // 打开后复位编辑状态，避免历史遗留
-                        self.preview.current_frame = 0;
-                        self.dirty = false; // 打开新文件，清除dirty标记
-                        self.import_img = None;
-                        self.import_armed = false;
-                        self.undo_stack.clear();
-                        self.redo_stack.clear();
-                        self.undo_frame_anchor = Some(0);
-                        self.preview.playing = false;
-                    }
-                    Err(e) => { self.status = format!("加载SHP失败: {}", e); }
-                },
-                Err(e) => { self.status = format!("读取文件失败: {}", e); }
+            if ui.button(tr("导出帧差异 PNG 序列...")).clicked() {
+                ui.close_menu();
+                self.show_delta_export = true;
             }
-        }
-    }
-
-    fn action_save_shp(&mut self) {
-        if let Some(shp) = &self.shp {
-            if let Some(path) = FileDialog::new().set_file_name("output.shp").save_file() {
-                match shp.save() {
-                    Ok(bytes) => {
-                        if let Err(e) = std::fs::write(&path, bytes) { 
-                            self.status = format!("保存失败: {}", e); 
-                        } else { 
-                            self.status = format!("已保存: {}", path.display()); 
-                            self.dirty = false; // 保存成功后清除dirty标记
-                        }
-                    }
-                    Err(e) => { self.status = format!("导出SHP失败: {}", e); }
-                }
+            if ui.button(tr("导出联系表 PNG...")).clicked() {
+                ui.close_menu();
+                self.show_contact_sheet = true;
             }
-        } else {
-            self.status = "当前没有SHP".into();
-        }
-    }
-
-    fn action_open_pal(&mut self) {
-        if let Some(path) = FileDialog::new().add_filter("PAL", &["pal"]).pick_file() {
-            match std::fs::read(&path) {
-                Ok(bytes) => match Palette::from_bytes(&bytes) {
-                    Ok(p) => { 
-                        self.palette = p; 
-                        self.status = format!("已加载 PAL: {}", path.display()); 
-                        self.dirty = true; // 切换调色板会影响显示，标记为需要保存
-                    }
-                    Err(e) => { self.status = format!("加载PAL失败: {}", e); }
-                },
-                Err(e) => { self.status = format!("读取文件失败: {}", e); }
+            if ui.button(tr("导出残影叠加 PNG...")).on_hover_text(tr("把全部帧以递增的不透明度叠到一张图上，便于检查走路循环、枪口火焰的轨迹；限定了播放范围（序列）时只叠加该范围")).clicked() {
+                ui.close_menu();
+                self.show_ghost_export = true;
             }
-        }
-    }
-
-    fn action_save_pal(&mut self) {
-        if let Some(path) = FileDialog::new().set_file_name("palette.pal").save_file() {
-            let bytes = self.palette.to_bytes();
-            if let Err(e) = std::fs::write(&path, bytes) {
-                self.status = format!("保存PAL失败: {}", e);
-            } else {
-                self.status = format!("已保存 PAL: {}", path.display());
+            ui.separator();
+            if ui.button(tr("批量转换 SHP 文件夹...")).clicked() {
+                ui.close_menu();
+                self.show_batch = true;
             }
-        }
-    }
+        });
+        if let Some((name, pal)) = switch_to { self.switch_palette(name, pal); }
+        if let Some((name, pal)) = builtin_pick { self.request_palette_switch(name, pal); }
+        if let Some(key) = pin_toggle { self.toggle_pinned_palette(key); }
 
-    fn action_import_image(&mut self, _ctx: &Context) {
-        if self.shp.is_none() { self.status = "请先新建或打开SHP".into(); return; }
-        if let Some(path) = FileDialog::new().add_filter("图片", &["png","jpg","jpeg","gif","apng"]).pick_file() {
-            match image_io::load_rgba_frames(&path) {
-                Ok(frames) => {
-                    // 取首帧作为导入源；进入Gizmo编辑态
-                    if let Some(rgba) = frames.get(0) {
-                        self.import_img = Some(rgba.clone());
-                        self.import_pos = egui::pos2(0.0, 0.0);
-                        self.import_scale = 1.0;
-                        self.import_angle_deg = 0.0;
-                        self.status = format!("已载入 {}，请在画布上拖动/缩放/固定。", path.display());
-                        self.import_armed = false; // 避免首次导入立即被外部点击固定
-                    }
-                }
-                Err(e) => { self.status = format!("导入失败: {}", e); }
+        ui.menu_button(tr("编辑"), |ui| {
+            let copy_text = trf!("复制帧为图像 ({})", self.settings.keymap.chord_text(Action::CopyFrameImage));
+            let paste_text = trf!("从剪贴板粘贴图像 ({})", self.settings.keymap.chord_text(Action::PasteImage));
+            if ui.add_enabled(self.shp.is_some(), egui::Button::new(copy_text)).clicked() { ui.close_menu(); self.action_copy_frame_image(); }
+            if ui.add_enabled(self.shp.is_some(), egui::Button::new(paste_text)).clicked() { ui.close_menu(); self.action_paste_image(); }
+            ui.separator();
+            if ui.button(tr("替换颜色...")).clicked() {
+                ui.close_menu();
+                self.replace_from = self.brush_index;
+                self.show_replace_dialog = true;
             }
-        }
-    }
-
-    fn action_export_png(&mut self) {
-        if let Some(shp) = &self.shp {
-            if let Some(path) = FileDialog::new().set_file_name("frame.png").save_file() {
-                let idx = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
-                match shp.export_frame_png(idx, &self.palette, path.clone()) {
-                    Ok(()) => { self.status = format!("已导出: {}", path.display()); }
-                    Err(e) => { self.status = format!("导出失败: {}", e); }
-                }
+            if ui.add_enabled(self.shp.is_some(), egui::Button::new(tr("转换到调色板..."))).clicked() {
+                ui.close_menu();
+                self.open_pal_convert();
             }
-        } else {
-            self.status = "当前没有SHP".into();
-        }
+            if ui.button(tr("调整调色板...")).clicked() {
+                ui.close_menu();
+                if self.pal_adjust_orig.is_none() { self.pal_adjust_orig = Some(self.palette.clone()); }
+                self.pal_adjust = PaletteAdjust { range: self.pal_adjust.range, ..Default::default() };
+                self.show_pal_adjust = true;
+            }
+            if ui.button(tr("对比/合并调色板...")).clicked() {
+                ui.close_menu();
+                self.show_pal_diff = true;
+            }
+            if ui.button(tr("整理调色板顺序...")).clicked() {
+                ui.close_menu();
+                self.pal_sort_order = (0..=255u8).collect();
+                self.show_pal_sort = true;
+            }
+            if ui.add_enabled(self.shp.is_some(), egui::Button::new(tr("生成阴影帧..."))).clicked() {
+                ui.close_menu();
+                if let Some(s) = self.palette.roles.shadow { self.shadow_params.index = s; }
+                self.show_shadow_dialog = true;
+            }
+            if ui.add_enabled(self.selected_frames.len() == 2, egui::Button::new(tr("补间..."))).on_disabled_hover_text(tr("先在时间轴上选中两帧")).clicked() {
+                ui.close_menu();
+                self.show_tween_dialog = true;
+            }
+            if ui.add_enabled(self.shp.is_some(), egui::Button::new(tr("生成朝向..."))).on_hover_text(tr("把帧旋转成 8/16/32 个朝向并追加到末尾，用于炮塔、抛射体")).clicked() {
+                ui.close_menu();
+                self.show_facings_dialog = true;
+            }
+            if ui.add_enabled(self.shp.is_some(), egui::Button::new(tr("重复帧分析..."))).clicked() {
+                ui.close_menu();
+                self.analyze_duplicates();
+                self.show_dedup = true;
+            }
+            ui.separator();
+            let has_sel = self.selection.is_some();
+            if ui.add_enabled(self.shp.is_some(), egui::Button::new(tr("全选"))).clicked() { ui.close_menu(); self.action_select_all(); }
+            if ui.add_enabled(has_sel, egui::Button::new(tr("取消选择"))).clicked() { ui.close_menu(); self.selection = None; }
+            if ui.add_enabled(has_sel, egui::Button::new(tr("反选"))).clicked() {
+                ui.close_menu();
+                if let Some(sel) = &mut self.selection { sel.invert(); }
+                if self.selection.as_ref().is_some_and(Selection::is_empty) { self.selection = None; }
+            }
+            if ui.add_enabled(has_sel, egui::Button::new(tr("用当前颜色填充选区"))).clicked() { ui.close_menu(); self.action_fill_selection(Some(self.brush_index)); }
+            if ui.add_enabled(has_sel, egui::Button::new(tr("删除选区内容"))).clicked() { ui.close_menu(); self.action_fill_selection(None); }
+            if ui.add_enabled(has_sel, egui::Button::new(tr("选区转为笔刷"))).on_hover_text(tr("保留原有索引，铅笔按原颜色盖章")).clicked() {
+                ui.close_menu();
+                self.action_brush_from_selection();
+            }
+            ui.separator();
+            if let Some(shp) = &mut self.shp {
+                ui.horizontal(|ui| {
+                    ui.label(tr("透明索引"));
+                    let changed = ui.add(egui::DragValue::new(&mut shp.transparent).clamp_range(0..=255)).changed();
+                    let (r, _) = ui.allocate_exact_size(egui::vec2(16.0, 14.0), Sense::hover());
+                    ui.painter().rect_filled(r, 0.0, self.palette.colors[shp.transparent as usize]);
+                    if changed { self.thumb_cache.clear(); }
+                }).response.on_hover_text(tr("该索引在预览、PNG 导出、橡皮和导入中视为透明（仅对当前文档有效）"));
+                ui.horizontal(|ui| {
+                    ui.label(tr("空帧写法"));
+                    for m in EmptyFrames::ALL {
+                        if ui.selectable_value(&mut shp.empty_frames, m, m.label()).changed() { self.dirty = true; }
+                    }
+                }).response.on_hover_text(tr("保存时没有像素的帧：帧头写 0 偏移，或写一个 1×1 透明块；有的程序把 0 偏移的帧当作缺失（仅对当前文档有效）"));
+            }
+        });
+
+        ui.menu_button(tr("图像"), |ui| {
+            let has = self.shp.is_some();
+            if ui.add_enabled(has, egui::Button::new(tr("画布大小..."))).clicked() {
+                ui.close_menu();
+                if let Some(shp) = &self.shp { self.canvas_new_w = shp.width; self.canvas_new_h = shp.height; }
+                self.show_canvas_size = true;
+            }
+            if ui.add_enabled(has, egui::Button::new(tr("重定画布..."))).on_hover_text(tr("按美术类型选择对齐方式，整段动画统一换到新画布尺寸")).clicked() {
+                ui.close_menu();
+                if let Some(shp) = &self.shp { self.retarget_w = shp.width; self.retarget_h = shp.height; }
+                self.show_retarget = true;
+            }
+            if ui.add_enabled(has, egui::Button::new(tr("缩放..."))).clicked() {
+                ui.close_menu();
+                if let Some(shp) = &self.shp { self.scale_new_w = shp.width; self.scale_new_h = shp.height; }
+                self.show_scale_dialog = true;
+            }
+            if ui.add_enabled(has, egui::Button::new(tr("裁剪到内容"))).clicked() {
+                ui.close_menu();
+                self.action_crop_to_content();
+            }
+            if ui.add_enabled(has, egui::Button::new(tr("描边..."))).clicked() {
+                ui.close_menu();
+                self.outline_index = self.brush_index;
+                self.show_outline_dialog = true;
+            }
+            if ui.add_enabled(has, egui::Button::new(tr("平移全部帧..."))).clicked() {
+                ui.close_menu();
+                self.show_shift_dialog = true;
+            }
+            if ui.add_enabled(has, egui::Button::new(tr("内容居中"))).clicked() {
+                ui.close_menu();
+                self.action_center_content();
+            }
+            ui.separator();
+            if ui.add_enabled(has, egui::Button::new(tr("生成图标 (cameo)..."))).clicked() {
+                ui.close_menu();
+                if self.cameo_pal.is_none() {
+                    self.cameo_pal = self.grouped_pals.iter().flat_map(|(_, items)| items).find(|(n, _)| n.eq_ignore_ascii_case("cameo")).cloned();
+                }
+                self.show_cameo_dialog = true;
+            }
+            ui.separator();
+            if ui.button(tr("脚本控制台...")).clicked() { ui.close_menu(); self.show_script_console = true; }
+            if ui.add_enabled(has, egui::Button::new(tr("运行脚本文件 (.rhai)..."))).clicked() {
+                ui.close_menu();
+                if let Some(path) = FileDialog::new().add_filter("Rhai", &["rhai"]).pick_file() {
+                    match std::fs::read_to_string(&path) {
+                        Ok(src) => self.action_run_script(&src),
+                        Err(e) => self.status = trf!("读取文件失败: {}", e),
+                    }
+                }
+            }
+        });
+
+        ui.menu_button(tr("滤镜"), |ui| {
+            for (i, f) in self.filters.iter().enumerate() {
+                if ui.add_enabled(self.shp.is_some(), egui::Button::new(format!("{}...", f.name()))).clicked() {
+                    ui.close_menu();
+                    self.filter_idx = i;
+                    self.show_filter_dialog = true;
+                }
+            }
+        });
+
+        ui.menu_button(tr("设置"), |ui| {
+            if ui.button(tr("快捷键...")).clicked() { ui.close_menu(); self.show_keymap_window = true; }
+            if ui.button(tr("使用统计...")).clicked() { ui.close_menu(); self.show_stats_window = true; }
+            if ui.button(tr("导出后执行命令...")).clicked() { ui.close_menu(); self.show_hook_window = true; }
+            ui.separator();
+            if ui.button(tr("检查更新...")).clicked() {
+                ui.close_menu();
+                self.update_result = None;
+                self.update_rx = Some(update::spawn_check());
+                self.show_update_window = true;
+            }
+            if ui.checkbox(&mut self.settings.check_updates, tr("启动时检查更新")).changed() { self.save_settings(); }
+            if ui.checkbox(&mut self.settings.watch_files, tr("外部修改打开的文件后自动重新加载")).changed() { self.save_settings(); }
+            if ui.checkbox(&mut self.settings.restore_session, tr("启动时重新打开上次的文档"))
+                .on_hover_text(tr("恢复文件、帧位置、缩放与调色板；通过命令行或文件关联打开文件时不恢复")).changed() { self.save_settings(); }
+            let mut report = !self.settings.skip_save_report;
+            if ui.checkbox(&mut report, tr("保存 SHP 前显示编码与体积")).changed() { self.settings.skip_save_report = !report; self.save_settings(); }
+            ui.menu_button(tr("选择内置PAL时"), |ui| {
+                for mode in PaletteSwitch::ALL {
+                    if ui.selectable_label(self.settings.pal_switch == mode, mode.label()).clicked() {
+                        self.settings.pal_switch = mode;
+                        self.save_settings();
+                        ui.close_menu();
+                    }
+                }
+            });
+            ui.menu_button(tr("语言"), |ui| {
+                for lang in Language::ALL {
+                    if ui.selectable_label(self.settings.language == lang, lang.native_name()).clicked() {
+                        self.settings.language = lang;
+                        i18n::set_language(lang);
+                        self.save_settings();
+                        ui.close_menu();
+                    }
+                }
+            });
+        });
+
+        ui.menu_button(tr("预览"), |ui| {
+            if ui.button(if self.preview.playing { tr("暂停") } else { tr("播放") }).clicked() {
+                let count = self.shp.as_ref().map_or(0, |s| s.frames.len());
+                self.preview.toggle(count);
+                ui.close_menu();
+            }
+            ui.add(egui::Slider::new(&mut self.preview.ms_per_frame, 30..=500).text(tr("间隔ms")));
+            ui.separator();
+            if ui.checkbox(&mut self.show_pal_gallery, tr("调色板预览墙")).clicked() { ui.close_menu(); }
+            if ui.checkbox(&mut self.show_color_usage, tr("颜色用量统计")).clicked() { ui.close_menu(); }
+            ui.menu_button(tr("色盲模拟"), |ui| {
+                for mode in ColorBlindMode::ALL {
+                    if ui.radio_value(&mut self.color_blind, mode, mode.label()).clicked() { ui.close_menu(); }
+                }
+            });
+            ui.menu_button(tr("HUD 遮挡区域"), |ui| {
+                ui.checkbox(&mut self.show_safe_area, tr("显示血条/选择框遮挡区域"));
+                ui.horizontal(|ui| {
+                    if ui.selectable_label(self.safe_area == SafeArea::INFANTRY, tr("步兵")).clicked() { self.safe_area = SafeArea::INFANTRY; }
+                    if ui.selectable_label(self.safe_area == SafeArea::VEHICLE, tr("载具")).clicked() { self.safe_area = SafeArea::VEHICLE; }
+                });
+                egui::Grid::new("safe_area_grid").num_columns(2).show(ui, |ui| {
+                    ui.label(tr("血条Y(相对中心)")); ui.add(egui::DragValue::new(&mut self.safe_area.bar_y).clamp_range(-512..=512)); ui.end_row();
+                    ui.label(tr("血条宽")); ui.add(egui::DragValue::new(&mut self.safe_area.bar_w).clamp_range(1..=512)); ui.end_row();
+                    ui.label(tr("血条高")); ui.add(egui::DragValue::new(&mut self.safe_area.bar_h).clamp_range(1..=64)); ui.end_row();
+                    ui.label(tr("选择框宽")); ui.add(egui::DragValue::new(&mut self.safe_area.box_w).clamp_range(1..=1024)); ui.end_row();
+                    ui.label(tr("选择框高")); ui.add(egui::DragValue::new(&mut self.safe_area.box_h).clamp_range(1..=1024)); ui.end_row();
+                });
+            });
+            ui.checkbox(&mut self.show_offsets, tr("按偏移显示"));
+            ui.checkbox(&mut self.show_minimap, tr("小地图"));
+            if ui.checkbox(&mut self.show_tile_preview, tr("平铺预览 (3×3)")).clicked() { ui.close_menu(); }
+            if ui.checkbox(&mut self.show_game_preview, tr("游戏比例预览")).clicked() { ui.close_menu(); }
+            if ui.checkbox(&mut self.show_frame_inspector, tr("帧属性检查器")).clicked() { ui.close_menu(); }
+            if ui.checkbox(&mut self.show_pal_split, tr("双调色板对比")).clicked() { ui.close_menu(); }
+            ui.menu_button(tr("画布背景"), |ui| {
+                let bg = &mut self.settings.canvas_bg;
+                let before = *bg;
+                ui.checkbox(&mut bg.game_black, tr("游戏内黑色背景预览"));
+                ui.add_enabled_ui(!bg.game_black, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut bg.solid, false, tr("棋盘格"));
+                        ui.radio_value(&mut bg.solid, true, tr("纯色"));
+                    });
+                    if bg.solid {
+                        ui.horizontal(|ui| { ui.label(tr("颜色")); ui.color_edit_button_srgb(&mut bg.color); });
+                    } else {
+                        ui.horizontal(|ui| {
+                            ui.label(tr("浅色")); ui.color_edit_button_srgb(&mut bg.checker_light);
+                            ui.label(tr("深色")); ui.color_edit_button_srgb(&mut bg.checker_dark);
+                        });
+                        ui.add(egui::Slider::new(&mut bg.checker_size, 2.0..=64.0).text(tr("格子大小")));
+                    }
+                    if ui.button(tr("恢复默认")).clicked() { *bg = CanvasBackground { game_black: bg.game_black, ..Default::default() }; }
+                });
+                if *bg != before { self.save_settings(); }
+            });
+            ui.menu_button(tr("参考背景"), |ui| {
+                if ui.button(tr("加载背景图...")).clicked() { ui.close_menu(); self.action_load_bg_image(ctx); }
+                ui.add_enabled_ui(self.bg_image.is_some(), |ui| {
+                    ui.checkbox(&mut self.show_bg_image, tr("显示背景图"));
+                    ui.horizontal(|ui| {
+                        ui.label(tr("位置"));
+                        ui.add(egui::DragValue::new(&mut self.bg_pos.x).prefix("X ").speed(1.0));
+                        ui.add(egui::DragValue::new(&mut self.bg_pos.y).prefix("Y ").speed(1.0));
+                    });
+                    if ui.button(tr("居中到画布")).clicked() && let (Some(tex), Some(shp)) = (&self.bg_image, &self.shp) {
+                        self.bg_pos = (egui::vec2(shp.width as f32, shp.height as f32) - tex.size_vec2()) / 2.0;
+                    }
+                    if ui.button(tr("移除背景图")).clicked() { ui.close_menu(); self.bg_image = None; }
+                });
+                ui.label(RichText::new(tr("在画布上按住中键拖动可移动背景")).small().color(Color32::GRAY));
+            });
+            ui.menu_button(tr("透视辅助线"), |ui| {
+                ui.checkbox(&mut self.show_guides, tr("显示辅助线"));
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.guides.mode, GuideMode::Isometric, tr("等角 2:1"));
+                    ui.selectable_value(&mut self.guides.mode, GuideMode::TwoPoint, tr("两点透视"));
+                });
+                egui::Grid::new("guides_grid").num_columns(2).show(ui, |ui| {
+                    match self.guides.mode {
+                        GuideMode::Isometric => {
+                            ui.label(tr("间距")); ui.add(egui::DragValue::new(&mut self.guides.spacing).clamp_range(2..=256)); ui.end_row();
+                        }
+                        GuideMode::TwoPoint => {
+                            for (label, vp) in [(tr("消失点1"), &mut self.guides.vp1), (tr("消失点2"), &mut self.guides.vp2)] {
+                                ui.label(label);
+                                ui.horizontal(|ui| {
+                                    ui.add(egui::DragValue::new(&mut vp.x).clamp_range(-4096.0..=4096.0).prefix("X "));
+                                    ui.add(egui::DragValue::new(&mut vp.y).clamp_range(-4096.0..=4096.0).prefix("Y "));
+                                });
+                                ui.end_row();
+                            }
+                            ui.label(tr("射线数")); ui.add(egui::DragValue::new(&mut self.guides.rays).clamp_range(2..=64)); ui.end_row();
+                        }
+                    }
+                });
+            });
+        });
+
+        // 顶部不再放工具菜单，遵循“左侧工具箱”设计
+
+        ui.separator();
+        ui.label(RichText::new(&self.status).color(Color32::LIGHT_GRAY));
+    }
+
+    // 调色板预览墙：把当前帧按每个内置PAL各渲染一张缩略图，点击即切换到该PAL
+    fn ui_pal_gallery(&mut self, ctx: &Context) {
+        let mut open = self.show_pal_gallery;
+        let mut picked: Option<(String, Palette)> = None;
+        egui::Window::new(tr("调色板预览墙")).open(&mut open).default_size(egui::vec2(640.0, 480.0)).show(ctx, |ui| {
+            let Some(shp) = &self.shp else { ui.label(tr("当前没有SHP")); return; };
+            let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+            // 缓存键：帧内容 + 帧号 + 亮度 + 调色板列表（MIX 导入、用户文件夹会在运行时增删），任一变化才重建纹理
+            let key = {
+                use std::hash::{Hash, Hasher};
+                let mut h = std::collections::hash_map::DefaultHasher::new();
+                shp.frames[fi].content_hash().hash(&mut h);
+                fi.hash(&mut h);
+                self.brightness.to_bits().hash(&mut h);
+                for (group, items) in &self.grouped_pals {
+                    (group, items.len()).hash(&mut h);
+                    for (name, _) in items { name.hash(&mut h); }
+                }
+                h.finish()
+            };
+            if self.pal_gallery_cache.as_ref().is_none_or(|(k, _)| *k != key) {
+                let mut texs = Vec::new();
+                for (group, items) in &self.grouped_pals {
+                    for (name, pal) in items {
+                        texs.push(shp.egui_texture_named(ctx, &format!("pal_gallery_{}_{}", group, name), fi, pal, self.brightness));
+                    }
+                }
+                self.pal_gallery_cache = Some((key, texs));
+            }
+            let Some((_, texs)) = &self.pal_gallery_cache else { return; };
+            let thumb = 96.0f32;
+            let k = thumb / (shp.width.max(shp.height) as f32);
+            let size = egui::vec2(shp.width as f32 * k, shp.height as f32 * k);
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                let mut ti = 0usize;
+                for (group, items) in &self.grouped_pals {
+                    ui.label(RichText::new(group).strong());
+                    ui.horizontal_wrapped(|ui| {
+                        for (name, pal) in items {
+                            let Some(tex) = texs.get(ti) else { break; };
+                            ti += 1;
+                            ui.vertical(|ui| {
+                                let (rect, resp) = ui.allocate_exact_size(size, Sense::click());
+                                ui.painter().rect_filled(rect, 0.0, Color32::from_gray(70));
+                                ui.painter().image(tex.id(), rect, egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)), Color32::WHITE);
+                                if self.current_pal_name == *name { ui.painter().rect_stroke(rect, 0.0, egui::Stroke::new(2.0, Color32::YELLOW)); }
+                                if resp.on_hover_text(format!("{}/{}", group, name)).clicked() { picked = Some((name.clone(), pal.clone())); }
+                                ui.label(RichText::new(name).small());
+                            });
+                        }
+                    });
+                    ui.separator();
+                }
+            });
+        });
+        if let Some((name, pal)) = picked {
+            self.save_undo_palette();
+            self.palette = pal;
+            self.current_pal_name = name;
+            self.dirty = true; // 与菜单切换PAL一致
+        }
+        self.show_pal_gallery = open;
+        if !open { self.pal_gallery_cache = None; }
+    }
+
+    // 颜色用量：各索引的像素数（整个文件时另计用到它的帧数），点击一行在画布上高亮该索引的像素
+    fn ui_color_usage(&mut self, ctx: &Context) {
+        let mut open = self.show_color_usage;
+        let mut jump: Option<usize> = None;
+        egui::Window::new(tr("颜色用量统计")).open(&mut open).default_width(360.0).show(ctx, |ui| {
+            let Some(shp) = &self.shp else { ui.label(tr("当前没有SHP")); return; };
+            let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.color_usage_all, false, tr("当前帧"));
+                ui.radio_value(&mut self.color_usage_all, true, tr("整个文件"));
+            });
+            let frames = if self.color_usage_all { &shp.frames[..] } else { &shp.frames[fi..=fi] };
+            let mut pixels = [0usize; 256];
+            let mut in_frames = [0usize; 256];
+            for fr in frames {
+                let mut seen = [false; 256];
+                for &p in &fr.pixels { pixels[p as usize] += 1; seen[p as usize] = true; }
+                for (n, s) in in_frames.iter_mut().zip(seen) { *n += s as usize; }
+            }
+            let used: Vec<u8> = (0..=255u8).filter(|&i| i != shp.transparent && pixels[i as usize] > 0).collect();
+            ui.label(trf!("使用 {} 种颜色（不含透明索引）", used.len()));
+            if let Some(idx) = self.usage_highlight {
+                ui.horizontal(|ui| {
+                    ui.label(trf!("高亮索引 {}", idx));
+                    // 从当前帧之后循环查找下一个含该索引的帧
+                    if ui.button(tr("下一个含此索引的帧")).clicked() {
+                        let n = shp.frames.len();
+                        jump = (1..=n).map(|k| (fi + k) % n).find(|&i| shp.frames[i].pixels.contains(&idx));
+                    }
+                    if ui.button(tr("取消高亮")).clicked() { self.usage_highlight = None; }
+                });
+            }
+            ui.separator();
+            egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                egui::Grid::new("color_usage_grid").striped(true).num_columns(4).show(ui, |ui| {
+                    ui.strong(tr("索引")); ui.strong(tr("像素数"));
+                    ui.strong(if self.color_usage_all { tr("帧数") } else { "" }); ui.strong(tr("类型")); ui.end_row();
+                    for &i in &used {
+                        ui.horizontal(|ui| {
+                            let (r, _) = ui.allocate_exact_size(egui::vec2(14.0, 14.0), Sense::hover());
+                            ui.painter().rect_filled(r, 0.0, self.palette.colors[i as usize]);
+                            let on = self.usage_highlight == Some(i);
+                            if ui.selectable_label(on, i.to_string()).on_hover_text(tr("在画布上高亮此索引的像素")).clicked() {
+                                self.usage_highlight = (!on).then_some(i);
+                            }
+                        });
+                        ui.label(format_number(pixels[i as usize]));
+                        ui.label(if self.color_usage_all { in_frames[i as usize].to_string() } else { String::new() });
+                        // 阴影/阵营色等特殊索引醒目标出，便于发现误用
+                        let role = self.palette.roles.role(i);
+                        if role == PaletteRole::Normal { ui.label(""); } else { ui.label(RichText::new(role.label()).color(Color32::YELLOW)); }
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+        if let Some(i) = jump { self.preview.current_frame = i; }
+        self.show_color_usage = open;
+        if !open { self.usage_highlight = None; }
+    }
+
+    // 文字工具：参数或颜色变化时重新栅格化并生成预览纹理；TTF 字体数据首次使用时读取
+    fn update_text_preview(&mut self, ctx: &Context) {
+        if self.text_params.font == TextFont::Ttf && self.text_font_data.is_none() { self.text_font_data = Some(ui_font_bytes()); }
+        let key = (self.text_params.clone(), self.brush_index);
+        if self.text_preview.as_ref().is_some_and(|t| t.key == key) { return; }
+        let mask = pixel_font::rasterize(&self.text_params, self.text_font_data.as_deref().unwrap_or_default());
+        let color = self.palette.colors[self.brush_index as usize];
+        let tex = mask.as_ref().map(|(w, h, m)| {
+            let img = egui::ColorImage { size: [*w as usize, *h as usize], pixels: m.iter().map(|&b| if b { color } else { Color32::TRANSPARENT }).collect() };
+            ctx.load_texture("text_preview", img, egui::TextureOptions::NEAREST)
+        });
+        self.text_preview = Some(TextPreview { key, mask, tex });
+    }
+
+    // 画布右键菜单：针对右键处像素的快捷操作；点击菜单外或按 Esc 关闭
+    fn ui_canvas_menu(&mut self, ctx: &Context) {
+        let Some((pos, (x, y))) = self.canvas_menu else { return; };
+        let Some(shp) = &self.shp else { self.canvas_menu = None; return; };
+        let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+        let inside = x >= 0 && y >= 0 && (x as u32) < shp.width && (y as u32) < shp.height;
+        let idx = inside.then(|| shp.frames[fi].pixels[(y as u32 * shp.width + x as u32) as usize]);
+        let mut close = false;
+        let area = egui::Area::new("canvas_menu".into()).order(egui::Order::Foreground).fixed_pos(pos).show(ctx, |ui| {
+            egui::Frame::menu(ui.style()).show(ui, |ui| {
+                ui.set_min_width(180.0);
+                ui.label(RichText::new(match idx { Some(i) => format!("({}, {}) · #{}", x, y, i), None => format!("({}, {})", x, y) }).weak());
+                ui.separator();
+                if let Some(i) = idx {
+                    if ui.button(tr("取此处颜色")).clicked() { self.brush_index = i; close = true; }
+                    if ui.button(tr("取此处颜色为副颜色")).clicked() { self.brush_index2 = i; close = true; }
+                    if ui.button(tr("在调色板中定位此索引")).clicked() { self.pal_flash = Some((i, Instant::now())); close = true; }
+                    if ui.button(trf!("从此处填充（索引 {}）", self.brush_index)).clicked() { self.action_fill_at(fi, x, y); close = true; }
+                }
+                if self.import_img.is_some() && ui.button(tr("把导入图片居中到此处")).clicked() {
+                    if let Some(img) = &self.import_img {
+                        let k = self.import_scale / self.import_downscale as f32;
+                        self.import_pos = egui::pos2(x as f32 - img.width() as f32 * k / 2.0, y as f32 - img.height() as f32 * k / 2.0);
+                    }
+                    close = true;
+                }
+                if ui.button(tr("复制坐标")).clicked() {
+                    ctx.output_mut(|o| o.copied_text = format!("{}, {}", x, y));
+                    self.status = trf!("已复制坐标 {}, {}", x, y);
+                    close = true;
+                }
+            });
+        });
+        let outside = ctx.input(|i| i.pointer.any_pressed() && i.pointer.interact_pos().is_some_and(|p| !area.response.rect.contains(p)));
+        if close || outside || ctx.input(|i| i.key_pressed(egui::Key::Escape)) { self.canvas_menu = None; }
+    }
+
+    // 在指定帧的像素处以主颜色填充；有选区时只改选区内
+    fn action_fill_at(&mut self, fi: usize, x: i32, y: i32) {
+        self.save_undo_frames(&[fi]);
+        let Some(shp) = &mut self.shp else { return; };
+        let before = shp.frames[fi].pixels.clone();
+        let fill = Fill::new(self.fill_pattern, self.brush_index, self.brush_index2, self.fill_ratio, self.custom_brush.as_ref());
+        Self::flood_fill_on_frame(shp, fi, x, y, fill, self.wrap_draw, self.fill_diagonal);
+        if let Some(sel) = &self.selection { sel.clip(&mut shp.frames[fi].pixels, &before); }
+        self.dirty = true;
+        self.record_op("填充", Some(fi));
+    }
+
+    // 小地图：画布超出可视区时在右下角显示整帧缩略与当前可视范围，点击或拖动可平移画布
+    fn ui_minimap(&mut self, ui: &mut egui::Ui, view: &egui::scroll_area::ScrollAreaOutput<()>, tex: Option<egui::TextureHandle>) {
+        self.minimap_rect = None;
+        let (inner, content, offset) = (view.inner_rect, view.content_size, view.state.offset);
+        let Some(tex) = tex.filter(|_| self.show_minimap && (content.x > inner.width() + 1.0 || content.y > inner.height() + 1.0)) else { return; };
+        let k = 160.0 / content.x.max(content.y);
+        let map = egui::Rect::from_min_size(inner.max - content * k - egui::vec2(12.0, 12.0), content * k);
+        let painter = ui.painter_at(inner);
+        painter.rect_filled(map.expand(2.0), 2.0, Color32::from_black_alpha(180));
+        painter.image(tex.id(), map, egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)), Color32::WHITE);
+        let shown = egui::Rect::from_min_size(map.min + offset * k, inner.size() * k).intersect(map);
+        painter.rect_stroke(shown, 0.0, egui::Stroke::new(1.5, Color32::YELLOW));
+        let resp = ui.interact(map, ui.id().with("minimap"), Sense::click_and_drag());
+        if let Some(pos) = resp.interact_pointer_pos().filter(|_| resp.is_pointer_button_down_on()) {
+            // 以指针处为可视区中心
+            let target = (pos - map.min) / k - inner.size() / 2.0;
+            self.canvas_scroll_to = Some(target.max(egui::Vec2::ZERO).min((content - inner.size()).max(egui::Vec2::ZERO)));
+            ui.ctx().request_repaint();
+        }
+        self.minimap_rect = Some(map.expand(2.0));
+    }
+
+    // 时间轴：可横向滚动的帧缩略图条。单击跳转，Ctrl+单击多选，Shift+单击连选
+    fn ui_timeline(&mut self, ui: &mut egui::Ui) {
+        let Some(shp) = &self.shp else { return; };
+        let count = shp.frames.len();
+        if self.thumb_cache.len() != count { self.thumb_cache.resize_with(count, || None); }
+        self.selected_frames.retain(|&i| i < count);
+        let pal_key = {
+            use std::hash::{Hash, Hasher};
+            let mut h = std::collections::hash_map::DefaultHasher::new();
+            self.palette.to_bytes().hash(&mut h);
+            self.brightness.to_bits().hash(&mut h);
+            h.finish()
+        };
+        let thumb = 48.0f32;
+        let cell = egui::vec2(thumb + 6.0, thumb + 18.0);
+        let cur = self.preview.current_frame.min(count.saturating_sub(1));
+        let follow = self.timeline_last_frame != cur;
+        self.timeline_last_frame = cur;
+        let mut clicked: Option<(usize, Modifiers)> = None;
+        let mut batch: Option<&'static str> = None;
+        egui::ScrollArea::horizontal().id_source("timeline").show_viewport(ui, |ui, viewport| {
+            let (strip, _) = ui.allocate_exact_size(egui::vec2(cell.x * count as f32, cell.y), Sense::hover());
+            let first = (viewport.min.x / cell.x).floor().max(0.0) as usize;
+            let last = ((viewport.max.x / cell.x).ceil() as usize + 1).min(count);
+            for i in first..last {
+                let r = egui::Rect::from_min_size(strip.min + egui::vec2(i as f32 * cell.x, 0.0), cell);
+                // 仅为可见帧计算内容键，避免每帧对全部像素做哈希
+                let key = shp.frames[i].content_hash() ^ pal_key;
+                if self.thumb_cache[i].as_ref().is_none_or(|(k, _)| *k != key) {
+                    let img = shp.thumbnail_image(i, &self.palette, self.brightness, thumb as u32);
+                    self.thumb_cache[i] = Some((key, ui.ctx().load_texture(format!("thumb_{}", i), img, egui::TextureOptions::NEAREST)));
+                }
+                let resp = ui.interact(r, ui.id().with(("thumb", i)), Sense::click());
+                let selected = self.selected_frames.contains(&i);
+                let bg = if i == cur { Color32::from_rgb(70, 90, 140) } else if selected { Color32::from_rgb(60, 70, 90) } else { Color32::from_gray(40) };
+                ui.painter().rect_filled(r.shrink(1.0), 2.0, bg);
+                if let Some((_, tex)) = &self.thumb_cache[i] {
+                    let ts = tex.size_vec2();
+                    let img_rect = egui::Rect::from_center_size(egui::pos2(r.center().x, r.min.y + 3.0 + thumb / 2.0), ts);
+                    ui.painter().image(tex.id(), img_rect, egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)), Color32::WHITE);
+                }
+                ui.painter().text(egui::pos2(r.center().x, r.max.y - 2.0), egui::Align2::CENTER_BOTTOM, i.to_string(), egui::FontId::proportional(11.0), Color32::LIGHT_GRAY);
+                // 保存时的空帧：左上角标记，悬停说明写法
+                let resp = if shp.saves_as_empty(i) {
+                    ui.painter().text(r.min + egui::vec2(4.0, 3.0), egui::Align2::LEFT_TOP, "∅", egui::FontId::proportional(11.0), Color32::from_rgb(255, 170, 60));
+                    resp.on_hover_text(trf!("空帧：保存时写为{}", shp.empty_frames.label()))
+                } else { resp };
+                if selected { ui.painter().rect_stroke(r.shrink(1.0), 2.0, egui::Stroke::new(1.5, Color32::LIGHT_BLUE)); }
+                if i == cur { ui.painter().rect_stroke(r.shrink(1.0), 2.0, egui::Stroke::new(2.0, Color32::YELLOW)); }
+                if follow && i == cur { ui.scroll_to_rect(r, None); }
+                if resp.clicked() { clicked = Some((i, ui.input(|inp| inp.modifiers))); }
+                resp.context_menu(|ui| {
+                    if ui.button(tr("清空选中帧")).clicked() { batch = Some("clear"); ui.close_menu(); }
+                    if ui.button(tr("复制选中帧到末尾")).clicked() { batch = Some("dup"); ui.close_menu(); }
+                    if ui.button(tr("删除选中帧")).clicked() { batch = Some("delete"); ui.close_menu(); }
+                    if self.selected_frames.len() == 2 && ui.button(tr("补间...")).clicked() { batch = Some("tween"); ui.close_menu(); }
+                });
+            }
+            // 当前帧不在可见范围时仍需滚动到位
+            if follow && (cur < first || cur >= last) {
+                let r = egui::Rect::from_min_size(strip.min + egui::vec2(cur as f32 * cell.x, 0.0), cell);
+                ui.scroll_to_rect(r, None);
+            }
+        });
+        if let Some((i, mods)) = clicked {
+            if mods.command {
+                if !self.selected_frames.remove(&i) { self.selected_frames.insert(i); }
+            } else if mods.shift {
+                let (a, b) = if i < cur { (i, cur) } else { (cur, i) };
+                self.selected_frames.extend(a..=b);
+            } else {
+                self.selected_frames.clear();
+                self.selected_frames.insert(i);
+            }
+            self.preview.current_frame = i;
+        }
+        match batch {
+            Some("clear") => self.action_clear_selected_frames(),
+            Some("dup") => self.action_duplicate_selected_frames(),
+            Some("delete") => self.action_delete_selected_frames(),
+            Some("tween") => self.show_tween_dialog = true,
+            _ => {}
+        }
+    }
+
+    // 批量操作的目标帧：有多选时用多选，否则为当前帧
+    fn batch_target_frames(&self) -> Vec<usize> {
+        let Some(shp) = &self.shp else { return Vec::new(); };
+        if self.selected_frames.is_empty() {
+            vec![self.preview.current_frame.min(shp.frames.len().saturating_sub(1))]
+        } else {
+            self.selected_frames.iter().copied().filter(|&i| i < shp.frames.len()).collect()
+        }
+    }
+
+    // 播放控制：播放/暂停、间隔、循环方式、倒放与播放范围
+    fn ui_playback(&mut self, ui: &mut egui::Ui) {
+        let count = self.frame_count();
+        if ui.button(if self.preview.playing { tr("暂停") } else { tr("播放") }).clicked() {
+            self.preview.toggle(count);
+        }
+        ui.add(egui::Slider::new(&mut self.preview.ms_per_frame, 30..=500).text(tr("间隔ms")));
+        egui::ComboBox::from_id_source("loop_mode").selected_text(self.preview.loop_mode.label()).show_ui(ui, |ui| {
+            for mode in LoopMode::ALL { ui.selectable_value(&mut self.preview.loop_mode, mode, mode.label()); }
+        });
+        ui.checkbox(&mut self.preview.backwards, tr("倒放"));
+        // 序列：选中即把播放范围设为该序列；范围与某个序列一致时显示其名称
+        let cur = self.preview.range.and_then(|r| self.sequences.iter().find(|s| s.bounds(count) == r));
+        let shown = cur.map_or_else(|| tr("（全部帧）").to_owned(), |s| s.name.clone());
+        egui::ComboBox::from_id_source("sequence").selected_text(shown).show_ui(ui, |ui| {
+            if ui.selectable_label(self.preview.range.is_none(), tr("（全部帧）")).clicked() { self.preview.range = None; }
+            for s in &self.sequences {
+                let r = s.bounds(count);
+                if ui.selectable_label(self.preview.range == Some(r), format!("{} ({}–{})", s.name, r.0, r.1)).clicked() {
+                    self.preview.range = Some(r);
+                    self.preview.current_frame = r.0;
+                }
+            }
+        });
+        if ui.button(tr("序列...")).clicked() { self.show_sequences = true; }
+        let mut limited = self.preview.range.is_some();
+        if ui.checkbox(&mut limited, tr("限定范围")).changed() {
+            self.preview.range = limited.then(|| self.preview.bounds(count));
+        }
+        if let Some((a, b)) = &mut self.preview.range {
+            let last = count.saturating_sub(1);
+            ui.add(egui::DragValue::new(a).clamp_range(0..=last).prefix(tr("从 ")));
+            ui.add(egui::DragValue::new(b).clamp_range(0..=last).prefix(tr("到 ")));
+            if let (Some(&first), Some(&end)) = (self.selected_frames.first(), self.selected_frames.last())
+                && ui.button(tr("用选中帧")).on_hover_text(tr("把播放范围设为选中帧的首尾")).clicked()
+            {
+                (*a, *b) = (first, end);
+            }
+        }
+    }
+
+    // 序列管理：名称与首尾帧；“帧数”一栏即 art.ini 中按起始帧引用动作时需要的长度
+    fn ui_sequences(&mut self, ctx: &Context) {
+        let mut open = self.show_sequences;
+        let count = self.frame_count();
+        let mut changed = false;
+        let mut save_now = false;
+        egui::Window::new(tr("动画序列")).open(&mut open).resizable(false).show(ctx, |ui| {
+            let mut remove = None;
+            egui::Grid::new("sequences_grid").num_columns(6).striped(true).show(ui, |ui| {
+                ui.label(tr("名称")); ui.label(tr("起始帧")); ui.label(tr("结束帧")); ui.label(tr("帧数")); ui.label(""); ui.label(""); ui.end_row();
+                let last = count.saturating_sub(1);
+                for (i, s) in self.sequences.iter_mut().enumerate() {
+                    changed |= ui.add(egui::TextEdit::singleline(&mut s.name).desired_width(110.0)).changed();
+                    changed |= ui.add(egui::DragValue::new(&mut s.start).clamp_range(0..=last)).changed();
+                    changed |= ui.add(egui::DragValue::new(&mut s.end).clamp_range(0..=last)).changed();
+                    ui.label(s.len().to_string());
+                    if ui.small_button(tr("播放")).clicked() {
+                        let r = s.bounds(count);
+                        self.preview.range = Some(r);
+                        self.preview.current_frame = r.0;
+                        self.preview.playing = true;
+                    }
+                    if ui.small_button("×").on_hover_text(tr("删除序列")).clicked() { remove = Some(i); }
+                    ui.end_row();
+                }
+            });
+            if let Some(i) = remove { self.sequences.remove(i); changed = true; }
+            ui.horizontal(|ui| {
+                if ui.button(tr("添加序列")).on_hover_text(tr("范围取选中帧的首尾，没有选中帧时取当前播放范围")).clicked() {
+                    let (start, end) = match (self.selected_frames.first(), self.selected_frames.last()) {
+                        (Some(&a), Some(&b)) => (a, b),
+                        _ => self.preview.bounds(count),
+                    };
+                    self.sequences.push(Sequence { name: format!("Seq{}", self.sequences.len() + 1), start, end });
+                    changed = true;
+                }
+                if ui.add_enabled(self.current_path.is_some(), egui::Button::new(tr("立即保存序列文件"))).clicked() { save_now = true; }
+            });
+            let note = match &self.current_path {
+                Some(p) => trf!("保存 SHP 时一并写入 {}", sequences::sidecar_path(p).display()),
+                None => tr("保存 SHP 后写入同名 .seq.json 文件").to_owned(),
+            };
+            ui.label(RichText::new(note).small().color(Color32::GRAY));
+        });
+        self.show_sequences = open;
+        if changed { self.dirty = true; }
+        if save_now && let Some(p) = &self.current_path {
+            self.status = match sequences::save(p, &self.sequences) {
+                Ok(()) => trf!("已保存序列文件: {}", sequences::sidecar_path(p).display()),
+                Err(e) => trf!("保存序列文件失败: {}", e),
+            };
+        }
+    }
+
+    // 帧属性：当前帧的对齐偏移（保存时写入帧头 x/y）
+    fn ui_frame_props(&mut self, ui: &mut egui::Ui) {
+        let targets = self.batch_target_frames();
+        let Some(shp) = &self.shp else { return; };
+        let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+        let mut off = shp.frames[fi].offset;
+        let mut sync = false;
+        ui.horizontal(|ui| {
+            ui.label(tr("帧偏移"));
+            let rx = ui.add(egui::DragValue::new(&mut off.0).prefix("X ").clamp_range(-1024..=1024));
+            let ry = ui.add(egui::DragValue::new(&mut off.1).prefix("Y ").clamp_range(-1024..=1024));
+            // 拖动开始或键入修改时记一次撤销点，拖动过程中不重复记录
+            let (edited, dragging) = (rx.changed() || ry.changed(), rx.dragged() || ry.dragged());
+            if rx.drag_started() || ry.drag_started() || (edited && !dragging) { self.save_undo_offsets(&[fi]); }
+            if edited && let Some(shp) = &mut self.shp { shp.frames[fi].offset = off; self.dirty = true; }
+            let Some(shp) = &self.shp else { return; };
+            if targets.len() > 1 && ui.button(trf!("应用到选中的 {} 帧", targets.len())).clicked() { sync = true; }
+            ui.checkbox(&mut self.show_offsets, tr("按偏移显示"))
+                .on_hover_text(tr("画布按帧偏移平移显示，便于检查开火等动画的对齐"));
+            if let Some((x, y, w, h)) = shp.frame_bounds(fi) {
+                let tip = trf!("保存时帧头: x={} y={} w={} h={}", x as i32 + off.0, y as i32 + off.1, w, h);
+                ui.label(RichText::new(tip).small().color(Color32::GRAY));
+            }
+        });
+        if sync {
+            self.save_undo_offsets(&targets);
+            let Some(shp) = &mut self.shp else { return; };
+            for &i in &targets { shp.frames[i].offset = off; }
+            self.dirty = true;
+            self.record_op("帧偏移", None);
+        }
+    }
+
+    // 当前帧在原文件中的存储信息与保存时将写入的内容，用于排查帧在游戏中显示异常
+    fn ui_frame_inspector(&mut self, ctx: &Context) {
+        let mut open = self.show_frame_inspector;
+        egui::Window::new(tr("帧属性检查器")).open(&mut open).default_width(340.0).show(ctx, |ui| {
+            let Some(shp) = &mut self.shp else { ui.label(tr("当前没有SHP")); return; };
+            let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+            let (cw, ch) = (shp.width, shp.height);
+            ui.strong(trf!("第 {} 帧 / 共 {} 帧", fi, shp.frames.len()));
+            ui.separator();
+            ui.label(RichText::new(tr("打开文件时")).strong());
+            match shp.frames[fi].source {
+                None => { ui.weak(tr("该帧不是从文件读取的（新建、导入或生成）")); }
+                Some(src) => {
+                    let (x, y, w, h) = src.rect;
+                    let empty = src.data_offset == 0 || w == 0 || h == 0;
+                    egui::Grid::new("inspector_source").num_columns(2).striped(true).show(ui, |ui| {
+                        ui.label(tr("存储格式")); ui.label(format!("{} (0x{:02X})", src.format_label(), src.flags)); ui.end_row();
+                        ui.label(tr("帧头矩形")); ui.label(format!("x={} y={} w={} h={}", x, y, w, h)); ui.end_row();
+                        ui.label(tr("数据偏移")); ui.label(format!("{} (0x{:X})", format_number(src.data_offset as usize), src.data_offset)); ui.end_row();
+                        ui.label(tr("解码大小")); ui.label(trf!("{} 字节", format_number(src.decoded_len()))); ui.end_row();
+                        ui.label(tr("空帧")); ui.label(if empty { tr("是") } else { tr("否") }); ui.end_row();
+                    });
+                    // 常见的游戏内异常来源
+                    if !empty && (x as u32 + w as u32 > cw || y as u32 + h as u32 > ch) {
+                        ui.colored_label(Color32::YELLOW, tr("帧头矩形超出画布，超出部分读取时已丢弃"));
+                    }
+                    if src.data_offset == 0 && w > 0 && h > 0 {
+                        ui.colored_label(Color32::YELLOW, tr("帧头有尺寸但数据偏移为 0，游戏中按空帧处理"));
+                    }
+                    if !src.td && src.flags & 3 == 2 {
+                        ui.weak(tr("逐行格式保存时按当前编码方式重新写入"));
+                    }
+                }
+            }
+            ui.separator();
+            ui.label(RichText::new(tr("当前")).strong());
+            let bounds = shp.frame_bounds(fi);
+            let info = shp.frame_save_info(fi);
+            let fr = &mut shp.frames[fi];
+            egui::Grid::new("inspector_current").num_columns(2).striped(true).show(ui, |ui| {
+                ui.label(tr("解码大小")); ui.label(trf!("{} 字节", format_number((cw * ch) as usize))); ui.end_row();
+                ui.label(tr("内容范围"));
+                match bounds {
+                    Some((x, y, w, h)) => ui.label(format!("x={} y={} w={} h={}", x, y, w, h)),
+                    None => ui.label(tr("空帧")),
+                };
+                ui.end_row();
+                ui.label(tr("保存时帧头"));
+                match info {
+                    Some(((x, y, w, h), _)) => ui.label(format!("x={} y={} w={} h={}", x, y, w, h)),
+                    None => ui.label(tr("空帧（数据偏移写 0）")),
+                };
+                ui.end_row();
+                if let Some((_, (raw, rle))) = info {
+                    ui.label(tr("编码"));
+                    // 直接修改保存时的编码方式，与保存报告中的选择一致
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut fr.compression, Compression::Uncompressed, tr("未压缩"));
+                        ui.add_enabled_ui(rle != usize::MAX, |ui| { ui.radio_value(&mut fr.compression, Compression::Rle0, "RLE0"); });
+                    });
+                    ui.end_row();
+                    ui.label(tr("数据块大小"));
+                    let size = if fr.compression == Compression::Rle0 { rle } else { raw };
+                    ui.label(trf!("{} 字节", format_number(size)));
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+            self.ui_frame_props(ui);
+        });
+        self.show_frame_inspector = open;
+    }
+
+    fn action_clear_selected_frames(&mut self) {
+        let targets = self.batch_target_frames();
+        self.save_undo_frames(&targets);
+        if let Some(shp) = &mut self.shp {
+            for &i in &targets { shp.frames[i].pixels.fill(shp.transparent); }
+            self.dirty = true;
+            self.status = trf!("已清空 {} 帧", targets.len());
+            self.record_op("清空帧", None);
+        }
+    }
+
+    // 追加帧前检查帧数是否仍能写入 SHP 文件头
+    fn check_frame_room(&mut self, extra: usize) -> bool {
+        let Some(shp) = &self.shp else { return false; };
+        match SHP::check_limits(shp.width, shp.height, shp.frames.len() + extra) {
+            Ok(()) => true,
+            Err(e) => { self.status = e; false }
+        }
+    }
+
+    fn action_duplicate_selected_frames(&mut self) {
+        let targets = self.batch_target_frames();
+        if !self.check_frame_room(targets.len()) { return; }
+        self.save_undo_document(false);
+        if let Some(shp) = &mut self.shp {
+            for &i in &targets { let f = shp.frames[i].clone(); shp.frames.push(f); }
+            self.dirty = true;
+            self.status = trf!("已复制 {} 帧到末尾", targets.len());
+            self.record_op("复制帧", None);
+        }
+    }
+
+    fn action_delete_selected_frames(&mut self) {
+        let targets = self.batch_target_frames();
+        if self.shp.as_ref().is_some_and(|s| targets.len() >= s.frames.len()) { self.status = tr("至少需要保留一帧").into(); return; }
+        self.save_undo_document(false);
+        if let Some(shp) = &mut self.shp {
+            for &i in targets.iter().rev() { shp.frames.remove(i); }
+            self.preview.current_frame = self.preview.current_frame.min(shp.frames.len() - 1);
+            self.selected_frames.clear();
+            self.dirty = true;
+            self.status = trf!("已删除 {} 帧", targets.len());
+            self.record_op("删除帧", None);
+        }
+    }
+
+    // 范围对应的帧列表
+    fn scope_frames(&self, scope: FrameScope) -> Vec<usize> {
+        let Some(shp) = &self.shp else { return Vec::new(); };
+        match scope {
+            FrameScope::Current => vec![self.preview.current_frame.min(shp.frames.len().saturating_sub(1))],
+            FrameScope::Selected => self.batch_target_frames(),
+            FrameScope::All => (0..shp.frames.len()).collect(),
+        }
+    }
+
+    // 范围选择控件：三个单选项
+    fn ui_scope_selector(ui: &mut egui::Ui, scope: &mut FrameScope) {
+        ui.horizontal(|ui| {
+            ui.radio_value(scope, FrameScope::Current, tr("当前帧"));
+            ui.radio_value(scope, FrameScope::Selected, tr("选中帧"));
+            ui.radio_value(scope, FrameScope::All, tr("所有帧"));
+        });
+    }
+
+    // 替换颜色：把索引 A 全部换成索引 B（当前帧可撤销）
+    fn ui_replace_dialog(&mut self, ctx: &Context) {
+        let mut open = self.show_replace_dialog;
+        let mut apply = false;
+        egui::Window::new(tr("替换颜色")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.vertical(|ui| {
+                    ui.label(trf!("原索引 A = {}", self.replace_from));
+                    palette_picker(ui, &self.palette, &mut self.replace_from, "replace_from");
+                });
+                ui.separator();
+                ui.vertical(|ui| {
+                    ui.label(trf!("新索引 B = {}", self.replace_to));
+                    palette_picker(ui, &self.palette, &mut self.replace_to, "replace_to");
+                });
+            });
+            ui.separator();
+            Self::ui_scope_selector(ui, &mut self.replace_scope);
+            ui.add_enabled(self.selection.is_some(), egui::Checkbox::new(&mut self.replace_in_selection, tr("仅限选区")));
+            ui.horizontal(|ui| {
+                if ui.add_enabled(self.replace_from != self.replace_to, egui::Button::new(tr("替换"))).clicked() { apply = true; }
+                if ui.button(tr("交换 A/B")).clicked() { std::mem::swap(&mut self.replace_from, &mut self.replace_to); }
+            });
+        });
+        self.show_replace_dialog = open;
+        if apply { self.action_replace_color(); }
+    }
+
+    fn action_select_all(&mut self) {
+        if let Some(shp) = &self.shp { self.selection = Some(Selection::all(shp.width, shp.height)); }
+    }
+
+    // 用索引填充当前帧的选区；None 表示填透明索引（删除）
+    fn action_fill_selection(&mut self, index: Option<u8>) {
+        let Some(sel) = &self.selection else { return; };
+        let Some(shp) = &self.shp else { return; };
+        let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+        let value = index.unwrap_or(shp.transparent);
+        let mask = sel.mask.clone();
+        self.save_undo_frames(&[fi]);
+        let Some(shp) = &mut self.shp else { return; };
+        for (p, m) in shp.frames[fi].pixels.iter_mut().zip(mask) { if m { *p = value; } }
+        self.dirty = true;
+        self.record_op(if index.is_some() { "填充选区" } else { "删除选区内容" }, Some(fi));
+    }
+
+    fn action_replace_color(&mut self) {
+        let frames = self.scope_frames(self.replace_scope);
+        self.save_undo_frames(&frames);
+        let Some(shp) = &mut self.shp else { return; };
+        let mut total = 0;
+        let mask = self.selection.as_ref().filter(|_| self.replace_in_selection).map(|s| &s.mask);
+        for &fi in &frames {
+            total += match mask {
+                // 仅替换选区内的像素
+                Some(mask) => shp.frames[fi].pixels.iter_mut().zip(mask).filter(|(p, m)| **m && **p == self.replace_from)
+                    .map(|(p, _)| *p = self.replace_to).count(),
+                None => shp.replace_index(fi, self.replace_from, self.replace_to),
+            };
+        }
+        if total > 0 { self.dirty = true; }
+        self.record_op("替换颜色", frames.first().copied().filter(|_| frames.len() == 1));
+        self.status = trf!("已将索引 {} 替换为 {}：{} 帧，{} 个像素", self.replace_from, self.replace_to, frames.len(), format_number(total));
+    }
+
+    // 调整调色板：实时预览写入 self.palette，取消/关闭窗口时恢复原调色板
+    fn ui_pal_adjust(&mut self, ctx: &Context) {
+        let Some(orig) = self.pal_adjust_orig.clone() else { self.show_pal_adjust = false; return; };
+        let mut open = self.show_pal_adjust;
+        let (mut apply, mut cancel) = (false, false);
+        let before = self.pal_adjust;
+        egui::Window::new(tr("调整调色板")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            let a = &mut self.pal_adjust;
+            ui.add(egui::Slider::new(&mut a.brightness, -1.0..=1.0).text(tr("亮度")));
+            ui.add(egui::Slider::new(&mut a.contrast, -1.0..=1.0).text(tr("对比度")));
+            ui.add(egui::Slider::new(&mut a.saturation, -1.0..=1.0).text(tr("饱和度")));
+            ui.add(egui::Slider::new(&mut a.hue, -180.0..=180.0).text(tr("色相")).suffix("°"));
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label(tr("索引范围"));
+                ui.add(egui::DragValue::new(&mut a.range.0).clamp_range(0..=255));
+                ui.label("–");
+                ui.add(egui::DragValue::new(&mut a.range.1).clamp_range(0..=255));
+            });
+            ui.horizontal(|ui| {
+                if ui.small_button(tr("全部")).clicked() { a.range = (0, 255); }
+                if let Some(r) = orig.roles.remap && ui.small_button(tr("阵营色")).clicked() { a.range = r; }
+                if ui.small_button(tr("当前颜色所在 16 色")).clicked() {
+                    let lo = self.brush_index & 0xF0;
+                    a.range = (lo, lo | 0x0F);
+                }
+            });
+            // 原色与调整后的色条对比
+            let (lo, hi) = (a.range.0.min(a.range.1) as usize, a.range.0.max(a.range.1) as usize);
+            let n = (hi - lo + 1) as f32;
+            let (strip, _) = ui.allocate_exact_size(egui::vec2(256.0, 24.0), Sense::hover());
+            let cw = strip.width() / n;
+            for (k, i) in (lo..=hi).enumerate() {
+                let x = strip.left() + k as f32 * cw;
+                let top = egui::Rect::from_min_size(egui::pos2(x, strip.top()), egui::vec2(cw.max(1.0), 12.0));
+                ui.painter().rect_filled(top, 0.0, orig.colors[i]);
+                ui.painter().rect_filled(top.translate(egui::vec2(0.0, 12.0)), 0.0, self.palette.colors[i]);
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button(tr("应用")).clicked() { apply = true; }
+                if ui.button(tr("取消")).clicked() { cancel = true; }
+                if ui.button(tr("重置")).clicked() { self.pal_adjust = PaletteAdjust { range: self.pal_adjust.range, ..Default::default() }; }
+            });
+        });
+        if self.pal_adjust != before { self.palette = self.pal_adjust.apply(&orig); }
+        if apply {
+            if !self.pal_adjust.is_identity() {
+                self.push_undo(Patch::Palette(Box::new(orig), self.current_pal_name.clone()));
+                self.dirty = true;
+                self.record_op("调整调色板", None);
+                self.status = tr("已调整调色板").into();
+            }
+            self.pal_adjust_orig = None;
+            self.show_pal_adjust = false;
+        } else if cancel || !open {
+            self.palette = orig;
+            self.pal_adjust_orig = None;
+            self.show_pal_adjust = false;
+        }
+    }
+
+    // 整理调色板顺序：自动排序或拖动格子调整，应用时按逆映射改写所有帧的索引，画面保持不变
+    fn ui_pal_sort(&mut self, ctx: &Context) {
+        if self.pal_sort_order.len() != 256 { self.pal_sort_order = (0..=255u8).collect(); }
+        let mut open = self.show_pal_sort;
+        let (mut apply, mut cancel) = (false, false);
+        egui::Window::new(tr("整理调色板顺序")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("pal_sort_by").selected_text(self.pal_sort_by.label()).show_ui(ui, |ui| {
+                    for by in PaletteSort::ALL { ui.selectable_value(&mut self.pal_sort_by, by, by.label()); }
+                });
+                if ui.button(tr("排序")).clicked() { self.pal_sort_order = self.palette.sorted_order(self.pal_sort_by, self.pal_sort_keep_special); }
+                if ui.button(tr("还原")).clicked() { self.pal_sort_order = (0..=255u8).collect(); }
+            });
+            ui.checkbox(&mut self.pal_sort_keep_special, tr("阴影、阵营色、循环色保持原位"));
+            ui.label(RichText::new(tr("拖动格子可手动调整位置；索引 0 固定不动")).small().color(Color32::GRAY));
+
+            const CELL: f32 = 18.0;
+            let (rect, resp) = ui.allocate_exact_size(egui::vec2(16.0 * CELL, 16.0 * CELL), Sense::drag());
+            let cell_at = |p: egui::Pos2| -> Option<usize> {
+                if !rect.contains(p) { return None; }
+                let (cx, cy) = (((p.x - rect.left()) / CELL) as usize, ((p.y - rect.top()) / CELL) as usize);
+                Some((cy.min(15) * 16 + cx.min(15)).max(1))
+            };
+            let cell_rect = |k: usize| egui::Rect::from_min_size(rect.min + egui::vec2((k % 16) as f32 * CELL, (k / 16) as f32 * CELL), egui::vec2(CELL, CELL));
+            let hover = resp.interact_pointer_pos().or(resp.hover_pos()).and_then(cell_at);
+            if resp.drag_started() { self.pal_sort_drag = hover; }
+            if resp.drag_stopped() {
+                // 拖到目标位置：从原位置取出再插入，中间的颜色依次挪一格
+                if let (Some(from), Some(to)) = (self.pal_sort_drag.take(), hover) && from != to {
+                    let v = self.pal_sort_order.remove(from);
+                    self.pal_sort_order.insert(to, v);
+                }
+            }
+            let painter = ui.painter_at(rect);
+            let transparent = self.shp.as_ref().map_or(0, |s| s.transparent);
+            for (k, &src) in self.pal_sort_order.iter().enumerate() {
+                let r = cell_rect(k).shrink(1.0);
+                if src == transparent { paint_checkerboard(&painter, r, 3.0, &CanvasBackground::default()); } else { painter.rect_filled(r, 0.0, self.palette.colors[src as usize]); }
+                if src as usize != k { painter.rect_stroke(r, 0.0, egui::Stroke::new(1.0, Color32::from_gray(200))); }
+            }
+            if let Some(from) = self.pal_sort_drag {
+                painter.rect_stroke(cell_rect(from), 0.0, egui::Stroke::new(2.0, Color32::YELLOW));
+                if let Some(to) = hover { painter.rect_stroke(cell_rect(to), 0.0, egui::Stroke::new(2.0, Color32::WHITE)); }
+            } else if let Some(k) = hover {
+                resp.on_hover_text(trf!("位置 {} ← 原索引 {}", k, self.pal_sort_order[k]));
+            }
+            let moved = self.pal_sort_order.iter().enumerate().filter(|&(k, &src)| src as usize != k).count();
+            ui.label(trf!("{} 个索引换了位置；所有帧的像素会同步改写，画面不变", moved));
+            ui.horizontal(|ui| {
+                if ui.add_enabled(moved > 0, egui::Button::new(tr("应用"))).clicked() { apply = true; }
+                if ui.button(tr("取消")).clicked() { cancel = true; }
+            });
+        });
+        self.show_pal_sort = open && !apply && !cancel;
+        if apply { self.action_reorder_palette(); }
+    }
+
+    fn action_reorder_palette(&mut self) {
+        let order = std::mem::take(&mut self.pal_sort_order);
+        let table = palette::inverse_order(&order);
+        if self.shp.is_some() { self.save_undo_document(true); } else { self.save_undo_palette(); }
+        self.palette = self.palette.reordered(&order);
+        if let Some(shp) = &mut self.shp {
+            shp.remap_all(&table);
+            shp.transparent = table[shp.transparent as usize];
+            self.after_canvas_change();
+        }
+        self.brush_index = table[self.brush_index as usize];
+        self.brush_index2 = table[self.brush_index2 as usize];
+        self.dirty = true;
+        self.record_op("整理调色板顺序", None);
+        self.status = tr("已整理调色板顺序并改写像素索引").into();
+    }
+
+    // 调色板对比：左右并排两张 16×16 色表，不同的索引红框标出；点选（Shift 扩展）索引范围后在两者间复制
+    fn ui_pal_diff(&mut self, ctx: &Context) {
+        let mut open = self.show_pal_diff;
+        let (mut to_a, mut to_b, mut open_b, mut save_b) = (false, false, false, false);
+        egui::Window::new(tr("对比/合并调色板")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(tr("对比调色板 B"));
+                let name = self.pal_diff_b.as_ref().map(|(n, _)| n.clone()).unwrap_or_else(|| tr("（未选择）").into());
+                ui.menu_button(name, |ui| {
+                    for (group, items) in &self.grouped_pals {
+                        ui.menu_button(group, |ui| {
+                            for (name, pal) in items {
+                                if ui.button(name).clicked() { self.pal_diff_b = Some((name.clone(), pal.clone())); ui.close_menu(); }
+                            }
+                        });
+                    }
+                });
+                if ui.button(tr("打开 PAL...")).clicked() { open_b = true; }
+            });
+            let Some((b_name, b)) = &self.pal_diff_b else { return; };
+            let a = &self.palette;
+            let diff: Vec<bool> = (0..256).map(|i| a.colors[i] != b.colors[i]).collect();
+            let runs = index_runs(&diff);
+            ui.label(if runs.is_empty() { tr("两个调色板完全相同").to_owned() } else {
+                let list: Vec<String> = runs.iter().map(|&(lo, hi)| if lo == hi { lo.to_string() } else { format!("{}–{}", lo, hi) }).collect();
+                trf!("{} 个索引不同: {}", diff.iter().filter(|d| **d).count(), list.join(", "))
+            });
+            let range = &mut self.pal_diff_range;
+            let (lo, hi) = (range.0.min(range.1), range.0.max(range.1));
+            let shift = ui.input(|i| i.modifiers.shift);
+            ui.horizontal_top(|ui| {
+                for (title, pal) in [(format!("A: {}", self.current_pal_name), a), (format!("B: {}", b_name), b)] {
+                    ui.vertical(|ui| {
+                        ui.label(title);
+                        egui::Grid::new(ui.next_auto_id()).spacing([1.0, 1.0]).show(ui, |ui| {
+                            for row in 0..16usize {
+                                for col in 0..16usize {
+                                    let idx = row * 16 + col;
+                                    let (rect, resp) = ui.allocate_exact_size(egui::vec2(12.0, 12.0), Sense::click());
+                                    ui.painter().rect_filled(rect, 0.0, pal.colors[idx]);
+                                    if (lo as usize..=hi as usize).contains(&idx) { ui.painter().rect_stroke(rect, 0.0, egui::Stroke::new(1.0, Color32::YELLOW)); }
+                                    if diff[idx] { ui.painter().rect_stroke(rect.shrink(1.5), 0.0, egui::Stroke::new(1.5, Color32::RED)); }
+                                    let [ar, ag, ab, _] = a.colors[idx].to_array();
+                                    let [br, bg, bb, _] = b.colors[idx].to_array();
+                                    let tip = trf!("索引 {}\nA: {}, {}, {}\nB: {}, {}, {}", idx, ar, ag, ab, br, bg, bb);
+                                    if resp.on_hover_text(tip).clicked() {
+                                        if shift { range.1 = idx as u8; } else { *range = (idx as u8, idx as u8); }
+                                    }
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+                }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label(tr("索引范围"));
+                ui.add(egui::DragValue::new(&mut range.0).clamp_range(0..=255));
+                ui.label("–");
+                ui.add(egui::DragValue::new(&mut range.1).clamp_range(0..=255));
+                if ui.small_button(tr("全部")).clicked() { *range = (0, 255); }
+                if let Some(r) = a.roles.remap && ui.small_button(tr("阵营色")).clicked() { *range = r; }
+                if let Some(s) = a.roles.shadow && ui.small_button(tr("阴影")).clicked() { *range = (s, s); }
+            });
+            ui.horizontal(|ui| {
+                if ui.button(tr("B → A（当前调色板）")).clicked() { to_a = true; }
+                if ui.button("A → B").clicked() { to_b = true; }
+                if ui.button(tr("保存 B 为 PAL...")).clicked() { save_b = true; }
+            });
+        });
+        self.show_pal_diff = open;
+        if open_b && let Some(path) = FileDialog::new().add_filter("PAL", &["pal"]).pick_file() {
+            match std::fs::read(&path).map_err(|e| e.to_string()).and_then(|b| Palette::from_bytes(&b)) {
+                Ok(p) => {
+                    let name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+                    self.pal_diff_b = Some((name, p));
+                }
+                Err(e) => self.status = trf!("加载PAL失败: {}", e),
+            }
+        }
+        let Some((_, b)) = &self.pal_diff_b else { return; };
+        let (lo, hi) = (self.pal_diff_range.0.min(self.pal_diff_range.1) as usize, self.pal_diff_range.0.max(self.pal_diff_range.1) as usize);
+        if to_a && self.palette.colors[lo..=hi] != b.colors[lo..=hi] {
+            let old = self.palette.clone();
+            self.palette.colors[lo..=hi].copy_from_slice(&b.colors[lo..=hi]);
+            self.push_undo(Patch::Palette(Box::new(old), self.current_pal_name.clone()));
+            self.dirty = true;
+            self.record_op("合并调色板", None);
+            self.status = trf!("已把 B 的索引 {}–{} 复制到当前调色板", lo, hi);
+        }
+        let Some((_, b)) = &mut self.pal_diff_b else { return; };
+        if to_b {
+            b.colors[lo..=hi].copy_from_slice(&self.palette.colors[lo..=hi]);
+            self.status = trf!("已把当前调色板的索引 {}–{} 复制到 B", lo, hi);
+        }
+        if save_b && let Some(path) = FileDialog::new().add_filter("PAL", &["pal"]).set_file_name("palette.pal").save_file() {
+            match std::fs::write(&path, b.to_bytes()) {
+                Ok(()) => self.status = trf!("已保存 PAL: {}", path.display()),
+                Err(e) => self.status = trf!("保存PAL失败: {}", e),
+            }
+        }
+    }
+
+    fn ui_canvas_size(&mut self, ctx: &Context) {
+        let mut open = self.show_canvas_size;
+        let mut apply = false;
+        let Some((ow, oh)) = self.shp.as_ref().map(|s| (s.width, s.height)) else { self.show_canvas_size = false; return; };
+        egui::Window::new(tr("画布大小")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.label(trf!("当前: {} x {}", ow, oh));
+            ui.horizontal(|ui| {
+                ui.label(tr("宽")); ui.add(egui::DragValue::new(&mut self.canvas_new_w).clamp_range(1..=4096));
+                ui.label(tr("高")); ui.add(egui::DragValue::new(&mut self.canvas_new_h).clamp_range(1..=4096));
+            });
+            ui.label(tr("锚点"));
+            egui::Grid::new("canvas_anchor").spacing([2.0, 2.0]).show(ui, |ui| {
+                for row in 0..3u8 {
+                    for col in 0..3u8 {
+                        let on = self.canvas_anchor == (col, row);
+                        if ui.add_sized([24.0, 24.0], egui::SelectableLabel::new(on, if on { "●" } else { "○" })).clicked() {
+                            self.canvas_anchor = (col, row);
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.label(RichText::new(tr("新增区域填充透明索引")).small().color(Color32::GRAY));
+            if ui.add_enabled((self.canvas_new_w, self.canvas_new_h) != (ow, oh), egui::Button::new(tr("应用"))).clicked() { apply = true; }
+        });
+        self.show_canvas_size = open;
+        if apply { self.action_resize_canvas(); }
+    }
+
+    fn action_resize_canvas(&mut self) {
+        let Some(shp) = &mut self.shp else { return; };
+        let (nw, nh) = (self.canvas_new_w, self.canvas_new_h);
+        let place = |a: u8, old: u32, new: u32| match a { 0 => 0, 1 => (new as i32 - old as i32) / 2, _ => new as i32 - old as i32 };
+        let (ox, oy) = (place(self.canvas_anchor.0, shp.width, nw), place(self.canvas_anchor.1, shp.height, nh));
+        let (old_w, old_h) = (shp.width, shp.height);
+        self.save_undo_document(false);
+        let Some(shp) = &mut self.shp else { return; };
+        shp.resize_canvas(nw, nh, ox, oy);
+        self.after_canvas_change();
+        self.record_op("画布大小", None);
+        self.status = trf!("画布大小: {} x {} → {} x {}", old_w, old_h, nw, nh);
+        self.show_canvas_size = false;
+    }
+
+    fn ui_retarget(&mut self, ctx: &Context) {
+        let mut open = self.show_retarget;
+        let mut apply = None;
+        egui::Window::new(tr("重定画布")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            let Some(shp) = &self.shp else { ui.label(tr("当前没有SHP")); return; };
+            ui.label(trf!("当前: {} x {}", shp.width, shp.height));
+            ui.horizontal(|ui| {
+                ui.label(tr("宽")); ui.add(egui::DragValue::new(&mut self.retarget_w).clamp_range(1..=4096));
+                ui.label(tr("高")); ui.add(egui::DragValue::new(&mut self.retarget_h).clamp_range(1..=4096));
+            });
+            ui.label(tr("对齐方式"));
+            for a in RetargetAnchor::ALL { ui.radio_value(&mut self.retarget_anchor, a, a.label()); }
+            ui.add_enabled_ui(self.retarget_anchor == RetargetAnchor::ContentBottom, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(tr("底边留白"));
+                    ui.add(egui::DragValue::new(&mut self.retarget_margin).clamp_range(0..=self.retarget_h.saturating_sub(1)));
+                });
+            });
+            let (nw, nh) = (self.retarget_w, self.retarget_h);
+            let Some((ox, oy)) = shp.retarget_offset(self.retarget_anchor, nw, nh, self.retarget_margin) else {
+                ui.colored_label(Color32::YELLOW, tr("所有帧都是空的，无法按内容对齐"));
+                return;
+            };
+            // 示意图：新画布（灰底）、原画布（细框）、全部帧内容的包围盒（黄框）
+            let scale = 200.0 / nw.max(nh).max(shp.width).max(shp.height) as f32;
+            let (resp, painter) = ui.allocate_painter(egui::Vec2::new(nw as f32 * scale, nh as f32 * scale), Sense::hover());
+            let o = resp.rect.min;
+            let rect = |x: i32, y: i32, w: u32, h: u32| egui::Rect::from_min_size(o + egui::Vec2::new(x as f32, y as f32) * scale, egui::Vec2::new(w as f32, h as f32) * scale);
+            painter.rect_filled(resp.rect, 0.0, Color32::from_gray(60));
+            painter.rect_stroke(rect(ox, oy, shp.width, shp.height), 0.0, egui::Stroke::new(1.0, Color32::GRAY));
+            let bounds = shp.content_bounds();
+            if let Some((x, y, w, h)) = bounds {
+                painter.rect_stroke(rect(x as i32 + ox, y as i32 + oy, w, h), 0.0, egui::Stroke::new(1.5, Color32::YELLOW));
+            }
+            ui.label(RichText::new(trf!("原内容平移 ({}, {})", ox, oy)).small().color(Color32::GRAY));
+            let clipped = bounds.is_some_and(|(x, y, w, h)| {
+                let (x0, y0) = (x as i32 + ox, y as i32 + oy);
+                x0 < 0 || y0 < 0 || x0 + w as i32 > nw as i32 || y0 + h as i32 > nh as i32
+            });
+            if clipped { ui.colored_label(Color32::YELLOW, tr("新画布放不下全部内容，超出部分会被裁掉")); }
+            if ui.add_enabled((nw, nh, ox, oy) != (shp.width, shp.height, 0, 0), egui::Button::new(tr("应用"))).clicked() {
+                apply = Some((ox, oy));
+            }
+        });
+        self.show_retarget = open && apply.is_none();
+        let Some((ox, oy)) = apply else { return; };
+        let Some(shp) = &self.shp else { return; };
+        let (old_w, old_h, nw, nh) = (shp.width, shp.height, self.retarget_w, self.retarget_h);
+        self.save_undo_document(false);
+        let Some(shp) = &mut self.shp else { return; };
+        shp.resize_canvas(nw, nh, ox, oy);
+        self.after_canvas_change();
+        self.record_op("重定画布", None);
+        self.status = trf!("画布大小: {} x {} → {} x {}", old_w, old_h, nw, nh);
+    }
+
+    fn action_crop_to_content(&mut self) {
+        let Some(shp) = &mut self.shp else { return; };
+        let Some((x, y, w, h)) = shp.content_bounds() else { self.status = tr("所有帧都是空的，无法裁剪").into(); return; };
+        if (x, y, w, h) == (0, 0, shp.width, shp.height) { self.status = tr("内容已占满画布，无需裁剪").into(); return; }
+        self.save_undo_document(false);
+        let Some(shp) = &mut self.shp else { return; };
+        shp.resize_canvas(w, h, -(x as i32), -(y as i32));
+        self.after_canvas_change();
+        self.record_op("裁剪到内容", None);
+        self.status = trf!("已裁剪到内容: {} x {}（偏移 {}, {}）", w, h, x, y);
+    }
+
+    fn ui_scale_dialog(&mut self, ctx: &Context) {
+        let mut open = self.show_scale_dialog;
+        let mut apply = false;
+        let Some((ow, oh)) = self.shp.as_ref().map(|s| (s.width, s.height)) else { self.show_scale_dialog = false; return; };
+        egui::Window::new(tr("缩放图像")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.label(trf!("当前: {} x {}", ow, oh));
+            egui::ComboBox::from_label(tr("算法"))
+                .selected_text(self.scaler.label())
+                .show_ui(ui, |ui| {
+                    for s in PixelScaler::ALL { ui.selectable_value(&mut self.scaler, s, s.label()); }
+                });
+            if let Some(k) = self.scaler.factor() {
+                (self.scale_new_w, self.scale_new_h) = (ow * k, oh * k);
+                ui.label(trf!("新尺寸: {} x {}", self.scale_new_w, self.scale_new_h));
+            } else {
+                ui.horizontal(|ui| {
+                    ui.label(tr("宽"));
+                    let rw = ui.add(egui::DragValue::new(&mut self.scale_new_w).clamp_range(1..=4096));
+                    ui.label(tr("高"));
+                    let rh = ui.add(egui::DragValue::new(&mut self.scale_new_h).clamp_range(1..=4096));
+                    if self.scale_keep_aspect {
+                        if rw.changed() { self.scale_new_h = ((self.scale_new_w as u64 * oh as u64 + ow as u64 / 2) / ow as u64).clamp(1, 4096) as u32; }
+                        if rh.changed() { self.scale_new_w = ((self.scale_new_h as u64 * ow as u64 + oh as u64 / 2) / oh as u64).clamp(1, 4096) as u32; }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.scale_keep_aspect, tr("保持比例"));
+                    for pct in [50u32, 200, 300] {
+                        if ui.small_button(format!("{}%", pct)).clicked() {
+                            self.scale_new_w = (ow * pct / 100).max(1);
+                            self.scale_new_h = (oh * pct / 100).max(1);
+                        }
+                    }
+                });
+            }
+            if ui.add_enabled((self.scale_new_w, self.scale_new_h) != (ow, oh), egui::Button::new(tr("应用"))).clicked() { apply = true; }
+        });
+        self.show_scale_dialog = open;
+        if apply { self.action_scale(); }
+    }
+
+    fn action_scale(&mut self) {
+        self.save_undo_document(false);
+        let Some(shp) = &mut self.shp else { return; };
+        let (old_w, old_h) = (shp.width, shp.height);
+        shp.scale(self.scaler, self.scale_new_w, self.scale_new_h);
+        let (nw, nh) = (shp.width, shp.height);
+        self.after_canvas_change();
+        self.record_op("缩放图像", None);
+        self.status = trf!("已缩放: {} x {} → {} x {}（{}）", old_w, old_h, nw, nh, self.scaler.label());
+        self.show_scale_dialog = false;
+    }
+
+    // 画布尺寸变化后：预览缓存全部失效
+    fn after_canvas_change(&mut self) {
+        self.dirty = true;
+        self.thumb_cache.clear();
+        self.canvas_tex = None;
+        self.shape_preview = None;
+    }
+
+    // 阴影帧生成：SHP 约定后半部分帧为前半部分的阴影
+    fn ui_shadow_dialog(&mut self, ctx: &Context) {
+        let mut open = self.show_shadow_dialog;
+        let mut apply = false;
+        let count = self.shp.as_ref().map(|s| s.frames.len()).unwrap_or(0);
+        egui::Window::new(tr("生成阴影帧")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            let p = &mut self.shadow_params;
+            egui::Grid::new("shadow_grid").num_columns(2).show(ui, |ui| {
+                ui.label(tr("阴影索引"));
+                ui.add(egui::DragValue::new(&mut p.index).clamp_range(0..=255));
+                ui.end_row();
+                ui.label(tr("错切")); ui.add(egui::Slider::new(&mut p.shear, -2.0..=2.0)); ui.end_row();
+                ui.label(tr("垂直压缩")); ui.add(egui::Slider::new(&mut p.squash, 0.05..=1.0)); ui.end_row();
+                ui.label(tr("偏移 X")); ui.add(egui::DragValue::new(&mut p.offset_x).clamp_range(-512..=512)); ui.end_row();
+                ui.label(tr("偏移 Y")); ui.add(egui::DragValue::new(&mut p.offset_y).clamp_range(-512..=512)); ui.end_row();
+            });
+            ui.separator();
+            ui.radio_value(&mut self.shadow_overwrite, false, trf!("追加：为全部 {} 帧生成阴影，共 {} 帧", count, count * 2));
+            ui.add_enabled_ui(count >= 2 && count.is_multiple_of(2), |ui| {
+                ui.radio_value(&mut self.shadow_overwrite, true, trf!("覆盖：由前 {} 帧生成后 {} 帧", count / 2, count / 2));
+            });
+            if self.shadow_overwrite && !count.is_multiple_of(2) { self.shadow_overwrite = false; }
+            if ui.button(tr("生成")).clicked() { apply = true; }
+        });
+        self.show_shadow_dialog = open;
+        if apply { self.action_generate_shadows(); }
+    }
+
+    fn action_generate_shadows(&mut self) {
+        let extra = if self.shadow_overwrite { 0 } else { self.shp.as_ref().map_or(0, |s| s.frames.len()) };
+        if !self.check_frame_room(extra) { return; }
+        self.save_undo_document(false);
+        let Some(shp) = &mut self.shp else { return; };
+        let n = shp.frames.len();
+        let body = if self.shadow_overwrite { n / 2 } else { n };
+        let shadows: Vec<_> = (0..body).map(|i| shp.shadow_frame(i, &self.shadow_params)).collect();
+        if self.shadow_overwrite {
+            for (i, f) in shadows.into_iter().enumerate() { shp.frames[body + i] = f; }
+        } else {
+            shp.frames.extend(shadows);
+        }
+        self.dirty = true;
+        self.show_shadow_dialog = false;
+        self.record_op("生成阴影帧", None);
+        self.status = trf!("已生成 {} 个阴影帧", body);
+    }
+
+    // 描边对话框：用所选索引沿剪影内侧或外侧画 1 像素轮廓
+    fn ui_outline_dialog(&mut self, ctx: &Context) {
+        let mut open = self.show_outline_dialog;
+        let mut apply = false;
+        egui::Window::new(tr("描边")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.label(trf!("描边索引 = {}", self.outline_index));
+            palette_picker(ui, &self.palette, &mut self.outline_index, "outline_index");
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.outline_outside, true, tr("外侧"));
+                ui.radio_value(&mut self.outline_outside, false, tr("内侧"));
+                ui.checkbox(&mut self.outline_diagonal, tr("含对角"))
+                    .on_hover_text(tr("对角相邻的像素也算贴边，外描边的转角更饱满"));
+            });
+            Self::ui_scope_selector(ui, &mut self.outline_scope);
+            if ui.button(tr("描边")).clicked() { apply = true; }
+        });
+        self.show_outline_dialog = open;
+        if apply { self.action_outline(); }
+    }
+
+    fn action_outline(&mut self) {
+        let frames = self.scope_frames(self.outline_scope);
+        self.save_undo_frames(&frames);
+        let Some(shp) = &mut self.shp else { return; };
+        let total: usize = frames.iter().map(|&fi| shp.outline_frame(fi, self.outline_index, self.outline_outside, self.outline_diagonal)).sum();
+        if total > 0 { self.after_canvas_change(); }
+        self.record_op("描边", frames.first().copied().filter(|_| frames.len() == 1));
+        self.status = trf!("已描边 {} 帧，{} 个像素", frames.len(), format_number(total));
+    }
+
+    fn ui_shift_dialog(&mut self, ctx: &Context) {
+        let mut open = self.show_shift_dialog;
+        let mut apply = false;
+        egui::Window::new(tr("平移全部帧")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("dx");
+                ui.add(egui::DragValue::new(&mut self.shift_dx));
+                ui.label("dy");
+                ui.add(egui::DragValue::new(&mut self.shift_dy));
+            });
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.shift_wrap, false, tr("限制在画布内"))
+                    .on_hover_text(tr("位移量不超过内容到画布边缘的距离，不会裁掉像素"));
+                ui.radio_value(&mut self.shift_wrap, true, tr("环绕"))
+                    .on_hover_text(tr("移出画布的像素从对边移入"));
+            });
+            ui.horizontal(|ui| {
+                if ui.button(tr("居中所需位移")).on_hover_text(tr("填入使全部帧内容的包围盒居中的位移量")).clicked()
+                    && let Some((dx, dy)) = self.shp.as_ref().and_then(|s| s.center_shift()) {
+                    (self.shift_dx, self.shift_dy) = (dx, dy);
+                }
+                if ui.button(tr("平移")).clicked() { apply = true; }
+            });
+        });
+        self.show_shift_dialog = open;
+        if apply { self.action_shift_pixels(self.shift_dx, self.shift_dy, self.shift_wrap); }
+    }
+
+    fn action_shift_pixels(&mut self, dx: i32, dy: i32, wrap: bool) {
+        let Some(shp) = &self.shp else { return; };
+        let (dx, dy) = if wrap { (dx, dy) } else { shp.clamp_shift(dx, dy) };
+        if (dx, dy) == (0, 0) { self.status = tr("位移为 0，未改动").into(); return; }
+        let frames: Vec<usize> = (0..shp.frames.len()).collect();
+        self.save_undo_frames(&frames);
+        let Some(shp) = &mut self.shp else { return; };
+        shp.shift_pixels(dx, dy, wrap);
+        self.after_canvas_change();
+        self.record_op("平移全部帧", None);
+        self.status = trf!("已平移全部帧: {}, {}", dx, dy);
+    }
+
+    fn action_center_content(&mut self) {
+        let Some(shp) = &self.shp else { return; };
+        let Some((dx, dy)) = shp.center_shift() else { self.status = tr("所有帧都是空的，无法居中").into(); return; };
+        if (dx, dy) == (0, 0) { self.status = tr("内容已经居中").into(); return; }
+        self.action_shift_pixels(dx, dy, false);
+    }
+
+    fn action_open_compare(&mut self) {
+        let Some(path) = FileDialog::new().add_filter("SHP", &["shp"]).pick_file() else { return; };
+        match std::fs::read(&path).map_err(|e| e.to_string()).and_then(|b| SHP::load(&b)) {
+            Ok(shp) => {
+                let name = path.file_name().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+                self.compare_doc = Some((name, shp));
+                self.compare_tex = None;
+                self.show_compare = true;
+            }
+            Err(e) => self.status = trf!("加载SHP失败: {}", e),
+        }
+    }
+
+    // 对比窗口：当前文档 (A) 与对比文档 (B) 的同序号帧并排显示，不同的像素以洋红标出
+    fn ui_compare(&mut self, ctx: &Context) {
+        let mut open = self.show_compare;
+        let mut jump: Option<usize> = None;
+        egui::Window::new(tr("对比 SHP")).open(&mut open).resizable(false).show(ctx, |ui| {
+            let (Some(a), Some((b_name, b))) = (&self.shp, &self.compare_doc) else { ui.label(tr("当前没有SHP")); return; };
+            let fi = self.preview.current_frame.min(a.frames.len().saturating_sub(1));
+            let a_name = self.current_path.as_ref().and_then(|p| p.file_name()).map_or_else(|| tr("（未保存）").to_owned(), |n| n.to_string_lossy().into_owned());
+            ui.label(trf!("A: {}（{}×{}，{} 帧）", a_name, a.width, a.height, a.frames.len()));
+            ui.label(trf!("B: {}（{}×{}，{} 帧）", b_name, b.width, b.height, b.frames.len()));
+            let (mask, count) = a.diff_frame(fi, b);
+            ui.horizontal(|ui| {
+                // 跳到上一/下一处有差异的帧
+                let differs = |i: usize| a.diff_frame(i, b).1 > 0;
+                if ui.button("◀").on_hover_text(tr("上一个有差异的帧")).clicked() { jump = (0..fi).rev().find(|&i| differs(i)); }
+                ui.label(trf!("第 {} 帧：{} 个像素不同", fi, format_number(count)));
+                if ui.button("▶").on_hover_text(tr("下一个有差异的帧")).clicked() { jump = (fi + 1..a.frames.len()).find(|&i| differs(i)); }
+            });
+            ui.horizontal(|ui| {
+                ui.add(egui::Slider::new(&mut self.compare_zoom, 1.0..=8.0).step_by(1.0).text(tr("缩放")));
+                ui.checkbox(&mut self.compare_overlay, tr("标出差异"));
+            });
+            let key = {
+                use std::hash::{Hash, Hasher};
+                let mut h = std::collections::hash_map::DefaultHasher::new();
+                (fi, a.frames[fi].content_hash(), b.frames.get(fi).map(|f| f.content_hash()), self.palette.to_bytes(), self.brightness.to_bits()).hash(&mut h);
+                h.finish()
+            };
+            if self.compare_tex.as_ref().is_none_or(|(k, _)| *k != key) {
+                let ta = a.egui_texture_named(ctx, "compare_a", fi, &self.palette, self.brightness);
+                let tb = b.egui_texture_named(ctx, "compare_b", fi.min(b.frames.len().saturating_sub(1)), &self.palette, self.brightness);
+                let diff = egui::ColorImage {
+                    size: [a.width as usize, a.height as usize],
+                    pixels: mask.iter().map(|&d| if d { Color32::from_rgba_unmultiplied(255, 0, 255, 170) } else { Color32::TRANSPARENT }).collect(),
+                };
+                let td = ctx.load_texture("compare_diff", diff, egui::TextureOptions::NEAREST);
+                self.compare_tex = Some((key, [ta, tb, td]));
+            }
+            let Some((_, [ta, tb, td])) = &self.compare_tex else { return; };
+            let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+            let z = self.compare_zoom;
+            let a_size = egui::vec2(a.width as f32, a.height as f32) * z;
+            egui::ScrollArea::both().max_width(1000.0).max_height(640.0).show(ui, |ui| {
+                ui.horizontal_top(|ui| {
+                    for (label, tex, size, has) in [("A", ta, a_size, true), ("B", tb, egui::vec2(b.width as f32, b.height as f32) * z, fi < b.frames.len())] {
+                        ui.vertical(|ui| {
+                            ui.label(label);
+                            if !has { ui.label(tr("B 没有此帧")); return; }
+                            let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+                            paint_checkerboard(ui.painter(), rect, z, &self.settings.canvas_bg);
+                            ui.painter().image(tex.id(), rect, uv, Color32::WHITE);
+                            // 差异掩码按 A 的尺寸，左上角对齐叠加
+                            if self.compare_overlay {
+                                ui.painter().with_clip_rect(rect).image(td.id(), egui::Rect::from_min_size(rect.min, a_size), uv, Color32::WHITE);
+                            }
+                        });
+                    }
+                });
+            });
+        });
+        self.show_compare = open;
+        if let Some(i) = jump { self.preview.current_frame = i; }
+        if !open { self.compare_tex = None; }
+    }
+
+    // 平铺预览：当前帧按 3×3 拼接显示，检查图块四边能否无缝衔接
+    fn ui_tile_preview(&mut self, ctx: &Context) {
+        let mut open = self.show_tile_preview;
+        egui::Window::new(tr("平铺预览")).open(&mut open).resizable(false).show(ctx, |ui| {
+            let Some(shp) = &self.shp else { ui.label(tr("当前没有SHP")); return; };
+            let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+            ui.add(egui::Slider::new(&mut self.tile_zoom, 1.0..=4.0).step_by(1.0).text(tr("缩放")));
+            let key = {
+                use std::hash::{Hash, Hasher};
+                let mut h = std::collections::hash_map::DefaultHasher::new();
+                (shp.frames[fi].content_hash(), self.palette.to_bytes(), self.brightness.to_bits()).hash(&mut h);
+                h.finish()
+            };
+            if self.tile_preview.as_ref().is_none_or(|(k, _)| *k != key) {
+                self.tile_preview = Some((key, shp.egui_texture_named(ctx, "tile_preview", fi, &self.palette, self.brightness)));
+            }
+            let Some((_, tex)) = &self.tile_preview else { return; };
+            let tile = egui::vec2(shp.width as f32, shp.height as f32) * self.tile_zoom;
+            let (rect, _) = ui.allocate_exact_size(tile * 3.0, Sense::hover());
+            paint_checkerboard(ui.painter(), rect, self.tile_zoom, &self.settings.canvas_bg);
+            let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+            for ty in 0..3 {
+                for tx in 0..3 {
+                    let r = egui::Rect::from_min_size(rect.min + egui::vec2(tx as f32 * tile.x, ty as f32 * tile.y), tile);
+                    ui.painter().image(tex.id(), r, uv, Color32::WHITE);
+                }
+            }
+        });
+        self.show_tile_preview = open;
+        if !open { self.tile_preview = None; }
+    }
+
+    // 游戏比例预览：随播放显示当前帧，按屏幕物理像素 1:1 绘制，模拟游戏内的实际大小与观感
+    fn ui_game_preview(&mut self, ctx: &Context) {
+        let mut open = self.show_game_preview;
+        egui::Window::new(tr("游戏比例预览")).open(&mut open).resizable(false).show(ctx, |ui| {
+            let Some(shp) = &self.shp else { ui.label(tr("当前没有SHP")); return; };
+            let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.game_zoom, 1, tr("1× 原始"));
+                ui.selectable_value(&mut self.game_zoom, 2, tr("2× 最近邻"));
+                ui.selectable_value(&mut self.game_zoom, 3, "3×");
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.game_crt, tr("CRT 扫描线"));
+                ui.add_enabled(self.game_crt, egui::Slider::new(&mut self.game_crt_strength, 0.1..=1.0).text(tr("强度")));
+            });
+            let key = {
+                use std::hash::{Hash, Hasher};
+                let mut h = std::collections::hash_map::DefaultHasher::new();
+                (shp.frames[fi].content_hash(), self.palette.to_bytes(), self.game_zoom, self.game_crt, self.game_crt_strength.to_bits()).hash(&mut h);
+                h.finish()
+            };
+            if self.game_preview.as_ref().is_none_or(|(k, _)| *k != key) {
+                let img = image_io::game_preview(&shp.frame_rgba(fi, &self.palette), self.game_zoom, self.game_crt.then_some(self.game_crt_strength));
+                let img = egui::ColorImage::from_rgba_unmultiplied([img.width() as usize, img.height() as usize], img.as_raw());
+                self.game_preview = Some((key, ctx.load_texture("game_preview", img, egui::TextureOptions::NEAREST)));
+            }
+            let Some((_, tex)) = &self.game_preview else { return; };
+            // 纹理按物理像素显示，不受界面缩放影响
+            let size = tex.size_vec2() / ctx.pixels_per_point();
+            let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+            ui.painter().image(tex.id(), rect, egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)), Color32::WHITE);
+            ui.label(RichText::new(trf!("第 {} 帧 · {}×", fi, self.game_zoom)).small().color(Color32::GRAY));
+        });
+        self.show_game_preview = open;
+        if !open { self.game_preview = None; }
+    }
+
+    // 用两个调色板渲染同一帧：分割线两侧各用一个（可在图上拖动分割线），或左右并排；
+    // 用于检查同一素材在雪地/温带/城市等调色板下是否都正常
+    fn ui_pal_split(&mut self, ctx: &Context) {
+        let mut open = self.show_pal_split;
+        let mut open_b = false;
+        egui::Window::new(tr("双调色板对比")).open(&mut open).resizable(false).show(ctx, |ui| {
+            let Some(shp) = &self.shp else { ui.label(tr("当前没有SHP")); return; };
+            let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+            ui.horizontal(|ui| {
+                ui.label(tr("调色板 B"));
+                let name = self.pal_split_b.as_ref().map(|(n, _)| n.clone()).unwrap_or_else(|| tr("（未选择）").into());
+                ui.menu_button(name, |ui| {
+                    for (group, items) in &self.grouped_pals {
+                        ui.menu_button(group, |ui| {
+                            for (name, pal) in items {
+                                if ui.button(name).clicked() { self.pal_split_b = Some((name.clone(), pal.clone())); ui.close_menu(); }
+                            }
+                        });
+                    }
+                });
+                if ui.button(tr("打开 PAL...")).clicked() { open_b = true; }
+            });
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.pal_split_side, false, tr("分割线"));
+                ui.selectable_value(&mut self.pal_split_side, true, tr("并排"));
+                ui.separator();
+                ui.add(egui::Slider::new(&mut self.pal_split_zoom, 1..=4).text(tr("缩放")).suffix("×"));
+            });
+            let Some((b_name, b)) = &self.pal_split_b else { ui.weak(tr("选择调色板 B 后显示对比")); return; };
+            let key = {
+                use std::hash::{Hash, Hasher};
+                let mut h = std::collections::hash_map::DefaultHasher::new();
+                (shp.frames[fi].content_hash(), self.palette.to_bytes(), b.to_bytes()).hash(&mut h);
+                h.finish()
+            };
+            if self.pal_split_tex.as_ref().is_none_or(|(k, _)| *k != key) {
+                let load = |name: &str, pal: &Palette| {
+                    let img = shp.frame_rgba(fi, pal);
+                    let img = egui::ColorImage::from_rgba_unmultiplied([img.width() as usize, img.height() as usize], img.as_raw());
+                    ctx.load_texture(name, img, egui::TextureOptions::NEAREST)
+                };
+                self.pal_split_tex = Some((key, [load("pal_split_a", &self.palette), load("pal_split_b", b)]));
+            }
+            let Some((_, [ta, tb])) = &self.pal_split_tex else { return; };
+            let zoom = self.pal_split_zoom as f32;
+            let size = egui::vec2(shp.width as f32, shp.height as f32) * zoom;
+            let full = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+            if self.pal_split_side {
+                ui.horizontal_top(|ui| {
+                    for (title, tex) in [(format!("A: {}", self.current_pal_name), ta), (format!("B: {}", b_name), tb)] {
+                        ui.vertical(|ui| {
+                            ui.label(title);
+                            let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+                            paint_checkerboard(ui.painter(), rect, zoom, &self.settings.canvas_bg);
+                            ui.painter().image(tex.id(), rect, full, Color32::WHITE);
+                        });
+                    }
+                });
+            } else {
+                ui.horizontal(|ui| {
+                    ui.label(format!("A: {}", self.current_pal_name));
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| { ui.label(format!("B: {}", b_name)); });
+                });
+                let (rect, resp) = ui.allocate_exact_size(size, Sense::click_and_drag());
+                if let Some(p) = resp.interact_pointer_pos() && (resp.dragged() || resp.clicked()) {
+                    self.pal_split_pos = ((p.x - rect.min.x) / rect.width()).clamp(0.0, 1.0);
+                }
+                let t = self.pal_split_pos;
+                let x = rect.min.x + rect.width() * t;
+                paint_checkerboard(ui.painter(), rect, zoom, &self.settings.canvas_bg);
+                let (left, right) = (egui::Rect::from_min_max(rect.min, egui::pos2(x, rect.max.y)), egui::Rect::from_min_max(egui::pos2(x, rect.min.y), rect.max));
+                ui.painter().image(ta.id(), left, egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(t, 1.0)), Color32::WHITE);
+                ui.painter().image(tb.id(), right, egui::Rect::from_min_max(egui::pos2(t, 0.0), egui::pos2(1.0, 1.0)), Color32::WHITE);
+                ui.painter().vline(x, rect.y_range(), egui::Stroke::new(1.0, Color32::YELLOW));
+                resp.on_hover_cursor(egui::CursorIcon::ResizeHorizontal);
+            }
+            ui.label(RichText::new(trf!("第 {} 帧 · {}×", fi, self.pal_split_zoom)).small().color(Color32::GRAY));
+        });
+        self.show_pal_split = open;
+        if !open { self.pal_split_tex = None; }
+        if open_b && let Some(path) = FileDialog::new().add_filter("PAL", &["pal"]).pick_file() {
+            match std::fs::read(&path).map_err(|e| e.to_string()).and_then(|b| Palette::from_bytes(&b)) {
+                Ok(p) => {
+                    let name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+                    self.pal_split_b = Some((name, p));
+                }
+                Err(e) => self.status = trf!("加载PAL失败: {}", e),
+            }
+        }
+    }
+
+    // 滤镜对话框：参数界面由滤镜自身提供，右侧预览当前帧的处理结果
+    fn ui_filter_dialog(&mut self, ctx: &Context) {
+        let mut open = self.show_filter_dialog;
+        let mut apply = false;
+        let title = self.filters.get(self.filter_idx).map_or("", |f| f.name());
+        egui::Window::new(tr("滤镜")).id(egui::Id::new("filter_dialog")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            let Some(shp) = &self.shp else { ui.label(tr("当前没有SHP")); return; };
+            egui::ComboBox::from_id_source("filter_kind").selected_text(title).show_ui(ui, |ui| {
+                for (i, f) in self.filters.iter().enumerate() { ui.selectable_value(&mut self.filter_idx, i, f.name()); }
+            });
+            let Some(filter) = self.filters.get_mut(self.filter_idx) else { return; };
+            ui.separator();
+            if filter.ui(ui) { self.filter_gen += 1; }
+            ui.separator();
+            Self::ui_scope_selector(ui, &mut self.filter_scope);
+            if self.selection.is_some() { ui.weak(tr("仅处理选区内的像素")); }
+            // 预览：当前帧处理结果
+            let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+            let key = {
+                use std::hash::{Hash, Hasher};
+                let mut h = std::collections::hash_map::DefaultHasher::new();
+                (shp.frames[fi].content_hash(), fi, self.filter_idx, self.filter_gen, self.palette.to_bytes()).hash(&mut h);
+                self.selection.as_ref().map(|s| s.mask.clone()).hash(&mut h);
+                h.finish()
+            };
+            if self.filter_preview.as_ref().is_none_or(|(k, _)| *k != key) {
+                let input = FilterInput { pixels: &shp.frames[fi].pixels, width: shp.width, height: shp.height, palette: &self.palette, transparent: shp.transparent, frame: fi };
+                let mut scratch = SHP::new(shp.width, shp.height, 1);
+                scratch.transparent = shp.transparent;
+                scratch.frames[0].pixels = filters::run(filter.as_ref(), &input, self.selection.as_ref().map(|s| s.mask.as_slice()));
+                self.filter_preview = Some((key, scratch.egui_texture_named(ctx, "filter_preview", 0, &self.palette, 1.0)));
+            }
+            if let Some((_, tex)) = &self.filter_preview {
+                // 小图整数倍放大，大图缩小到 256 以内
+                let k = (256.0 / shp.width.max(shp.height) as f32).min(4.0);
+                let k = if k >= 1.0 { k.floor() } else { k };
+                ui.image((tex.id(), egui::vec2(shp.width as f32, shp.height as f32) * k));
+            }
+            if ui.button(tr("应用")).clicked() { apply = true; }
+        });
+        self.show_filter_dialog = open;
+        if apply { self.action_apply_filter(); }
+        if !self.show_filter_dialog { self.filter_preview = None; }
+    }
+
+    fn action_apply_filter(&mut self) {
+        let frames = self.scope_frames(self.filter_scope);
+        let Some(filter) = self.filters.get(self.filter_idx) else { return; };
+        let Some(shp) = &self.shp else { return; };
+        let mask = self.selection.as_ref().map(|s| s.mask.as_slice());
+        let results: Vec<(usize, Vec<u8>)> = frames.iter().map(|&fi| {
+            let input = FilterInput { pixels: &shp.frames[fi].pixels, width: shp.width, height: shp.height, palette: &self.palette, transparent: shp.transparent, frame: fi };
+            (fi, filters::run(filter.as_ref(), &input, mask))
+        }).filter(|(fi, px)| *px != shp.frames[*fi].pixels).collect();
+        let name = filter.name();
+        if results.is_empty() { self.status = trf!("{}：没有像素变化", name); return; }
+        let changed: Vec<usize> = results.iter().map(|(fi, _)| *fi).collect();
+        self.save_undo_frames(&changed);
+        let Some(shp) = &mut self.shp else { return; };
+        for (fi, px) in results { shp.frames[fi].pixels = px; }
+        self.after_canvas_change();
+        self.record_op("滤镜", changed.first().copied().filter(|_| changed.len() == 1));
+        self.status = trf!("{}：已处理 {} 帧", name, changed.len());
+    }
+
+    // 生成图标：当前帧缩放到 60×48 并换到图标调色板，另存为单帧 SHP
+    fn ui_cameo_dialog(&mut self, ctx: &Context) {
+        let mut open = self.show_cameo_dialog;
+        let mut save = false;
+        egui::Window::new(tr("生成图标 (cameo)")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            let Some(shp) = &self.shp else { ui.label(tr("当前没有SHP")); return; };
+            let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+            ui.label(trf!("源帧: {}", fi));
+            ui.horizontal(|ui| {
+                ui.label(tr("图标调色板"));
+                let name = self.cameo_pal.as_ref().map(|(n, _)| n.clone()).unwrap_or_else(|| tr("（未选择）").into());
+                ui.menu_button(name, |ui| {
+                    for (group, items) in &self.grouped_pals {
+                        ui.menu_button(group, |ui| {
+                            for (name, pal) in items {
+                                if ui.button(name).clicked() { self.cameo_pal = Some((name.clone(), pal.clone())); ui.close_menu(); }
+                            }
+                        });
+                    }
+                });
+            });
+            let opts = &mut self.cameo_opts;
+            egui::ComboBox::from_label(tr("重采样")).selected_text(opts.resample.label()).show_ui(ui, |ui| {
+                for r in image_io::Resample::ALL { ui.selectable_value(&mut opts.resample, r, r.label()); }
+            });
+            ui.horizontal(|ui| {
+                ui.label(tr("底色"));
+                ui.color_edit_button_srgb(&mut opts.background);
+            });
+            ui.checkbox(&mut opts.overlay, tr("叠加边框与底部文字区"));
+            let Some((_, cameo_pal)) = &self.cameo_pal else { return; };
+            // 参数或帧内容变化时重新生成预览
+            let key = {
+                use std::hash::{Hash, Hasher};
+                let mut h = std::collections::hash_map::DefaultHasher::new();
+                (shp.frames[fi].content_hash(), self.palette.to_bytes(), cameo_pal.to_bytes()).hash(&mut h);
+                (opts.resample.label(), opts.background, opts.overlay).hash(&mut h);
+                h.finish()
+            };
+            if self.cameo_preview.as_ref().is_none_or(|(k, _)| *k != key) {
+                let icon = cameo::render(shp, fi, &self.palette, cameo_pal, opts);
+                self.cameo_preview = Some((key, icon.egui_texture_named(ctx, "cameo_preview", 0, cameo_pal, 1.0)));
+            }
+            if let Some((_, tex)) = &self.cameo_preview {
+                ui.image((tex.id(), egui::vec2(cameo::CAMEO_W as f32, cameo::CAMEO_H as f32) * 2.0));
+            }
+            if ui.button(tr("另存为 SHP...")).clicked() { save = true; }
+        });
+        self.show_cameo_dialog = open;
+        if !open { self.cameo_preview = None; }
+        if save { self.action_save_cameo(); }
+    }
+
+    fn action_save_cameo(&mut self) {
+        let (Some(shp), Some((_, cameo_pal))) = (&self.shp, &self.cameo_pal) else { return; };
+        let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+        let stem = self.current_path.as_ref().and_then(|p| p.file_stem()).map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "unit".into());
+        let Some(path) = FileDialog::new().add_filter("SHP", &["shp"]).set_file_name(format!("{}icon.shp", stem)).save_file() else { return; };
+        let icon = cameo::render(shp, fi, &self.palette, cameo_pal, &self.cameo_opts);
+        match icon.save().and_then(|bytes| std::fs::write(&path, bytes).map_err(|e| e.to_string())) {
+            Ok(()) => {
+                self.status = trf!("已保存图标: {}", path.display());
+                self.record_op("生成图标", Some(fi));
+                self.run_export_hook(&path);
+            }
+            Err(e) => self.status = trf!("保存失败: {}", e),
+        }
+    }
+
+    // 补间对话框：在两个选中帧之间插入若干过渡帧
+    fn ui_tween_dialog(&mut self, ctx: &Context) {
+        let mut open = self.show_tween_dialog;
+        let mut apply = false;
+        egui::Window::new(tr("补间")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            let (Some(&a), Some(&b)) = (self.selected_frames.first(), self.selected_frames.last()) else { return; };
+            if self.selected_frames.len() != 2 { ui.label(tr("先在时间轴上选中两帧")); return; }
+            ui.label(trf!("在第 {} 帧与第 {} 帧之间插入", a, b));
+            ui.add(egui::DragValue::new(&mut self.tween_steps).clamp_range(1..=64).suffix(tr(" 帧")));
+            for mode in TweenMode::ALL { ui.radio_value(&mut self.tween_mode, mode, mode.label()); }
+            let hint = match self.tween_mode {
+                TweenMode::CrossFade => tr("按 RGB 混合两帧颜色并重新匹配调色板，适合淡入淡出"),
+                TweenMode::Motion => tr("把前一帧的内容按包围盒位置移向后一帧；有选区时只移动选区内内容，选区需覆盖运动路径"),
+            };
+            ui.label(RichText::new(hint).small().color(Color32::GRAY));
+            if ui.button(tr("生成")).clicked() { apply = true; }
+        });
+        self.show_tween_dialog = open;
+        if apply { self.action_tween(); }
+    }
+
+    fn action_tween(&mut self) {
+        let (Some(&a), Some(&b)) = (self.selected_frames.first(), self.selected_frames.last()) else { return; };
+        if self.selected_frames.len() != 2 || !self.check_frame_room(self.tween_steps) { return; }
+        self.save_undo_document(false);
+        let Some(shp) = &mut self.shp else { return; };
+        let mask = self.selection.as_ref().filter(|s| s.mask.len() == shp.frames[a].pixels.len()).map(|s| s.mask.as_slice());
+        let tweens = shp.tween_frames(a, b, self.tween_steps, self.tween_mode, &self.palette, mask);
+        // 插在前一帧之后
+        shp.frames.splice(a + 1..a + 1, tweens);
+        self.selected_frames.clear();
+        self.selected_frames.extend([a, b + self.tween_steps]);
+        self.show_tween_dialog = false;
+        self.after_canvas_change();
+        self.record_op("补间", None);
+        self.status = trf!("已在第 {} 帧与第 {} 帧之间生成 {} 帧", a, b, self.tween_steps);
+    }
+
+    // 生成朝向对话框：把当前帧（或全部帧）旋转成 N 个朝向，按游戏的朝向顺序追加
+    fn ui_facings_dialog(&mut self, ctx: &Context) {
+        let mut open = self.show_facings_dialog;
+        let mut apply = false;
+        egui::Window::new(tr("生成朝向")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            let Some(shp) = &self.shp else { ui.label(tr("当前没有SHP")); return; };
+            ui.horizontal(|ui| {
+                ui.label(tr("朝向数"));
+                for n in [8, 16, 32] { ui.selectable_value(&mut self.facings_count, n, n.to_string()); }
+            });
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.facings_all, false, trf!("当前帧 ({})", self.preview.current_frame));
+                ui.radio_value(&mut self.facings_all, true, trf!("全部 {} 帧", shp.frames.len()));
+            });
+            ui.horizontal(|ui| {
+                ui.label(tr("源帧朝向"));
+                for (i, label) in [tr("北（上）"), tr("东（右）"), tr("南（下）"), tr("西（左）")].into_iter().enumerate() {
+                    ui.selectable_value(&mut self.facings_source, i, label);
+                }
+            });
+            ui.checkbox(&mut self.facings_clockwise, tr("顺时针排列")).on_hover_text(tr("游戏中的朝向从北开始逆时针排列"));
+            ui.checkbox(&mut self.facings_smooth, tr("平滑旋转")).on_hover_text(tr("双线性插值后重新匹配调色板；默认最近邻，只搬运原有索引"));
+            let sources = if self.facings_all { shp.frames.len() } else { 1 };
+            let hint = if sources > 1 { trf!("将追加 {} 帧：每个朝向依次包含全部源帧", self.facings_count * sources) } else { trf!("将追加 {} 帧", self.facings_count) };
+            ui.label(RichText::new(hint).small().color(Color32::GRAY));
+            if ui.button(tr("生成")).clicked() { apply = true; }
+        });
+        self.show_facings_dialog = open && !apply;
+        if apply { self.action_generate_facings(); }
+    }
+
+    fn action_generate_facings(&mut self) {
+        let Some(shp) = &self.shp else { return; };
+        let sources: Vec<usize> = if self.facings_all { (0..shp.frames.len()).collect() } else { vec![self.preview.current_frame.min(shp.frames.len().saturating_sub(1))] };
+        let n = self.facings_count;
+        if !self.check_frame_room(n * sources.len()) { return; }
+        let step = 360.0 / n as f32;
+        let src_heading = FACING_SOURCES[self.facings_source.min(FACING_SOURCES.len() - 1)];
+        let sign = if self.facings_clockwise { -1.0 } else { 1.0 };
+        // 朝向 k 的方位（自北逆时针）为 sign·k·step；旋转角按顺时针为正
+        let jobs: Vec<(f32, usize)> = (0..n).flat_map(|k| sources.iter().map(move |&fi| (src_heading - sign * k as f32 * step, fi))).collect();
+        let Some(shp) = &self.shp else { return; };
+        let frames: Vec<crate::shp::Frame> = jobs.par_iter().map(|&(deg, fi)| shp.rotated_frame(fi, deg, self.facings_smooth, &self.palette)).collect();
+        self.save_undo_document(false);
+        let Some(shp) = &mut self.shp else { return; };
+        let first = shp.frames.len();
+        let added = frames.len();
+        shp.frames.extend(frames);
+        self.preview.current_frame = first;
+        self.after_canvas_change();
+        self.record_op("生成朝向", None);
+        self.status = trf!("已生成 {} 个朝向，追加 {} 帧（从第 {} 帧开始）", n, added, first);
+    }
+
+    fn analyze_duplicates(&mut self) {
+        let Some(shp) = &self.shp else { return; };
+        self.dedup_groups = shp.duplicate_groups();
+        // 每组除首帧外的数据都可省下（按各帧当前的编码方式）
+        let sizes = shp.encoded_sizes();
+        self.dedup_saving = self.dedup_groups.iter().flat_map(|g| &g[1..])
+            .map(|&i| if shp.frames[i].compression == Compression::Rle0 { sizes[i].1 } else { sizes[i].0 }).sum();
+    }
+
+    fn ui_dedup(&mut self, ctx: &Context) {
+        let mut open = self.show_dedup;
+        let (mut reanalyze, mut keep_unique, mut share_changed) = (false, false, false);
+        egui::Window::new(tr("重复帧分析")).open(&mut open).default_width(360.0).show(ctx, |ui| {
+            let Some(shp) = &self.shp else { ui.label(tr("当前没有SHP")); return; };
+            let dup: usize = self.dedup_groups.iter().map(|g| g.len() - 1).sum();
+            if self.dedup_groups.is_empty() {
+                ui.label(tr("没有内容相同的帧"));
+            } else {
+                ui.label(trf!("{} 组相同帧，共 {} 帧可省去", self.dedup_groups.len(), dup));
+                egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                    for g in &self.dedup_groups {
+                        ui.horizontal_wrapped(|ui| {
+                            ui.label(trf!("第 {} 帧 =", g[0]));
+                            for &i in g {
+                                if ui.selectable_label(self.preview.current_frame == i, i.to_string()).clicked() { self.preview.current_frame = i; }
+                            }
+                        });
+                    }
+                });
+            }
+            ui.weak(tr("像素与对齐偏移都相同才算相同；修改文档后请重新分析"));
+            ui.separator();
+            let share = ui.checkbox(&mut self.settings.share_duplicate_frames, tr("保存时相同帧共用数据"))
+                .on_hover_text(tr("相同帧的帧头指向同一份数据，帧数与动画不变，只减小文件"));
+            share_changed = share.changed();
+            if self.dedup_saving > 0 { ui.weak(trf!("可减小约 {} 字节", format_number(self.dedup_saving))); }
+            ui.horizontal(|ui| {
+                if ui.button(tr("重新分析")).clicked() { reanalyze = true; }
+                let can = dup > 0 && dup < shp.frames.len();
+                if ui.add_enabled(can, egui::Button::new(tr("只保留唯一帧"))).on_hover_text(tr("删除每组中首帧之外的帧；帧序号与动画节奏会随之改变")).clicked() {
+                    keep_unique = true;
+                }
+            });
+        });
+        self.show_dedup = open;
+        if share_changed { self.save_settings(); }
+        if keep_unique { self.action_keep_unique_frames(); }
+        if reanalyze || keep_unique { self.analyze_duplicates(); }
+    }
+
+    fn action_keep_unique_frames(&mut self) {
+        // 对话框打开期间帧可能被修改、删除或重排：按当前文档重新分组
+        self.analyze_duplicates();
+        let mut remove: Vec<usize> = self.dedup_groups.iter().flat_map(|g| g[1..].iter().copied()).collect();
+        if remove.is_empty() { return; }
+        remove.sort_unstable();
+        self.save_undo_document(false);
+        let Some(shp) = &mut self.shp else { return; };
+        for &i in remove.iter().rev() { shp.frames.remove(i); }
+        self.preview.current_frame = self.preview.current_frame.min(shp.frames.len() - 1);
+        self.selected_frames.clear();
+        self.after_canvas_change();
+        self.record_op("删除重复帧", None);
+        self.status = trf!("已删除 {} 个重复帧", remove.len());
+    }
+
+    fn open_pal_convert(&mut self) {
+        let Some(shp) = &self.shp else { return; };
+        let mut usage = vec![0usize; 256];
+        for fr in &shp.frames { for &p in &fr.pixels { usage[p as usize] += 1; } }
+        self.pal_convert_usage = usage;
+        self.show_pal_convert = true;
+    }
+
+    // 转换到调色板：选择目标调色板，预览索引映射表，确认后改写所有帧并切换调色板
+    fn ui_pal_convert(&mut self, ctx: &Context) {
+        let mut open = self.show_pal_convert;
+        let mut apply = false;
+        egui::Window::new(tr("转换到调色板")).open(&mut open).default_width(420.0).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(trf!("当前: {}", self.current_pal_name));
+                ui.label("→");
+                let target_name = self.pal_convert_target.as_ref().map(|(n, _)| n.clone()).unwrap_or_else(|| tr("（未选择）").into());
+                ui.menu_button(target_name, |ui| {
+                    for (group, items) in &self.grouped_pals {
+                        ui.menu_button(group, |ui| {
+                            for (name, pal) in items {
+                                if ui.button(name).clicked() {
+                                    self.pal_convert_target = Some((name.clone(), pal.clone()));
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    }
+                });
+                if ui.button(tr("打开 PAL...")).clicked()
+                    && let Some(path) = FileDialog::new().add_filter("PAL", &["pal"]).pick_file()
+                {
+                    match std::fs::read(&path).map_err(|e| e.to_string()).and_then(|b| Palette::from_bytes(&b)) {
+                        Ok(p) => {
+                            let name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+                            self.pal_convert_target = Some((name, p));
+                        }
+                        Err(e) => { self.status = trf!("加载PAL失败: {}", e); }
+                    }
+                }
+            });
+            let Some((_, target)) = &self.pal_convert_target else { return; };
+            let table = color_match::palette_remap_table(&self.palette, target);
+            ui.checkbox(&mut self.pal_convert_used_only, tr("仅显示使用中的索引"));
+            let usage = &self.pal_convert_usage;
+            let transparent = self.shp.as_ref().map_or(0, |s| s.transparent) as usize;
+            egui::ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                egui::Grid::new("pal_convert_table").striped(true).num_columns(4).show(ui, |ui| {
+                    ui.label(tr("原索引")); ui.label(""); ui.label(tr("新索引")); ui.label(tr("像素数")); ui.end_row();
+                    for (i, &j) in table.iter().enumerate() {
+                        let n = usage.get(i).copied().unwrap_or(0);
+                        if self.pal_convert_used_only && n == 0 { continue; }
+                        let j = j as usize;
+                        let swatch = |ui: &mut egui::Ui, c: Color32, idx: usize| {
+                            ui.horizontal(|ui| {
+                                let (r, _) = ui.allocate_exact_size(egui::vec2(16.0, 14.0), Sense::hover());
+                                if idx == transparent { paint_checkerboard(ui.painter(), r, 2.0, &CanvasBackground::default()); } else { ui.painter().rect_filled(r, 0.0, c); }
+                                ui.label(idx.to_string());
+                            });
+                        };
+                        swatch(ui, self.palette.colors[i], i);
+                        ui.label("→");
+                        swatch(ui, target.colors[j], j);
+                        ui.label(format_number(n));
+                        ui.end_row();
+                    }
+                });
+            });
+            ui.separator();
+            ui.label(RichText::new(tr("将改写所有帧的索引并切换到目标调色板")).small().color(Color32::GRAY));
+            if ui.button(tr("转换")).clicked() { apply = true; }
+        });
+        self.show_pal_convert = open;
+        if apply { self.action_convert_palette(); }
+    }
+
+    fn action_convert_palette(&mut self) {
+        let Some((name, target)) = self.pal_convert_target.clone() else { return; };
+        self.show_pal_convert = false;
+        self.convert_to_palette(name, target);
+    }
+
+    // 改写所有帧的索引使外观尽量不变，再切换到目标调色板
+    fn convert_to_palette(&mut self, name: String, target: Palette) {
+        if self.shp.is_none() { return; }
+        self.save_undo_document(true);
+        let Some(shp) = &mut self.shp else { return; };
+        let mut used = [false; 256];
+        for fr in &shp.frames { for &p in &fr.pixels { used[p as usize] = true; } }
+        let table = color_match::palette_remap_table(&self.palette, &target);
+        shp.remap_all(&table);
+        let changed = (1..256).filter(|&i| table[i] as usize != i && used[i]).count();
+        self.palette = target;
+        self.current_pal_name = name.clone();
+        self.pal_path = None;
+        self.dirty = true;
+        self.record_op("转换到调色板", None);
+        self.status = trf!("已转换到调色板 {}：{} 个使用中的索引被改写", name, changed);
+    }
+
+    // 从内置调色板菜单切换：按设置直接处理，或弹出确认框让用户选择方式
+    fn request_palette_switch(&mut self, name: String, pal: Palette) {
+        if self.shp.is_none() { self.switch_palette(name, pal); return; }
+        match self.settings.pal_switch {
+            PaletteSwitch::Ask => { self.pal_switch_pending = Some((name, pal)); self.pal_switch_remember = false; }
+            PaletteSwitch::Reassign => self.switch_palette(name, pal),
+            PaletteSwitch::Remap => self.convert_to_palette(name, pal),
+        }
+    }
+
+    fn ui_pal_switch(&mut self, ctx: &Context) {
+        let Some((name, _)) = &self.pal_switch_pending else { return; };
+        let mut open = true;
+        let mut choice = None;
+        egui::Window::new(tr("切换调色板")).open(&mut open).collapsible(false).resizable(false).default_width(380.0).show(ctx, |ui| {
+            ui.label(format!("{} → {}", self.current_pal_name, name));
+            ui.add_space(4.0);
+            ui.label(RichText::new(tr("仅换调色板（索引不变）")).strong());
+            ui.label(tr("像素索引不动，每个像素改用新调色板中同一索引的颜色显示，外观会随之变化。适合同一套索引规划的调色板之间切换，例如单位与建筑 PAL。"));
+            ui.add_space(4.0);
+            ui.label(RichText::new(tr("改写索引以保持外观")).strong());
+            ui.label(tr("为每个使用中的索引在新调色板中找最接近的颜色并改写所有帧，画面尽量保持原样。阴影与阵营色区间按角色对应。"));
+            ui.add_space(4.0);
+            ui.checkbox(&mut self.pal_switch_remember, tr("记住选择，以后不再询问"))
+                .on_hover_text(tr("可在 设置 → 选择内置PAL时 中改回"));
+            ui.horizontal(|ui| {
+                if ui.button(tr("仅换调色板（索引不变）")).clicked() { choice = Some(PaletteSwitch::Reassign); }
+                if ui.button(tr("改写索引以保持外观")).clicked() { choice = Some(PaletteSwitch::Remap); }
+                if ui.button(tr("取消")).clicked() { choice = Some(PaletteSwitch::Ask); }
+            });
+        });
+        if !open { self.pal_switch_pending = None; return; }
+        let Some(mode) = choice else { return; };
+        let Some((name, pal)) = self.pal_switch_pending.take() else { return; };
+        if mode != PaletteSwitch::Ask && self.pal_switch_remember {
+            self.settings.pal_switch = mode;
+            self.save_settings();
+        }
+        match mode {
+            PaletteSwitch::Reassign => self.switch_palette(name, pal),
+            PaletteSwitch::Remap => self.convert_to_palette(name, pal),
+            PaletteSwitch::Ask => {}
+        }
+    }
+
+    // 快捷键设置：点击“修改”后按下新的组合键，Esc 取消
+    fn ui_keymap_window(&mut self, ctx: &Context) {
+        if let Some(action) = self.keymap_capture {
+            let captured = ctx.input(|i| i.events.iter().find_map(|e| match e {
+                egui::Event::Key { key, pressed: true, modifiers, .. } => Some((*key, *modifiers)),
+                // Ctrl+X/C/V 只以剪切/复制/粘贴事件出现
+                egui::Event::Cut => Some((Key::X, i.modifiers)),
+                egui::Event::Copy => Some((Key::C, i.modifiers)),
+                egui::Event::Paste(_) => Some((Key::V, i.modifiers)),
+                _ => None,
+            }));
+            if let Some((key, mods)) = captured {
+                if key != Key::Escape || !mods.is_none() {
+                    self.settings.keymap.bindings.insert(action, KeyChord::new(mods, key));
+                    self.save_settings();
+                }
+                self.keymap_capture = None;
+            }
+        }
+        let mut open = self.show_keymap_window;
+        egui::Window::new(tr("快捷键")).open(&mut open).resizable(false).show(ctx, |ui| {
+            egui::Grid::new("keymap_grid").num_columns(3).striped(true).show(ui, |ui| {
+                for action in Action::ALL {
+                    ui.label(action.label());
+                    if self.keymap_capture == Some(action) {
+                        ui.label(RichText::new(tr("请按下组合键… (Esc 取消)")).color(Color32::YELLOW));
+                    } else {
+                        let conflicts = self.settings.keymap.conflicts(action);
+                        let text = self.settings.keymap.chord_text(action);
+                        if conflicts.is_empty() {
+                            ui.monospace(text);
+                        } else {
+                            let names: Vec<&str> = conflicts.iter().map(|a| a.label()).collect();
+                            ui.monospace(RichText::new(text).color(Color32::LIGHT_RED)).on_hover_text(trf!("与 {} 冲突", names.join(tr("、"))));
+                        }
+                    }
+                    if ui.button(tr("修改")).clicked() { self.keymap_capture = Some(action); }
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+            if ui.button(tr("恢复默认")).clicked() {
+                self.settings.keymap = Default::default();
+                self.save_settings();
+            }
+            ui.label(RichText::new(trf!("配置文件: {}", Settings::path().display())).small().color(Color32::GRAY));
+        });
+        if !open { self.keymap_capture = None; }
+        self.show_keymap_window = open;
+    }
+
+    // 统计：记录一次操作（未开启统计时忽略）
+    fn record_op(&mut self, op: &'static str, frame: Option<usize>) {
+        if self.settings.stats_enabled { self.session.record(op, frame); }
+    }
+
+    // 切换文档前把当前会话并入累计统计
+    fn begin_document_stats(&mut self) {
+        if self.settings.stats_enabled && !self.session.doc_key.is_empty() {
+            self.session.flush_into(&mut self.settings.stats);
+            self.save_settings();
+        }
+        let key = self.current_path.as_ref().map(|p| p.display().to_string()).unwrap_or_else(|| tr("(未命名)").to_owned());
+        self.session = SessionStats::new(key);
+    }
+
+    fn ui_stats_window(&mut self, ctx: &Context) {
+        let mut open = self.show_stats_window;
+        egui::Window::new(tr("使用统计")).open(&mut open).default_width(420.0).show(ctx, |ui| {
+            if ui.checkbox(&mut self.settings.stats_enabled, tr("记录本地使用统计（仅保存在本机）")).changed() {
+                self.save_settings();
+            }
+            if !self.settings.stats_enabled { ui.label(RichText::new(tr("统计未开启。")).color(Color32::GRAY)); return; }
+            ui.separator();
+            ui.heading(tr("本次会话"));
+            ui.label(trf!("文档: {}", self.session.doc_key));
+            ui.label(trf!("编辑时间: {}", stats::format_duration(self.session.active_secs as u64)));
+            ui.label(trf!("操作次数: {}，编辑过的帧: {}", self.session.total_ops(), self.session.frames_touched.len()));
+            for (op, n) in &self.session.ops { ui.label(format!("  {} × {}", tr(op), n)); }
+            ui.separator();
+            ui.heading(tr("累计"));
+            egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                egui::Grid::new("stats_docs").num_columns(4).striped(true).show(ui, |ui| {
+                    ui.strong(tr("文档")); ui.strong(tr("时间")); ui.strong(tr("帧")); ui.strong(tr("操作")); ui.end_row();
+                    for (doc, d) in &self.settings.stats.documents {
+                        ui.label(doc); ui.label(stats::format_duration(d.seconds)); ui.label(d.frames_edited.to_string()); ui.label(d.ops.to_string()); ui.end_row();
+                    }
+                });
+            });
+            if ui.button(tr("清空累计统计")).clicked() {
+                self.settings.stats = Default::default();
+                self.save_settings();
+            }
+        });
+        self.show_stats_window = open;
+    }
+
+    fn ui_hook_window(&mut self, ctx: &Context) {
+        let mut open = self.show_hook_window;
+        let mut changed = false;
+        egui::Window::new(tr("导出后执行命令")).open(&mut open).default_width(480.0).show(ctx, |ui| {
+            changed |= ui.checkbox(&mut self.settings.export_hook_enabled, tr("保存/导出成功后执行")).changed();
+            changed |= ui.add(egui::TextEdit::multiline(&mut self.settings.export_hook).desired_rows(2).desired_width(f32::INFINITY).code_editor()).lost_focus();
+            ui.label(RichText::new(tr("占位符: {file} 完整路径, {dir} 目录, {name} 文件名(无扩展名), {ext} 扩展名；路径含空格时请加引号")).small().color(Color32::GRAY));
+            ui.label(RichText::new(tr("示例: copy \"{file}\" \"D:\\Mod\\\" && D:\\Mod\\gamemd.exe")).small().color(Color32::GRAY));
+            if let Some(path) = &self.current_path {
+                ui.label(RichText::new(trf!("当前展开: {}", hook::expand(&self.settings.export_hook, path))).small());
+            }
+        });
+        if changed { self.save_settings(); }
+        self.show_hook_window = open;
+    }
+
+    fn ui_script_console(&mut self, ctx: &Context) {
+        let mut open = self.show_script_console;
+        let (mut run, mut load, mut save) = (false, false, false);
+        egui::Window::new(tr("脚本控制台")).open(&mut open).default_width(560.0).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.add_enabled(self.shp.is_some(), egui::Button::new(tr("运行"))).clicked() { run = true; }
+                if ui.button(tr("打开 .rhai...")).clicked() { load = true; }
+                if ui.button(tr("保存 .rhai...")).clicked() { save = true; }
+                if let Some(p) = &self.script_path { ui.weak(p.display().to_string()); }
+            });
+            ui.collapsing(tr("可用函数"), |ui| { ui.label(RichText::new(script::api_help()).monospace().small()); });
+            egui::ScrollArea::vertical().id_source("script_src").max_height(320.0).show(ui, |ui| {
+                ui.add(egui::TextEdit::multiline(&mut self.script_source).code_editor().desired_rows(14).desired_width(f32::INFINITY));
+            });
+            ui.separator();
+            ui.label(tr("输出"));
+            egui::ScrollArea::vertical().id_source("script_log").max_height(140.0).stick_to_bottom(true).show(ui, |ui| {
+                for line in &self.script_log { ui.label(RichText::new(line).monospace()); }
+            });
+        });
+        self.show_script_console = open;
+        if run { let src = self.script_source.clone(); self.action_run_script(&src); }
+        if load && let Some(path) = FileDialog::new().add_filter("Rhai", &["rhai"]).pick_file() {
+            match std::fs::read_to_string(&path) {
+                Ok(src) => { self.script_source = src; self.script_path = Some(path); }
+                Err(e) => self.status = trf!("读取文件失败: {}", e),
+            }
+        }
+        if save && let Some(path) = FileDialog::new().add_filter("Rhai", &["rhai"]).set_file_name("script.rhai").save_file() {
+            match std::fs::write(&path, &self.script_source) {
+                Ok(()) => { self.status = trf!("已保存脚本: {}", path.display()); self.script_path = Some(path); }
+                Err(e) => self.status = trf!("保存失败: {}", e),
+            }
+        }
+    }
+
+    // 在文档副本上运行脚本；成功且有改动时整体替换文档（连同调色板一步撤销），出错时文档不变
+    fn action_run_script(&mut self, source: &str) {
+        let Some(shp) = &self.shp else { self.status = tr("请先新建或打开SHP").into(); return; };
+        let out = script::run(source, shp, &self.palette, self.preview.current_frame);
+        self.script_log = out.log;
+        match out.result {
+            Ok((new_shp, new_pal)) => {
+                let pal_changed = new_pal.colors != self.palette.colors;
+                if !pal_changed && self.shp.as_ref().is_some_and(|s| s.frames.len() == new_shp.frames.len()
+                    && s.frames.iter().zip(&new_shp.frames).all(|(a, b)| a.pixels == b.pixels && a.offset == b.offset)) {
+                    self.status = tr("脚本运行完成，文档没有变化").into();
+                    return;
+                }
+                self.save_undo_document(pal_changed);
+                self.shp = Some(new_shp);
+                self.palette = new_pal;
+                self.record_op("运行脚本", None);
+                self.after_canvas_change();
+                self.status = tr("脚本运行完成").into();
+            }
+            Err(e) => {
+                self.script_log.push(trf!("错误: {}", e));
+                self.status = trf!("脚本出错: {}", e);
+            }
+        }
+    }
+
+    // 保存/导出成功后调用：按设置执行外部命令
+    fn run_export_hook(&mut self, path: &std::path::Path) {
+        if !self.settings.export_hook_enabled || self.settings.export_hook.trim().is_empty() { return; }
+        let cmdline = hook::expand(&self.settings.export_hook, path);
+        match hook::spawn(&cmdline) {
+            Ok(()) => { self.status = trf!("{}；已执行: {}", self.status, cmdline); }
+            Err(e) => { self.status = trf!("执行导出命令失败: {}", e); }
+        }
+    }
+
+    // 监视打开的 SHP/PAL：按当前路径（重）建监视；外部程序写完后重新加载，SHP 有未保存的修改时先询问
+    fn poll_file_watch(&mut self, ctx: &Context) {
+        let paths: Vec<std::path::PathBuf> = if self.settings.watch_files {
+            [self.current_path.clone(), self.pal_path.clone()].into_iter().flatten().collect()
+        } else { Vec::new() };
+        if paths.is_empty() { self.watch = None; return; }
+        if !self.watch.as_ref().is_some_and(|w| w.watches(&paths)) {
+            match FileWatch::new(&paths, ctx) {
+                Ok(w) => self.watch = Some(w),
+                Err(e) => { self.watch = None; self.settings.watch_files = false; self.status = trf!("无法监视文件: {}", e); return; }
+            }
+        }
+        let Some(w) = &mut self.watch else { return; };
+        let (changed, waiting) = w.poll();
+        if waiting { ctx.request_repaint_after(std::time::Duration::from_millis(100)); }
+        for (path, bytes) in changed {
+            if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("pal")) {
+                match Palette::from_bytes(&bytes) {
+                    Ok(p) => {
+                        self.save_undo_palette();
+                        self.palette = p;
+                        self.dirty = true;
+                        self.status = trf!("PAL 已被外部修改，已重新加载: {}", path.display());
+                    }
+                    Err(e) => self.status = trf!("加载PAL失败: {}", e),
+                }
+            } else if self.dirty {
+                self.watch_conflict = true;
+            } else {
+                self.reload_shp_from_disk();
+            }
+        }
+    }
+
+    // 重新读取当前 SHP，尽量停留在原来的帧
+    fn reload_shp_from_disk(&mut self) {
+        let Some(path) = self.current_path.clone() else { return; };
+        self.spawn_open_shp(&path, Some(self.preview.current_frame));
+    }
+
+    fn ui_watch_conflict(&mut self, ctx: &Context) {
+        let name = self.current_path.as_ref().map(|p| p.display().to_string()).unwrap_or_default();
+        egui::Window::new(tr("文件已被外部修改")).collapsible(false).resizable(false).anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0]).show(ctx, |ui| {
+            ui.label(trf!("{} 在磁盘上被其他程序修改，而编辑器中有未保存的修改。", name));
+            ui.horizontal(|ui| {
+                if ui.button(tr("重新加载（放弃编辑器中的修改）")).clicked() { self.watch_conflict = false; self.reload_shp_from_disk(); }
+                if ui.button(tr("保留编辑器中的版本")).clicked() { self.watch_conflict = false; }
+            });
+        });
+    }
+
+    // 轮询后台更新检查；启动时的自动检查只在发现新版本时弹窗
+    fn poll_update_check(&mut self, ctx: &Context) {
+        let Some(rx) = &self.update_rx else { return; };
+        match rx.try_recv() {
+            Ok(res) => {
+                if matches!(&res, Ok(info) if info.is_newer()) { self.show_update_window = true; }
+                self.update_result = Some(res);
+                self.update_rx = None;
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => ctx.request_repaint_after(std::time::Duration::from_millis(200)),
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => { self.update_rx = None; }
+        }
+    }
+
+    fn ui_update_window(&mut self, ctx: &Context) {
+        let mut open = self.show_update_window;
+        egui::Window::new(tr("检查更新")).open(&mut open).default_width(460.0).show(ctx, |ui| {
+            ui.label(trf!("当前版本: {}", update::current_version()));
+            match &self.update_result {
+                None => { ui.horizontal(|ui| { ui.spinner(); ui.label(tr("正在查询 GitHub 发布...")); }); }
+                Some(Err(e)) => {
+                    ui.colored_label(Color32::LIGHT_RED, trf!("检查失败: {}", e));
+                    ui.hyperlink_to(tr("打开发布页面"), update::RELEASES_PAGE);
+                }
+                Some(Ok(info)) => {
+                    if info.is_newer() {
+                        ui.label(RichText::new(trf!("发现新版本: {}", info.tag_name)).strong().color(Color32::LIGHT_GREEN));
+                    } else {
+                        ui.label(trf!("已是最新版本（最新发布: {}）", info.tag_name));
+                    }
+                    if let Some(name) = info.name.as_deref().filter(|n| !n.is_empty() && *n != info.tag_name) { ui.label(name); }
+                    if let Some(date) = info.published_at.as_deref().and_then(i18n::format_date) { ui.label(trf!("发布日期: {}", date)); }
+                    ui.separator();
+                    ui.label(tr("更新日志"));
+                    egui::ScrollArea::vertical().max_height(280.0).show(ui, |ui| {
+                        ui.label(RichText::new(info.body.as_deref().unwrap_or("")).monospace());
+                    });
+                    ui.separator();
+                    ui.hyperlink_to(tr("前往下载"), &info.html_url);
+                }
+            }
+        });
+        self.show_update_window = open;
+    }
+
+    fn save_settings(&mut self) {
+        if let Err(e) = self.settings.save() { self.status = trf!("保存设置失败: {}", e); }
+    }
+
+    /// 切换调色板（可撤销）；切换会影响显示，标记为需要保存
+    fn switch_palette(&mut self, name: String, pal: Palette) {
+        let old = std::mem::replace(&mut self.palette, pal);
+        let old_name = std::mem::replace(&mut self.current_pal_name, name);
+        self.pal_path = None;
+        self.history.push(self.preview.current_frame, Patch::Palette(Box::new(old), old_name), self.max_undo_steps, self.shp.as_ref());
+        self.dirty = true;
+    }
+
+    // 记入最近使用：已在列表中的移到最前
+    fn push_recent_color(&mut self, idx: u8) {
+        self.recent_colors.retain(|&i| i != idx);
+        self.recent_colors.insert(0, idx);
+        self.recent_colors.truncate(RECENT_COLORS);
+    }
+
+    fn action_swap_colors(&mut self) {
+        std::mem::swap(&mut self.brush_index, &mut self.brush_index2);
+        self.brush_hud_until = Some(Instant::now() + BRUSH_HUD_TIME);
+    }
+
+    /// 在文档调色板组内切到下一个；当前调色板不在组内时切到第一个
+    fn action_cycle_palette(&mut self) {
+        if self.doc_pals.is_empty() {
+            self.status = tr("文档调色板组为空：在 文件 → 文档调色板组 中加入调色板").to_owned();
+            return;
+        }
+        let next = self.doc_pals.iter().position(|(n, _)| *n == self.current_pal_name).map_or(0, |i| (i + 1) % self.doc_pals.len());
+        let (name, pal) = self.doc_pals[next].clone();
+        self.status = trf!("调色板: {} ({}/{})", name, next + 1, self.doc_pals.len());
+        self.switch_palette(name, pal);
+    }
+
+    // 已固定且仍存在的调色板 (分组, 名称, 调色板)；所在分组被移除的跳过
+    fn pinned_palettes(&self) -> Vec<(String, String, Palette)> {
+        self.settings.pinned_palettes.iter().filter_map(|(group, name)| {
+            let (_, items) = self.grouped_pals.iter().find(|(g, _)| g == group)?;
+            let (_, pal) = items.iter().find(|(n, _)| n == name)?;
+            Some((group.clone(), name.clone(), pal.clone()))
+        }).collect()
+    }
+
+    fn toggle_pinned_palette(&mut self, key: (String, String)) {
+        let pins = &mut self.settings.pinned_palettes;
+        if let Some(i) = pins.iter().position(|k| *k == key) {
+            pins.remove(i);
+            self.status = trf!("已取消固定: {}", key.1);
+        } else {
+            pins.push(key);
+            self.status = if pins.len() <= MAX_PINNED_KEYS { trf!("已固定: {}（快捷键 {}）", pins[pins.len() - 1].1, pins.len()) } else { trf!("已固定: {}", pins[pins.len() - 1].1) };
+        }
+        self.save_settings();
+    }
+
+    /// 切到第 n 个（从 0 计）固定的调色板
+    fn action_pinned_palette(&mut self, n: usize) {
+        let Some((_, name, pal)) = self.pinned_palettes().into_iter().nth(n) else { return; };
+        if name == self.current_pal_name { return; }
+        self.request_palette_switch(name, pal);
+    }
+
+    fn action_new_shp(&mut self) {
+        // 简化：固定弹窗交互改为默认值；后续补对话框
+        let width = 128u32;
+        let height = 128u32;
+        let frames = 8usize;
+        self.shp = Some(SHP::new(width, height, frames));
+        self.current_path = None;
+        self.project_path = None;
+        self.mix_source = None;
+        self.begin_document_stats();
+        self.preview.current_frame = 0;
+        self.status = trf!("已新建 SHP: {}x{}, 帧数 {}", width, height, frames);
+        // 新建后复位编辑状态，避免历史遗留
+        self.dirty = false; // 新建文件，清除dirty标记
+        self.import_img = None;
+        self.import_armed = false;
+        self.history.clear();
+        self.preview.playing = false;
+        self.selected_frames.clear();
+        self.doc_pals.clear();
+        self.sequences.clear();
+    }
+
+    fn action_open_shp(&mut self) {
+        if let Some(path) = FileDialog::new().add_filter("SHP", &["shp"]).pick_file() {
+            self.load_shp_path(&path);
+        }
+    }
+
+    fn load_shp_path(&mut self, path: &std::path::Path) {
+        self.spawn_open_shp(path, None);
+    }
+
+    // 后台读取并解码 SHP；查看模式下的超大 SHP 按需解码，避免一次性占用数百 MB
+    fn spawn_open_shp(&mut self, path: &std::path::Path, reload: Option<usize>) {
+        if !self.io_idle() { return; }
+        let path = path.to_path_buf();
+        let lazy = self.view_only.then_some(LAZY_THRESHOLD);
+        let label = trf!("正在打开 {}", path.display());
+        self.io_task = Some(IoTask::spawn(label, true, &self.egui_ctx, move || {
+            let result = io_task::read_shp(&path, lazy);
+            IoDone::OpenShp { path, result, reload }
+        }));
+    }
+
+    fn finish_open_shp(&mut self, path: std::path::PathBuf, result: Result<LoadedShp, String>, reload: Option<usize>) {
+        match result {
+            Ok(LoadedShp::Lazy(lazy)) => {
+                self.restore_view = None;
+                self.status = trf!("已加载 SHP（{} 帧，按需解码）: {}", lazy.frame_count(), path.display());
+                self.shp = None;
+                self.lazy_shp = Some(lazy);
+                self.current_path = Some(path);
+                self.canvas_tex = None;
+                self.preview.current_frame = 0;
+                self.preview.playing = true;
+            }
+            Ok(LoadedShp::Full(shp)) => {
+                self.adopt_shp(shp, Some(path.clone()));
+                self.status = trf!("已加载 SHP: {}", path.display());
+                match sequences::load(&path) {
+                    Ok(seqs) => self.sequences = seqs,
+                    Err(e) => self.status = trf!("{}；读取序列文件失败: {}", self.status, e),
+                }
+                if let Some((frame, scale)) = self.restore_view.take() {
+                    if let Some(shp) = &self.shp { self.preview.current_frame = frame.min(shp.frames.len().saturating_sub(1)); }
+                    if scale > 0.0 { self.scale = scale; }
+                    self.status = trf!("已恢复上次的文档: {}", path.display());
+                }
+                if let Some(frame) = reload {
+                    if let Some(shp) = &self.shp { self.preview.current_frame = frame.min(shp.frames.len().saturating_sub(1)); }
+                    self.after_canvas_change();
+                    self.status = trf!("SHP 已被外部修改，已重新加载: {}", path.display());
+                }
+            }
+            Err(e) => { self.restore_view = None; self.status = e; }
+        }
+    }
+
+    // 同一时间只进行一个后台文件操作
+    fn io_idle(&mut self) -> bool {
+        if self.io_task.is_some() { self.status = tr("另一个文件操作尚未完成").into(); return false; }
+        true
+    }
+
+    fn poll_io_task(&mut self) {
+        let Some(done) = self.io_task.as_ref().and_then(IoTask::poll) else { return; };
+        self.io_task = None;
+        match done {
+            Err(e) => self.status = e,
+            Ok(IoDone::OpenShp { path, result, reload }) => self.finish_open_shp(path, result, reload),
+            Ok(IoDone::SaveShp { path, result, doc_hash }) => self.finish_save_shp(path, result, doc_hash),
+            Ok(IoDone::Image { path, result }) => self.finish_load_image(&path, result),
+            Ok(IoDone::Animation { path, result }) => self.finish_import_frames(path, result),
+        }
+    }
+
+    // 后台任务进行中：半透明遮罩挡住画布与菜单，中间显示进度与取消按钮
+    fn ui_io_overlay(&mut self, ctx: &Context) {
+        let Some(task) = &self.io_task else { return; };
+        let mut cancel = false;
+        let screen = ctx.screen_rect();
+        egui::Area::new("io_overlay".into()).order(egui::Order::Foreground).fixed_pos(screen.min).show(ctx, |ui| {
+            let (rect, _) = ui.allocate_exact_size(screen.size(), Sense::click_and_drag());
+            ui.painter().rect_filled(rect, 0.0, Color32::from_black_alpha(120));
+            let inner = egui::Rect::from_center_size(rect.center(), egui::vec2(360.0, 90.0));
+            ui.allocate_ui_at_rect(inner, |ui| {
+                egui::Frame::window(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(&task.label);
+                    });
+                    ui.label(RichText::new(trf!("已用时 {} 秒", format!("{:.1}", task.started.elapsed().as_secs_f32()))).small().color(Color32::GRAY));
+                    if task.cancellable && ui.button(tr("取消")).clicked() { cancel = true; }
+                });
+            });
+        });
+        ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        // 线程无法中途打断：取消只是丢弃结果
+        if cancel {
+            self.io_task = None;
+            self.restore_view = None;
+            self.status = tr("已取消").into();
+        }
+    }
+
+    fn adopt_shp(&mut self, shp: SHP, path: Option<std::path::PathBuf>) {
+        self.shp = Some(shp);
+        self.lazy_shp = None;
+        self.current_path = path;
+        self.project_path = None;
+        self.mix_source = None;
+        self.begin_document_stats();
+        // 打开后复位编辑状态，避免历史遗留
+        self.preview.current_frame = 0;
+        self.dirty = false; // 打开新文件，清除dirty标记
+        self.import_img = None;
+        self.import_armed = false;
+        self.history.clear();
+        self.preview.playing = self.view_only;
+        self.selected_frames.clear();
+        self.doc_pals.clear();
+        self.sequences.clear();
+    }
+
+    // 保存前先显示各帧编码与体积（可在设置中关闭）
+    fn action_save_shp(&mut self) {
+        let Some(shp) = &self.shp else { self.status = tr("当前没有SHP").into(); return; };
+        if self.settings.skip_save_report {
+            self.write_shp_as();
+        } else {
+            self.save_sizes = shp.encoded_sizes();
+            self.show_save_report = true;
+        }
+    }
+
+    fn ui_save_report(&mut self, ctx: &Context) {
+        let mut open = self.show_save_report;
+        let mut save = false;
+        egui::Window::new(tr("保存 SHP")).open(&mut open).collapsible(false).default_width(380.0).show(ctx, |ui| {
+            let Some(shp) = &mut self.shp else { ui.label(tr("当前没有SHP")); return; };
+            if self.save_sizes.len() != shp.frames.len() { self.save_sizes = shp.encoded_sizes(); }
+            let sizes = &self.save_sizes;
+            ui.horizontal(|ui| {
+                ui.label(tr("全部设为"));
+                for c in Compression::ALL {
+                    // 单行过宽时 RLE0 不可用，保持未压缩
+                    if ui.button(c.label()).clicked() {
+                        for (fr, &(_, rle)) in shp.frames.iter_mut().zip(sizes) { if c == Compression::Uncompressed || rle != usize::MAX { fr.compression = c; } }
+                    }
+                }
+                if ui.button(tr("较小者")).on_hover_text(tr("每帧选体积更小的编码")).clicked() {
+                    for (fr, &(raw, rle)) in shp.frames.iter_mut().zip(sizes) {
+                        fr.compression = if rle < raw { Compression::Rle0 } else { Compression::Uncompressed };
+                    }
+                }
+            });
+            let chosen = |fr: &crate::shp::Frame, (raw, rle): (usize, usize)| if fr.compression == Compression::Rle0 { rle } else { raw };
+            let data: usize = shp.frames.iter().zip(sizes).map(|(fr, &s)| chosen(fr, s)).sum();
+            let header = 8 + 24 * shp.frames.len();
+            let all_raw: usize = sizes.iter().map(|s| s.0).sum();
+            let all_rle: usize = sizes.iter().map(|s| if s.1 == usize::MAX { s.0 } else { s.1 }).sum();
+            ui.label(trf!("预计文件大小: {} 字节", format_number(header + data)));
+            ui.weak(trf!("全部未压缩 {} / 全部 RLE0 {} 字节", format_number(header + all_raw), format_number(header + all_rle)));
+            ui.weak(tr("未压缩块解码最快；RLE0 对大片透明的帧体积更小"));
+            ui.separator();
+            egui::ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                egui::Grid::new("save_report_grid").num_columns(4).striped(true).show(ui, |ui| {
+                    ui.strong(tr("帧")); ui.strong(tr("未压缩")); ui.strong("RLE0"); ui.strong(tr("编码")); ui.end_row();
+                    for (i, (fr, &(raw, rle))) in shp.frames.iter_mut().zip(sizes).enumerate() {
+                        ui.label(i.to_string());
+                        if raw == 0 {
+                            ui.label(tr("（空）")); ui.label(""); ui.label(""); ui.end_row();
+                            continue;
+                        }
+                        ui.label(format_number(raw));
+                        ui.label(if rle == usize::MAX { "—".to_string() } else { format_number(rle) });
+                        ui.horizontal(|ui| {
+                            ui.radio_value(&mut fr.compression, Compression::Uncompressed, tr("未压缩"));
+                            ui.add_enabled_ui(rle != usize::MAX, |ui| { ui.radio_value(&mut fr.compression, Compression::Rle0, "RLE0"); });
+                        });
+                        ui.end_row();
+                    }
+                });
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button(tr("保存 SHP...")).clicked() { save = true; }
+                if ui.checkbox(&mut self.settings.skip_save_report, tr("以后保存时不再显示")).changed() { self.save_settings(); }
+            });
+        });
+        self.show_save_report = open && !save;
+        if save { self.write_shp_as(); }
+    }
+
+    fn write_shp_as(&mut self) {
+        if self.shp.is_none() { self.status = tr("当前没有SHP").into(); return; }
+        if !self.io_idle() { return; }
+        let Some(path) = FileDialog::new().set_file_name("output.shp").save_file() else { return; };
+        let Some(shp) = self.shp.clone() else { return; };
+        // 编码与写入在后台进行；记下此刻的内容，完成时据此判断期间是否又有改动
+        let doc_hash = doc_hash(&shp);
+        let share = self.settings.share_duplicate_frames;
+        let label = trf!("正在保存 {}", path.display());
+        self.io_task = Some(IoTask::spawn(label, false, &self.egui_ctx, move || {
+            let result = shp.save_with(share).map_err(|e| trf!("导出SHP失败: {}", e))
+                .and_then(|bytes| std::fs::write(&path, &bytes).map(|_| bytes).map_err(|e| trf!("保存失败: {}", e)));
+            IoDone::SaveShp { path, result, doc_hash }
+        }));
+    }
+
+    fn finish_save_shp(&mut self, path: std::path::PathBuf, result: Result<Vec<u8>, String>, saved_hash: u64) {
+        let bytes = match result {
+            Ok(b) => b,
+            Err(e) => { self.status = e; return; }
+        };
+        if let Some(w) = &mut self.watch { w.mark(&path, &bytes); }
+        self.status = trf!("已保存: {}", path.display());
+        // 保存成功后清除dirty标记
+        if self.shp.as_ref().is_some_and(|s| doc_hash(s) == saved_hash) { self.dirty = false; }
+        self.current_path = Some(path);
+        self.session.doc_key = self.current_path.as_ref().map(|p| p.display().to_string()).unwrap_or_default();
+        self.record_op("保存", None);
+        if let Some(p) = self.current_path.clone() {
+            if let Err(e) = sequences::save(&p, &self.sequences) { self.status = trf!("{}；保存序列文件失败: {}", self.status, e); }
+            self.run_export_hook(&p);
+        }
+    }
+
+    // 工程连同调色板与编辑状态一起保存，重新打开时原样还原
+    fn action_save_project(&mut self) {
+        let Some(shp) = &self.shp else { self.status = tr("当前没有SHP").into(); return; };
+        let name = match (&self.project_path, &self.current_path) {
+            (Some(p), _) => p.file_name().map(|s| s.to_string_lossy().into_owned()),
+            (None, Some(p)) => p.file_stem().map(|s| format!("{}.lvshp", s.to_string_lossy())),
+            _ => None,
+        }.unwrap_or_else(|| "project.lvshp".into());
+        let Some(path) = FileDialog::new().add_filter(tr("工程"), &["lvshp"]).set_file_name(name).save_file() else { return; };
+        let editor = EditorState {
+            current_frame: self.preview.current_frame,
+            scale: self.scale,
+            selected_frames: self.selected_frames.iter().copied().collect(),
+            brush_index: self.brush_index,
+            brush_index2: self.brush_index2,
+            export_path: self.current_path.as_ref().map(|p| p.display().to_string()),
+        };
+        let project = Project::capture(shp, &self.current_pal_name, &self.palette, &self.sequences, editor);
+        match project.to_bytes().and_then(|b| std::fs::write(&path, b).map_err(|e| e.to_string())) {
+            Ok(()) => {
+                self.status = trf!("已保存工程: {}", path.display());
+                self.dirty = false;
+                self.project_path = Some(path);
+                self.record_op("保存工程", None);
+            }
+            Err(e) => self.status = trf!("保存工程失败: {}", e),
+        }
+    }
+
+    fn action_open_project(&mut self) {
+        let Some(path) = FileDialog::new().add_filter(tr("工程"), &["lvshp"]).pick_file() else { return; };
+        self.open_project_path(&path);
+    }
+
+    fn open_project_path(&mut self, path: &std::path::Path) {
+        let loaded = std::fs::read(path).map_err(|e| e.to_string())
+            .and_then(|b| Project::load(&b))
+            .and_then(|p| Ok((p.to_shp()?, p.to_palette()?, p)));
+        let (shp, pal, project) = match loaded {
+            Ok(v) => v,
+            Err(e) => { self.status = trf!("打开工程失败: {}", e); return; }
+        };
+        let count = shp.frames.len();
+        let export = project.editor.export_path.as_ref().map(std::path::PathBuf::from);
+        self.adopt_shp(shp, export);
+        self.project_path = Some(path.to_path_buf());
+        self.palette = pal;
+        self.current_pal_name = project.palette_name;
+        self.pal_path = None;
+        self.sequences = project.sequences;
+        let ed = project.editor;
+        self.preview.current_frame = ed.current_frame.min(count.saturating_sub(1));
+        if ed.scale > 0.0 { self.scale = ed.scale; }
+        self.selected_frames = ed.selected_frames.into_iter().filter(|&i| i < count).collect();
+        self.brush_index = ed.brush_index;
+        self.brush_index2 = ed.brush_index2;
+        self.after_canvas_change();
+        self.status = trf!("已打开工程: {}", path.display());
+    }
+
+    fn action_open_mix(&mut self) {
+        if let Some(path) = FileDialog::new().add_filter("MIX", &["mix"]).pick_file() {
+            self.open_mix_path(&path);
+        }
+    }
+
+    fn open_mix_path(&mut self, path: &std::path::Path) {
+        match MixFile::open(path) {
+            Ok(m) => {
+                self.status = trf!("已打开 MIX: {}（{} 个条目）", path.display(), m.entries.len());
+                if let Some(local) = m.local_names() { self.mix_names.load_bytes(local); }
+                self.mix_scheme = m.guess_scheme();
+                self.mix = Some(m);
+                self.mix_selected = None;
+                self.mix_source = None;
+                self.show_mix_window = true;
+            }
+            Err(e) => { self.status = trf!("打开 MIX 失败: {}", e); }
+        }
+    }
+
+    fn action_add_palette_folder(&mut self) {
+        let Some(dir) = FileDialog::new().pick_folder() else { return; };
+        self.settings.palette_folder = Some(dir.display().to_string());
+        let n = self.load_user_palettes();
+        self.save_settings();
+        self.status = if n == 0 { trf!("{} 中没有找到调色板", dir.display()) } else { trf!("已从调色板文件夹加载 {} 个调色板", n) };
+    }
+
+    // 扫描设置中的用户调色板文件夹，替换「User」分组；返回找到的调色板数
+    fn load_user_palettes(&mut self) -> usize {
+        self.grouped_pals.retain(|(g, _)| g != USER_PAL_GROUP);
+        let Some(dir) = &self.settings.palette_folder else { return 0; };
+        let mut items = Palette::from_directory(std::path::Path::new(dir));
+        items.sort_by_key(|(name, _)| name.to_lowercase());
+        let n = items.len();
+        if n > 0 { self.grouped_pals.push((USER_PAL_GROUP.into(), items)); }
+        n
+    }
+
+    fn action_load_mix_names(&mut self) {
+        let Some(path) = FileDialog::new().add_filter(tr("名称列表"), &["dat", "txt", "lst"]).pick_file() else { return; };
+        match std::fs::read(&path) {
+            Ok(bytes) => {
+                let n = self.mix_names.load_bytes(&bytes);
+                self.status = trf!("已加载 {} 个文件名（共 {} 个 ID）", n, self.mix_names.len());
+                let p = path.display().to_string();
+                if !self.settings.mix_name_lists.contains(&p) {
+                    self.settings.mix_name_lists.push(p);
+                    self.save_settings();
+                }
+            }
+            Err(e) => { self.status = trf!("读取文件失败: {}", e); }
+        }
+    }
+
+    fn ui_mix_window(&mut self, ctx: &Context) {
+        let mut open = self.show_mix_window;
+        // 按钮动作在窗口闭包外执行，避免与 self.mix 的借用冲突
+        let mut act: Option<MixAction> = None;
+        let Some(mix) = &self.mix else { self.show_mix_window = false; return; };
+        let title = format!("{}{}", tr("MIX 归档"), if mix.dirty { " *" } else { "" });
+        egui::Window::new(title).id(egui::Id::new("mix_window")).open(&mut open).default_width(420.0).show(ctx, |ui| {
+            ui.label(RichText::new(mix.path.display().to_string()).small());
+            ui.label(trf!("大小: {}，条目数: {}", mix::format_size(mix.file_size), mix.entries.len()));
+            ui.horizontal(|ui| {
+                ui.label(tr("筛选"));
+                ui.text_edit_singleline(&mut self.mix_search).on_hover_text(tr("ID 十六进制片段或文件名"));
+                if ui.small_button("✕").clicked() { self.mix_search.clear(); }
+            });
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label(tr("ID 算法"))
+                    .selected_text(self.mix_scheme.label())
+                    .show_ui(ui, |ui| {
+                        for s in IdScheme::ALL { ui.selectable_value(&mut self.mix_scheme, s, s.label()); }
+                    });
+                if ui.button(tr("加载名称列表...")).clicked() { act = Some(MixAction::LoadNames); }
+                ui.label(RichText::new(trf!("已知 {} 个", self.mix_names.len())).small().color(Color32::GRAY));
+            });
+            ui.separator();
+            egui::ScrollArea::vertical().max_height(300.0).auto_shrink([false, true]).show(ui, |ui| {
+                for e in mix.search(&self.mix_search, &self.mix_names) {
+                    let name = self.mix_names.get(e.id).unwrap_or("");
+                    let text = format!("{:08X}  {:<24} {:>10}{}", e.id, name, mix::format_size(e.size as u64), if e.is_modified() { "  *" } else { "" });
+                    let sel = self.mix_selected == Some(e.id);
+                    let r = ui.selectable_label(sel, RichText::new(text).monospace());
+                    if r.clicked() { self.mix_selected = Some(e.id); }
+                    if r.double_clicked() { act = Some(MixAction::OpenShp(e.id)); }
+                }
+            });
+            ui.separator();
+            let sel = self.mix_selected.filter(|id| mix.find(*id).is_some());
+            ui.horizontal_wrapped(|ui| {
+                if ui.add_enabled(sel.is_some(), egui::Button::new(tr("作为 SHP 打开"))).clicked() { act = sel.map(MixAction::OpenShp); }
+                if ui.add_enabled(sel.is_some() && self.shp.is_some(), egui::Button::new(tr("用当前 SHP 替换"))).clicked() { act = sel.map(MixAction::ReplaceWithShp); }
+                if ui.add_enabled(sel.is_some(), egui::Button::new(tr("用文件替换..."))).clicked() { act = sel.map(MixAction::ReplaceWithFile); }
+                if ui.add_enabled(sel.is_some(), egui::Button::new(tr("导出条目..."))).clicked() { act = sel.map(MixAction::Extract); }
+                if ui.add_enabled(sel.is_some(), egui::Button::new(tr("删除条目"))).clicked() { act = sel.map(MixAction::Delete); }
+            });
+            ui.horizontal(|ui| {
+                ui.label(tr("新条目 ID"));
+                ui.add(egui::TextEdit::singleline(&mut self.mix_new_id).desired_width(120.0).hint_text("1A2B3C4D / name.shp"))
+                    .on_hover_text(tr("十六进制 ID，或带扩展名的文件名（按所选算法计算 ID）"));
+                let id = self.mix_scheme.parse_id(&self.mix_new_id);
+                if ui.add_enabled(id.is_some() && self.shp.is_some(), egui::Button::new(tr("添加当前 SHP"))).clicked() { act = id.map(MixAction::AddShp); }
+                if ui.add_enabled(id.is_some(), egui::Button::new(tr("添加文件..."))).clicked() { act = id.map(MixAction::AddFile); }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button(tr("提取调色板到菜单")).on_hover_text(tr("把 MIX 中的 .pal 条目加入“选择内置PAL”菜单")).clicked() { act = Some(MixAction::ExtractPalettes); }
+                if ui.add_enabled(mix.dirty, egui::Button::new(tr("保存 MIX"))).clicked() { act = Some(MixAction::Save); }
+                if ui.button(tr("另存为...")).clicked() { act = Some(MixAction::SaveAs); }
+                if let Some(id) = self.mix_source {
+                    ui.label(RichText::new(trf!("当前 SHP 来自条目 {}", format!("{:08X}", id))).small().color(Color32::GRAY));
+                }
+            });
+        });
+        self.show_mix_window = open;
+        if let Some(a) = act { self.apply_mix_action(a); }
+    }
+
+    fn apply_mix_action(&mut self, act: MixAction) {
+        if let MixAction::LoadNames = act { return self.action_load_mix_names(); }
+        // 以文件名添加时顺便登记到名称库
+        if matches!(act, MixAction::AddShp(_) | MixAction::AddFile(_)) && self.mix_new_id.contains('.') {
+            self.mix_names.add(&self.mix_new_id);
+        }
+        let Some(mix) = &mut self.mix else { return; };
+        let names = &self.mix_names;
+        let hex = |id: u32| match names.get(id) { Some(n) => format!("{:08X} ({})", id, n), None => format!("{:08X}", id) };
+        match act {
+            MixAction::OpenShp(id) => {
+                let Some(bytes) = mix.read(id) else { return; };
+                match SHP::load(bytes) {
+                    Ok(shp) => {
+                        let label = hex(id);
+                        self.adopt_shp(shp, None);
+                        self.mix_source = Some(id);
+                        self.status = trf!("已从 MIX 打开条目 {}", label);
+                    }
+                    Err(e) => { self.status = trf!("条目 {} 不是有效的 SHP: {}", hex(id), e); }
+                }
+            }
+            MixAction::ReplaceWithShp(id) | MixAction::AddShp(id) => {
+                let Some(shp) = &self.shp else { return; };
+                match shp.save_with(self.settings.share_duplicate_frames) {
+                    Ok(bytes) => {
+                        mix.put(id, bytes);
+                        self.mix_source = Some(id);
+                        self.mix_selected = Some(id);
+                        self.status = trf!("已写入条目 {}（MIX 尚未保存）", hex(id));
+                    }
+                    Err(e) => { self.status = trf!("导出SHP失败: {}", e); }
+                }
+            }
+            MixAction::ReplaceWithFile(id) | MixAction::AddFile(id) => {
+                let Some(path) = FileDialog::new().pick_file() else { return; };
+                match std::fs::read(&path) {
+                    Ok(bytes) => {
+                        mix.put(id, bytes);
+                        self.mix_selected = Some(id);
+                        self.status = trf!("已写入条目 {}（MIX 尚未保存）", hex(id));
+                    }
+                    Err(e) => { self.status = trf!("读取文件失败: {}", e); }
+                }
+            }
+            MixAction::Extract(id) => {
+                let Some(bytes) = mix.read(id) else { return; };
+                let file_name = self.mix_names.get(id).map(str::to_owned).unwrap_or_else(|| format!("{:08X}.bin", id));
+                let Some(path) = FileDialog::new().set_file_name(file_name).save_file() else { return; };
+                match std::fs::write(&path, bytes) {
+                    Ok(_) => { self.status = trf!("已导出条目到: {}", path.display()); }
+                    Err(e) => { self.status = trf!("保存失败: {}", e); }
+                }
+            }
+            MixAction::Delete(id) => {
+                if mix.remove(id) {
+                    if self.mix_source == Some(id) { self.mix_source = None; }
+                    self.status = trf!("已删除条目 {}（MIX 尚未保存）", hex(id));
+                }
+            }
+            MixAction::LoadNames => {}
+            MixAction::ExtractPalettes => {
+                let group = format!("MIX: {}", mix.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default());
+                let roles = PaletteRoles::for_game(if mix.guess_scheme() == IdScheme::Td { "TD" } else { "RA2" });
+                let items: Vec<(String, Palette)> = mix.palettes(names).into_iter()
+                    .filter_map(|(name, data)| Palette::from_bytes(data).ok().map(|mut p| { p.roles = roles.clone(); (name, p) }))
+                    .collect();
+                if items.is_empty() { self.status = tr("该 MIX 中没有找到调色板").into(); return; }
+                self.status = trf!("已从 MIX 添加 {} 个调色板到「{}」", items.len(), group);
+                // 同名分组（重复提取同一 MIX）直接覆盖
+                self.grouped_pals.retain(|(g, _)| *g != group);
+                self.grouped_pals.push((group, items));
+            }
+            MixAction::Save => match mix.save() {
+                Ok(_) => { self.status = trf!("已保存 MIX: {}", mix.path.display()); }
+                Err(e) => { self.status = trf!("保存 MIX 失败: {}", e); }
+            },
+            MixAction::SaveAs => {
+                let name = mix.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| "new.mix".into());
+                let Some(path) = FileDialog::new().add_filter("MIX", &["mix"]).set_file_name(name).save_file() else { return; };
+                match mix.save_as(&path) {
+                    Ok(_) => { self.status = trf!("已保存 MIX: {}", path.display()); }
+                    Err(e) => { self.status = trf!("保存 MIX 失败: {}", e); }
+                }
+            }
+        }
+    }
+
+    fn action_open_pal(&mut self) {
+        if let Some(path) = FileDialog::new().add_filter("PAL", &["pal"]).pick_file() {
+            self.load_pal_path(&path);
+        }
+    }
+
+    fn load_pal_path(&mut self, path: &std::path::Path) {
+        match std::fs::read(path) {
+            Ok(bytes) => match Palette::from_bytes(&bytes) {
+                Ok(p) => {
+                    self.save_undo_palette();
+                    self.palette = p;
+                    self.pal_path = Some(path.to_path_buf());
+                    self.status = trf!("已加载 PAL: {}", path.display());
+                    self.dirty = true; // 切换调色板会影响显示，标记为需要保存
+                }
+                Err(e) => { self.status = trf!("加载PAL失败: {}", e); }
+            },
+            Err(e) => { self.status = trf!("读取文件失败: {}", e); }
+        }
+    }
+
+    // 退出时记下当前文档；只记录已保存到磁盘的 SHP 或工程
+    fn remember_session(&mut self) {
+        let path = self.project_path.as_ref().or(self.current_path.as_ref()).filter(|_| self.shp.is_some() && self.mix_source.is_none());
+        self.settings.last_session = path.map(|p| LastSession {
+            path: p.display().to_string(),
+            frame: self.preview.current_frame,
+            scale: self.scale,
+            palette: self.current_pal_name.clone(),
+            palette_path: self.pal_path.as_ref().map(|p| p.display().to_string()),
+        });
+    }
+
+    // 重新打开上次的文档与调色板；文件已不存在时只提示
+    fn restore_session(&mut self, last: LastSession) {
+        let path = std::path::PathBuf::from(&last.path);
+        if !path.is_file() { self.status = trf!("上次的文档已不存在: {}", last.path); return; }
+        if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("lvshp")) {
+            // 工程自带调色板与编辑状态
+            self.open_project_path(&path);
+            return;
+        }
+        match &last.palette_path {
+            Some(p) if std::path::Path::new(p).is_file() => self.load_pal_path(std::path::Path::new(p)),
+            _ => {
+                let found = self.grouped_pals.iter().flat_map(|(_, items)| items).find(|(n, _)| *n == last.palette).map(|(_, p)| p.clone());
+                if let Some(pal) = found {
+                    self.palette = pal;
+                    self.current_pal_name = last.palette.clone();
+                }
+            }
+        }
+        // 只有真正开始读取时才记下要回到的位置；被其他文件操作挡住时不留残余
+        self.load_shp_path(&path);
+        if self.io_task.is_some() { self.restore_view = Some((last.frame, last.scale)); }
+    }
+
+    // 命令行/文件关联传入的路径：按扩展名分派到 SHP、工程、PAL、MIX 或图片导入
+    fn open_path(&mut self, path: &std::path::Path) {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+        match ext.as_str() {
+            "shp" => self.load_shp_path(path),
+            "lvshp" if !self.view_only => self.open_project_path(path),
+            "pal" => self.load_pal_path(path),
+            "mix" if !self.view_only => self.open_mix_path(path),
+            e if !self.view_only && IMAGE_EXTS.contains(&e) => self.load_image_path(path),
+            _ => self.status = trf!("无法打开 {}：不支持的文件类型", path.display()),
+        }
+    }
+
+    fn action_save_pal(&mut self) {
+        if let Some(path) = FileDialog::new().set_file_name("palette.pal").save_file() {
+            let bytes = self.palette.to_bytes();
+            if let Err(e) = std::fs::write(&path, &bytes) {
+                self.status = trf!("保存PAL失败: {}", e);
+            } else {
+                self.status = trf!("已保存 PAL: {}", path.display());
+                if let Some(w) = &mut self.watch { w.mark(&path, &bytes); }
+            }
+        }
+    }
+
+    fn action_import_image(&mut self, _ctx: &Context) {
+        if self.shp.is_none() { self.status = tr("请先新建或打开SHP").into(); return; }
+        if let Some(path) = FileDialog::new().add_filter(tr("图片"), &IMAGE_EXTS).pick_file() {
+            self.load_image_path(&path);
+        }
+    }
+
+    // 图片进入导入 Gizmo；尚无文档时先按图片尺寸新建单帧 SHP
+    fn load_image_path(&mut self, path: &std::path::Path) {
+        if !self.io_idle() { return; }
+        let path = path.to_path_buf();
+        let label = trf!("正在读取 {}", path.display());
+        self.io_task = Some(IoTask::spawn(label, true, &self.egui_ctx, move || {
+            let result = image_io::load_rgba_frames(&path);
+            IoDone::Image { path, result }
+        }));
+    }
+
+    fn finish_load_image(&mut self, path: &std::path::Path, result: Result<Vec<image::RgbaImage>, String>) {
+        match result {
+            Ok(frames) => {
+                // 取首帧作为导入源；进入Gizmo编辑态
+                let Some(rgba) = frames.into_iter().next() else { return; };
+                if self.shp.is_none() {
+                    match SHP::try_new(rgba.width(), rgba.height(), 1) {
+                        Ok(shp) => self.adopt_shp(shp, None),
+                        Err(e) => { self.status = trf!("导入失败: {}", e); return; }
+                    }
+                }
+                self.begin_import(rgba);
+                self.status = trf!("已载入 {}，请在画布上拖动/缩放/固定。", path.display());
+            }
+            Err(e) => { self.status = trf!("导入失败: {}", e); }
+        }
+    }
+
+    fn begin_import(&mut self, rgba: image::RgbaImage) {
+        self.import_img = Some(rgba);
+        self.import_preview = None;
+        self.import_pos = egui::pos2(0.0, 0.0);
+        self.import_scale = 1.0;
+        self.import_angle_deg = 0.0;
+        self.import_armed = false; // 避免首次导入立即被外部点击固定
+    }
+
+    fn clipboard(&mut self) -> Result<&mut arboard::Clipboard, String> {
+        if self.clipboard.is_none() {
+            self.clipboard = Some(arboard::Clipboard::new().map_err(|e| e.to_string())?);
+        }
+        Ok(self.clipboard.as_mut().unwrap())
+    }
+
+    // 当前帧按调色板转为 RGBA 写入系统剪贴板，便于贴到 Photoshop/Aseprite
+    fn action_copy_frame_image(&mut self) {
+        let Some(shp) = &self.shp else { self.status = tr("当前没有SHP").into(); return; };
+        let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+        let img = shp.frame_rgba(fi, &self.palette);
+        self.status = match self.clipboard().and_then(|c| image_io::copy_to_clipboard(c, &img)) {
+            Ok(()) => trf!("已复制第 {} 帧到剪贴板", fi),
+            Err(e) => trf!("复制到剪贴板失败: {}", e),
+        };
+    }
+
+    // 剪贴板图像进入导入 Gizmo，与“导入图片”一致
+    fn action_paste_image(&mut self) {
+        if self.shp.is_none() { self.status = tr("请先新建或打开SHP").into(); return; }
+        match self.clipboard().and_then(image_io::paste_from_clipboard) {
+            Ok(rgba) => {
+                self.status = trf!("已从剪贴板粘贴 {}x{} 图像，请在画布上拖动/缩放/固定。", rgba.width(), rgba.height());
+                self.begin_import(rgba);
+            }
+            Err(e) => self.status = trf!("从剪贴板粘贴失败: {}", e),
+        }
+    }
+
+    // 读取 GIF/APNG 等多帧图片后先弹出对应方式对话框
+    fn action_import_frames(&mut self) {
+        if self.shp.is_none() { self.status = tr("请先新建或打开SHP").into(); return; }
+        let Some(path) = FileDialog::new().add_filter(tr("图片"), &IMAGE_EXTS).pick_file() else { return; };
+        if !self.io_idle() { return; }
+        let label = trf!("正在读取 {}", path.display());
+        self.io_task = Some(IoTask::spawn(label, true, &self.egui_ctx, move || {
+            let result = image_io::load_animation(&path);
+            IoDone::Animation { path, result }
+        }));
+    }
+
+    fn finish_import_frames(&mut self, path: std::path::PathBuf, result: Result<Vec<(image::RgbaImage, u32)>, String>) {
+        let Some(shp) = &self.shp else { self.status = tr("请先新建或打开SHP").into(); return; };
+        let (frames, delays): (Vec<_>, Vec<u32>) = match result {
+            Ok(f) => f.into_iter().unzip(),
+            Err(e) => { self.status = trf!("导入失败: {}", e); return; }
+        };
+        let start = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+        self.frame_import = Some(FrameImport { path, frames, delays, mode: FrameImportMode::Append, start });
+    }
+
+    fn ui_frame_import(&mut self, ctx: &Context) {
+        let (Some(imp), Some(shp)) = (&mut self.frame_import, &self.shp) else { self.frame_import = None; return; };
+        let (src, dst) = (imp.frames.len(), shp.frames.len());
+        let mut open = true;
+        let (mut apply, mut cancel) = (false, false);
+        egui::Window::new(tr("导入帧序列")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.label(trf!("{}：{} 帧；当前 SHP：{} 帧", imp.path.display(), src, dst));
+            ui.separator();
+            for m in FrameImportMode::ALL { ui.radio_value(&mut imp.mode, m, m.label()); }
+            ui.add_enabled_ui(imp.mode != FrameImportMode::Append, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(tr("起始帧"));
+                    ui.add(egui::DragValue::new(&mut imp.start).clamp_range(0..=dst.saturating_sub(1)));
+                });
+            });
+            let plan = imp.mode.plan(src, dst, imp.start);
+            let added = plan.iter().filter(|(t, _)| *t >= dst).count();
+            let overwritten = plan.len() - added;
+            ui.label(trf!("覆盖 {} 帧，新增 {} 帧，未使用的源帧 {} 个", overwritten, added, src - plan.iter().map(|p| p.1).collect::<std::collections::HashSet<_>>().len()));
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.add_enabled(!plan.is_empty(), egui::Button::new(tr("导入"))).clicked() { apply = true; }
+                if ui.button(tr("取消")).clicked() { cancel = true; }
+            });
+        });
+        if apply { self.apply_frame_import(); } else if !open || cancel { self.frame_import = None; }
+    }
+
+    // 按对话框选定的对应方式量化写入（居中贴入，超出画布的部分裁掉）；带帧延时时顺便把预览间隔设为其中位数
+    fn apply_frame_import(&mut self) {
+        let Some(FrameImport { path, frames, mut delays, mode, start }) = self.frame_import.take() else { return; };
+        let Some(shp) = &self.shp else { return; };
+        let dst = shp.frames.len();
+        let plan = mode.plan(frames.len(), dst, start);
+        let added = plan.iter().filter(|(t, _)| *t >= dst).count();
+        if !self.check_frame_room(added) { return; }
+        self.save_undo_document(false);
+        let Some(shp) = &mut self.shp else { return; };
+        let quantized = color_match::quantize_frames(&frames, &self.palette.colors, self.import_alpha_min, Some(shp.transparent));
+        for &(t, k) in &plan {
+            let blank = vec![shp.transparent; (shp.width * shp.height) as usize];
+            if t < shp.frames.len() { shp.frames[t].pixels = blank; } else { shp.frames.push(crate::shp::Frame::new(blank)); }
+            let img = &frames[k];
+            let dx = (shp.width as i32 - img.width() as i32) / 2;
+            let dy = (shp.height as i32 - img.height() as i32) / 2;
+            shp.paste_indices_at(t, &quantized[k], img.width(), img.height(), dx, dy);
+        }
+        let first = plan.first().map_or(0, |p| p.0);
+        self.dirty = true;
+        self.preview.current_frame = first;
+        self.record_op("导入帧序列", None);
+        self.status = trf!("已从 {} 导入 {} 帧（第 {} 帧起）", path.display(), plan.len(), first);
+        delays.retain(|&d| d > 0);
+        if !delays.is_empty() {
+            delays.sort_unstable();
+            self.preview.ms_per_frame = (delays[delays.len() / 2] as u64).clamp(30, 500);
+            self.status = trf!("{}；预览间隔 {} ms", self.status, self.preview.ms_per_frame);
+        }
+    }
+
+    fn action_load_bg_image(&mut self, ctx: &Context) {
+        let Some(path) = FileDialog::new().add_filter(tr("图片"), &["png", "jpg", "jpeg", "bmp"]).pick_file() else { return; };
+        match image::open(&path) {
+            Ok(img) => {
+                let rgba = img.to_rgba8();
+                let size = [rgba.width() as usize, rgba.height() as usize];
+                let ci = egui::ColorImage::from_rgba_unmultiplied(size, rgba.as_raw());
+                self.bg_image = Some(ctx.load_texture("bg_image", ci, egui::TextureOptions::NEAREST));
+                self.show_bg_image = true;
+                // 默认让背景中心对齐画布中心
+                if let Some(shp) = &self.shp { self.bg_pos = (egui::vec2(shp.width as f32, shp.height as f32) - egui::vec2(size[0] as f32, size[1] as f32)) / 2.0; }
+                self.status = trf!("已加载背景图: {}", path.display());
+            }
+            Err(e) => { self.status = trf!("读取图片失败: {}", e); }
+        }
+    }
+
+    fn action_brush_from_selection(&mut self) {
+        let (Some(shp), Some(sel)) = (&self.shp, &self.selection) else { return; };
+        let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+        match BrushStamp::from_selection(shp, fi, sel) {
+            Ok(stamp) => {
+                self.status = trf!("已从选区创建笔刷 ({}x{})", stamp.width, stamp.height);
+                self.custom_brush = Some(stamp);
+                self.brush_shape = BrushShape::Custom;
+                // 图案填充时留在填充工具
+                if self.tool != Tool::Fill { self.tool = Tool::Pencil; }
+                // 选区会把绘制限制在选区内，转为笔刷后取消
+                self.selection = None;
+            }
+            Err(e) => self.status = e,
+        }
+    }
+
+    fn action_load_brush_png(&mut self) {
+        if let Some(path) = FileDialog::new().add_filter("PNG", &["png"]).pick_file() {
+            match BrushStamp::load_png(&path) {
+                Ok(stamp) => {
+                    self.status = trf!("已加载笔刷: {} ({}x{})", path.display(), stamp.width, stamp.height);
+                    self.custom_brush = Some(stamp);
+                    self.brush_shape = BrushShape::Custom;
+                }
+                Err(e) => { self.status = trf!("加载笔刷失败: {}", e); }
+            }
+        }
+    }
+
+    fn ui_delta_export(&mut self, ctx: &Context) {
+        let mut open = self.show_delta_export;
+        let mut go = false;
+        egui::Window::new(tr("导出帧差异")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(tr("文件名前缀"));
+                ui.text_edit_singleline(&mut self.export_prefix);
+            });
+            let prefix = sanitize_file_stem(&self.export_prefix);
+            ui.label(RichText::new(trf!("示例: {}0000.png", prefix)).small().color(Color32::GRAY));
+            if ui.button(tr("选择文件夹并导出...")).clicked() { go = true; }
+        });
+        self.show_delta_export = open;
+        if go { self.show_delta_export = false; self.action_export_deltas(); }
+    }
+
+    fn action_export_deltas(&mut self) {
+        let Some(shp) = &self.shp else { self.status = tr("当前没有SHP").into(); return; };
+        let Some(dir) = FileDialog::new().pick_folder() else { return; };
+        let prefix = sanitize_file_stem(&self.export_prefix);
+        let mut total = 0usize;
+        let mut still = 0usize;
+        let (lo, hi) = self.preview.bounds(shp.frames.len());
+        for i in lo..=hi {
+            let path = dir.join(format!("{}{:04}.png", prefix, i));
+            match shp.export_frame_delta_png(i, &self.palette, path) {
+                Ok(n) => { total += n; if n == 0 { still += 1; } }
+                Err(e) => { self.status = trf!("导出失败(帧 {}): {}", i, e); return; }
+            }
+        }
+        self.status = trf!("已导出 {} 帧差异到 {}，共 {} 个变化像素，{} 帧无变化", hi - lo + 1, dir.display(), format_number(total), still);
+        self.run_export_hook(&dir);
+    }
+
+    fn ui_contact_sheet(&mut self, ctx: &Context) {
+        let mut open = self.show_contact_sheet;
+        let mut go = false;
+        egui::Window::new(tr("导出联系表")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            let opts = &mut self.contact_sheet;
+            egui::Grid::new("contact_sheet_grid").num_columns(2).show(ui, |ui| {
+                ui.label(tr("每行列数")); ui.add(egui::DragValue::new(&mut opts.columns).clamp_range(1..=16)); ui.end_row();
+                ui.label(tr("每页行数")); ui.add(egui::DragValue::new(&mut opts.rows).clamp_range(1..=16)); ui.end_row();
+                ui.label(tr("格子尺寸")); ui.add(egui::DragValue::new(&mut opts.cell).clamp_range(32..=512).suffix(" px")); ui.end_row();
+                ui.label(tr("标题")); ui.text_edit_singleline(&mut opts.title); ui.end_row();
+            });
+            ui.label(tr("备注（印在每页页眉）"));
+            ui.add(egui::TextEdit::multiline(&mut opts.notes).desired_rows(3).desired_width(f32::INFINITY));
+            if let Some(shp) = &self.shp {
+                let pages = shp.frames.len().div_ceil((opts.columns * opts.rows).max(1) as usize).max(1);
+                ui.label(RichText::new(trf!("共 {} 页", pages)).small().color(Color32::GRAY));
+            }
+            if ui.button(tr("导出...")).clicked() { go = true; }
+        });
+        self.show_contact_sheet = open;
+        if go { self.action_export_contact_sheet(); }
+    }
+
+    // 多页时按 名称_p1.png、名称_p2.png … 依次写出
+    fn action_export_contact_sheet(&mut self) {
+        let Some(shp) = &self.shp else { self.status = tr("当前没有SHP").into(); return; };
+        let stem = self.current_path.as_ref().and_then(|p| p.file_stem()).map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "sheet".into());
+        let Some(path) = FileDialog::new().add_filter("PNG", &["png"]).set_file_name(format!("{}_contact.png", stem)).save_file() else { return; };
+        if self.contact_sheet.title.trim().is_empty() { self.contact_sheet.title = stem; }
+        let pages = match contact_sheet::render_pages(shp, &self.palette, &self.contact_sheet, &ui_font_bytes()) {
+            Ok(p) => p,
+            Err(e) => { self.status = trf!("导出失败: {}", e); return; }
+        };
+        let file_stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        for (i, page) in pages.iter().enumerate() {
+            let out = if pages.len() == 1 { path.clone() } else { path.with_file_name(format!("{}_p{}.png", file_stem, i + 1)) };
+            if let Err(e) = page.save(&out) { self.status = trf!("导出失败: {}", e); return; }
+        }
+        self.status = trf!("已导出联系表 {} 页: {}", pages.len(), path.display());
+        self.run_export_hook(&path);
+    }
+
+    fn ui_ghost_export(&mut self, ctx: &Context) {
+        let mut open = self.show_ghost_export;
+        let mut go = false;
+        egui::Window::new(tr("导出残影叠加")).open(&mut open).collapsible(false).resizable(false).show(ctx, |ui| {
+            ui.add(egui::Slider::new(&mut self.ghost_first_alpha, 0.0..=1.0).text(tr("首帧不透明度")));
+            ui.add(egui::Slider::new(&mut self.ghost_last_alpha, 0.0..=1.0).text(tr("末帧不透明度")));
+            ui.add(egui::Slider::new(&mut self.ghost_step, 1..=16).text(tr("每隔几帧取一帧")));
+            if let Some(shp) = &self.shp {
+                let (lo, hi) = self.preview.bounds(shp.frames.len());
+                let n = (lo..=hi).step_by(self.ghost_step.max(1)).count();
+                ui.label(RichText::new(trf!("叠加第 {}–{} 帧中的 {} 帧", lo, hi, n)).small().color(Color32::GRAY));
+            }
+            if ui.button(tr("导出...")).clicked() { go = true; }
+        });
+        self.show_ghost_export = open;
+        if go { self.action_export_ghost(); }
+    }
+
+    fn action_export_ghost(&mut self) {
+        let Some(shp) = &self.shp else { self.status = tr("当前没有SHP").into(); return; };
+        let stem = self.current_path.as_ref().and_then(|p| p.file_stem()).map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "frames".into());
+        let Some(path) = FileDialog::new().add_filter("PNG", &["png"]).set_file_name(format!("{}_ghost.png", stem)).save_file() else { return; };
+        let (lo, hi) = self.preview.bounds(shp.frames.len());
+        let frames: Vec<usize> = (lo..=hi).step_by(self.ghost_step.max(1)).collect();
+        let img = shp.ghost_composite(&frames, &self.palette, self.ghost_first_alpha, self.ghost_last_alpha);
+        if let Err(e) = img.save(&path) { self.status = trf!("导出失败: {}", e); return; }
+        self.status = trf!("已导出 {} 帧的残影叠加: {}", frames.len(), path.display());
+        self.run_export_hook(&path);
+    }
+
+    // 批量转换：选择输入文件夹与调色板，后台逐个导出，界面保持响应
+    fn ui_batch(&mut self, ctx: &Context) {
+        let mut open = self.show_batch;
+        let running = self.batch_rx.is_some();
+        let mut start = false;
+        egui::Window::new(tr("批量转换 SHP")).open(&mut open).default_width(460.0).show(ctx, |ui| {
+            ui.add_enabled_ui(!running, |ui| {
+                egui::Grid::new("batch_grid").num_columns(2).show(ui, |ui| {
+                    ui.label(tr("输入文件夹"));
+                    ui.horizontal(|ui| {
+                        if ui.button(tr("选择...")).clicked() && let Some(dir) = FileDialog::new().pick_folder() {
+                            if self.batch_output.is_none() { self.batch_output = Some(dir.join("png")); }
+                            self.batch_input = Some(dir);
+                        }
+                        match &self.batch_input {
+                            Some(dir) => { ui.label(trf!("{}（{} 个 SHP）", dir.display(), batch::list_shps(dir).len())); }
+                            None => { ui.weak(tr("（未选择）")); }
+                        }
+                    });
+                    ui.end_row();
+                    ui.label(tr("输出文件夹"));
+                    ui.horizontal(|ui| {
+                        if ui.button(tr("选择...")).clicked() && let Some(dir) = FileDialog::new().pick_folder() { self.batch_output = Some(dir); }
+                        match &self.batch_output {
+                            Some(dir) => { ui.label(dir.display().to_string()); }
+                            None => { ui.weak(tr("（未选择）")); }
+                        }
+                    });
+                    ui.end_row();
+                    ui.label(tr("调色板"));
+                    let pal_name = self.batch_pal.as_ref().map_or_else(|| trf!("当前调色板 ({})", self.current_pal_name), |(n, _)| n.clone());
+                    ui.menu_button(pal_name, |ui| {
+                        if ui.button(tr("当前调色板")).clicked() { self.batch_pal = None; ui.close_menu(); }
+                        for (group, items) in &self.grouped_pals {
+                            ui.menu_button(group, |ui| {
+                                for (name, pal) in items {
+                                    if ui.button(name).clicked() { self.batch_pal = Some((name.clone(), pal.clone())); ui.close_menu(); }
+                                }
+                            });
+                        }
+                    });
+                    ui.end_row();
+                    ui.label(tr("格式"));
+                    ui.vertical(|ui| {
+                        for f in BatchFormat::ALL { ui.radio_value(&mut self.batch_format, f, f.label()); }
+                        if self.batch_format == BatchFormat::Gif { ui.weak(trf!("帧间隔 {} ms（预览 → 间隔ms）", self.preview.ms_per_frame)); }
+                    });
+                    ui.end_row();
+                });
+            });
+            ui.separator();
+            let (done, total, file) = &self.batch_progress;
+            if running {
+                ui.add(egui::ProgressBar::new(if *total == 0 { 0.0 } else { *done as f32 / *total as f32 }).text(format!("{}/{} {}", done, total, file)));
+                if ui.button(tr("取消")).clicked() && let Some(c) = &self.batch_cancel { c.store(true, std::sync::atomic::Ordering::Relaxed); }
+            } else {
+                let ready = self.batch_input.is_some() && self.batch_output.is_some();
+                if ui.add_enabled(ready, egui::Button::new(tr("开始转换"))).clicked() { start = true; }
+            }
+            if !self.batch_log.is_empty() {
+                egui::ScrollArea::vertical().max_height(160.0).stick_to_bottom(true).show(ui, |ui| {
+                    for line in &self.batch_log { ui.label(RichText::new(line).small()); }
+                });
+            }
+        });
+        // 运行中关闭窗口不中断任务，完成后结果写入状态栏
+        self.show_batch = open;
+        if start { self.start_batch(ctx); }
+    }
+
+    fn start_batch(&mut self, ctx: &Context) {
+        let (Some(input), Some(output)) = (self.batch_input.clone(), self.batch_output.clone()) else { return; };
+        let palette = self.batch_pal.as_ref().map_or_else(|| self.palette.clone(), |(_, p)| p.clone());
+        let job = BatchJob { input, output, palette, format: self.batch_format, ms_per_frame: self.preview.ms_per_frame as u32 };
+        let (rx, cancel) = batch::spawn(job, ctx);
+        self.batch_rx = Some(rx);
+        self.batch_cancel = Some(cancel);
+        self.batch_progress = (0, 0, String::new());
+        self.batch_log.clear();
+    }
+
+    fn poll_batch(&mut self) {
+        let Some(rx) = &self.batch_rx else { return; };
+        let mut finished = false;
+        loop {
+            let msg = match rx.try_recv() {
+                Ok(msg) => msg,
+                Err(std::sync::mpsc::TryRecvError::Empty) => break,
+                // 后台线程意外退出
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => { finished = true; break; }
+            };
+            match msg {
+                BatchMsg::Progress { done, total, file } => self.batch_progress = (done, total, file),
+                BatchMsg::Failed { file, error } => self.batch_log.push(trf!("{} 失败: {}", file, error)),
+                BatchMsg::Finished { converted, failed, cancelled } => {
+                    let msg = if cancelled { trf!("批量转换已取消：完成 {} 个，失败 {} 个", converted, failed) } else { trf!("批量转换完成：{} 个成功，{} 个失败", converted, failed) };
+                    self.batch_log.push(msg.clone());
+                    self.status = msg;
+                    finished = true;
+                }
+            }
+        }
+        if finished {
+            self.batch_rx = None;
+            self.batch_cancel = None;
+            if let Some(dir) = self.batch_output.clone() { self.run_export_hook(&dir); }
+        }
+    }
+
+    fn action_export_pcx(&mut self) {
+        let Some(shp) = &self.shp else { self.status = tr("当前没有SHP").into(); return; };
+        let Some(dir) = FileDialog::new().pick_folder() else { return; };
+        let stem = self.current_path.as_ref().and_then(|p| p.file_stem()).map(|s| s.to_string_lossy().into_owned()).unwrap_or_else(|| "frame".into());
+        let (lo, hi) = self.preview.bounds(shp.frames.len());
+        for i in lo..=hi {
+            let path = dir.join(format!("{} {:04}.pcx", stem, i));
+            if let Err(e) = std::fs::write(&path, image_io::encode_pcx(shp.width, shp.height, &shp.frames[i].pixels, &self.palette)) {
+                self.status = trf!("导出失败(帧 {}): {}", i, e);
+                return;
+            }
+        }
+        self.status = trf!("已导出 {} 个 PCX 到 {}", hi - lo + 1, dir.display());
+        self.record_op("导出 PCX", None);
+        self.run_export_hook(&dir);
+    }
+
+    fn action_export_png(&mut self) {
+        if let Some(shp) = &self.shp {
+            if let Some(path) = FileDialog::new().set_file_name("frame.png").save_file() {
+                let idx = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+                match shp.export_frame_png(idx, &self.palette, path.clone()) {
+                    Ok(()) => { self.status = trf!("已导出: {}", path.display()); self.run_export_hook(&path); }
+                    Err(e) => { self.status = trf!("导出失败: {}", e); }
+                }
+            }
+        } else {
+            self.status = tr("当前没有SHP").into();
+        }
+    }
+}
+
+/// 棋盘格背景：方格尺寸随缩放变化
+fn paint_checkerboard(painter: &egui::Painter, rect: egui::Rect, scale: f32, bg: &CanvasBackground) {
+    if bg.game_black || bg.solid {
+        let [r, g, b] = if bg.game_black { [0, 0, 0] } else { bg.color };
+        painter.rect_filled(rect, 0.0, Color32::from_rgb(r, g, b));
+        return;
+    }
+    let sq = bg.checker_size.max(1.0).max(scale);
+    let mut y = rect.top();
+    let [dark, light] = [bg.checker_dark, bg.checker_light].map(|[r, g, b]| Color32::from_rgb(r, g, b));
+    let mut row = 0;
+    while y < rect.bottom() {
+        let mut x = rect.left();
+        let row_offset = row % 2;
+        let mut col = 0;
+        while x < rect.right() {
+            let r = egui::Rect::from_min_size(egui::pos2(x, y), egui::vec2(sq, sq));
+            let c = if (col + row_offset) % 2 == 0 { light } else { dark };
+            painter.rect_filled(r.intersect(rect), 0.0, c);
+            x += sq; col += 1;
+        }
+        y += sq; row += 1;
+    }
+}
+
+/// Bresenham 直线经过的像素（含两端）
+fn line_points(mut x0: i32, mut y0: i32, x1: i32, y1: i32) -> Vec<(i32, i32)> {
+    let dx = (x1 - x0).abs();
+    let sx = if x0 < x1 { 1 } else { -1 };
+    let dy = -(y1 - y0).abs();
+    let sy = if y0 < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    let mut pts = Vec::new();
+    loop {
+        pts.push((x0, y0));
+        if x0 == x1 && y0 == y1 { break; }
+        let e2 = 2 * err;
+        if e2 >= dy { err += dy; x0 += sx; }
+        if e2 <= dx { err += dx; y0 += sy; }
+    }
+    pts
+}
+
+// 导入图的目标尺寸，限制单边不超过 4096，防止误操作导致超大分配
+fn import_target_size(img_w: u32, img_h: u32, scale: f32) -> (u32, u32) {
+    let mut sw = (img_w as f32 * scale).round().max(1.0) as u32;
+    let mut sh = (img_h as f32 * scale).round().max(1.0) as u32;
+    let max_side = 4096u32;
+    if sw > max_side { let k = max_side as f32 / sw as f32; sw = max_side; sh = (sh as f32 * k).round().max(1.0) as u32; }
+    if sh > max_side { let k = max_side as f32 / sh as f32; sh = max_side; sw = (sw as f32 * k).round().max(1.0) as u32; }
+    (sw, sh)
+}
+
+// 导入图按 Gizmo 参数处理后的 RGBA，以及旋转扩展画布后左上角的前移量
+fn import_pixels(img: &image::RgbaImage, size: (u32, u32), chroma: Option<([u8; 3], u8)>, downscale: u32, sharpen: f32, resample: Resample, angle_deg: f32) -> (image::RgbaImage, (i32, i32)) {
+    // 先抠背景再缩小，边缘像素能得到正确的半透明
+    let keyed;
+    let src = if let Some((color, tol)) = chroma {
+        keyed = image_io::chroma_key(img, color, tol);
+        &keyed
+    } else { img };
+    let downscaled;
+    let src = if downscale > 1 {
+        downscaled = image_io::sharpen(&image_io::downscale_area(src, downscale), sharpen);
+        &downscaled
+    } else { src };
+    let resized = image::imageops::resize(src, size.0, size.1, resample.filter());
+    // 绕中心旋转，画布扩展后左上角相应前移，保持中心不变
+    image_io::rotate_expand(&resized, angle_deg, resample)
+}
+
+/// 清理用户输入的文件名片段：保留中文等 Unicode 字符，仅替换各平台的非法字符与控制字符
+fn sanitize_file_stem(s: &str) -> String {
+    s.trim().chars().map(|c| match c {
+        '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+        c if c.is_control() => '_',
+        c => c,
+    }).collect()
+}
+
+/// 紧凑的 16×16 调色板选择网格；点击设置 `selected`
+fn palette_picker(ui: &mut egui::Ui, pal: &Palette, selected: &mut u8, id: &str) {
+    egui::Grid::new(id).spacing([1.0, 1.0]).show(ui, |ui| {
+        for row in 0..16usize {
+            for col in 0..16usize {
+                let idx = (row * 16 + col) as u8;
+                let (rect, resp) = ui.allocate_exact_size(egui::vec2(11.0, 11.0), Sense::click());
+                ui.painter().rect_filled(rect, 0.0, pal.colors[idx as usize]);
+                if *selected == idx { ui.painter().rect_stroke(rect, 0.0, egui::Stroke::new(1.5, Color32::YELLOW)); }
+                if resp.on_hover_text(trf!("索引 {}", idx)).clicked() { *selected = idx; }
+            }
+            ui.end_row();
+        }
+    });
+}
+
+// 把标记数组压缩为连续区间 (起, 止)
+fn index_runs(marks: &[bool]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (i, _) in marks.iter().enumerate().filter(|(_, m)| **m) {
+        match runs.last_mut() {
+            Some(r) if r.1 + 1 == i => r.1 = i,
+            _ => runs.push((i, i)),
+        }
+    }
+    runs
+}
+
+// 环绕绘制时在画布四周各平移一份，越界部分经裁剪后即落在对边；不环绕时只有原位
+fn wrap_offsets(width: u32, height: u32, wrap: bool) -> Vec<(i32, i32)> {
+    if !wrap { return vec![(0, 0)]; }
+    let (w, h) = (width as i32, height as i32);
+    [-h, 0, h].into_iter().flat_map(|dy| [-w, 0, w].into_iter().map(move |dx| (dx, dy))).collect()
+}
+
+/// 形状工具的修饰键约束，返回最终端点 (x0, y0, x1, y1)：
+/// - Shift：直线吸附到 0/45/90°，矩形约束为正方形，圆半径取最接近的整数
+/// - Ctrl：直线/矩形以起点为中心向两侧展开（圆本身即以起点为圆心）
+fn constrain_shape(tool: Tool, s: egui::Pos2, e: egui::Pos2, modifiers: egui::Modifiers) -> (i32, i32, i32, i32) {
+    let (x0, y0) = (s.x as i32, s.y as i32);
+    let (mut dx, mut dy) = (e.x as i32 - x0, e.y as i32 - y0);
+    match tool {
+        Tool::Circle => {
+            if modifiers.shift {
+                let r = ((dx * dx + dy * dy) as f32).sqrt().round() as i32;
+                return (x0, y0, x0 + r, y0);
+            }
+            return (x0, y0, x0 + dx, y0 + dy);
+        }
+        Tool::Line if modifiers.shift => {
+            // 按 45° 扇区吸附：接近水平/竖直时压平，否则取等长对角
+            let (ax, ay) = (dx.abs(), dy.abs());
+            if ay * 5 < ax * 2 { dy = 0; }
+            else if ax * 5 < ay * 2 { dx = 0; }
+            else { let m = ax.max(ay); dx = m * dx.signum(); dy = m * dy.signum(); }
+        }
+        Tool::Rectangle if modifiers.shift => {
+            let m = dx.abs().max(dy.abs());
+            dx = m * if dx < 0 { -1 } else { 1 };
+            dy = m * if dy < 0 { -1 } else { 1 };
+        }
+        _ => {}
     }
+    if modifiers.ctrl { (x0 - dx, y0 - dy, x0 + dx, y0 + dy) } else { (x0, y0, x0 + dx, y0 + dy) }
+}
+
+// 调色板格子上的角色标记：透明=红色斜线，阴影=黑点，阵营色=品红角标，循环色=青色底边
+fn paint_role_badge(painter: &egui::Painter, rect: egui::Rect, role: PaletteRole) {
+    match role {
+        PaletteRole::Normal => {}
+        PaletteRole::Transparent => {
+            painter.line_segment([rect.left_bottom(), rect.right_top()], egui::Stroke::new(1.5, Color32::RED));
+        }
+        PaletteRole::Shadow => {
+            painter.circle(rect.center(), rect.width() * 0.22, Color32::BLACK, egui::Stroke::new(1.0, Color32::WHITE));
+        }
+        PaletteRole::Remap => {
+            let r = rect.right_top();
+            let s = rect.width() * 0.5;
+            painter.add(egui::Shape::convex_polygon(vec![r, r + egui::vec2(0.0, s), r - egui::vec2(s, 0.0)], Color32::from_rgb(255, 0, 255), egui::Stroke::NONE));
+        }
+        PaletteRole::Cycling => {
+            let bar = egui::Rect::from_min_max(egui::pos2(rect.left(), rect.bottom() - 3.0), rect.right_bottom());
+            painter.rect_filled(bar, 0.0, Color32::from_rgb(0, 220, 255));
+        }
+    }
+}
+
+// 整份文档内容的摘要：尺寸、透明索引与各帧像素
+fn doc_hash(shp: &SHP) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut h = std::collections::hash_map::DefaultHasher::new();
+    (shp.width, shp.height, shp.transparent).hash(&mut h);
+    for fr in &shp.frames { fr.content_hash().hash(&mut h); }
+    h.finish()
+}
+
+// 感知亮度（Rec.601 加权），用于在色块上选黑/白文字
+fn color_luma(c: Color32) -> f32 {
+    0.299 * c.r() as f32 + 0.587 * c.g() as f32 + 0.114 * c.b() as f32
 }
 
 fn load_embedded_palettes() -> (Vec<(String, Vec<(String, Palette)>)>, Vec<(String, Palette)>) {
@@ -485,38 +5198,174 @@ fn load_embedded_palettes() -> (Vec<(String, Vec<(String, Palette)>)>, Vec<(Stri
 
 fn setup_fonts(ctx: &egui::Context) {
     let mut fonts = egui::FontDefinitions::default();
+    fonts.font_data.insert("wqy".to_owned(), egui::FontData::from_owned(ui_font_bytes()));
+    // 将中文字体置于优先位置
+    fonts.families.entry(egui::FontFamily::Proportional).or_default().insert(0, "wqy".to_owned());
+    fonts.families.entry(egui::FontFamily::Monospace).or_default().insert(0, "wqy".to_owned());
+    ctx.set_fonts(fonts);
+}
 
+/// 界面用中文字体数据：优先项目根目录、可执行文件旁或系统字体目录的字体文件，找不到时用内置字体
+fn ui_font_bytes() -> Vec<u8> {
     // 搜索字体：项目根目录或可执行文件旁
-    let mut data_opt: Option<Vec<u8>> = None;
+    let exe_dir = std::env::current_exe().ok().and_then(|p| p.parent().map(|d| d.to_path_buf()));
     let candidates = [
         std::path::PathBuf::from("wqy-microhei.ttc"),
-        std::env::current_exe().ok().and_then(|p| p.parent().map(|d| d.join("wqy-microhei.ttc"))).unwrap_or_default(),
+        exe_dir.as_ref().map(|d| d.join("wqy-microhei.ttc")).unwrap_or_default(),
+        // macOS .app 包：Contents/MacOS/可执行文件 → Contents/Resources
+        exe_dir.as_ref().map(|d| d.join("..").join("Resources").join("wqy-microhei.ttc")).unwrap_or_default(),
+        // Linux 发行版字体包安装位置
+        std::path::PathBuf::from("/usr/share/fonts/truetype/wqy/wqy-microhei.ttc"),
+        std::path::PathBuf::from("/usr/share/fonts/wqy-microhei/wqy-microhei.ttc"),
     ];
     for p in candidates.iter() {
         if p.as_os_str().is_empty() { continue; }
-        if let Ok(bytes) = std::fs::read(p) { data_opt = Some(bytes); break; }
+        if let Ok(bytes) = std::fs::read(p) { return bytes; }
     }
+    // 使用内置字体
+    EMBED_WQY_MICROHEI.to_vec()
+}
+
+fn setup_theme(ctx: &egui::Context) {
+    ctx.set_visuals(egui::Visuals::dark());
+}
 
-    if data_opt.is_none() {
-        // 使用内置字体
-        data_opt = Some(EMBED_WQY_MICROHEI.to_vec());
+impl MixApp {
+    // 只读查看模式的界面：打开文件、选择调色板、播放控制与画布，不处理任何编辑输入
+    fn update_viewer(&mut self, ctx: &Context) {
+        // 文件在后台读取：查看模式同样要取回结果并显示进度遮罩（打开即播放见 adopt_shp）
+        self.poll_io_task();
+        if self.preview.playing { ctx.request_repaint_after(std::time::Duration::from_millis(10)); } else { self.play_tex.clear(); }
+        egui::TopBottomPanel::top("viewer_top").show(ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                if ui.button(tr("打开 SHP...")).clicked() { self.action_open_shp(); }
+                ui.menu_button(tr("选择内置PAL"), |ui| {
+                    for (group, items) in &self.grouped_pals {
+                        ui.menu_button(group, |ui| {
+                            for (name, pal) in items {
+                                if ui.selectable_label(self.current_pal_name == *name, name).clicked() {
+                                    self.palette = pal.clone();
+                                    self.current_pal_name = name.clone();
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                    }
+                });
+                ui.separator();
+                ui.label(RichText::new(&self.status).color(Color32::LIGHT_GRAY));
+            });
+        });
+        egui::TopBottomPanel::bottom("viewer_bottom").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                self.ui_playback(ui);
+                ui.separator();
+                ui.label(tr("缩放"));
+                ui.add(egui::Slider::new(&mut self.scale, 1.0..=12.0));
+                ui.add(egui::Slider::new(&mut self.brightness, 0.5..=3.0).text(tr("预览亮度")));
+                egui::ComboBox::from_id_source("viewer_cb").selected_text(self.color_blind.label()).show_ui(ui, |ui| {
+                    for mode in ColorBlindMode::ALL { ui.selectable_value(&mut self.color_blind, mode, mode.label()); }
+                });
+                let count = self.frame_count();
+                if count > 0 {
+                    let _ = self.preview.tick(count);
+                    ui.separator();
+                    let mut f = self.preview.current_frame as u32;
+                    ui.add(egui::Slider::new(&mut f, 0..=count.saturating_sub(1) as u32).text(tr("帧")));
+                    self.preview.current_frame = f as usize;
+                }
+            });
+        });
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let view_pal = self.palette.simulated(self.color_blind);
+            let fi = self.preview.current_frame.min(self.frame_count().saturating_sub(1));
+            let tex = if let Some(lazy) = &mut self.lazy_shp {
+                // 按需解码：当前帧包成单帧 SHP 交给纹理缓存，相邻帧只做差异上传
+                let (width, height) = (lazy.width, lazy.height);
+                match lazy.frame(fi) {
+                    Ok(frame) => {
+                        let one = SHP { width, height, frames: vec![frame.clone()], transparent: 0, empty_frames: EmptyFrames::default() };
+                        if self.preview.playing { self.play_tex.get(ui.ctx(), &one, 0, &view_pal, self.brightness) }
+                        else { FrameTexture::get(&mut self.canvas_tex, ui.ctx(), &one, 0, &view_pal, self.brightness) }
+                    }
+                    Err(e) => { ui.colored_label(Color32::from_rgb(255, 120, 120), trf!("解码第 {} 帧失败: {}", fi, e)); return; }
+                }
+            } else if let Some(shp) = &self.shp {
+                if self.preview.playing { self.play_tex.get(ui.ctx(), shp, fi, &view_pal, self.brightness) }
+                else { FrameTexture::get(&mut self.canvas_tex, ui.ctx(), shp, fi, &view_pal, self.brightness) }
+            } else {
+                ui.centered_and_justified(|ui| { ui.label(tr("打开一个 SHP 进行查看")); });
+                return;
+            };
+            let mut scroll = egui::ScrollArea::both().id_source("canvas_scroll");
+            if let Some(off) = self.canvas_scroll_to.take() { scroll = scroll.scroll_offset(off); }
+            let view = scroll.show(ui, |ui| {
+                let (rect, _) = ui.allocate_exact_size(tex.size_vec2() * self.scale, Sense::hover());
+                paint_checkerboard(ui.painter(), rect, self.scale, &self.settings.canvas_bg);
+                ui.painter().image(tex.id(), rect, egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)), Color32::WHITE);
+            });
+            self.ui_minimap(ui, &view, Some(tex));
+        });
+        if self.io_task.is_some() { self.ui_io_overlay(ctx); }
+        // 查看模式仍支持基本的帧切换快捷键
+        if !ctx.wants_keyboard_input() {
+            let km = &self.settings.keymap;
+            let (open, prev, next, quit) = ctx.input(|i| (km.pressed(i, Action::OpenShp), km.pressed(i, Action::PrevFrame), km.pressed(i, Action::NextFrame), km.pressed(i, Action::Quit)));
+            if open { self.action_open_shp(); }
+            let count = self.frame_count();
+            if count > 0 {
+                if prev { self.preview.current_frame = self.preview.current_frame.saturating_sub(1); }
+                if next && self.preview.current_frame + 1 < count { self.preview.current_frame += 1; }
+            }
+            if quit { ctx.send_viewport_cmd(egui::ViewportCommand::Close); }
+            self.frame_nav_keys(ctx);
+        }
     }
 
-    if let Some(bytes) = data_opt {
-        fonts.font_data.insert("wqy".to_owned(), egui::FontData::from_owned(bytes));
-        // 将中文字体置于优先位置
-        fonts.families.entry(egui::FontFamily::Proportional).or_default().insert(0, "wqy".to_owned());
-        fonts.families.entry(egui::FontFamily::Monospace).or_default().insert(0, "wqy".to_owned());
-        ctx.set_fonts(fonts);
+    // 当前文档帧数（含查看模式下按需解码的 SHP）
+    fn frame_count(&self) -> usize {
+        match (&self.lazy_shp, &self.shp) {
+            (Some(lazy), _) => lazy.frame_count(),
+            (None, Some(shp)) => shp.frames.len(),
+            (None, None) => 0,
+        }
     }
-}
 
-fn setup_theme(ctx: &egui::Context) {
-    ctx.set_visuals(egui::Visuals::dark());
+    // 长动画的帧导航：首/末帧、前后 10 帧、播放/暂停（编辑与查看模式共用）
+    fn frame_nav_keys(&mut self, ctx: &Context) {
+        let km = &self.settings.keymap;
+        let pressed = |a: Action| ctx.input(|i| km.pressed(i, a));
+        let jump = if pressed(Action::FirstFrame) { Some(isize::MIN) }
+            else if pressed(Action::LastFrame) { Some(isize::MAX) }
+            else if pressed(Action::BackTenFrames) { Some(-10) }
+            else if pressed(Action::ForwardTenFrames) { Some(10) }
+            else { None };
+        let toggle = pressed(Action::PlayPause);
+        let count = self.frame_count();
+        if let Some(d) = jump { self.preview.jump(d, count); }
+        if toggle && count > 0 { self.preview.toggle(count); }
+    }
 }
 
 impl eframe::App for MixApp {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        // 退出时把本次会话并入累计统计
+        let flush = self.settings.stats_enabled && !self.session.doc_key.is_empty();
+        if flush { self.session.flush_into(&mut self.settings.stats); }
+        // 查看模式不改动上次会话
+        let remember = self.settings.restore_session && !self.view_only;
+        if remember { self.remember_session(); }
+        if flush || remember { self.save_settings(); }
+    }
+
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+        if self.view_only { self.update_viewer(ctx); return; }
+        if !self.preview.playing { self.play_tex.clear(); }
+        if self.settings.stats_enabled && self.shp.is_some() { self.session.tick(); }
+        self.poll_update_check(ctx);
+        self.poll_io_task();
+        self.poll_batch();
+        self.poll_file_watch(ctx);
         // 播放时主动驱动重绘，避免无输入时不刷新导致不播放
         if self.preview.playing {
             ctx.request_repaint_after(std::time::Duration::from_millis(10));
@@ -529,269 +5378,779 @@ impl eframe::App for MixApp {
         // 左侧：工具与调色板（Windows画图风格）
         egui::SidePanel::left("left").resizable(true).default_width(280.0).show(ctx, |ui| {
             // 撤销/重做快捷按钮
-            let can_undo = !self.undo_stack.is_empty();
-            let can_redo = !self.redo_stack.is_empty();
+            let can_undo = self.history.can_undo();
+            let can_redo = self.history.can_redo();
             ui.horizontal(|ui| {
-                if ui.add_enabled(can_undo, egui::Button::new("撤销 (Ctrl+Z)")).clicked() { self.undo(); }
-                if ui.add_enabled(can_redo, egui::Button::new("重做 (Ctrl+Y)")).clicked() { self.redo(); }
+                let undo_text = trf!("撤销 ({})", self.settings.keymap.chord_text(Action::Undo));
+                let redo_text = trf!("重做 ({})", self.settings.keymap.chord_text(Action::Redo));
+                if ui.add_enabled(can_undo, egui::Button::new(undo_text)).clicked() { self.undo(); }
+                if ui.add_enabled(can_redo, egui::Button::new(redo_text)).clicked() { self.redo(); }
             });
             ui.separator();
-            ui.heading("工具");
+            ui.heading(tr("工具"));
             egui::Grid::new("tools_grid").num_columns(2).spacing([6.0,6.0]).show(ui, |ui| {
-                if ui.selectable_label(self.tool==Tool::Pencil, "✏️ 铅笔").clicked(){ self.tool=Tool::Pencil; }
-                if ui.selectable_label(self.tool==Tool::Eraser, "🧽 橡皮").clicked(){ self.tool=Tool::Eraser; }
+                if ui.selectable_label(self.tool==Tool::Pencil, tr("✏️ 铅笔")).clicked(){ self.tool=Tool::Pencil; }
+                if ui.selectable_label(self.tool==Tool::Eraser, tr("🧽 橡皮")).clicked(){ self.tool=Tool::Eraser; }
+                ui.end_row();
+                if ui.selectable_label(self.tool==Tool::Fill, tr("🪣 填充")).clicked(){ self.tool=Tool::Fill; }
+                if ui.selectable_label(self.tool==Tool::Line, tr("📏 直线")).clicked(){ self.tool=Tool::Line; }
+                ui.end_row();
+                if ui.selectable_label(self.tool==Tool::Rectangle, tr("⬛ 矩形")).clicked(){ self.tool=Tool::Rectangle; }
+                if ui.selectable_label(self.tool==Tool::Circle, tr("⚪ 圆")).clicked(){ self.tool=Tool::Circle; }
                 ui.end_row();
-                if ui.selectable_label(self.tool==Tool::Fill, "🪣 填充").clicked(){ self.tool=Tool::Fill; }
-                if ui.selectable_label(self.tool==Tool::Line, "📏 直线").clicked(){ self.tool=Tool::Line; }
+                if ui.selectable_label(self.tool==Tool::SelectRect, tr("⬚ 矩形选区")).clicked(){ self.tool=Tool::SelectRect; }
+                if ui.selectable_label(self.tool==Tool::Lasso, tr("➰ 套索")).clicked(){ self.tool=Tool::Lasso; }
                 ui.end_row();
-                if ui.selectable_label(self.tool==Tool::Rectangle, "⬛ 矩形").clicked(){ self.tool=Tool::Rectangle; }
-                if ui.selectable_label(self.tool==Tool::Circle, "⚪ 圆").clicked(){ self.tool=Tool::Circle; }
+                if ui.selectable_label(self.tool==Tool::Wand, tr("✨ 魔棒")).clicked(){ self.tool=Tool::Wand; }
+                if ui.selectable_label(self.tool==Tool::Text, tr("🔤 文字")).clicked(){ self.tool=Tool::Text; }
                 ui.end_row();
             });
             ui.separator();
-            ui.label("画笔大小");
-            ui.add(egui::Slider::new(&mut self.brush_size, 1..=20).text("px"));
-            if matches!(self.tool, Tool::Rectangle | Tool::Circle) { ui.checkbox(&mut self.fill_mode, "填充形状"); }
+            ui.label(tr("画笔大小"));
+            ui.add(egui::Slider::new(&mut self.brush_size, 1..=20).text("px"))
+                .on_hover_text(tr("悬停画布时 Ctrl+滚轮调整大小，Alt+滚轮或 [ ] 切换颜色索引"));
+            if matches!(self.tool, Tool::Pencil | Tool::Eraser) {
+                ui.horizontal(|ui| {
+                    ui.label(tr("笔刷形状"));
+                    ui.selectable_value(&mut self.brush_shape, BrushShape::Circle, tr("● 圆"));
+                    ui.selectable_value(&mut self.brush_shape, BrushShape::Square, tr("■ 方"));
+                    ui.selectable_value(&mut self.brush_shape, BrushShape::Diagonal, tr("╱ 斜线"));
+                    ui.add_enabled_ui(self.custom_brush.is_some(), |ui| {
+                        ui.selectable_value(&mut self.brush_shape, BrushShape::Custom, tr("★ 自定义"));
+                    });
+                });
+                ui.checkbox(&mut self.pixel_perfect, tr("像素完美"))
+                    .on_hover_text(tr("去掉斜线笔画拐角处多出的像素（仅 1 像素画笔）"));
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.pressure_size, tr("压感控制大小"))
+                        .on_hover_text(tr("数位板笔压按比例缩放画笔，满压为上面设定的大小；需要驱动以触控力度上报笔压"));
+                    if self.pressure_size && let Some(p) = self.pen_pressure { ui.weak(format!("{:.0}%", p * 100.0)); }
+                });
+            }
+            if matches!(self.tool, Tool::Pencil | Tool::Eraser | Tool::Line | Tool::Rectangle | Tool::Circle | Tool::Fill) {
+                ui.checkbox(&mut self.wrap_draw, tr("环绕绘制"))
+                    .on_hover_text(tr("越过画布边缘的部分从对边继续，用于制作无缝平铺的图块"));
+            }
+            if matches!(self.tool, Tool::Fill | Tool::Wand) {
+                ui.checkbox(&mut self.fill_diagonal, tr("对角连通 (8 连通)"))
+                    .on_hover_text(tr("对角相邻的同色像素也算连通：可一次填满或选中单像素宽的斜线；注意填充也会从斜线描边的对角缝隙漏出"));
+            }
+            if self.tool == Tool::Fill {
+                ui.horizontal(|ui| {
+                    for p in FillPattern::ALL { ui.selectable_value(&mut self.fill_pattern, p, p.label()); }
+                });
+                match self.fill_pattern {
+                    FillPattern::Solid => {}
+                    FillPattern::Dither => {
+                        ui.add(egui::Slider::new(&mut self.fill_ratio, 0..=100).suffix("%").text(tr("另一颜色占比")))
+                            .on_hover_text(tr("主颜色与副颜色按 4×4 有序抖动混合；右键填充时两者对调"));
+                    }
+                    FillPattern::Stamp if self.custom_brush.is_none() => {
+                        ui.label(RichText::new(tr("先加载笔刷 PNG 或从选区创建笔刷；没有笔刷时按单色填充")).small().color(Color32::GRAY));
+                    }
+                    FillPattern::Stamp => {
+                        ui.label(RichText::new(tr("自定义笔刷从画布左上角起平铺；笔刷空白处保持原样")).small().color(Color32::GRAY));
+                    }
+                }
+            }
+            if matches!(self.tool, Tool::Pencil | Tool::Eraser) || (self.tool == Tool::Fill && self.fill_pattern == FillPattern::Stamp) {
+                ui.horizontal(|ui| {
+                    if ui.button(tr("加载自定义笔刷 PNG...")).clicked() { self.action_load_brush_png(); }
+                    if ui.add_enabled(self.selection.is_some(), egui::Button::new(tr("选区转为笔刷"))).clicked() { self.action_brush_from_selection(); }
+                });
+            }
+            if matches!(self.tool, Tool::Rectangle | Tool::Circle) { ui.checkbox(&mut self.fill_mode, tr("填充形状")); }
+            if self.tool == Tool::Text {
+                let p = &mut self.text_params;
+                ui.add(egui::TextEdit::multiline(&mut p.text).desired_rows(2).desired_width(f32::INFINITY));
+                ui.horizontal(|ui| {
+                    for f in TextFont::ALL { ui.radio_value(&mut p.font, f, f.label()); }
+                });
+                match p.font {
+                    TextFont::Bitmap => { ui.add(egui::Slider::new(&mut p.scale, 1..=8).text(tr("倍数"))); }
+                    TextFont::Ttf => {
+                        ui.add(egui::Slider::new(&mut p.size, 6..=64).text(tr("字号")));
+                        ui.add(egui::Slider::new(&mut p.threshold, 1..=255).text(tr("覆盖率阈值")));
+                    }
+                }
+                ui.label(RichText::new(tr("在画布上单击放置文字，拖动文字框可移动；点阵字体仅支持 ASCII")).small().color(Color32::GRAY));
+            }
+            if matches!(self.tool, Tool::SelectRect | Tool::Lasso | Tool::Wand) {
+                ui.label(RichText::new(tr("Shift 加选，Alt 减选；在选区内拖动可移动像素")).small().color(Color32::GRAY));
+            }
+            if let Some(n) = self.selection.as_ref().map(Selection::count) {
+                ui.horizontal(|ui| {
+                    ui.label(trf!("选区: {} 像素", format_number(n)));
+                    if ui.small_button(tr("取消选择")).clicked() { self.selection = None; }
+                });
+                ui.label(RichText::new(tr("绘制、填充、文字与贴入的图片只影响选区内的像素")).small().color(Color32::GRAY));
+            }
             ui.separator();
-            ui.heading("调色板");
-            let mut chosen = self.brush_index;
+            ui.heading(tr("调色板"));
+            let (mut chosen, mut chosen2) = (self.brush_index, self.brush_index2);
+            // 右键菜单“在调色板中定位”：闪烁标出该索引一段时间
+            if self.pal_flash.is_some_and(|(_, t)| t.elapsed() > PAL_FLASH_TIME) { self.pal_flash = None; }
+            let flash = self.pal_flash.map(|(i, t)| (i, (t.elapsed().as_secs_f32() * 6.0).sin() > 0.0));
+            if flash.is_some() { ctx.request_repaint(); }
+            // 当前帧各索引的像素数：用于标出用到的索引和悬停提示
+            let usage: Option<Vec<usize>> = self.pal_show_used.then(|| {
+                let mut n = vec![0usize; 256];
+                if let Some(fr) = self.shp.as_ref().and_then(|s| s.frames.get(self.preview.current_frame)) {
+                    for &p in &fr.pixels { n[p as usize] += 1; }
+                }
+                n
+            });
+            let cell = self.pal_cell;
+            let mut hovered_row = None;
             let desired_columns = 16usize;
             egui::Grid::new("pal-grid").spacing([2.0, 2.0]).show(ui, |ui| {
                 for row in 0..16 {
                     for col in 0..16 {
                         let idx = (row * desired_columns + col) as u8;
                         let color = self.palette.colors[idx as usize];
-                        let (rect, response) = ui.allocate_exact_size(egui::vec2(14.0, 14.0), Sense::click());
+                        let (rect, response) = ui.allocate_exact_size(egui::vec2(cell, cell), Sense::click());
                         ui.painter().rect_filled(rect, 0.0, color);
+                        let role = self.palette.roles.role(idx);
+                        if self.show_pal_roles { paint_role_badge(ui.painter(), rect, role); }
+                        let used = usage.as_ref().map(|n| n[idx as usize]);
+                        // 当前帧没用到的索引压暗，用到的就显得突出
+                        if used == Some(0) { ui.painter().rect_filled(rect, 0.0, Color32::from_black_alpha(170)); }
+                        if self.pal_show_numbers {
+                            let ink = if color_luma(color) > 128.0 { Color32::BLACK } else { Color32::WHITE };
+                            ui.painter().text(rect.center(), egui::Align2::CENTER_CENTER, idx, egui::FontId::monospace((cell * 0.42).max(6.0)), ink);
+                        }
+                        if response.hovered() { hovered_row = Some(row); }
+                        let response = response.on_hover_ui(|ui| {
+                            ui.label(trf!("索引 {} · {} · RGB({}, {}, {})", idx, format!("#{:02X}{:02X}{:02X}", color.r(), color.g(), color.b()), color.r(), color.g(), color.b()));
+                            if role != PaletteRole::Normal { ui.label(role.label()); }
+                            if let Some(n) = used { ui.label(trf!("当前帧 {} 像素", format_number(n))); }
+                        });
                         if response.clicked() { chosen = idx; }
+                        if response.secondary_clicked() { chosen2 = idx; }
+                        if let Some((i, on)) = flash && i == idx {
+                            ui.painter().rect_stroke(rect.expand(2.0), 0.0, egui::Stroke::new(2.0, if on { Color32::YELLOW } else { Color32::RED }));
+                        }
+                        // 主颜色白框，副颜色青色虚角
+                        if idx == self.brush_index { ui.painter().rect_stroke(rect.expand(1.0), 0.0, egui::Stroke::new(1.0, Color32::WHITE)); }
+                        if idx == self.brush_index2 {
+                            let p = ui.painter();
+                            p.line_segment([rect.right_bottom() - egui::vec2(6.0, 0.0), rect.right_bottom()], egui::Stroke::new(2.0, Color32::from_rgb(0, 220, 255)));
+                            p.line_segment([rect.right_bottom() - egui::vec2(0.0, 6.0), rect.right_bottom()], egui::Stroke::new(2.0, Color32::from_rgb(0, 220, 255)));
+                        }
                     }
                     ui.end_row();
                 }
             });
             self.brush_index = chosen;
-            let c = self.palette.colors[self.brush_index as usize];
+            self.brush_index2 = chosen2;
+            // 放大显示鼠标所在的一行（不在色表上时为主颜色所在行）
+            let row = hovered_row.unwrap_or(self.brush_index as usize / 16);
+            let (strip, _) = ui.allocate_exact_size(egui::vec2(ui.available_width().min(16.0 * (cell + 2.0)), 28.0), Sense::hover());
+            let w = strip.width() / 16.0;
+            for k in 0..16 {
+                let idx = row * 16 + k;
+                let c = self.palette.colors[idx];
+                let r = egui::Rect::from_min_size(strip.min + egui::vec2(k as f32 * w, 0.0), egui::vec2(w, strip.height())).shrink(0.5);
+                ui.painter().rect_filled(r, 0.0, c);
+                let ink = if color_luma(c) > 128.0 { Color32::BLACK } else { Color32::WHITE };
+                ui.painter().text(r.center_bottom() - egui::vec2(0.0, 2.0), egui::Align2::CENTER_BOTTOM, idx, egui::FontId::monospace(8.0), ink);
+                if idx == self.brush_index as usize { ui.painter().rect_stroke(r, 0.0, egui::Stroke::new(1.0, Color32::WHITE)); }
+            }
+            if !self.recent_colors.is_empty() {
+                ui.horizontal_wrapped(|ui| {
+                    ui.spacing_mut().item_spacing.x = 2.0;
+                    ui.label(RichText::new(tr("最近使用")).small());
+                    for &idx in &self.recent_colors {
+                        let c = self.palette.colors[idx as usize];
+                        let (rect, resp) = ui.allocate_exact_size(egui::vec2(14.0, 14.0), Sense::click());
+                        ui.painter().rect_filled(rect, 1.0, c);
+                        if idx == self.brush_index { ui.painter().rect_stroke(rect.expand(1.0), 1.0, egui::Stroke::new(1.0, Color32::WHITE)); }
+                        let resp = resp.on_hover_text(trf!("索引 {}", idx));
+                        if resp.clicked() { self.brush_index = idx; }
+                        if resp.secondary_clicked() { self.brush_index2 = idx; }
+                    }
+                });
+            }
             ui.horizontal(|ui| {
-                ui.label(format!("索引 {}", self.brush_index));
-                let (rect, _) = ui.allocate_exact_size(egui::vec2(24.0, 14.0), Sense::hover());
-                ui.painter().rect_filled(rect, 2.0, c);
+                ui.checkbox(&mut self.pal_show_numbers, tr("显示编号"));
+                ui.checkbox(&mut self.pal_show_used, tr("标出当前帧用到的"));
             });
-            ui.add(egui::Slider::new(&mut self.brightness, 0.5..=3.0).text("预览亮度"));
+            ui.add(egui::Slider::new(&mut self.pal_cell, 10.0..=28.0).step_by(1.0).text(tr("格子大小")));
+            ui.label(RichText::new(tr("左键选主颜色，右键选副颜色（右键绘制时使用）")).small().color(Color32::GRAY));
+            for (secondary, idx) in [(false, self.brush_index), (true, self.brush_index2)] {
+                ui.horizontal(|ui| {
+                    ui.label(if secondary { trf!("副 索引 {}", idx) } else { trf!("索引 {}", idx) });
+                    let (rect, _) = ui.allocate_exact_size(egui::vec2(24.0, 14.0), Sense::hover());
+                    ui.painter().rect_filled(rect, 2.0, self.palette.colors[idx as usize]);
+                    let role = self.palette.roles.role(idx);
+                    if role != PaletteRole::Normal { ui.label(RichText::new(role.label()).small().color(Color32::YELLOW)); }
+                    if secondary && ui.small_button("⇄").on_hover_text(Action::SwapColors.label()).clicked() { self.action_swap_colors(); }
+                });
+            }
+            ui.checkbox(&mut self.show_pal_roles, tr("标记特殊索引"));
+            if self.show_pal_roles {
+                ui.horizontal_wrapped(|ui| {
+                    for role in [PaletteRole::Transparent, PaletteRole::Shadow, PaletteRole::Remap, PaletteRole::Cycling] {
+                        let (rect, _) = ui.allocate_exact_size(egui::vec2(14.0, 14.0), Sense::hover());
+                        ui.painter().rect_filled(rect, 0.0, Color32::from_gray(90));
+                        paint_role_badge(ui.painter(), rect, role);
+                        ui.label(RichText::new(role.label()).small());
+                    }
+                });
+            }
+            ui.add(egui::Slider::new(&mut self.brightness, 0.5..=3.0).text(tr("预览亮度")));
         });
 
         // 底部：帧与预览控制
         egui::TopBottomPanel::bottom("bottom").default_height(120.0).show(ctx, |ui| {
             ui.horizontal(|ui| {
-                ui.label("缩放");
+                ui.label(tr("缩放"));
                 ui.add(egui::Slider::new(&mut self.scale, 1.0..=12.0));
                 ui.separator();
-                ui.checkbox(&mut self.preview.playing, "播放");
-                ui.add(egui::Slider::new(&mut self.preview.ms_per_frame, 30..=500).text("间隔ms"));
+                self.ui_playback(ui);
             });
 
-            if let Some(shp) = &mut self.shp {
+            if let Some(shp) = &self.shp {
                 let count = shp.frames.len();
                 let _ = self.preview.tick(count);
                 ui.separator();
                 ui.horizontal(|ui| {
                     let prev_disabled = self.preview.current_frame == 0;
                     let next_disabled = self.preview.current_frame + 1 >= count;
-                    if ui.add_enabled(!prev_disabled, egui::Button::new("← 上一帧")).clicked() {
+                    if ui.add_enabled(!prev_disabled, egui::Button::new(tr("← 上一帧"))).clicked() {
                         if self.preview.current_frame > 0 { self.preview.current_frame -= 1; }
                     }
-                    let mut frame_val = self.preview.current_frame as u32;
-                    ui.add(egui::Slider::new(&mut frame_val, 0..=count.saturating_sub(1) as u32).text("帧"));
-                    self.preview.current_frame = frame_val as usize;
-                    if ui.add_enabled(!next_disabled, egui::Button::new("下一帧 →")).clicked() {
+                    if ui.add_enabled(!next_disabled, egui::Button::new(tr("下一帧 →"))).clicked() {
                         if self.preview.current_frame + 1 < count { self.preview.current_frame += 1; }
                     }
-                    ui.label(format!("/ 共 {} 帧", count));
-                });
-                // 帧切换锚点：一旦当前帧不同于撤销历史所属帧，清空撤销/重做，避免跨帧污染
-                let cur = self.preview.current_frame.min(count.saturating_sub(1));
-                match self.undo_frame_anchor {
-                    None => self.undo_frame_anchor = Some(cur),
-                    Some(anchor) if anchor != cur => {
-                        self.undo_stack.clear();
-                        self.redo_stack.clear();
-                        self.undo_frame_anchor = Some(cur);
+                    ui.label(trf!("第 {} 帧 / 共 {} 帧", self.preview.current_frame, count));
+                    if !self.selected_frames.is_empty() {
+                        ui.separator();
+                        ui.label(trf!("已选 {} 帧", self.selected_frames.len()));
                     }
-                    _ => {}
-                }
+                });
             }
+            self.ui_frame_props(ui);
+            if self.shp.is_some() { self.ui_timeline(ui); }
         });
 
         // 中央：画布
         egui::CentralPanel::default().show(ctx, |ui| {
             let mut pending_undo: Option<Vec<u8>> = None;
-            if let Some(shp) = &mut self.shp {
-                let frame_idx = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
-                let tex = shp.egui_texture_with_brightness(ui.ctx(), frame_idx, &self.palette, self.brightness);
-                let size = tex.size_vec2() * self.scale;
-                let (rect, response) = ui.allocate_exact_size(size, Sense::click_and_drag());
-                let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
-                // 画棋盘背景，便于透明像素可见
-                {
-                    let sq = 8.0_f32.max(self.scale); // 方格尺寸随缩放变化
-                    let mut y = rect.top();
-                    let dark = egui::Color32::from_gray(60);
-                    let light = egui::Color32::from_gray(90);
-                    let mut row = 0;
-                    while y < rect.bottom() {
-                        let mut x = rect.left();
-                        let row_offset = row % 2;
-                        let mut col = 0;
-                        while x < rect.right() {
-                            let r = egui::Rect::from_min_size(egui::pos2(x, y), egui::vec2(sq, sq));
-                            let c = if (col + row_offset) % 2 == 0 { light } else { dark };
-                            ui.painter().rect_filled(r.intersect(rect), 0.0, c);
-                            x += sq; col += 1;
-                        }
-                        y += sq; row += 1;
-                    }
-                }
-                ui.painter().image(tex.id(), rect, uv, egui::Color32::WHITE);
-
-                // 绘制/取色逻辑 + 撤销记录
-                // 更稳健的输入判定：鼠标在画布内即处理
-                let pointer_pos_opt = ui.input(|i| i.pointer.interact_pos());
-                let pointer_down = ui.input(|i| i.pointer.primary_down());
-                if let Some(pp) = pointer_pos_opt { if rect.contains(pp) {
-                    let pos = response.interact_pointer_pos().unwrap_or(rect.min);
-                    let local = (pos - rect.min) / self.scale;
-                    let x = local.x.floor() as i32; let y = local.y.floor() as i32;
-
-                    if response.clicked() || (pointer_down && !self.drawing) {
-                        // 无论何种工具，都在操作开始时记录一次撤销点
-                        pending_undo = Some(shp.frames[frame_idx].pixels.clone());
-                        self.drawing = true;
-                        self.draw_start = Some(egui::pos2(x as f32, y as f32));
-                        self.draw_end = Some(egui::pos2(x as f32, y as f32));
-                        match self.tool {
-                            Tool::Pencil => { Self::stamp_disc_on_frame(shp, frame_idx, x, y, self.brush_size, self.brush_index); self.dirty=true; },
-                            Tool::Eraser => { Self::stamp_disc_on_frame(shp, frame_idx, x, y, self.brush_size, 0); self.dirty=true; },
-                            // 填充为一次性操作：立即完成并结束drawing
-                            Tool::Fill => { Self::flood_fill_on_frame(shp, frame_idx, x, y, self.brush_index); self.dirty=true; self.drawing=false; },
-                            _ => {}
+            let mut import_fixed = false;
+            if self.tool == Tool::Text { self.update_text_preview(ctx); } else { self.text_pos = None; }
+            // 笔压来自触控事件的力度；抬笔后清除，回到固定大小
+            ctx.input(|i| for e in &i.events {
+                if let egui::Event::Touch { phase, force, .. } = e {
+                    self.pen_pressure = if matches!(phase, egui::TouchPhase::End | egui::TouchPhase::Cancel) { None } else { *force };
+                }
+            });
+            let size = match self.pen_pressure.filter(|_| self.pressure_size) {
+                Some(p) => ((self.brush_size as f32 * p.clamp(0.0, 1.0)).round() as u32).max(1),
+                None => self.brush_size,
+            };
+            let brush = Brush { shape: self.brush_shape, size, custom: self.custom_brush.as_ref() };
+            let perfect = self.pixel_perfect && self.brush_size <= 1 && self.brush_shape != BrushShape::Custom;
+            // 画布放在滚动区内：放大后可滚动查看，小地图拖动时由 canvas_scroll_to 指定新的滚动位置
+            let mut scroll = egui::ScrollArea::both().id_source("canvas_scroll").drag_to_scroll(false);
+            if let Some(off) = self.canvas_scroll_to.take() { scroll = scroll.scroll_offset(off); }
+            let mut minimap_tex: Option<egui::TextureHandle> = None;
+            let view = scroll.show(ui, |ui| {
+                if let Some(shp) = &mut self.shp {
+                    let frame_idx = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
+                    let erase = shp.transparent;
+                    let sel_tool = matches!(self.tool, Tool::SelectRect | Tool::Lasso | Tool::Wand);
+                    if self.selection.as_ref().is_some_and(|s| s.width != shp.width || s.height != shp.height) { self.selection = None; }
+                    let view_pal = self.palette.simulated(self.color_blind);
+                    let tex = if self.preview.playing { self.play_tex.get(ui.ctx(), shp, frame_idx, &view_pal, self.brightness) }
+                        else { FrameTexture::get(&mut self.canvas_tex, ui.ctx(), shp, frame_idx, &view_pal, self.brightness) };
+                    let size = tex.size_vec2() * self.scale;
+                    minimap_tex = Some(tex.clone());
+                    let (canvas_rect, response) = ui.allocate_exact_size(size, Sense::click_and_drag());
+                    // 按帧偏移显示：图像与编辑坐标一起平移，棋盘格仍表示画布范围
+                    let rect = if self.show_offsets {
+                        let (ox, oy) = shp.frames[frame_idx].offset;
+                        canvas_rect.translate(egui::vec2(ox as f32, oy as f32) * self.scale)
+                    } else { canvas_rect };
+                    let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                    // 悬停画布时：Ctrl+滚轮调画笔大小，Alt+滚轮切换颜色索引
+                    if response.hovered() {
+                        let (zoom, scroll, alt) = ui.input(|i| (i.zoom_delta(), i.raw_scroll_delta.y, i.modifiers.alt));
+                        if zoom != 1.0 {
+                            self.brush_size = if zoom > 1.0 { (self.brush_size + 1).min(20) } else { self.brush_size.saturating_sub(1).max(1) };
+                            self.brush_hud_until = Some(Instant::now() + BRUSH_HUD_TIME);
+                        }
+                        if alt && scroll != 0.0 {
+                            self.brush_index = if scroll > 0.0 { self.brush_index.wrapping_sub(1) } else { self.brush_index.wrapping_add(1) };
+                            self.brush_hud_until = Some(Instant::now() + BRUSH_HUD_TIME);
                         }
                     }
-                    if response.dragged() || (pointer_down && self.drawing) {
-                        self.draw_end = Some(egui::pos2(x as f32, y as f32));
-                        match self.tool {
-                            Tool::Pencil => { Self::stamp_disc_on_frame(shp, frame_idx, x, y, self.brush_size, self.brush_index); self.dirty=true; },
-                            Tool::Eraser => { Self::stamp_disc_on_frame(shp, frame_idx, x, y, self.brush_size, 0); self.dirty=true; },
-                            _ => {}
+                    // 中键拖动：移动参考背景
+                    if self.bg_image.is_some() && self.show_bg_image && response.dragged_by(egui::PointerButton::Middle) {
+                        self.bg_pos += response.drag_delta() / self.scale;
+                    }
+                    // 画棋盘背景，便于透明像素可见；有参考背景图时改画背景图
+                    match self.bg_image.as_ref().filter(|_| self.show_bg_image) {
+                        Some(bg) => {
+                            let r = egui::Rect::from_min_size(canvas_rect.min + self.bg_pos * self.scale, bg.size_vec2() * self.scale);
+                            ui.painter().rect_filled(canvas_rect, 0.0, Color32::from_gray(40));
+                            ui.painter().with_clip_rect(canvas_rect).image(bg.id(), r, uv, Color32::WHITE);
                         }
+                        None => paint_checkerboard(ui.painter(), canvas_rect, self.scale, &self.settings.canvas_bg),
                     }
-                    if (!pointer_down) && self.drawing {
-                        self.drawing = false;
-                        if let (Some(s), Some(e)) = (self.draw_start, self.draw_end) {
-                            let x0 = s.x as i32; let y0 = s.y as i32; let x1 = e.x as i32; let y1 = e.y as i32;
+                    ui.painter().image(tex.id(), rect, uv, egui::Color32::WHITE);
+                    if rect != canvas_rect { ui.painter().rect_stroke(rect, 0.0, egui::Stroke::new(1.0, Color32::from_rgb(255, 120, 0))); }
+                    if self.show_safe_area { self.safe_area.paint(ui.painter(), canvas_rect, self.scale); }
+                    if self.show_guides { self.guides.paint(ui.painter(), canvas_rect, self.scale); }
+
+                    // 绘制/取色逻辑 + 撤销记录
+                    // 更稳健的输入判定：鼠标在画布内即处理
+                    let pointer_pos_opt = ui.input(|i| i.pointer.interact_pos());
+                    // 绘图工具也响应右键，用副颜色绘制
+                    let paint_tool = matches!(self.tool, Tool::Pencil | Tool::Eraser | Tool::Line | Tool::Rectangle | Tool::Circle | Tool::Fill);
+                    let modifiers = ui.input(|i| i.modifiers);
+                    let (primary_down, secondary_down) = ui.input(|i| (i.pointer.primary_down(), i.pointer.secondary_down()));
+                    // Ctrl+右键留给右键菜单
+                    let pointer_down = primary_down || (paint_tool && secondary_down && !modifiers.ctrl);
+                    // 指针须在画布可见部分内，且不在小地图上
+                    // 浮在画布上的窗口、工具条等上层区域也不算
+                    let visible = |pp: egui::Pos2| ui.clip_rect().contains(pp) && !self.minimap_rect.is_some_and(|r| r.contains(pp))
+                        && ui.ctx().layer_id_at(pp) == Some(ui.layer_id());
+                    if let Some(pp) = pointer_pos_opt { if rect.contains(pp) && visible(pp) {
+                        let pos = response.interact_pointer_pos().unwrap_or(rect.min);
+                        let local = (pos - rect.min) / self.scale;
+                        let x = local.x.floor() as i32; let y = local.y.floor() as i32;
+
+                        // 右键菜单：非绘图工具直接右键，绘图工具按住 Ctrl 右键
+                        if response.secondary_clicked() && (modifiers.ctrl || !paint_tool) && !self.drawing {
+                            self.canvas_menu = Some((pp, (x, y)));
+                        }
+                        if response.clicked() || (pointer_down && !self.drawing) {
+                            // 无论何种工具，都在操作开始时记录一次撤销点
+                            pending_undo = Some(shp.frames[frame_idx].pixels.clone());
+                            self.drawing = true;
+                            self.draw_index = if primary_down { self.brush_index } else { self.brush_index2 };
+                            self.draw_start = Some(egui::pos2(x as f32, y as f32));
+                            self.draw_end = Some(egui::pos2(x as f32, y as f32));
+                            // 有选区时记下绘制前的帧，之后把选区外被改动的像素还原
+                            if !sel_tool { self.clip_base = self.selection.as_ref().map(|_| shp.frames[frame_idx].pixels.clone()); }
+                            match self.tool {
+                                Tool::Pencil | Tool::Eraser => {
+                                    self.stroke = Stroke { perfect, wrap: self.wrap_draw, ..Default::default() };
+                                    let color = if self.tool == Tool::Pencil { self.draw_index } else { erase };
+                                    Self::stroke_to(shp, frame_idx, &mut self.stroke, x, y, brush, color);
+                                    self.dirty = true;
+                                }
+                                // 文字：在文字框内按下为拖动，框外按下把文字放到该处；固定到帧时才记撤销点
+                                Tool::Text => {
+                                    pending_undo = None;
+                                    let p = egui::pos2(x as f32, y as f32);
+                                    let size = self.text_preview.as_ref().and_then(|t| t.mask.as_ref()).map_or(egui::Vec2::ZERO, |m| egui::vec2(m.0 as f32, m.1 as f32));
+                                    match self.text_pos {
+                                        Some(tp) if egui::Rect::from_min_size(tp, size).contains(p) => self.text_grab = p - tp,
+                                        _ => { self.text_pos = Some(p); self.text_grab = egui::Vec2::ZERO; }
+                                    }
+                                }
+                                // 填充为一次性操作：立即完成并结束drawing
+                                Tool::Fill => {
+                                    let other = if primary_down { self.brush_index2 } else { self.brush_index };
+                                    let fill = Fill::new(self.fill_pattern, self.draw_index, other, self.fill_ratio, self.custom_brush.as_ref());
+                                    Self::flood_fill_on_frame(shp, frame_idx, x, y, fill, self.wrap_draw, self.fill_diagonal);
+                                    self.dirty = true;
+                                    self.drawing = false;
+                                }
+                                Tool::SelectRect | Tool::Lasso | Tool::Wand => {
+                                    let op = SelectOp::from_modifiers(modifiers.shift, modifiers.alt);
+                                    // 在选区内按下：开始移动选中的像素
+                                    let grab = self.selection.as_ref().filter(|s| op == SelectOp::Replace && s.contains(x, y)).map(|sel| {
+                                        let src = shp.frames[frame_idx].pixels.clone();
+                                        let mut base = src.clone();
+                                        for (p, &m) in base.iter_mut().zip(&sel.mask) { if m { *p = erase; } }
+                                        SelMove { start: (x, y), offset: (0, 0), src, base, mask: sel.clone() }
+                                    });
+                                    if grab.is_some() {
+                                        self.sel_move = grab;
+                                    } else {
+                                        // 仅改变选区，不产生撤销点
+                                        pending_undo = None;
+                                        match self.tool {
+                                            Tool::Wand => {
+                                                let new = Selection::wand(&shp.frames[frame_idx].pixels, shp.width, shp.height, x, y, self.fill_diagonal);
+                                                Selection::merge_into(&mut self.selection, new, op);
+                                                self.drawing = false;
+                                            }
+                                            Tool::Lasso => { self.lasso_pts = vec![(local.x, local.y)]; }
+                                            _ => {}
+                                        }
+                                    }
+                                }
+                                _ => {}
+                            }
+                        }
+                        if response.dragged() || (pointer_down && self.drawing) {
+                            self.draw_end = Some(egui::pos2(x as f32, y as f32));
                             match self.tool {
-                                Tool::Line => { Self::draw_line_on_frame(shp, frame_idx, x0, y0, x1, y1, self.brush_index); self.dirty=true; },
-                                Tool::Rectangle => { if self.fill_mode { Self::fill_rect_on_frame(shp, frame_idx, x0, y0, x1, y1, self.brush_index); } else { Self::draw_rect_on_frame(shp, frame_idx, x0, y0, x1, y1, self.brush_index); } self.dirty=true; },
-                                Tool::Circle => { let r = (((x1-x0)*(x1-x0) + (y1-y0)*(y1-y0)) as f32).sqrt() as i32; if self.fill_mode { Self::fill_circle_on_frame(shp, frame_idx, x0, y0, r, self.brush_index); } else { Self::draw_circle_on_frame(shp, frame_idx, x0, y0, r, self.brush_index); } self.dirty=true; },
+                                Tool::Pencil | Tool::Eraser => {
+                                    let color = if self.tool == Tool::Pencil { self.draw_index } else { erase };
+                                    Self::stroke_to(shp, frame_idx, &mut self.stroke, x, y, brush, color);
+                                    self.dirty = true;
+                                }
+                                Tool::Lasso if self.sel_move.is_none() && self.lasso_pts.last() != Some(&(local.x, local.y)) => {
+                                    self.lasso_pts.push((local.x, local.y));
+                                }
+                                Tool::Text => { self.text_pos = Some(egui::pos2(x as f32, y as f32) - self.text_grab); }
                                 _ => {}
                             }
+                            if let Some(mv) = &mut self.sel_move {
+                                let off = (x - mv.start.0, y - mv.start.1);
+                                if off != mv.offset {
+                                    mv.offset = off;
+                                    mv.render(&mut shp.frames[frame_idx].pixels, erase);
+                                    self.dirty = true;
+                                }
+                            }
+                        }
+                        if (!pointer_down) && self.drawing {
+                            self.drawing = false;
+                            if let Some(mv) = self.sel_move.take() {
+                                self.selection = Some(mv.mask.shifted(mv.offset.0, mv.offset.1));
+                            } else if sel_tool {
+                                let op = SelectOp::from_modifiers(modifiers.shift, modifiers.alt);
+                                let new = match (self.tool, self.draw_start, self.draw_end) {
+                                    // 原地单击（未拖出范围）视为空选区：替换模式下即取消选择
+                                    (Tool::SelectRect, Some(s), Some(e)) if s != e => Selection::rect(shp.width, shp.height, s.x as i32, s.y as i32, e.x as i32, e.y as i32),
+                                    (Tool::Lasso, _, _) => Selection::lasso(shp.width, shp.height, &self.lasso_pts),
+                                    _ => Selection::empty(shp.width, shp.height),
+                                };
+                                Selection::merge_into(&mut self.selection, new, op);
+                                self.lasso_pts.clear();
+                            } else if let (Some(s), Some(e)) = (self.draw_start, self.draw_end)
+                                && matches!(self.tool, Tool::Line | Tool::Rectangle | Tool::Circle)
+                            {
+                                let (x0, y0, x1, y1) = constrain_shape(self.tool, s, e, modifiers);
+                                for (dx, dy) in wrap_offsets(shp.width, shp.height, self.wrap_draw) {
+                                    Self::draw_shape_on_frame(shp, frame_idx, self.tool, (x0 + dx, y0 + dy, x1 + dx, y1 + dy), self.fill_mode, self.brush_size, self.draw_index);
+                                }
+                                self.dirty = true;
+                            }
+                            self.draw_start=None; self.draw_end=None;
+                        }
+                    }}
+                    if let (Some(base), Some(sel)) = (&self.clip_base, &self.selection) { sel.clip(&mut shp.frames[frame_idx].pixels, base); }
+                    if !self.drawing { self.clip_base = None; }
+
+                    // 选区轮廓与正在拖出的矩形/套索
+                    let to_screen = |x: f32, y: f32| rect.min + egui::vec2(x, y) * self.scale;
+                    let moving = self.sel_move.as_ref().map(|m| m.mask.shifted(m.offset.0, m.offset.1));
+                    if let Some(sel) = moving.as_ref().or(self.selection.as_ref()) {
+                        let painter = ui.painter();
+                        for [(ax, ay), (bx, by)] in sel.outline() {
+                            let seg = [to_screen(ax as f32, ay as f32), to_screen(bx as f32, by as f32)];
+                            painter.line_segment(seg, egui::Stroke::new(3.0, Color32::BLACK));
+                            painter.line_segment(seg, egui::Stroke::new(1.0, Color32::WHITE));
+                        }
+                    }
+                    // 颜色用量面板选中的索引：逐像素描框（像素过多时只画前一部分）
+                    if let Some(idx) = self.usage_highlight {
+                        let w = shp.width as usize;
+                        let stroke = egui::Stroke::new(1.0, Color32::from_rgb(255, 0, 255));
+                        for (i, _) in shp.frames[frame_idx].pixels.iter().enumerate().filter(|(_, p)| **p == idx).take(20000) {
+                            let (x, y) = ((i % w) as f32, (i / w) as f32);
+                            ui.painter().rect_stroke(egui::Rect::from_two_pos(to_screen(x, y), to_screen(x + 1.0, y + 1.0)), 0.0, stroke);
+                        }
+                    }
+                    if self.drawing && self.sel_move.is_none() {
+                        let stroke = egui::Stroke::new(1.0, Color32::from_rgb(0, 200, 255));
+                        match (self.tool, self.draw_start, self.draw_end) {
+                            (Tool::SelectRect, Some(s), Some(e)) => {
+                                let r = egui::Rect::from_two_pos(to_screen(s.x.min(e.x), s.y.min(e.y)), to_screen(s.x.max(e.x) + 1.0, s.y.max(e.y) + 1.0));
+                                ui.painter().rect_stroke(r, 0.0, stroke);
+                            }
+                            (Tool::Lasso, _, _) if self.lasso_pts.len() > 1 => {
+                                let pts: Vec<egui::Pos2> = self.lasso_pts.iter().map(|&(x, y)| to_screen(x, y)).collect();
+                                ui.painter().add(egui::Shape::closed_line(pts, stroke));
+                            }
+                            _ => {}
                         }
-                        self.draw_start=None; self.draw_end=None;
                     }
-                }}
 
-                // 绘制形状预览
-                if self.drawing { if let (Some(s), Some(e)) = (self.draw_start, self.draw_end) {
-                    let start = rect.min + egui::vec2(s.x * self.scale, s.y * self.scale);
-                    let end   = rect.min + egui::vec2(e.x * self.scale, e.y * self.scale);
-                    match self.tool { 
-                        Tool::Line => { let _ = ui.painter().line_segment([start,end], egui::Stroke::new(1.0, egui::Color32::WHITE)); }
-                        Tool::Rectangle => { let r = egui::Rect::from_two_pos(start,end); let _ = ui.painter().rect_stroke(r,0.0, egui::Stroke::new(1.0, egui::Color32::WHITE)); }
-                        Tool::Circle => { let r = start.distance(end); let _ = ui.painter().circle_stroke(start, r, egui::Stroke::new(1.0, egui::Color32::WHITE)); }
-                        _ => {}
+                    // 绘制形状预览：按实际颜色/画笔大小/填充模式光栅化，与松开鼠标后的结果一致
+                    if self.drawing && matches!(self.tool, Tool::Line | Tool::Rectangle | Tool::Circle)
+                        && let (Some(s), Some(e)) = (self.draw_start, self.draw_end)
+                    {
+                        let pts = constrain_shape(self.tool, s, e, modifiers);
+                        // 透明索引以半透明暗色示意擦除区域
+                        let color = if self.draw_index == shp.transparent { Color32::from_black_alpha(140) } else {
+                            let c = view_pal.colors[self.draw_index as usize];
+                            let b = self.brightness.clamp(0.2, 3.0);
+                            let ch = |v: u8| ((v as f32) * b).round().min(255.0) as u8;
+                            Color32::from_rgb(ch(c.r()), ch(c.g()), ch(c.b()))
+                        };
+                        let key = (self.tool, pts, self.fill_mode, self.brush_size, color, self.wrap_draw);
+                        if self.shape_preview.as_ref().is_none_or(|(k, _)| *k != key) {
+                            let (x0, y0, x1, y1) = pts;
+                            let shapes: Vec<_> = wrap_offsets(shp.width, shp.height, self.wrap_draw).into_iter().map(|(dx, dy)| (x0 + dx, y0 + dy, x1 + dx, y1 + dy)).collect();
+                            let img = Self::shape_preview_image(shp.width, shp.height, self.tool, &shapes, self.fill_mode, self.brush_size, color);
+                            match self.shape_preview.as_mut() {
+                                Some((k, tex)) => { tex.set(img, egui::TextureOptions::NEAREST); *k = key; }
+                                None => { self.shape_preview = Some((key, ui.ctx().load_texture("shape_preview", img, egui::TextureOptions::NEAREST))); }
+                            }
+                        }
+                        if let Some((_, tex)) = &self.shape_preview { ui.painter().image(tex.id(), rect, uv, Color32::WHITE); }
                     }
-                }}
 
-                // 导入图片Gizmo（拖动/缩放，点击外部固定）
-                if let Some(img) = &self.import_img {
-                    let img_w = img.width();
-                    let img_h = img.height();
-                    let gizmo_size = egui::vec2((img_w as f32)*self.scale*self.import_scale, (img_h as f32)*self.scale*self.import_scale);
-                    let gizmo_rect = egui::Rect::from_min_size(rect.min + (self.import_pos.to_vec2()*self.scale), gizmo_size);
-                    ui.painter().rect_stroke(gizmo_rect, 0.0, egui::Stroke::new(1.0, egui::Color32::YELLOW));
-                    ui.painter().rect_filled(gizmo_rect, 0.0, egui::Color32::from_rgba_unmultiplied(255,255,255,20));
-                    let gizmo_resp = ui.interact(gizmo_rect, ui.id().with("import_gizmo"), Sense::click_and_drag());
-                    if gizmo_resp.dragged() { let d = gizmo_resp.drag_delta()/self.scale; self.import_pos.x += d.x; self.import_pos.y += d.y; }
-
-                    let mut should_fix = false;
-                    let mut should_cancel = false;
-                    egui::Area::new("import_toolbar".into()).fixed_pos(rect.min + egui::vec2(8.0, 8.0)).show(ctx, |ui| {
-                        egui::Frame::none().fill(egui::Color32::from_rgba_unmultiplied(0,0,0,128)).show(ui, |ui| {
-                            ui.label("导入图变换");
-                            ui.add(egui::Slider::new(&mut self.import_scale, 0.1..=8.0).text("缩放"));
-                            if ui.button("固定到帧").clicked() { should_fix = true; }
-                            if ui.button("取消").clicked() { should_cancel = true; }
+                    // 文字：预览框与工具条，固定到帧时按当前颜色写入
+                    if let (Some(tp), Some(TextPreview { mask: Some((tw, th, tmask)), tex: Some(tex), .. })) = (self.text_pos, &self.text_preview) {
+                        let r = egui::Rect::from_min_size(rect.min + tp.to_vec2() * self.scale, egui::vec2(*tw as f32, *th as f32) * self.scale);
+                        ui.painter().image(tex.id(), r, uv, Color32::WHITE);
+                        ui.painter().rect_stroke(r.expand(1.0), 0.0, egui::Stroke::new(1.0, Color32::YELLOW));
+                        let (mut fix, mut cancel) = (false, false);
+                        egui::Area::new("text_toolbar".into()).fixed_pos(canvas_rect.min + egui::vec2(8.0, 8.0)).show(ctx, |ui| {
+                            egui::Frame::none().fill(Color32::from_black_alpha(160)).inner_margin(4.0).show(ui, |ui| {
+                                ui.horizontal(|ui| {
+                                    if ui.button(tr("固定到帧")).clicked() { fix = true; }
+                                    if ui.button(tr("取消")).clicked() { cancel = true; }
+                                });
+                            });
                         });
-                    });
+                        if fix {
+                            pending_undo = Some(shp.frames[frame_idx].pixels.clone());
+                            let (ox, oy) = (tp.x as i32, tp.y as i32);
+                            for (i, _) in tmask.iter().enumerate().filter(|(_, m)| **m) {
+                                let (x, y) = ((i as u32 % tw) as i32, (i as u32 / tw) as i32);
+                                Self::frame_set_pixel(shp, frame_idx, ox + x, oy + y, self.brush_index);
+                            }
+                            if let (Some(base), Some(sel)) = (&pending_undo, &self.selection) { sel.clip(&mut shp.frames[frame_idx].pixels, base); }
+                            self.dirty = true;
+                        }
+                        if fix || cancel { self.text_pos = None; }
+                    }
 
-                    // 仅当已“武装”后才允许通过点击gizmo外部来固定
-                    // 初次导入后，等到鼠标没有按下的一个刷新帧后，才设置为武装状态
-                    ctx.input(|i| {
-                        if !self.import_armed {
-                            if !i.pointer.any_down() { self.import_armed = true; }
+                    // 导入图片Gizmo（拖动/缩放，点击外部固定）
+                    if let Some(img) = &self.import_img {
+                        // 缩放与尺寸均相对预缩小后的图
+                        let img_w = img.width().div_ceil(self.import_downscale);
+                        let img_h = img.height().div_ceil(self.import_downscale);
+                        let gizmo_size = egui::vec2((img_w as f32)*self.scale*self.import_scale, (img_h as f32)*self.scale*self.import_scale);
+                        let gizmo_rect = egui::Rect::from_min_size(rect.min + (self.import_pos.to_vec2()*self.scale), gizmo_size);
+                        let (sw, sh) = import_target_size(img_w, img_h, self.import_scale);
+                        // 实时预览固定后的效果：参数、调色板或（混合时）位置与帧内容变化才重新生成
+                        let key = {
+                            use std::hash::{Hash, Hasher};
+                            let mut h = std::collections::hash_map::DefaultHasher::new();
+                            (sw, sh, self.import_angle_deg.to_bits(), self.import_resample, self.import_downscale, self.import_sharpen.to_bits()).hash(&mut h);
+                            (self.import_chroma, self.import_chroma_color, self.import_chroma_tol, self.import_alpha_min, self.import_show_quantized).hash(&mut h);
+                            for c in self.palette.colors.iter() { c.to_array().hash(&mut h); }
+                            if self.import_blend && self.import_show_quantized {
+                                (self.import_pos.x.round() as i32, self.import_pos.y.round() as i32, frame_idx).hash(&mut h);
+                                shp.frames[frame_idx].pixels.hash(&mut h);
+                            }
+                            h.finish()
+                        };
+                        if self.import_preview.as_ref().is_none_or(|(k, _, _)| *k != key) {
+                            let chroma = self.import_chroma.then_some((self.import_chroma_color, self.import_chroma_tol));
+                            let (rgba, (ox, oy)) = import_pixels(img, (sw, sh), chroma, self.import_downscale, self.import_sharpen, self.import_resample, self.import_angle_deg);
+                            let (w, h) = (rgba.width() as usize, rgba.height() as usize);
+                            let ci = if self.import_show_quantized {
+                                let dest = (self.import_pos.x.round() as i32 - ox, self.import_pos.y.round() as i32 - oy);
+                                let rgba = if self.import_blend { shp.composite_over_frame(frame_idx, &rgba, dest.0, dest.1, &self.palette, self.import_alpha_min) } else { rgba };
+                                let indices = self.quant_cache.get_or_quantize(&rgba, &self.palette.colors, self.import_alpha_min, Some(shp.transparent));
+                                let px: Vec<Color32> = indices.iter().map(|i| i.map_or(Color32::TRANSPARENT, |i| self.palette.colors[i as usize])).collect();
+                                egui::ColorImage { size: [w, h], pixels: px }
+                            } else {
+                                egui::ColorImage::from_rgba_unmultiplied([w, h], rgba.as_raw())
+                            };
+                            self.import_preview = Some((key, ctx.load_texture("import_preview", ci, egui::TextureOptions::NEAREST), (ox, oy)));
                         }
-                    });
+                        if let Some((_, tex, (ox, oy))) = &self.import_preview {
+                            let min = rect.min + egui::vec2((self.import_pos.x.round() - *ox as f32) * self.scale, (self.import_pos.y.round() - *oy as f32) * self.scale);
+                            let r = egui::Rect::from_min_size(min, tex.size_vec2() * self.scale);
+                            ui.painter().image(tex.id(), r, egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)), Color32::WHITE);
+                        }
+                        ui.painter().rect_stroke(gizmo_rect, 0.0, egui::Stroke::new(1.0, egui::Color32::YELLOW));
+                        // 旋转后的实际落点轮廓
+                        if self.import_angle_deg.rem_euclid(360.0) != 0.0 {
+                            let (s, c) = self.import_angle_deg.to_radians().sin_cos();
+                            let center = gizmo_rect.center();
+                            let pts: Vec<egui::Pos2> = [gizmo_rect.left_top(), gizmo_rect.right_top(), gizmo_rect.right_bottom(), gizmo_rect.left_bottom()]
+                                .iter().map(|p| { let d = *p - center; center + egui::vec2(c * d.x - s * d.y, s * d.x + c * d.y) }).collect();
+                            ui.painter().add(egui::Shape::closed_line(pts, egui::Stroke::new(1.5, Color32::from_rgb(255, 160, 0))));
+                        }
+                        let gizmo_resp = ui.interact(gizmo_rect, ui.id().with("import_gizmo"), Sense::click_and_drag());
+                        if gizmo_resp.dragged() { let d = gizmo_resp.drag_delta()/self.scale; self.import_pos.x += d.x; self.import_pos.y += d.y; }
+
+                        let mut should_fix = false;
+                        let mut should_cancel = false;
+                        egui::Area::new("import_toolbar".into()).fixed_pos(rect.min + egui::vec2(8.0, 8.0)).show(ctx, |ui| {
+                            egui::Frame::none().fill(egui::Color32::from_rgba_unmultiplied(0,0,0,128)).show(ui, |ui| {
+                                ui.label(tr("导入图变换"));
+                                ui.horizontal(|ui| {
+                                    ui.label(tr("预览"));
+                                    ui.selectable_value(&mut self.import_show_quantized, false, tr("原图"));
+                                    ui.selectable_value(&mut self.import_show_quantized, true, tr("量化结果"));
+                                }).response.on_hover_text(tr("量化结果即固定后写入帧的颜色；有选区时只写入选区内的部分"));
+                                ui.add(egui::Slider::new(&mut self.import_scale, 0.1..=8.0).text(tr("缩放")));
+                                ui.add(egui::Slider::new(&mut self.import_angle_deg, -180.0..=180.0).text(tr("旋转°")));
+                                ui.add(egui::Slider::new(&mut self.import_alpha_min, 1..=255).text(tr("Alpha 阈值")));
+                                ui.checkbox(&mut self.import_blend, tr("半透明像素与帧内容混合"));
+                                egui::ComboBox::from_id_source("import_resample").selected_text(self.import_resample.label()).show_ui(ui, |ui| {
+                                    for r in Resample::ALL { ui.selectable_value(&mut self.import_resample, r, r.label()); }
+                                });
+                                ui.add(egui::Slider::new(&mut self.import_downscale, 1..=8).text(tr("预缩小倍数")).prefix("1/"))
+                                    .on_hover_text(tr("按整数倍面积平均缩小后再量化，适合 2×/4× 渲染的素材"));
+                                ui.add_enabled(self.import_downscale > 1, egui::Slider::new(&mut self.import_sharpen, 0.0..=2.0).text(tr("锐化")));
+                                ui.horizontal(|ui| {
+                                    ui.checkbox(&mut self.import_chroma, tr("抠除背景色"));
+                                    ui.add_enabled_ui(self.import_chroma, |ui| {
+                                        ui.color_edit_button_srgb(&mut self.import_chroma_color);
+                                        if ui.small_button(tr("品红")).clicked() { self.import_chroma_color = [255, 0, 255]; }
+                                        if ui.small_button(tr("绿")).clicked() { self.import_chroma_color = [0, 255, 0]; }
+                                        if ui.small_button(tr("取左上角")).on_hover_text(tr("使用图片左上角像素的颜色")).clicked() {
+                                            let p = img.get_pixel(0, 0).0;
+                                            self.import_chroma_color = [p[0], p[1], p[2]];
+                                        }
+                                    });
+                                });
+                                ui.add_enabled(self.import_chroma, egui::Slider::new(&mut self.import_chroma_tol, 0..=160).text(tr("容差")));
+                                // 精确数值：位置与目标尺寸（尺寸按比例联动缩放）
+                                ui.horizontal(|ui| {
+                                    ui.label("X"); ui.add(egui::DragValue::new(&mut self.import_pos.x).speed(1.0));
+                                    ui.label("Y"); ui.add(egui::DragValue::new(&mut self.import_pos.y).speed(1.0));
+                                });
+                                ui.horizontal(|ui| {
+                                    let mut w = (img_w as f32 * self.import_scale).round();
+                                    let mut h = (img_h as f32 * self.import_scale).round();
+                                    ui.label(tr("宽"));
+                                    if ui.add(egui::DragValue::new(&mut w).speed(1.0).clamp_range(1.0..=4096.0)).changed() { self.import_scale = w / img_w as f32; }
+                                    ui.label(tr("高"));
+                                    if ui.add(egui::DragValue::new(&mut h).speed(1.0).clamp_range(1.0..=4096.0)).changed() { self.import_scale = h / img_h as f32; }
+                                });
+                                if ui.button(tr("固定到帧")).clicked() { should_fix = true; }
+                                if ui.button(tr("取消")).clicked() { should_cancel = true; }
+                            });
+                        });
+
+                        // 仅当已“武装”后才允许通过点击gizmo外部来固定
+                        // 初次导入后，等到鼠标没有按下的一个刷新帧后，才设置为武装状态
+                        ctx.input(|i| {
+                            if !self.import_armed {
+                                if !i.pointer.any_down() { self.import_armed = true; }
+                            }
+                        });
 
-                    let mut clicked_outside_pressed = false;
-                    ctx.input(|i| {
-                        if i.pointer.primary_pressed() {
-                            if let Some(pos) = i.pointer.interact_pos() {
-                                if !gizmo_rect.contains(pos) { clicked_outside_pressed = true; }
+                        let mut clicked_outside_pressed = false;
+                        ctx.input(|i| {
+                            if i.pointer.primary_pressed() {
+                                if let Some(pos) = i.pointer.interact_pos() {
+                                    if !gizmo_rect.contains(pos) { clicked_outside_pressed = true; }
+                                }
                             }
+                        });
+                        if self.import_armed && clicked_outside_pressed { should_fix = true; }
+
+                        if should_fix {
+                            // 工具栏本帧可能刚改过缩放
+                            let (sw, sh) = import_target_size(img_w, img_h, self.import_scale);
+                            let chroma = self.import_chroma.then_some((self.import_chroma_color, self.import_chroma_tol));
+                            let (resized, (ox, oy)) = import_pixels(img, (sw, sh), chroma, self.import_downscale, self.import_sharpen, self.import_resample, self.import_angle_deg);
+                            let dest_x = self.import_pos.x.round() as i32 - ox; let dest_y = self.import_pos.y.round() as i32 - oy;
+                            let resized = if self.import_blend {
+                                shp.composite_over_frame(frame_idx, &resized, dest_x, dest_y, &self.palette, self.import_alpha_min)
+                            } else { resized };
+                            let indices = self.quant_cache.get_or_quantize(&resized, &self.palette.colors, self.import_alpha_min, Some(shp.transparent));
+                            pending_undo = Some(shp.frames[frame_idx].pixels.clone());
+                            import_fixed = true;
+                            shp.paste_indices_at(frame_idx, &indices, resized.width(), resized.height(), dest_x, dest_y);
+                            // 有选区时只贴入选区内的部分
+                            if let (Some(base), Some(sel)) = (&pending_undo, &self.selection) { sel.clip(&mut shp.frames[frame_idx].pixels, base); }
+                            self.dirty = true;
+                            self.import_img = None;
                         }
-                    });
-                    if self.import_armed && clicked_outside_pressed { should_fix = true; }
-
-                    if should_fix {
-                        // 缩放尺寸安全上限，防止误操作导致超大分配
-                        let mut sw = (img_w as f32 * self.import_scale).round().max(1.0) as u32;
-                        let mut sh = (img_h as f32 * self.import_scale).round().max(1.0) as u32;
-                        let max_side = 4096u32;
-                        if sw > max_side { let k = max_side as f32 / sw as f32; sw = max_side; sh = (sh as f32 * k).round().max(1.0) as u32; }
-                        if sh > max_side { let k = max_side as f32 / sh as f32; sh = max_side; sw = (sw as f32 * k).round().max(1.0) as u32; }
-                        let resized = image::imageops::resize(img, sw, sh, image::imageops::Nearest);
-                        let dest_x = self.import_pos.x.round() as i32; let dest_y = self.import_pos.y.round() as i32;
-                        shp.paste_rgba_at(frame_idx, &resized, dest_x, dest_y, &self.palette);
-                        self.dirty = true;
-                        self.import_img = None;
+                        if should_cancel { self.import_img = None; }
+                        if self.import_img.is_none() { self.import_preview = None; }
+                        // 一帧展示后才允许外部点击固定
+                        self.import_armed = true;
                     }
-                    if should_cancel { self.import_img = None; }
-                    // 一帧展示后才允许外部点击固定
-                    self.import_armed = true;
-                }
-            } else { ui.centered_and_justified(|ui| { ui.label("新建或打开一个 SHP 开始绘制"); }); }
+                    // 画笔 HUD：滚轮/快捷键改变画笔后在光标旁短暂显示当前颜色与大小
+                    if let (Some(until), Some(pos)) = (self.brush_hud_until, response.hover_pos()) {
+                        if Instant::now() < until {
+                            let r = egui::Rect::from_min_size(pos + egui::vec2(16.0, 16.0), egui::vec2(86.0, 22.0));
+                            let painter = ui.painter();
+                            painter.rect_filled(r, 3.0, Color32::from_black_alpha(200));
+                            let sw = egui::Rect::from_min_size(r.min + egui::vec2(4.0, 4.0), egui::vec2(14.0, 14.0));
+                            painter.rect_filled(sw, 0.0, self.palette.colors[self.brush_index as usize]);
+                            painter.rect_stroke(sw, 0.0, egui::Stroke::new(1.0, Color32::WHITE));
+                            let text = format!("#{} {}px", self.brush_index, self.brush_size);
+                            painter.text(egui::pos2(sw.right() + 5.0, r.center().y), egui::Align2::LEFT_CENTER, text, egui::FontId::monospace(12.0), Color32::WHITE);
+                            ui.ctx().request_repaint_after(until - Instant::now());
+                        } else {
+                            self.brush_hud_until = None;
+                        }
+                    }
+                } else { ui.centered_and_justified(|ui| { ui.label(tr("新建或打开一个 SHP 开始绘制")); }); }
+            });
+            self.ui_minimap(ui, &view, minimap_tex);
 
             // 在释放对shp的可变借用后，推入撤销栈
             if let Some(data) = pending_undo {
-                self.undo_stack.push(data);
-                if self.undo_stack.len() > self.max_undo_steps { self.undo_stack.remove(0); }
-                self.redo_stack.clear();
-                // 记录历史所属的当前帧
-                if let Some(shp) = &self.shp {
-                    let fi = self.preview.current_frame.min(shp.frames.len().saturating_sub(1));
-                    self.undo_frame_anchor = Some(fi);
+                let fi = self.preview.current_frame;
+                let op = match self.tool {
+                    Tool::Pencil => "铅笔", Tool::Eraser => "橡皮", Tool::Line => "直线",
+                    Tool::Rectangle => "矩形", Tool::Circle => "圆", Tool::Fill => "填充",
+                    Tool::SelectRect | Tool::Lasso | Tool::Wand => "移动选区",
+                    Tool::Text => "文字",
+                };
+                self.record_op(if import_fixed { "导入图片" } else { op }, Some(fi));
+                self.push_undo(Patch::Pixels(vec![(fi, FramePixels::Full(data))]));
+                if !import_fixed && matches!(self.tool, Tool::Pencil | Tool::Line | Tool::Rectangle | Tool::Circle | Tool::Fill) {
+                    self.push_recent_color(self.draw_index);
                 }
             }
         });
 
-        // 快捷键
-        if ctx.input(|i| i.modifiers == Modifiers::CTRL && i.key_pressed(Key::N)) { self.action_new_shp(); }
-        if ctx.input(|i| i.modifiers == Modifiers::CTRL && i.key_pressed(Key::O)) { self.action_open_shp(); }
-        if ctx.input(|i| i.modifiers == Modifiers::CTRL && i.key_pressed(Key::S)) { self.action_save_shp(); }
-        if ctx.input(|i| i.modifiers == Modifiers::CTRL && i.key_pressed(Key::Z)) { self.undo(); }
-        if ctx.input(|i| i.modifiers == Modifiers::CTRL && i.key_pressed(Key::Y)) { self.redo(); }
-        if ctx.input(|i| i.key_pressed(Key::ArrowLeft)) {
-            if let Some(shp) = &self.shp { if self.preview.current_frame > 0 && shp.frames.len() > 0 { self.preview.current_frame -= 1; } }
-        }
-        if ctx.input(|i| i.key_pressed(Key::ArrowRight)) {
-            if let Some(shp) = &self.shp { if self.preview.current_frame + 1 < shp.frames.len() { self.preview.current_frame += 1; } }
+        // 快捷键：经由可配置的 keymap；正在录制新快捷键、或文本框占用键盘（含输入法组字）时不触发
+        let typing = ctx.wants_keyboard_input() || ctx.input(|i| i.events.iter().any(|e| matches!(e, egui::Event::CompositionStart | egui::Event::CompositionUpdate(_))));
+        if self.keymap_capture.is_none() && !typing {
+            let km = &self.settings.keymap;
+            let pressed = |a: Action| ctx.input(|i| km.pressed(i, a));
+            let (new_, open, save, undo, redo, prev, next) = (
+                pressed(Action::NewShp), pressed(Action::OpenShp), pressed(Action::SaveShp),
+                pressed(Action::Undo), pressed(Action::Redo),
+                pressed(Action::PrevFrame), pressed(Action::NextFrame),
+            );
+            let (select_all, deselect, delete_sel) = (pressed(Action::SelectAll), pressed(Action::Deselect), pressed(Action::DeleteSelection));
+            let (copy_img, paste_img) = (pressed(Action::CopyFrameImage), pressed(Action::PasteImage));
+            let (prev_color, next_color) = (pressed(Action::PrevColor), pressed(Action::NextColor));
+            if prev_color || next_color {
+                self.brush_index = if prev_color { self.brush_index.wrapping_sub(1) } else { self.brush_index.wrapping_add(1) };
+                self.brush_hud_until = Some(Instant::now() + BRUSH_HUD_TIME);
+            }
+            let (cycle_pal, swap_colors) = (pressed(Action::CyclePalette), pressed(Action::SwapColors));
+            // 数字键 1–9 切换固定的调色板；已被 keymap 占用的组合键让给对应操作
+            let pinned_key = PIN_KEYS.iter().position(|&k| {
+                let chord = KeyChord::new(Modifiers::NONE, k);
+                !km.bindings.values().any(|c| *c == chord) && ctx.input(|i| chord.pressed(i))
+            });
+            if cycle_pal { self.action_cycle_palette(); }
+            if let Some(n) = pinned_key { self.action_pinned_palette(n); }
+            if swap_colors { self.action_swap_colors(); }
+            if copy_img { self.action_copy_frame_image(); }
+            if paste_img { self.action_paste_image(); }
+            if select_all { self.action_select_all(); }
+            if deselect { self.selection = None; }
+            if delete_sel { self.action_fill_selection(None); }
+            if new_ { self.action_new_shp(); }
+            if open { self.action_open_shp(); }
+            if save { self.action_save_shp(); }
+            if undo { self.undo(); }
+            if redo { self.redo(); }
+            if prev {
+                if let Some(shp) = &self.shp { if self.preview.current_frame > 0 && shp.frames.len() > 0 { self.preview.current_frame -= 1; } }
+            }
+            if next {
+                if let Some(shp) = &self.shp { if self.preview.current_frame + 1 < shp.frames.len() { self.preview.current_frame += 1; } }
+            }
+            self.frame_nav_keys(ctx);
         }
 
         // 退出保护：拦截窗口关闭请求
@@ -801,18 +6160,18 @@ impl eframe::App for MixApp {
             self.show_exit_confirm = true;
         }
         if self.show_exit_confirm {
-            egui::Window::new("⚠️ 确认退出")
+            egui::Window::new(tr("⚠️ 确认退出"))
                 .collapsible(false)
                 .resizable(false)
                 .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
                 .frame(egui::Frame::window(&ctx.style()).fill(egui::Color32::from_rgba_unmultiplied(30,30,30,240)))
                 .show(ctx, |ui| {
-                    ui.heading(if self.dirty { "有未保存的更改" } else { "退出程序" });
+                    ui.heading(if self.dirty { tr("有未保存的更改") } else { tr("退出程序") });
                     ui.separator();
-                    ui.label("建议先保存再退出，避免丢失编辑。");
+                    ui.label(tr("建议先保存再退出，避免丢失编辑。"));
                     ui.add_space(8.0);
                     ui.horizontal(|ui| {
-                        if ui.add(egui::Button::new("💾 保存并退出").fill(egui::Color32::from_rgb(0,120,0))).clicked() {
+                        if ui.add(egui::Button::new(tr("💾 保存并退出")).fill(egui::Color32::from_rgb(0,120,0))).clicked() {
                             if let Some(shp) = &self.shp {
                                 if let Some(path) = FileDialog::new().set_file_name("output.shp").save_file() {
                                     if let Ok(bytes) = shp.save() {
@@ -824,12 +6183,12 @@ impl eframe::App for MixApp {
                             self.show_exit_confirm = false;
                             ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                         }
-                        if ui.add(egui::Button::new("🗙 不保存退出").fill(egui::Color32::from_rgb(120,0,0))).clicked() {
+                        if ui.add(egui::Button::new(tr("🗙 不保存退出")).fill(egui::Color32::from_rgb(120,0,0))).clicked() {
                             self.show_exit_confirm = false;
                             self.dirty = false;
                             ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                         }
-                        if ui.add(egui::Button::new("取消").fill(egui::Color32::DARK_GRAY)).clicked() {
+                        if ui.add(egui::Button::new(tr("取消")).fill(egui::Color32::DARK_GRAY)).clicked() {
                             self.show_exit_confirm = false;
                         }
                     });
@@ -837,7 +6196,7 @@ impl eframe::App for MixApp {
         }
 
         // 键盘快捷键退出确认
-        if ctx.input(|i| i.modifiers == Modifiers::CTRL && i.key_pressed(Key::Q)) {
+        if self.keymap_capture.is_none() && !typing && ctx.input(|i| self.settings.keymap.pressed(i, Action::Quit)) {
             if self.dirty {
                 self.show_exit_confirm = true;
             } else {
@@ -845,30 +6204,81 @@ impl eframe::App for MixApp {
             }
         }
 
+        if self.show_pal_gallery { self.ui_pal_gallery(ctx); }
+        if self.show_color_usage { self.ui_color_usage(ctx); }
+        if self.show_replace_dialog { self.ui_replace_dialog(ctx); }
+        if self.show_keymap_window { self.ui_keymap_window(ctx); }
+        if self.show_delta_export { self.ui_delta_export(ctx); }
+        if self.show_contact_sheet { self.ui_contact_sheet(ctx); }
+        if self.show_ghost_export { self.ui_ghost_export(ctx); }
+        if self.show_batch { self.ui_batch(ctx); }
+        if self.show_pal_convert { self.ui_pal_convert(ctx); }
+        if self.pal_switch_pending.is_some() { self.ui_pal_switch(ctx); }
+        if self.show_shadow_dialog { self.ui_shadow_dialog(ctx); }
+        if self.show_tween_dialog { self.ui_tween_dialog(ctx); }
+        if self.show_facings_dialog { self.ui_facings_dialog(ctx); }
+        if self.show_dedup { self.ui_dedup(ctx); }
+        if self.show_outline_dialog { self.ui_outline_dialog(ctx); }
+        if self.show_shift_dialog { self.ui_shift_dialog(ctx); }
+        if self.show_cameo_dialog { self.ui_cameo_dialog(ctx); }
+        if self.show_filter_dialog { self.ui_filter_dialog(ctx); }
+        if self.show_tile_preview { self.ui_tile_preview(ctx); }
+        if self.show_game_preview { self.ui_game_preview(ctx); }
+        if self.show_frame_inspector { self.ui_frame_inspector(ctx); }
+        if self.show_pal_split { self.ui_pal_split(ctx); }
+        if self.show_sequences { self.ui_sequences(ctx); }
+        if self.show_compare { self.ui_compare(ctx); }
+        if self.watch_conflict { self.ui_watch_conflict(ctx); }
+        if self.show_save_report { self.ui_save_report(ctx); }
+        if self.frame_import.is_some() { self.ui_frame_import(ctx); }
+        if self.canvas_menu.is_some() { self.ui_canvas_menu(ctx); }
+        if self.io_task.is_some() { self.ui_io_overlay(ctx); }
+        if self.show_canvas_size { self.ui_canvas_size(ctx); }
+        if self.show_retarget { self.ui_retarget(ctx); }
+        if self.show_pal_adjust { self.ui_pal_adjust(ctx); }
+        if self.show_pal_diff { self.ui_pal_diff(ctx); }
+        if self.show_pal_sort { self.ui_pal_sort(ctx); }
+        if self.show_scale_dialog { self.ui_scale_dialog(ctx); }
+        if self.show_update_window { self.ui_update_window(ctx); }
+        if self.show_mix_window { self.ui_mix_window(ctx); }
+        if self.show_stats_window { self.ui_stats_window(ctx); }
+        if self.show_hook_window { self.ui_hook_window(ctx); }
+        if self.show_script_console { self.ui_script_console(ctx); }
+
         // 新建SHP大弹窗
         if self.show_new_dialog {
-            egui::Window::new("新建 SHP")
+            egui::Window::new(tr("新建 SHP"))
                 .collapsible(false)
                 .resizable(false)
                 .fixed_size(egui::vec2(420.0, 240.0))
                 .show(ctx, |ui| {
-                    ui.label("请输入尺寸与帧数：");
+                    ui.label(tr("请输入尺寸与帧数："));
                     ui.separator();
                     ui.horizontal(|ui| {
-                        ui.label("宽"); ui.add(egui::DragValue::new(&mut self.new_w).clamp_range(1..=4096));
-                        ui.label("高"); ui.add(egui::DragValue::new(&mut self.new_h).clamp_range(1..=4096));
-                        ui.label("帧数"); ui.add(egui::DragValue::new(&mut self.new_frames).clamp_range(1..=20000));
+                        ui.label(tr("宽")); ui.add(egui::DragValue::new(&mut self.new_w).clamp_range(1..=4096));
+                        ui.label(tr("高")); ui.add(egui::DragValue::new(&mut self.new_h).clamp_range(1..=4096));
+                        ui.label(tr("帧数")); ui.add(egui::DragValue::new(&mut self.new_frames).clamp_range(1..=20000));
                     });
+                    let bytes = self.new_w as usize * self.new_h as usize * self.new_frames;
+                    ui.label(RichText::new(trf!("约占内存 {} 字节", format_number(bytes))).small().color(Color32::GRAY));
+                    let check = SHP::check_limits(self.new_w, self.new_h, self.new_frames);
+                    if let Err(e) = &check { ui.colored_label(Color32::from_rgb(255, 120, 120), e); }
                     ui.separator();
                     ui.horizontal(|ui| {
-                        if ui.button("确定").clicked() {
-                            self.shp = Some(SHP::new(self.new_w, self.new_h, self.new_frames));
+                        if ui.add_enabled(check.is_ok(), egui::Button::new(tr("确定"))).clicked()
+                            && let Ok(shp) = SHP::try_new(self.new_w, self.new_h, self.new_frames)
+                        {
+                            self.shp = Some(shp);
+                            self.current_path = None;
+                            self.begin_document_stats();
                             self.preview.current_frame = 0;
-                            self.status = format!("已新建 SHP: {}x{}, 帧数 {}", self.new_w, self.new_h, self.new_frames);
+                            self.status = trf!("已新建 SHP: {}x{}, 帧数 {}", self.new_w, self.new_h, self.new_frames);
                             self.show_new_dialog = false;
                             self.dirty = false; // 新建文件，清除dirty标记
+                            self.history.clear();
+                            self.selected_frames.clear();
                         }
-                        if ui.button("取消").clicked() { self.show_new_dialog = false; }
+                        if ui.button(tr("取消")).clicked() { self.show_new_dialog = false; }
                     });
                 });
         }
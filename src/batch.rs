@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+
+use eframe::egui;
+
+use crate::i18n::tr;
+use crate::palette::Palette;
+use crate::shp::SHP;
+
+/// 批量转换的输出格式
+#[derive(Clone, Copy, PartialEq)]
+pub enum BatchFormat {
+    /// 每个 SHP 一个子文件夹，每帧一张 PNG
+    Png,
+    /// 每个 SHP 一个 GIF 动画，直接使用调色板索引
+    Gif,
+}
+
+impl BatchFormat {
+    pub const ALL: [BatchFormat; 2] = [BatchFormat::Png, BatchFormat::Gif];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            BatchFormat::Png => tr("PNG 序列（每个 SHP 一个子文件夹）"),
+            BatchFormat::Gif => tr("GIF 动画（每个 SHP 一个文件）"),
+        }
+    }
+}
+
+pub struct BatchJob {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub palette: Palette,
+    pub format: BatchFormat,
+    /// GIF 帧间隔
+    pub ms_per_frame: u32,
+}
+
+/// 后台线程发回的进度
+pub enum BatchMsg {
+    /// 开始处理第 `done` 个（从 0 计）文件
+    Progress { done: usize, total: usize, file: String },
+    Failed { file: String, error: String },
+    Finished { converted: usize, failed: usize, cancelled: bool },
+}
+
+/// 在后台线程转换文件夹内所有 .shp，进度通过通道返回；置位 `cancel` 后在下一个文件前停止
+pub fn spawn(job: BatchJob, ctx: &egui::Context) -> (Receiver<BatchMsg>, Arc<AtomicBool>) {
+    let (tx, rx) = mpsc::channel();
+    let cancel = Arc::new(AtomicBool::new(false));
+    let stop = cancel.clone();
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        let send = |m: BatchMsg| { let _ = tx.send(m); ctx.request_repaint(); };
+        let files = list_shps(&job.input);
+        let (mut converted, mut failed) = (0, 0);
+        for (i, path) in files.iter().enumerate() {
+            if stop.load(Ordering::Relaxed) {
+                send(BatchMsg::Finished { converted, failed, cancelled: true });
+                return;
+            }
+            let file = path.file_name().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+            send(BatchMsg::Progress { done: i, total: files.len(), file: file.clone() });
+            match convert_one(path, &job) {
+                Ok(()) => converted += 1,
+                Err(error) => { failed += 1; send(BatchMsg::Failed { file, error }); }
+            }
+        }
+        send(BatchMsg::Finished { converted, failed, cancelled: false });
+    });
+    (rx, cancel)
+}
+
+/// 文件夹内的 .shp（不递归），按文件名排序
+pub fn list_shps(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir).into_iter().flatten().flatten().map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|e| e.eq_ignore_ascii_case("shp")))
+        .collect();
+    files.sort();
+    files
+}
+
+fn convert_one(path: &Path, job: &BatchJob) -> Result<(), String> {
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    let shp = SHP::load(&bytes)?;
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    match job.format {
+        BatchFormat::Png => {
+            let dir = job.output.join(&stem);
+            std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+            for i in 0..shp.frames.len() {
+                shp.export_frame_png(i, &job.palette, dir.join(format!("{} {:04}.png", stem, i)))?;
+            }
+            Ok(())
+        }
+        BatchFormat::Gif => {
+            std::fs::create_dir_all(&job.output).map_err(|e| e.to_string())?;
+            let file = std::fs::File::create(job.output.join(format!("{}.gif", stem))).map_err(|e| e.to_string())?;
+            write_gif(&shp, &job.palette, job.ms_per_frame, file)
+        }
+    }
+}
+
+// 索引像素直接写入 GIF，调色板作为全局色表，文档的透明索引即 GIF 透明色
+fn write_gif(shp: &SHP, pal: &Palette, ms_per_frame: u32, out: impl std::io::Write) -> Result<(), String> {
+    let (w, h) = (u16::try_from(shp.width).map_err(|e| e.to_string())?, u16::try_from(shp.height).map_err(|e| e.to_string())?);
+    let mut enc = gif::Encoder::new(out, w, h, &pal.to_bytes()).map_err(|e| e.to_string())?;
+    enc.set_repeat(gif::Repeat::Infinite).map_err(|e| e.to_string())?;
+    let delay = (ms_per_frame / 10).clamp(1, u16::MAX as u32) as u16;
+    for fr in &shp.frames {
+        let frame = gif::Frame {
+            width: w,
+            height: h,
+            buffer: std::borrow::Cow::Borrowed(&fr.pixels),
+            transparent: Some(shp.transparent),
+            delay,
+            dispose: gif::DisposalMethod::Background,
+            ..Default::default()
+        };
+        enc.write_frame(&frame).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
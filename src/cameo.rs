@@ -0,0 +1,65 @@
+use image::{Rgba, RgbaImage};
+
+use crate::image_io::Resample;
+use crate::palette::Palette;
+use crate::shp::{Frame, SHP};
+
+/// 侧边栏图标（cameo）尺寸
+pub const CAMEO_W: u32 = 60;
+pub const CAMEO_H: u32 = 48;
+// 底部名称文字区高度
+const TEXT_BAND: u32 = 10;
+
+/// 图标生成参数
+pub struct CameoOptions {
+    pub resample: Resample,
+    /// 图标不透明，精灵透明处填此底色
+    pub background: [u8; 3],
+    /// 叠加边框与底部文字区（压暗）
+    pub overlay: bool,
+}
+
+impl Default for CameoOptions {
+    fn default() -> Self {
+        Self { resample: Resample::Triangle, background: [24, 28, 32], overlay: true }
+    }
+}
+
+/// 把第 `frame` 帧按内容包围盒裁切、等比缩放到 60×48 内居中，铺底色后量化到图标调色板，得到单帧 SHP
+pub fn render(shp: &SHP, frame: usize, pal: &Palette, cameo_pal: &Palette, opts: &CameoOptions) -> SHP {
+    let full = shp.frame_rgba(frame, pal);
+    let (bx, by, bw, bh) = shp.frame_bounds(frame).unwrap_or((0, 0, shp.width, shp.height));
+    let sprite = image::imageops::crop_imm(&full, bx, by, bw, bh).to_image();
+    let k = (CAMEO_W as f32 / bw as f32).min(CAMEO_H as f32 / bh as f32);
+    let (sw, sh) = (((bw as f32 * k).round() as u32).clamp(1, CAMEO_W), ((bh as f32 * k).round() as u32).clamp(1, CAMEO_H));
+    let sprite = image::imageops::resize(&sprite, sw, sh, opts.resample.filter());
+
+    let [r, g, b] = opts.background;
+    let mut img = RgbaImage::from_pixel(CAMEO_W, CAMEO_H, Rgba([r, g, b, 255]));
+    let (ox, oy) = ((CAMEO_W - sw) / 2, (CAMEO_H - sh) / 2);
+    for (x, y, px) in sprite.enumerate_pixels() {
+        let dst = img.get_pixel_mut(ox + x, oy + y);
+        let a = px[3] as f32 / 255.0;
+        for c in 0..3 { dst.0[c] = (px[c] as f32 * a + dst.0[c] as f32 * (1.0 - a)).round() as u8; }
+    }
+    if opts.overlay { draw_overlay(&mut img); }
+
+    // 图标不透明：量化时排除索引 0，避免暗色落成透明
+    let indices = crate::color_match::quantize_rgba(&img, &cameo_pal.colors, 0, Some(0));
+    let mut out = SHP::new(CAMEO_W, CAMEO_H, 1);
+    out.frames[0] = Frame::new(indices.into_iter().map(|i| i.unwrap_or(0)).collect());
+    out
+}
+
+// 标准图标外观：底部文字区压暗一半，四周 1 像素深色边框
+fn draw_overlay(img: &mut RgbaImage) {
+    for y in CAMEO_H - TEXT_BAND..CAMEO_H {
+        for x in 0..CAMEO_W {
+            let px = img.get_pixel_mut(x, y);
+            for c in 0..3 { px.0[c] /= 2; }
+        }
+    }
+    let edge = Rgba([8, 8, 8, 255]);
+    for x in 0..CAMEO_W { img.put_pixel(x, 0, edge); img.put_pixel(x, CAMEO_H - 1, edge); }
+    for y in 0..CAMEO_H { img.put_pixel(0, y, edge); img.put_pixel(CAMEO_W - 1, y, edge); }
+}
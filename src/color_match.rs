@@ -1,5 +1,11 @@
+use std::sync::Arc;
+
 use eframe::egui::Color32;
 
+use rayon::prelude::*;
+
+use crate::palette::{Palette, PaletteRole};
+
 // 颜色匹配：在 256 色调色板中寻找与目标 RGB 距离最小的索引
 // 用于将 RGBA 图片量化到当前调色板（SHP 使用 8-bit palette 索引）
 // 简化：使用欧氏距离平方（不含开方，性能更好）
@@ -11,15 +17,217 @@ fn dist_rgb2(a: Color32, b: Color32) -> u32 {
     (dr * dr + dg * dg + db * db) as u32
 }
 
-/// 在 `palette` 中返回与 `color` 最接近的调色板索引
-pub fn best_index_rgb(color: Color32, palette: &[Color32; 256]) -> u8 {
-    let mut best = 0u8;
-    let mut best_d = u32::MAX;
-    for i in 0..256u16 {
-        let d = dist_rgb2(color, palette[i as usize]);
-        if d < best_d { best_d = d; best = i as u8; if d == 0 { break; } }
+/// 从 `from` 调色板换到 `to` 调色板时的索引映射表：
+/// - 索引 0（透明）保持为 0
+/// - 阴影索引对应到目标的阴影索引；阵营色区间按区间内偏移一一对应
+/// - 其余索引按 RGB 最接近匹配，且只在目标的普通索引中挑选，避免普通颜色落进阵营色/阴影
+pub fn palette_remap_table(from: &Palette, to: &Palette) -> [u8; 256] {
+    let mut candidates: Vec<u8> = (1..=255u8).filter(|&i| to.roles.role(i) == PaletteRole::Normal).collect();
+    if candidates.is_empty() { candidates = (1..=255u8).collect(); }
+    let mut table = [0u8; 256];
+    for i in 1..=255u8 {
+        table[i as usize] = match (from.roles.role(i), from.roles.remap, to.roles.remap) {
+            (PaletteRole::Shadow, _, _) if to.roles.shadow.is_some() => to.roles.shadow.unwrap_or(i),
+            (PaletteRole::Remap, Some((fa, _)), Some((ta, tb))) => ta.saturating_add(i - fa).min(tb),
+            _ => {
+                let c = from.colors[i as usize];
+                candidates.iter().copied().min_by_key(|&j| dist_rgb2(c, to.colors[j as usize])).unwrap_or(i)
+            }
+        };
+    }
+    table
+}
+
+const LUT_BITS: u32 = 5;
+const LUT_SIDE: usize = 1 << LUT_BITS;
+const BIN: i32 = 256 / LUT_SIDE as i32;
+// 最近用过的加速结构个数：构建一次要扫 32³ 格，按 (调色板, 跳过索引) 复用
+const MATCHER_CACHE: usize = 4;
+
+type MatcherKey = ([Color32; 256], Option<u8>);
+static MATCHERS: std::sync::Mutex<Vec<(MatcherKey, Arc<ColorMatcher>)>> = std::sync::Mutex::new(Vec::new());
+
+/// 加速的最近色匹配：把 RGB 空间切成 32×32×32 个小格，每格预先算出“可能是最近色”的候选索引，
+/// 查询时只比较所在格的候选（通常只有几个），结果与全表线性扫描完全一致。
+/// `skip` 为不参与匹配的索引（文档的透明索引，避免不透明像素被量化成透明）
+pub struct ColorMatcher {
+    palette: [Color32; 256],
+    // 第 b 格的候选为 candidates[offsets[b]..offsets[b + 1]]，按索引升序（与线性扫描的并列取小一致）
+    offsets: Vec<u32>,
+    candidates: Vec<u8>,
+}
+
+impl ColorMatcher {
+    pub fn new(palette: &[Color32; 256], skip: Option<u8>) -> Self {
+        // 点到区间 [lo, lo+BIN-1] 的最近/最远距离
+        let near = |c: i32, lo: i32| if c < lo { lo - c } else if c > lo + BIN - 1 { c - (lo + BIN - 1) } else { 0 };
+        let far = |c: i32, lo: i32| (c - lo).abs().max((c - (lo + BIN - 1)).abs());
+        let per_bin: Vec<Vec<u8>> = (0..LUT_SIDE * LUT_SIDE * LUT_SIDE).into_par_iter().map(|bin| {
+            let (lr, lg, lb) = Self::bin_origin(bin);
+            // 格内任意点到最近色的距离不超过 min(各色最远距离)，最近距离大于它的色不可能胜出
+            let mut dmin = [0i32; 256];
+            let mut bound = i32::MAX;
+            for (i, c) in palette.iter().enumerate() {
+                if skip == Some(i as u8) { dmin[i] = i32::MAX; continue; }
+                let (r, g, b) = (c.r() as i32, c.g() as i32, c.b() as i32);
+                let (nr, ng, nb) = (near(r, lr), near(g, lg), near(b, lb));
+                dmin[i] = nr * nr + ng * ng + nb * nb;
+                let (fr, fg, fb) = (far(r, lr), far(g, lg), far(b, lb));
+                bound = bound.min(fr * fr + fg * fg + fb * fb);
+            }
+            (0..=255u8).filter(|&i| dmin[i as usize] <= bound).collect()
+        }).collect();
+        let mut offsets = Vec::with_capacity(per_bin.len() + 1);
+        let mut candidates = Vec::new();
+        for c in per_bin {
+            offsets.push(candidates.len() as u32);
+            candidates.extend(c);
+        }
+        offsets.push(candidates.len() as u32);
+        Self { palette: *palette, offsets, candidates }
+    }
+
+    /// 取共用的加速结构：同一调色板与跳过索引只构建一次（保留最近用过的几个）
+    pub fn shared(palette: &[Color32; 256], skip: Option<u8>) -> Arc<ColorMatcher> {
+        let key = (*palette, skip);
+        let mut cache = MATCHERS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(pos) = cache.iter().position(|(k, _)| *k == key) {
+            let e = cache.remove(pos);
+            let m = e.1.clone();
+            cache.push(e);
+            return m;
+        }
+        let m = Arc::new(ColorMatcher::new(palette, skip));
+        if cache.len() >= MATCHER_CACHE { cache.remove(0); }
+        cache.push((key, m.clone()));
+        m
+    }
+
+    fn bin_origin(bin: usize) -> (i32, i32, i32) {
+        let r = bin / (LUT_SIDE * LUT_SIDE);
+        let g = (bin / LUT_SIDE) % LUT_SIDE;
+        let b = bin % LUT_SIDE;
+        (r as i32 * BIN, g as i32 * BIN, b as i32 * BIN)
+    }
+
+    pub fn nearest(&self, r: u8, g: u8, b: u8) -> u8 {
+        let shift = 8 - LUT_BITS;
+        let bin = ((r >> shift) as usize * LUT_SIDE + (g >> shift) as usize) * LUT_SIDE + (b >> shift) as usize;
+        let color = Color32::from_rgb(r, g, b);
+        let mut best = 0u8;
+        let mut best_d = u32::MAX;
+        for &i in &self.candidates[self.offsets[bin] as usize..self.offsets[bin + 1] as usize] {
+            let d = dist_rgb2(color, self.palette[i as usize]);
+            if d < best_d { best_d = d; best = i; if d == 0 { break; } }
+        }
+        best
     }
-    best
 }
 
+/// 将 RGBA 图片量化为调色板索引缓冲：alpha 低于 `alpha_min` 的像素记为 None（不写入）
+/// 图片中重复出现的颜色只匹配一次（按颜色记忆结果）
+pub fn quantize_rgba(rgba: &image::RgbaImage, palette: &[Color32; 256], alpha_min: u8, skip: Option<u8>) -> Vec<Option<u8>> {
+    quantize_with(&ColorMatcher::shared(palette, skip), rgba, alpha_min)
+}
 
+fn quantize_with(matcher: &ColorMatcher, rgba: &image::RgbaImage, alpha_min: u8) -> Vec<Option<u8>> {
+    // 按 64 行一块并行，每块各自维护颜色记忆
+    let block = rgba.width().max(1) as usize * 4 * 64;
+    rgba.as_raw().par_chunks(block).flat_map_iter(|chunk| {
+        let mut memo: std::collections::HashMap<[u8; 3], u8> = std::collections::HashMap::new();
+        chunk.chunks_exact(4).map(|px| {
+            if px[3] < alpha_min { return None; }
+            let key = [px[0], px[1], px[2]];
+            Some(*memo.entry(key).or_insert_with(|| matcher.nearest(key[0], key[1], key[2])))
+        }).collect::<Vec<_>>()
+    }).collect()
+}
+
+/// 多帧量化（如 GIF 导入）：共用一个加速结构，各帧并行处理
+pub fn quantize_frames(frames: &[image::RgbaImage], palette: &[Color32; 256], alpha_min: u8, skip: Option<u8>) -> Vec<Vec<Option<u8>>> {
+    let matcher = ColorMatcher::shared(palette, skip);
+    frames.par_iter().map(|f| quantize_with(&matcher, f, alpha_min)).collect()
+}
+
+/// 量化结果缓存：键为 (图片内容 + 尺寸 + 调色板 + 参数) 的哈希，值为索引缓冲
+/// 反复固定同一张导入图、或重复导入相同帧时直接复用，避免逐像素重新匹配
+pub struct QuantizeCache {
+    entries: Vec<(u64, Arc<Vec<Option<u8>>>)>,
+    capacity: usize,
+}
+
+impl QuantizeCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { entries: Vec::new(), capacity: capacity.max(1) }
+    }
+
+    fn key(rgba: &image::RgbaImage, palette: &[Color32; 256], alpha_min: u8, skip: Option<u8>) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut h = std::collections::hash_map::DefaultHasher::new();
+        rgba.dimensions().hash(&mut h);
+        rgba.as_raw().hash(&mut h);
+        for c in palette.iter() { c.to_array().hash(&mut h); }
+        alpha_min.hash(&mut h);
+        skip.hash(&mut h);
+        h.finish()
+    }
+
+    /// 取缓存结果；未命中则量化并写入（超出容量时淘汰最久未用的条目）
+    pub fn get_or_quantize(&mut self, rgba: &image::RgbaImage, palette: &[Color32; 256], alpha_min: u8, skip: Option<u8>) -> Arc<Vec<Option<u8>>> {
+        let key = Self::key(rgba, palette, alpha_min, skip);
+        if let Some(pos) = self.entries.iter().position(|(k, _)| *k == key) {
+            let e = self.entries.remove(pos);
+            let v = e.1.clone();
+            self.entries.push(e);
+            return v;
+        }
+        let v = Arc::new(quantize_rgba(rgba, palette, alpha_min, skip));
+        if self.entries.len() >= self.capacity { self.entries.remove(0); }
+        self.entries.push((key, v.clone()));
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 全表线性扫描：ColorMatcher 的参照实现，并列时取较小的索引
+    fn best_index_rgb(color: Color32, palette: &[Color32; 256], skip: Option<u8>) -> u8 {
+        let mut best = 0u8;
+        let mut best_d = u32::MAX;
+        for i in 0..256u16 {
+            if skip == Some(i as u8) { continue; }
+            let d = dist_rgb2(color, palette[i as usize]);
+            if d < best_d { best_d = d; best = i as u8; if d == 0 { break; } }
+        }
+        best
+    }
+
+    #[test]
+    fn matcher_equals_linear_scan() {
+        // 伪随机调色板，再放几组重复色检验并列取小
+        let mut seed = 12345u32;
+        let mut palette = [Color32::BLACK; 256];
+        for c in palette.iter_mut() {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let [r, g, b, _] = seed.to_le_bytes();
+            *c = Color32::from_rgb(r, g, b);
+        }
+        palette[200] = palette[40];
+        palette[17] = palette[3];
+        for skip in [None, Some(3)] {
+            let m = ColorMatcher::new(&palette, skip);
+            for r in (0..=255u8).step_by(7) {
+                for g in (0..=255u8).step_by(7) {
+                    for b in (0..=255u8).step_by(7) {
+                        assert_eq!(m.nearest(r, g, b), best_index_rgb(Color32::from_rgb(r, g, b), &palette, skip), "{r},{g},{b} skip {skip:?}");
+                    }
+                }
+            }
+            for c in palette {
+                assert_eq!(m.nearest(c.r(), c.g(), c.b()), best_index_rgb(c, &palette, skip));
+            }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use image::{Rgba, RgbaImage};
+
+use crate::i18n::tr;
+use crate::palette::Palette;
+use crate::shp::SHP;
+
+/// 联系表（审片用总览图）排版参数
+pub struct SheetOptions {
+    pub columns: u32,
+    pub rows: u32,
+    /// 缩略图格子边长（像素），小帧按整数倍放大，大帧等比缩小
+    pub cell: u32,
+    pub title: String,
+    pub notes: String,
+}
+
+const MARGIN: u32 = 16;
+const GAP: u32 = 10;
+const CAPTION: u32 = 30;
+const TEXT_PX: f32 = 13.0;
+const BG: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const INK: Rgba<u8> = Rgba([30, 30, 30, 255]);
+const DIM: Rgba<u8> = Rgba([120, 120, 120, 255]);
+const BOX: Rgba<u8> = Rgba([230, 40, 40, 255]);
+
+/// 按页渲染整份 SHP：每格含帧号、缩略图（棋盘底）、非透明包围盒及其数值；页眉为标题、备注与页码
+pub fn render_pages(shp: &SHP, pal: &Palette, opts: &SheetOptions, font_data: &[u8]) -> Result<Vec<RgbaImage>, String> {
+    let font = FontRef::try_from_slice(font_data).map_err(|e| e.to_string())?;
+    let cols = opts.columns.max(1);
+    let rows = opts.rows.max(1);
+    let cell = opts.cell.max(16);
+    let per_page = (cols * rows) as usize;
+    let pages = shp.frames.len().div_ceil(per_page).max(1);
+    let note_lines: Vec<&str> = opts.notes.lines().filter(|l| !l.trim().is_empty()).collect();
+    let header = MARGIN + (2 + note_lines.len() as u32) * (TEXT_PX as u32 + 4) + GAP;
+    let page_w = MARGIN * 2 + cols * cell + (cols - 1) * GAP;
+    let page_h = header + rows * (cell + CAPTION) + (rows - 1) * GAP + MARGIN;
+
+    // 缩略图放大/缩小倍率：能整数放大就整数放大，保持像素清晰
+    let side = shp.width.max(shp.height).max(1);
+    let k = if side <= cell { (cell / side) as f32 } else { cell as f32 / side as f32 };
+    let (tw, th) = (((shp.width as f32 * k) as u32).max(1), ((shp.height as f32 * k) as u32).max(1));
+
+    let mut out = Vec::with_capacity(pages);
+    for page in 0..pages {
+        let mut img = RgbaImage::from_pixel(page_w, page_h, BG);
+        let mut y = MARGIN;
+        draw_text(&mut img, &font, MARGIN, y, &opts.title, INK);
+        let page_no = format!("{} / {}", page + 1, pages);
+        let pw = text_width(&font, &page_no);
+        draw_text(&mut img, &font, page_w.saturating_sub(MARGIN + pw), y, &page_no, DIM);
+        y += TEXT_PX as u32 + 4;
+        let info = trf!("画布 {}x{}，共 {} 帧", shp.width, shp.height, shp.frames.len());
+        draw_text(&mut img, &font, MARGIN, y, &info, DIM);
+        for line in &note_lines {
+            y += TEXT_PX as u32 + 4;
+            draw_text(&mut img, &font, MARGIN, y, line, INK);
+        }
+
+        for slot in 0..per_page {
+            let fi = page * per_page + slot;
+            if fi >= shp.frames.len() { break; }
+            let (c, r) = (slot as u32 % cols, slot as u32 / cols);
+            let cx = MARGIN + c * (cell + GAP);
+            let cy = header + r * (cell + CAPTION + GAP);
+            // 格子边框与居中的缩略图
+            outline(&mut img, cx.saturating_sub(1), cy.saturating_sub(1), cell + 2, cell + 2, DIM);
+            let ox = cx + (cell - tw.min(cell)) / 2;
+            let oy = cy + (cell - th.min(cell)) / 2;
+            for py in 0..th.min(cell) {
+                for px in 0..tw.min(cell) {
+                    let sx = ((px as f32 / k) as u32).min(shp.width - 1);
+                    let sy = ((py as f32 / k) as u32).min(shp.height - 1);
+                    let idx = shp.frames[fi].pixels[(sy * shp.width + sx) as usize];
+                    let color = if idx == shp.transparent {
+                        // 透明像素用棋盘格表示
+                        if ((px / 6) + (py / 6)) % 2 == 0 { Rgba([205, 205, 205, 255]) } else { Rgba([240, 240, 240, 255]) }
+                    } else {
+                        let c = pal.colors[idx as usize];
+                        Rgba([c.r(), c.g(), c.b(), 255])
+                    };
+                    img.put_pixel(ox + px, oy + py, color);
+                }
+            }
+            let bounds = shp.frame_bounds(fi);
+            if let Some((bx, by, bw, bh)) = bounds {
+                let sc = |v: u32| (v as f32 * k) as u32;
+                outline(&mut img, ox + sc(bx), oy + sc(by), sc(bw).max(1), sc(bh).max(1), BOX);
+            }
+            let ty = cy + cell + 3;
+            draw_text(&mut img, &font, cx, ty, &trf!("帧 {}", fi), INK);
+            let bbox_text = match bounds {
+                Some((bx, by, bw, bh)) => format!("{},{} {}x{}", bx, by, bw, bh),
+                None => tr("空帧").to_string(),
+            };
+            let bw = text_width(&font, &bbox_text);
+            draw_text(&mut img, &font, (cx + cell).saturating_sub(bw), ty, &bbox_text, DIM);
+        }
+        out.push(img);
+    }
+    Ok(out)
+}
+
+fn outline(img: &mut RgbaImage, x: u32, y: u32, w: u32, h: u32, color: Rgba<u8>) {
+    let mut put = |px: u32, py: u32| { if px < img.width() && py < img.height() { img.put_pixel(px, py, color); } };
+    for i in 0..w { put(x + i, y); put(x + i, y + h - 1); }
+    for j in 0..h { put(x, y + j); put(x + w - 1, y + j); }
+}
+
+fn text_width(font: &FontRef, text: &str) -> u32 {
+    let sf = font.as_scaled(PxScale::from(TEXT_PX));
+    text.chars().map(|ch| sf.h_advance(sf.glyph_id(ch))).sum::<f32>().ceil() as u32
+}
+
+// 单行文字（左上角定位），按覆盖率与底色混合
+fn draw_text(img: &mut RgbaImage, font: &FontRef, x: u32, y: u32, text: &str, color: Rgba<u8>) {
+    let sf = font.as_scaled(PxScale::from(TEXT_PX));
+    let mut pen = x as f32;
+    let baseline = y as f32 + sf.ascent();
+    for ch in text.chars() {
+        let id = sf.glyph_id(ch);
+        let glyph = id.with_scale_and_position(PxScale::from(TEXT_PX), ab_glyph::point(pen, baseline));
+        pen += sf.h_advance(id);
+        let Some(outlined) = font.outline_glyph(glyph) else { continue; };
+        let b = outlined.px_bounds();
+        outlined.draw(|gx, gy, cov| {
+            let (px, py) = (b.min.x as i32 + gx as i32, b.min.y as i32 + gy as i32);
+            if px < 0 || py < 0 || px as u32 >= img.width() || py as u32 >= img.height() { return; }
+            let dst = img.get_pixel_mut(px as u32, py as u32);
+            for c in 0..3 { dst.0[c] = (dst.0[c] as f32 * (1.0 - cov) + color.0[c] as f32 * cov).round() as u8; }
+        });
+    }
+}
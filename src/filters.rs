@@ -0,0 +1,363 @@
+use eframe::egui;
+
+use crate::color_match::ColorMatcher;
+use crate::i18n::tr;
+use crate::palette::Palette;
+
+/// 滤镜输入：一帧的索引像素及其上下文
+pub struct FilterInput<'a> {
+    pub pixels: &'a [u8],
+    pub width: u32,
+    pub height: u32,
+    pub palette: &'a Palette,
+    pub transparent: u8,
+    /// 帧序号：随机类滤镜据此让每帧结果不同但可复现
+    pub frame: usize,
+}
+
+impl FilterInput<'_> {
+    fn rgb(&self, i: usize) -> [f32; 3] {
+        let c = self.palette.colors[self.pixels[i] as usize];
+        [c.r() as f32, c.g() as f32, c.b() as f32]
+    }
+
+    fn opaque(&self, i: usize) -> bool {
+        self.pixels[i] != self.transparent
+    }
+
+    fn matcher(&self) -> std::sync::Arc<ColorMatcher> {
+        ColorMatcher::shared(&self.palette.colors, Some(self.transparent))
+    }
+}
+
+/// 帧滤镜：新增滤镜只需实现此 trait 并加入 `registry`
+pub trait Filter {
+    fn name(&self) -> &'static str;
+    /// 参数界面，返回参数是否有改动（用于刷新预览）
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool;
+    /// 处理整帧，返回新的索引像素
+    fn apply(&self, input: &FilterInput) -> Vec<u8>;
+}
+
+/// 全部内置滤镜，按菜单顺序
+pub fn registry() -> Vec<Box<dyn Filter>> {
+    vec![
+        Box::new(Noise::default()),
+        Box::new(Despeckle::default()),
+        Box::new(Blur::default()),
+        Box::new(Emboss::default()),
+        Box::new(AutoShade::default()),
+    ]
+}
+
+/// 运行滤镜；有选区时只改选区内的像素
+pub fn run(filter: &dyn Filter, input: &FilterInput, mask: Option<&[bool]>) -> Vec<u8> {
+    let mut out = filter.apply(input);
+    if let Some(mask) = mask {
+        for ((o, &src), &m) in out.iter_mut().zip(input.pixels).zip(mask) {
+            if !m { *o = src; }
+        }
+    }
+    out
+}
+
+// splitmix64：滤镜用的轻量可复现随机数
+fn next_rand(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn quantize(matcher: &ColorMatcher, rgb: [f32; 3]) -> u8 {
+    let c = rgb.map(|v| v.round().clamp(0.0, 255.0) as u8);
+    matcher.nearest(c[0], c[1], c[2])
+}
+
+/// 色阶内噪点：索引落在范围内的像素按概率在范围内上下偏移
+pub struct Noise {
+    pub range: (u8, u8),
+    pub amount: f32,
+    pub strength: u8,
+    pub seed: u64,
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self { range: (16, 31), amount: 0.3, strength: 1, seed: 1 }
+    }
+}
+
+impl Filter for Noise {
+    fn name(&self) -> &'static str { tr("色阶噪点") }
+
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label(tr("色阶范围"));
+            changed |= ui.add(egui::DragValue::new(&mut self.range.0).clamp_range(0..=255)).changed();
+            ui.label("–");
+            changed |= ui.add(egui::DragValue::new(&mut self.range.1).clamp_range(0..=255)).changed();
+        });
+        changed |= ui.add(egui::Slider::new(&mut self.amount, 0.0..=1.0).text(tr("密度"))).changed();
+        changed |= ui.add(egui::Slider::new(&mut self.strength, 1..=8).text(tr("最大偏移"))).changed();
+        ui.horizontal(|ui| {
+            changed |= ui.add(egui::DragValue::new(&mut self.seed).prefix(tr("种子 "))).changed();
+            if ui.small_button(tr("换一组")).clicked() { self.seed = next_rand(&mut self.seed); changed = true; }
+        });
+        changed
+    }
+
+    fn apply(&self, input: &FilterInput) -> Vec<u8> {
+        let (lo, hi) = (self.range.0.min(self.range.1) as i32, self.range.0.max(self.range.1) as i32);
+        let mut state = self.seed ^ (input.frame as u64).wrapping_mul(0x2545_F491_4F6C_DD1D);
+        let s = self.strength as i32;
+        input.pixels.iter().map(|&p| {
+            let v = p as i32;
+            if p == input.transparent || v < lo || v > hi { return p; }
+            let r = next_rand(&mut state);
+            if (r >> 40) as f32 / (1u64 << 24) as f32 >= self.amount { return p; }
+            // 偏移量取 ±1..=±strength
+            let d = (r % s as u64) as i32 + 1;
+            let d = if r & (1 << 32) == 0 { d } else { -d };
+            (v + d).clamp(lo, hi) as u8
+        }).collect()
+    }
+}
+
+/// 去杂点：8 邻域中与自身相同的像素不超过阈值时，改为邻域中最多的索引
+#[derive(Default)]
+pub struct Despeckle {
+    pub max_same: u8,
+    pub opaque_only: bool,
+}
+
+impl Filter for Despeckle {
+    fn name(&self) -> &'static str { tr("去杂点") }
+
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = ui.add(egui::Slider::new(&mut self.max_same, 0..=3).text(tr("相同邻居不超过")))
+            .on_hover_text(tr("0 只处理完全孤立的像素；数值越大去除得越多")).changed();
+        changed |= ui.checkbox(&mut self.opaque_only, tr("不填补透明小孔")).changed();
+        changed
+    }
+
+    fn apply(&self, input: &FilterInput) -> Vec<u8> {
+        let (w, h) = (input.width as i32, input.height as i32);
+        let mut out = input.pixels.to_vec();
+        for y in 0..h {
+            for x in 0..w {
+                let i = (y * w + x) as usize;
+                let p = input.pixels[i];
+                if self.opaque_only && p == input.transparent { continue; }
+                let mut counts: Vec<(u8, u8)> = Vec::with_capacity(8);
+                let mut same = 0;
+                for (dx, dy) in NEIGHBORS_8 {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx >= w || ny >= h { continue; }
+                    let n = input.pixels[(ny * w + nx) as usize];
+                    if n == p { same += 1; continue; }
+                    match counts.iter_mut().find(|(v, _)| *v == n) {
+                        Some(c) => c.1 += 1,
+                        None => counts.push((n, 1)),
+                    }
+                }
+                if same > self.max_same { continue; }
+                if let Some(&(v, _)) = counts.iter().max_by_key(|(_, c)| *c) { out[i] = v; }
+            }
+        }
+        out
+    }
+}
+
+const NEIGHBORS_8: [(i32, i32); 8] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)];
+
+/// 模糊：在 RGB 中对不透明邻域取平均后量化回调色板；透明像素保持不变，轮廓不外扩
+pub struct Blur {
+    pub radius: u32,
+    pub amount: f32,
+}
+
+impl Default for Blur {
+    fn default() -> Self {
+        Self { radius: 1, amount: 1.0 }
+    }
+}
+
+impl Filter for Blur {
+    fn name(&self) -> &'static str { tr("模糊") }
+
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = ui.add(egui::Slider::new(&mut self.radius, 1..=4).text(tr("半径"))).changed();
+        changed |= ui.add(egui::Slider::new(&mut self.amount, 0.0..=1.0).text(tr("强度"))).changed();
+        changed
+    }
+
+    fn apply(&self, input: &FilterInput) -> Vec<u8> {
+        let (w, h, r) = (input.width as i32, input.height as i32, self.radius as i32);
+        let matcher = input.matcher();
+        let mut out = input.pixels.to_vec();
+        for y in 0..h {
+            for x in 0..w {
+                let i = (y * w + x) as usize;
+                if !input.opaque(i) { continue; }
+                let (mut sum, mut n) = ([0.0f32; 3], 0.0f32);
+                for ny in (y - r).max(0)..=(y + r).min(h - 1) {
+                    for nx in (x - r).max(0)..=(x + r).min(w - 1) {
+                        let j = (ny * w + nx) as usize;
+                        if !input.opaque(j) { continue; }
+                        let c = input.rgb(j);
+                        for k in 0..3 { sum[k] += c[k]; }
+                        n += 1.0;
+                    }
+                }
+                let src = input.rgb(i);
+                let mixed: [f32; 3] = std::array::from_fn(|k| src[k] + (sum[k] / n - src[k]) * self.amount);
+                out[i] = quantize(&matcher, mixed);
+            }
+        }
+        out
+    }
+}
+
+/// 浮雕：按光照方向比较两侧亮度，提亮迎光面、压暗背光面后量化回调色板
+pub struct Emboss {
+    pub angle: f32,
+    pub strength: f32,
+}
+
+impl Default for Emboss {
+    fn default() -> Self {
+        Self { angle: 135.0, strength: 1.0 }
+    }
+}
+
+impl Filter for Emboss {
+    fn name(&self) -> &'static str { tr("浮雕") }
+
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = ui.add(egui::Slider::new(&mut self.angle, 0.0..=315.0).step_by(45.0).text(tr("光照方向")).suffix("°")).changed();
+        changed |= ui.add(egui::Slider::new(&mut self.strength, 0.0..=3.0).text(tr("强度"))).changed();
+        changed
+    }
+
+    fn apply(&self, input: &FilterInput) -> Vec<u8> {
+        let (w, h) = (input.width as i32, input.height as i32);
+        let (s, c) = self.angle.to_radians().sin_cos();
+        // 屏幕坐标 y 向下：角度按常规方位（0° 为右，90° 为上）
+        let (dx, dy) = (c.round() as i32, -s.round() as i32);
+        let matcher = input.matcher();
+        // 亮度当作高度，透明与画布外视为 0：轮廓迎光一侧提亮、背光一侧压暗
+        let luma = |x: i32, y: i32| -> f32 {
+            if x < 0 || y < 0 || x >= w || y >= h { return 0.0; }
+            let i = (y * w + x) as usize;
+            if !input.opaque(i) { return 0.0; }
+            let c = input.rgb(i);
+            0.299 * c[0] + 0.587 * c[1] + 0.114 * c[2]
+        };
+        let mut out = input.pixels.to_vec();
+        for y in 0..h {
+            for x in 0..w {
+                let i = (y * w + x) as usize;
+                if !input.opaque(i) { continue; }
+                let d = luma(x - dx, y - dy) - luma(x + dx, y + dy);
+                let k = 1.0 + self.strength * d / 255.0;
+                out[i] = quantize(&matcher, input.rgb(i).map(|v| v * k));
+            }
+        }
+        out
+    }
+}
+
+/// 自动明暗：按光照方向，在轮廓边缘附近把像素沿所在色阶提亮（迎光面）或压暗（背光面）。
+/// 只在同一色阶内移动索引，轮廓与不属于任何色阶的像素保持不变
+pub struct AutoShade {
+    pub angle: f32,
+    /// 影响范围：距轮廓多少像素以内
+    pub width: u32,
+    /// 紧贴轮廓处最多移动的色阶格数，向内逐渐减弱
+    pub steps: u8,
+    /// 色阶的索引范围（含两端）；明暗方向按调色板亮度自动判断
+    pub ramps: Vec<(u8, u8)>,
+}
+
+impl Default for AutoShade {
+    fn default() -> Self {
+        Self { angle: 135.0, width: 2, steps: 1, ramps: sixteen_ramps() }
+    }
+}
+
+// 按 16 格分段：16–31、32–47 … 240–255（0–15 多为透明与特殊色，不计入）
+fn sixteen_ramps() -> Vec<(u8, u8)> {
+    (1..16u8).map(|k| (k * 16, k * 16 + 15)).collect()
+}
+
+impl Filter for AutoShade {
+    fn name(&self) -> &'static str { tr("自动明暗") }
+
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = ui.add(egui::Slider::new(&mut self.angle, 0.0..=315.0).step_by(45.0).text(tr("光照方向")).suffix("°")).changed();
+        changed |= ui.add(egui::Slider::new(&mut self.width, 1..=6).text(tr("边缘宽度"))).changed();
+        changed |= ui.add(egui::Slider::new(&mut self.steps, 1..=4).text(tr("最多移动格数"))).changed();
+        ui.horizontal(|ui| {
+            ui.label(tr("色阶"));
+            if ui.small_button(tr("添加")).clicked() { self.ramps.push((16, 31)); changed = true; }
+            if ui.small_button(tr("按 16 格分段")).clicked() { self.ramps = sixteen_ramps(); changed = true; }
+            if ui.small_button(tr("清空")).clicked() { self.ramps.clear(); changed = true; }
+        });
+        let mut remove = None;
+        egui::ScrollArea::vertical().max_height(120.0).show(ui, |ui| {
+            for (i, r) in self.ramps.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    changed |= ui.add(egui::DragValue::new(&mut r.0).clamp_range(0..=255)).changed();
+                    ui.label("–");
+                    changed |= ui.add(egui::DragValue::new(&mut r.1).clamp_range(0..=255)).changed();
+                    if ui.small_button("×").clicked() { remove = Some(i); }
+                });
+            }
+        });
+        if let Some(i) = remove { self.ramps.remove(i); changed = true; }
+        changed
+    }
+
+    fn apply(&self, input: &FilterInput) -> Vec<u8> {
+        let (w, h) = (input.width as i32, input.height as i32);
+        let (s, c) = self.angle.to_radians().sin_cos();
+        // 指向光源的单位步长（屏幕 y 向下，0° 为右，90° 为上）
+        let (dx, dy) = (c.round() as i32, -s.round() as i32);
+        let luma = |i: u8| {
+            let c = input.palette.colors[i as usize];
+            0.299 * c.r() as f32 + 0.587 * c.g() as f32 + 0.114 * c.b() as f32
+        };
+        // 每个色阶：(低端, 高端, 索引增大是否变亮)
+        let ramps: Vec<(i32, i32, bool)> = self.ramps.iter().map(|&(a, b)| {
+            let (lo, hi) = (a.min(b), a.max(b));
+            (lo as i32, hi as i32, luma(hi) >= luma(lo))
+        }).filter(|(lo, hi, _)| hi > lo).collect();
+        let outside = |x: i32, y: i32| x < 0 || y < 0 || x >= w || y >= h || !input.opaque((y * w + x) as usize);
+        // 沿 (sx, sy) 方向最近的轮廓外像素距离，超出影响范围为 None
+        let edge = |x: i32, y: i32, sx: i32, sy: i32| (1..=self.width as i32).find(|&k| outside(x + sx * k, y + sy * k));
+        // 距轮廓越近移动越多：紧贴轮廓为 steps，到影响范围边缘减到 1
+        let amount = |k: i32| {
+            let t = (self.width as i32 - k + 1) as f32 / self.width as f32;
+            (self.steps as f32 * t).ceil() as i32
+        };
+        let mut out = input.pixels.to_vec();
+        for y in 0..h {
+            for x in 0..w {
+                let i = (y * w + x) as usize;
+                let p = input.pixels[i];
+                if !input.opaque(i) { continue; }
+                let Some(&(lo, hi, up)) = ramps.iter().find(|(lo, hi, _)| (*lo..=*hi).contains(&(p as i32))) else { continue; };
+                let lit = edge(x, y, dx, dy).map_or(0, amount);
+                let shade = edge(x, y, -dx, -dy).map_or(0, amount);
+                let d = lit - shade;
+                if d == 0 { continue; }
+                let d = if up { d } else { -d };
+                out[i] = (p as i32 + d).clamp(lo, hi) as u8;
+            }
+        }
+        out
+    }
+}
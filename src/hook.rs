@@ -0,0 +1,33 @@
+use std::path::Path;
+
+/// 导出钩子：保存/导出完成后执行的外部命令模板
+/// 占位符（原样替换，含空格的路径请自行加引号）：
+/// - `{file}` 写出的文件完整路径
+/// - `{dir}`  所在目录
+/// - `{name}` 不含扩展名的文件名
+/// - `{ext}`  扩展名（不含点）
+pub fn expand(template: &str, path: &Path) -> String {
+    let s = |o: Option<&std::ffi::OsStr>| o.map(|v| v.to_string_lossy().into_owned()).unwrap_or_default();
+    template
+        .replace("{file}", &path.display().to_string())
+        .replace("{dir}", &path.parent().map(|d| d.display().to_string()).unwrap_or_default())
+        .replace("{name}", &s(path.file_stem()))
+        .replace("{ext}", &s(path.extension()))
+}
+
+/// 通过系统 shell 异步启动命令（界面不等待结束，避免阻塞）
+pub fn spawn(cmdline: &str) -> Result<(), String> {
+    let mut cmd = if cfg!(target_os = "windows") {
+        let mut c = std::process::Command::new("cmd");
+        c.arg("/C").arg(cmdline);
+        c
+    } else {
+        let mut c = std::process::Command::new("sh");
+        c.arg("-c").arg(cmdline);
+        c
+    };
+    let mut child = cmd.spawn().map_err(|e| e.to_string())?;
+    // 在后台线程等待结束以回收子进程，否则 Unix 下每个执行过的钩子都会留下僵尸进程
+    std::thread::spawn(move || { let _ = child.wait(); });
+    Ok(())
+}
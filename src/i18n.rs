@@ -0,0 +1,1015 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+/// 界面语言
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    ZhCn,
+    EnUs,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::ZhCn, Language::EnUs];
+
+    /// 语言名称总是用其本身的语言显示，便于在任何界面语言下找到
+    pub fn native_name(self) -> &'static str {
+        match self {
+            Language::ZhCn => "简体中文",
+            Language::EnUs => "English",
+        }
+    }
+
+    // 千位分隔符：目前两种界面语言都习惯每三位用逗号分组，新增语言时在此区分
+    fn group_separator(self) -> char {
+        match self {
+            Language::ZhCn | Language::EnUs => ',',
+        }
+    }
+
+    /// 按本语言习惯书写日期：2026年10月16日 / Oct 16, 2026
+    pub fn format_date(self, year: u32, month: u32, day: u32) -> String {
+        const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+        match self {
+            Language::ZhCn => format!("{}年{}月{}日", year, month, day),
+            Language::EnUs => format!("{} {}, {}", MONTHS[(month.clamp(1, 12) - 1) as usize], day, year),
+        }
+    }
+}
+
+static CURRENT: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_language(lang: Language) {
+    CURRENT.store(lang as u8, Ordering::Relaxed);
+}
+
+pub fn language() -> Language {
+    match CURRENT.load(Ordering::Relaxed) {
+        1 => Language::EnUs,
+        _ => Language::ZhCn,
+    }
+}
+
+/// 翻译：以中文原文为键查字符串表；当前为中文或缺少译文时原样返回
+pub fn tr(zh: &'static str) -> &'static str {
+    if language() == Language::ZhCn { return zh; }
+    static TABLE: OnceLock<HashMap<&'static str, &'static str>> = OnceLock::new();
+    TABLE.get_or_init(|| STRINGS.iter().copied().collect()).get(zh).copied().unwrap_or(zh)
+}
+
+/// 依次用参数替换模板中的 `{}`（供 `trf!` 使用）
+pub fn fill(template: &str, args: &[&dyn std::fmt::Display]) -> String {
+    let mut out = String::with_capacity(template.len() + 16);
+    let mut rest = template;
+    let mut it = args.iter();
+    while let Some(pos) = rest.find("{}") {
+        out.push_str(&rest[..pos]);
+        if let Some(a) = it.next() { out.push_str(&a.to_string()); }
+        rest = &rest[pos + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 数字千位分组（1234567 → "1,234,567"），分隔符随界面语言
+pub fn format_number(n: usize) -> String {
+    let sep = language().group_separator();
+    let s = n.to_string();
+    let mut out = String::with_capacity(s.len() + s.len() / 3);
+    for (i, ch) in s.chars().enumerate() {
+        if i > 0 && (s.len() - i).is_multiple_of(3) { out.push(sep); }
+        out.push(ch);
+    }
+    out
+}
+
+/// ISO 8601 日期（"2026-10-16" 或 "2026-10-16T08:00:00Z"）按界面语言格式化；无法解析时返回 None
+pub fn format_date(iso: &str) -> Option<String> {
+    let mut parts = iso.get(..10)?.split('-').map(|p| p.parse::<u32>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    ((1..=12).contains(&month) && (1..=31).contains(&day)).then(|| language().format_date(year, month, day))
+}
+
+/// 带参数的翻译：`trf!("已加载 SHP: {}", path.display())`，模板中只支持 `{}`
+macro_rules! trf {
+    ($zh:literal $(, $arg:expr)* $(,)?) => {
+        $crate::i18n::fill($crate::i18n::tr($zh), &[$(&$arg as &dyn std::fmt::Display),*])
+    };
+}
+
+/// 字符串表：(中文原文, English)
+static STRINGS: &[(&str, &str)] = &[
+    ("SHP 编辑器", "SHP Editor"),
+    ("SHP 查看器", "SHP Viewer"),
+    ("打开一个 SHP 进行查看", "Open an SHP to view"),
+    ("帧", "Frame"),
+    // 菜单
+    ("文件", "File"),
+    ("新建 SHP...", "New SHP..."),
+    ("打开 SHP...", "Open SHP..."),
+    ("保存 SHP...", "Save SHP..."),
+    ("选择内置PAL", "Built-in PAL"),
+    ("打开 PAL...", "Open PAL..."),
+    ("保存 PAL...", "Save PAL..."),
+    ("导入图片为帧 (PNG/JPG/GIF/APNG)...", "Import image as frame (PNG/JPG/GIF/APNG)..."),
+    ("导出当前帧为 PNG...", "Export current frame as PNG..."),
+    ("导出帧差异 PNG 序列...", "Export frame deltas as PNG sequence..."),
+    ("编辑", "Edit"),
+    ("替换颜色...", "Replace color..."),
+    ("设置", "Settings"),
+    ("快捷键...", "Shortcuts..."),
+    ("语言", "Language"),
+    ("使用统计...", "Usage statistics..."),
+    ("导出后执行命令...", "Post-export command..."),
+    // 调色板索引角色
+    ("普通", "Normal"),
+    ("透明", "Transparent"),
+    ("阴影", "Shadow"),
+    ("阵营色 (Remap)", "House color (remap)"),
+    ("循环动画色", "Color cycling"),
+    ("索引 {} · {} · RGB({}, {}, {})", "Index {} · {} · RGB({}, {}, {})"),
+    ("标记特殊索引", "Badge special indices"),
+    // 导入变换
+    ("旋转°", "Rotate°"),
+    ("最近邻 (Nearest)", "Nearest"),
+    ("双线性 (Triangle)", "Bilinear (Triangle)"),
+    ("双三次 (CatmullRom)", "Bicubic (CatmullRom)"),
+    // 透视辅助线
+    ("透视辅助线", "Perspective guides"),
+    ("显示辅助线", "Show guides"),
+    ("等角 2:1", "Isometric 2:1"),
+    ("两点透视", "Two-point perspective"),
+    ("间距", "Spacing"),
+    ("消失点1", "Vanishing point 1"),
+    ("消失点2", "Vanishing point 2"),
+    ("射线数", "Rays"),
+    // 联系表
+    ("导出联系表 PNG...", "Export contact sheet PNG..."),
+    ("导出联系表", "Export contact sheet"),
+    ("每行列数", "Columns"),
+    ("每页行数", "Rows per page"),
+    ("格子尺寸", "Cell size"),
+    ("标题", "Title"),
+    ("备注（印在每页页眉）", "Notes (printed in each page header)"),
+    ("共 {} 页", "{} page(s)"),
+    ("导出...", "Export..."),
+    ("已导出联系表 {} 页: {}", "Exported contact sheet, {} page(s): {}"),
+    ("画布 {}x{}，共 {} 帧", "Canvas {}x{}, {} frames"),
+    ("帧 {}", "Frame {}"),
+    ("空帧", "Empty frame"),
+    // 转换到调色板
+    ("转换到调色板...", "Convert to palette..."),
+    ("转换到调色板", "Convert to palette"),
+    ("当前: {}", "Current: {}"),
+    ("（未选择）", "(none selected)"),
+    ("仅显示使用中的索引", "Show used indices only"),
+    ("原索引", "From"),
+    ("新索引", "To"),
+    ("像素数", "Pixels"),
+    ("将改写所有帧的索引并切换到目标调色板", "Rewrites indices in every frame and switches to the target palette"),
+    ("转换", "Convert"),
+    ("已转换到调色板 {}：{} 个使用中的索引被改写", "Converted to palette {}: {} used indices remapped"),
+    // 检查更新
+    ("检查更新...", "Check for updates..."),
+    ("启动时检查更新", "Check for updates on startup"),
+    ("检查更新", "Check for updates"),
+    ("当前版本: {}", "Current version: {}"),
+    ("正在查询 GitHub 发布...", "Querying GitHub releases..."),
+    ("检查失败: {}", "Check failed: {}"),
+    ("打开发布页面", "Open releases page"),
+    ("发现新版本: {}", "New version available: {}"),
+    ("已是最新版本（最新发布: {}）", "You are up to date (latest release: {})"),
+    ("更新日志", "Changelog"),
+    ("前往下载", "Go to download"),
+    // 导入帧序列
+    ("导入动画帧序列 (GIF/APNG)...", "Import animation frames (GIF/APNG)..."),
+    ("导入帧序列", "Import frame sequence"),
+    // 透明索引
+    ("透明索引", "Transparent index"),
+    ("Alpha 阈值", "Alpha threshold"),
+    ("半透明像素与帧内容混合", "Blend semi-transparent pixels with frame content"),
+    // 阴影帧
+    ("生成阴影帧...", "Generate shadow frames..."),
+    ("生成阴影帧", "Generate shadow frames"),
+    ("阴影索引", "Shadow index"),
+    ("错切", "Shear"),
+    ("垂直压缩", "Vertical squash"),
+    ("偏移 X", "Offset X"),
+    ("偏移 Y", "Offset Y"),
+    ("追加：为全部 {} 帧生成阴影，共 {} 帧", "Append: generate shadows for all {} frames, {} frames total"),
+    ("覆盖：由前 {} 帧生成后 {} 帧", "Overwrite: build the last {} frames from the first {}"),
+    ("生成", "Generate"),
+    ("已生成 {} 个阴影帧", "Generated {} shadow frames"),
+    // 图像菜单
+    ("图像", "Image"),
+    ("画布大小...", "Canvas size..."),
+    ("裁剪到内容", "Crop to content"),
+    ("画布大小", "Canvas size"),
+    ("当前: {} x {}", "Current: {} x {}"),
+    ("锚点", "Anchor"),
+    ("新增区域填充透明索引", "New area is filled with the transparent index"),
+    ("应用", "Apply"),
+    ("画布大小: {} x {} → {} x {}", "Canvas size: {} x {} → {} x {}"),
+    ("所有帧都是空的，无法裁剪", "All frames are empty, nothing to crop"),
+    ("内容已占满画布，无需裁剪", "Content already fills the canvas"),
+    ("已裁剪到内容: {} x {}（偏移 {}, {}）", "Cropped to content: {} x {} (offset {}, {})"),
+    ("缩放...", "Scale..."),
+    ("缩放图像", "Scale image"),
+    ("算法", "Method"),
+    ("新尺寸: {} x {}", "New size: {} x {}"),
+    ("保持比例", "Keep aspect ratio"),
+    ("已缩放: {} x {} → {} x {}（{}）", "Scaled: {} x {} → {} x {} ({})"),
+    // MIX 归档
+    ("打开 MIX...", "Open MIX..."),
+    ("MIX 归档窗口", "MIX archive window"),
+    ("已打开 MIX: {}（{} 个条目）", "Opened MIX: {} ({} entries)"),
+    ("打开 MIX 失败: {}", "Failed to open MIX: {}"),
+    ("MIX 归档", "MIX archive"),
+    ("大小: {}，条目数: {}", "Size: {}, entries: {}"),
+    ("筛选", "Filter"),
+    ("作为 SHP 打开", "Open as SHP"),
+    ("用当前 SHP 替换", "Replace with current SHP"),
+    ("用文件替换...", "Replace with file..."),
+    ("导出条目...", "Extract entry..."),
+    ("删除条目", "Delete entry"),
+    ("新条目 ID", "New entry ID"),
+    ("添加当前 SHP", "Add current SHP"),
+    ("添加文件...", "Add file..."),
+    ("保存 MIX", "Save MIX"),
+    ("另存为...", "Save as..."),
+    ("当前 SHP 来自条目 {}", "Current SHP comes from entry {}"),
+    ("已从 MIX 打开条目 {}", "Opened MIX entry {}"),
+    ("条目 {} 不是有效的 SHP: {}", "Entry {} is not a valid SHP: {}"),
+    ("已写入条目 {}（MIX 尚未保存）", "Wrote entry {} (MIX not saved yet)"),
+    ("已导出条目到: {}", "Extracted entry to: {}"),
+    ("已删除条目 {}（MIX 尚未保存）", "Deleted entry {} (MIX not saved yet)"),
+    ("已保存 MIX: {}", "Saved MIX: {}"),
+    ("保存 MIX 失败: {}", "Failed to save MIX: {}"),
+    ("MIX 文件过短", "MIX file is too short"),
+    ("暂不支持加密的 MIX", "Encrypted MIX files are not supported yet"),
+    ("MIX 索引与文件大小不符", "MIX index does not match file size"),
+    ("条目 {} 超出数据区", "Entry {} lies outside the data area"),
+    ("MIX 条目数超过 65535", "MIX has more than 65535 entries"),
+    ("MIX 数据超过 4GB", "MIX data exceeds 4GB"),
+    ("名称列表", "Name list"),
+    ("已加载 {} 个文件名（共 {} 个 ID）", "Loaded {} file names ({} IDs in total)"),
+    ("ID 十六进制片段或文件名", "Hex ID fragment or file name"),
+    ("ID 算法", "ID scheme"),
+    ("加载名称列表...", "Load name list..."),
+    ("已知 {} 个", "{} known"),
+    ("提取调色板到菜单", "Add palettes to menu"),
+    ("把 MIX 中的 .pal 条目加入“选择内置PAL”菜单", "Add the .pal entries of this MIX to the built-in PAL menu"),
+    ("该 MIX 中没有找到调色板", "No palettes found in this MIX"),
+    ("已从 MIX 添加 {} 个调色板到「{}」", "Added {} palettes from MIX to \"{}\""),
+    // 选区
+    ("⬚ 矩形选区", "⬚ Rect select"),
+    ("➰ 套索", "➰ Lasso"),
+    ("✨ 魔棒", "✨ Magic wand"),
+    ("Shift 加选，Alt 减选；在选区内拖动可移动像素", "Shift adds, Alt subtracts; drag inside the selection to move pixels"),
+    ("选区: {} 像素", "Selection: {} px"),
+    ("取消选择", "Deselect"),
+    ("全选", "Select all"),
+    ("反选", "Invert selection"),
+    ("用当前颜色填充选区", "Fill selection with current color"),
+    ("删除选区内容", "Delete selection content"),
+    ("填充选区", "Fill selection"),
+    ("移动选区", "Move selection"),
+    ("仅限选区", "Selection only"),
+    // 调整调色板
+    ("调整调色板...", "Adjust palette..."),
+    ("调整调色板", "Adjust palette"),
+    ("亮度", "Brightness"),
+    ("对比度", "Contrast"),
+    ("饱和度", "Saturation"),
+    ("色相", "Hue"),
+    ("索引范围", "Index range"),
+    ("全部", "All"),
+    ("阵营色", "Remap"),
+    ("当前颜色所在 16 色", "Current color's row of 16"),
+    ("重置", "Reset"),
+    ("已调整调色板", "Palette adjusted"),
+    // 帧偏移
+    ("帧偏移", "Frame offset"),
+    ("应用到选中的 {} 帧", "Apply to {} selected frames"),
+    ("按偏移显示", "Show at offsets"),
+    ("画布按帧偏移平移显示，便于检查开火等动画的对齐", "Shift the canvas by the frame offset to check alignment of firing and similar anims"),
+    ("保存时帧头: x={} y={} w={} h={}", "Saved frame header: x={} y={} w={} h={}"),
+    // 参考背景
+    ("参考背景", "Reference background"),
+    ("加载背景图...", "Load background image..."),
+    ("显示背景图", "Show background image"),
+    ("位置", "Position"),
+    ("居中到画布", "Center on canvas"),
+    ("移除背景图", "Remove background image"),
+    ("在画布上按住中键拖动可移动背景", "Drag with the middle mouse button on the canvas to move the background"),
+    ("已加载背景图: {}", "Loaded background image: {}"),
+    ("读取图片失败: {}", "Failed to read image: {}"),
+    // 播放范围与循环方式
+    ("循环", "Loop"),
+    ("往返", "Ping-pong"),
+    ("播放一次", "Play once"),
+    ("倒放", "Reverse"),
+    ("限定范围", "Limit range"),
+    ("从 ", "From "),
+    ("到 ", "To "),
+    ("用选中帧", "Use selected frames"),
+    ("把播放范围设为选中帧的首尾", "Set the playback range to the first and last selected frames"),
+    // 剪贴板
+    ("复制帧为图像", "Copy frame as image"),
+    ("从剪贴板粘贴图像", "Paste image from clipboard"),
+    ("复制帧为图像 ({})", "Copy frame as image ({})"),
+    ("从剪贴板粘贴图像 ({})", "Paste image from clipboard ({})"),
+    ("剪贴板中没有图像", "The clipboard contains no image"),
+    ("剪贴板图像数据无效", "Invalid clipboard image data"),
+    ("已复制第 {} 帧到剪贴板", "Copied frame {} to the clipboard"),
+    ("复制到剪贴板失败: {}", "Failed to copy to the clipboard: {}"),
+    ("已从剪贴板粘贴 {}x{} 图像，请在画布上拖动/缩放/固定。", "Pasted a {}x{} image from the clipboard; drag/scale/commit it on the canvas."),
+    ("从剪贴板粘贴失败: {}", "Failed to paste from the clipboard: {}"),
+    // 撤销历史
+    ("没有可撤销的操作", "Nothing to undo"),
+    ("没有可重做的操作", "Nothing to redo"),
+    ("导入图片", "Import image"),
+    // SHP 文件头范围
+    ("画布尺寸 {}x{} 超出 SHP 范围（1~{}）", "Canvas size {}x{} is outside the SHP range (1-{})"),
+    ("帧数 {} 超出 SHP 上限 {}", "Frame count {} exceeds the SHP limit of {}"),
+    ("帧数据超过 4GB，无法保存为 SHP", "Frame data exceeds 4 GB and cannot be saved as SHP"),
+    // 命令行打开
+    ("无法打开 {}：不支持的文件类型", "Cannot open {}: unsupported file type"),
+    // 滚轮调整画笔
+    ("上一个颜色索引", "Previous color index"),
+    ("下一个颜色索引", "Next color index"),
+    ("悬停画布时 Ctrl+滚轮调整大小，Alt+滚轮或 [ ] 切换颜色索引", "While hovering the canvas: Ctrl+wheel changes the size, Alt+wheel or [ ] steps the color index"),
+    // APNG 导入
+    ("APNG没有帧", "APNG has no frames"),
+    ("{}；预览间隔 {} ms", "{}; preview interval {} ms"),
+    // 按需解码
+    ("已加载 SHP（{} 帧，按需解码）: {}", "Loaded SHP ({} frames, decoded on demand): {}"),
+    ("解码第 {} 帧失败: {}", "Failed to decode frame {}: {}"),
+    // TD/RA1 SHP
+    ("TD/RA1 SHP 数据损坏", "TD/RA1 SHP data is corrupt"),
+    ("TD/RA1 SHP 第 {} 帧的参考帧无效", "TD/RA1 SHP frame {} has an invalid reference frame"),
+    ("TD/RA1 SHP 第 {} 帧格式未知: {}", "TD/RA1 SHP frame {} has unknown format: {}"),
+    // 补间
+    ("交叉淡化", "Cross-fade"),
+    ("位置移动", "Motion"),
+    ("补间...", "Tween..."),
+    ("补间", "Tween"),
+    ("先在时间轴上选中两帧", "Select two frames in the timeline first"),
+    ("在第 {} 帧与第 {} 帧之间插入", "Insert between frame {} and frame {}"),
+    (" 帧", " frames"),
+    ("按 RGB 混合两帧颜色并重新匹配调色板，适合淡入淡出", "Blends the two frames in RGB and re-matches the palette; good for fades"),
+    ("把前一帧的内容按包围盒位置移向后一帧；有选区时只移动选区内内容，选区需覆盖运动路径", "Moves the first frame's content toward the second by bounding box; with a selection only its content moves, so the selection must cover the whole path"),
+    ("已在第 {} 帧与第 {} 帧之间生成 {} 帧", "Generated between frame {} and frame {}: {} frames"),
+    // 描边
+    ("描边...", "Outline..."),
+    ("描边", "Outline"),
+    ("描边索引 = {}", "Outline index = {}"),
+    ("外侧", "Outside"),
+    ("内侧", "Inside"),
+    ("含对角", "Include diagonals"),
+    ("对角相邻的像素也算贴边，外描边的转角更饱满", "Diagonal neighbours count as edges, giving fuller corners on outside outlines"),
+    ("已描边 {} 帧，{} 个像素", "Outlined {} frames, {} pixels"),
+    // 生成图标
+    ("生成图标 (cameo)...", "Generate cameo..."),
+    ("生成图标 (cameo)", "Generate cameo"),
+    ("源帧: {}", "Source frame: {}"),
+    ("图标调色板", "Cameo palette"),
+    ("重采样", "Resampling"),
+    ("底色", "Background"),
+    ("叠加边框与底部文字区", "Overlay border and bottom text area"),
+    ("另存为 SHP...", "Save as SHP..."),
+    ("已保存图标: {}", "Cameo saved: {}"),
+    ("生成图标", "Generate cameo"),
+    // 颜色用量统计
+    ("颜色用量统计", "Color usage"),
+    ("整个文件", "Whole file"),
+    ("使用 {} 种颜色（不含透明索引）", "{} colors used (excluding the transparent index)"),
+    ("高亮索引 {}", "Highlighting index {}"),
+    ("下一个含此索引的帧", "Next frame using this index"),
+    ("取消高亮", "Clear highlight"),
+    ("索引", "Index"),
+    ("类型", "Type"),
+    ("在画布上高亮此索引的像素", "Highlight pixels of this index on the canvas"),
+    // 小地图
+    ("小地图", "Minimap"),
+    // 选区笔刷
+    ("选区转为笔刷", "Selection to brush"),
+    ("保留原有索引，铅笔按原颜色盖章", "Keeps the original indices; the pencil stamps them as-is"),
+    ("选区内没有非透明像素", "The selection contains no opaque pixels"),
+    ("已从选区创建笔刷 ({}x{})", "Brush created from selection ({}x{})"),
+    // 文字工具
+    ("🔤 文字", "🔤 Text"),
+    ("文字", "Text"),
+    ("点阵 5×7", "Bitmap 5×7"),
+    ("TTF 字体", "TTF font"),
+    ("倍数", "Scale"),
+    ("字号", "Size"),
+    ("覆盖率阈值", "Coverage threshold"),
+    ("在画布上单击放置文字，拖动文字框可移动；点阵字体仅支持 ASCII", "Click the canvas to place the text and drag its box to move it; the bitmap font covers ASCII only"),
+    // PCX 导出
+    ("导出全部帧为 PCX 序列...", "Export all frames as PCX set..."),
+    ("每帧一个 8 位 PCX，内嵌当前调色板，按 XCC 习惯命名为“名称 0000.pcx”；限定了播放范围（序列）时只导出该范围", "One 8-bit PCX per frame with the current palette embedded, named \"name 0000.pcx\" as XCC expects; only the playback range (sequence) is exported when one is set"),
+    ("已导出 {} 个 PCX 到 {}", "Exported {} PCX files to {}"),
+    ("导出 PCX", "Export PCX"),
+    // 画布背景
+    ("画布背景", "Canvas background"),
+    ("游戏内黑色背景预览", "Preview on in-game black"),
+    ("棋盘格", "Checkerboard"),
+    ("纯色", "Solid color"),
+    ("颜色", "Color"),
+    ("浅色", "Light"),
+    ("深色", "Dark"),
+    ("格子大小", "Cell size"),
+    // 像素完美
+    ("像素完美", "Pixel-perfect"),
+    ("去掉斜线笔画拐角处多出的像素（仅 1 像素画笔）", "Removes the extra corner pixels of diagonal strokes (1 px brush only)"),
+    // 文档调色板组
+    ("文档调色板组", "Document palettes"),
+    ("移出调色板组", "Remove from palette set"),
+    ("加入当前调色板", "Add current palette"),
+    ("快捷键 {} 在组内轮换", "Press {} to cycle through the set"),
+    ("文档调色板组为空：在 文件 → 文档调色板组 中加入调色板", "No document palettes: add some under File → Document palettes"),
+    ("调色板: {} ({}/{})", "Palette: {} ({}/{})"),
+    ("轮换文档调色板", "Cycle document palettes"),
+    ("（空）", "(empty)"),
+    // 调色板对比/合并
+    ("对比/合并调色板...", "Compare / merge palettes..."),
+    ("对比/合并调色板", "Compare / merge palettes"),
+    ("对比调色板 B", "Palette B"),
+    ("两个调色板完全相同", "The palettes are identical"),
+    ("{} 个索引不同: {}", "{} indices differ: {}"),
+    ("索引 {}\nA: {}, {}, {}\nB: {}, {}, {}", "Index {}\nA: {}, {}, {}\nB: {}, {}, {}"),
+    ("B → A（当前调色板）", "B → A (current palette)"),
+    ("保存 B 为 PAL...", "Save B as PAL..."),
+    ("合并调色板", "Merge palettes"),
+    ("已把 B 的索引 {}–{} 复制到当前调色板", "Copied indices {}–{} from B to the current palette"),
+    ("已把当前调色板的索引 {}–{} 复制到 B", "Copied indices {}–{} from the current palette to B"),
+    // 脚本
+    ("脚本控制台...", "Script console..."),
+    ("脚本控制台", "Script console"),
+    ("运行脚本文件 (.rhai)...", "Run script file (.rhai)..."),
+    ("运行", "Run"),
+    ("打开 .rhai...", "Open .rhai..."),
+    ("保存 .rhai...", "Save .rhai..."),
+    ("可用函数", "Available functions"),
+    ("输出", "Output"),
+    ("返回替换的像素数；f 为 -1 时作用于全部帧", "returns pixels replaced; f = -1 means all frames"),
+    ("返回新帧序号", "returns the new frame index"),
+    ("示例：把当前帧中索引 16-23 的像素改为 24-31", "Example: move indices 16-23 in the current frame to 24-31"),
+    ("已保存脚本: {}", "Script saved: {}"),
+    ("运行脚本", "Run script"),
+    ("脚本运行完成", "Script finished"),
+    ("脚本运行完成，文档没有变化", "Script finished; the document is unchanged"),
+    ("脚本出错: {}", "Script error: {}"),
+    ("错误: {}", "Error: {}"),
+    ("图片尺寸 {}x{} 与文档 {}x{} 不一致", "Image size {}x{} does not match the document ({}x{})"),
+    ("坐标 ({}, {}) 超出画布", "Coordinate ({}, {}) is outside the canvas"),
+    ("帧序号 {} 超出范围 0..{}", "Frame {} is out of range 0..{}"),
+    ("颜色索引 {} 超出 0..255", "Color index {} is outside 0..255"),
+    // 滤镜
+    ("滤镜", "Filters"),
+    ("仅处理选区内的像素", "Only pixels inside the selection are changed"),
+    ("{}：没有像素变化", "{}: no pixels changed"),
+    ("{}：已处理 {} 帧", "{}: processed {} frames"),
+    ("色阶噪点", "Ramp noise"),
+    ("色阶范围", "Ramp range"),
+    ("密度", "Density"),
+    ("最大偏移", "Max shift"),
+    ("种子 ", "Seed "),
+    ("换一组", "Reroll"),
+    ("去杂点", "Despeckle"),
+    ("相同邻居不超过", "Max matching neighbours"),
+    ("0 只处理完全孤立的像素；数值越大去除得越多", "0 only removes fully isolated pixels; higher values remove more"),
+    ("不填补透明小孔", "Keep transparent pinholes"),
+    ("模糊", "Blur"),
+    ("半径", "Radius"),
+    ("强度", "Strength"),
+    ("浮雕", "Emboss"),
+    ("光照方向", "Light direction"),
+    // 环绕绘制
+    ("环绕绘制", "Wrap around edges"),
+    ("越过画布边缘的部分从对边继续，用于制作无缝平铺的图块", "Strokes, shapes and fills crossing an edge continue on the opposite side, for seamless tiles"),
+    ("平铺预览 (3×3)", "Tiled preview (3×3)"),
+    ("平铺预览", "Tiled preview"),
+    // 动画序列
+    ("（全部帧）", "(all frames)"),
+    ("序列...", "Sequences..."),
+    ("动画序列", "Animation sequences"),
+    ("名称", "Name"),
+    ("起始帧", "Start"),
+    ("结束帧", "End"),
+    ("删除序列", "Delete sequence"),
+    ("添加序列", "Add sequence"),
+    ("范围取选中帧的首尾，没有选中帧时取当前播放范围", "Uses the first/last selected frame, or the current playback range if none are selected"),
+    ("立即保存序列文件", "Save sequence file now"),
+    ("保存 SHP 时一并写入 {}", "Written to {} when the SHP is saved"),
+    ("保存 SHP 后写入同名 .seq.json 文件", "Written to a matching .seq.json file once the SHP is saved"),
+    ("已保存序列文件: {}", "Sequence file saved: {}"),
+    ("保存序列文件失败: {}", "Failed to save sequence file: {}"),
+    ("{}；保存序列文件失败: {}", "{}; failed to save sequence file: {}"),
+    ("{}；读取序列文件失败: {}", "{}; failed to read sequence file: {}"),
+    // SHP 对比
+    ("与另一个 SHP 对比...", "Compare with another SHP..."),
+    ("对比 SHP", "Compare SHP"),
+    ("（未保存）", "(unsaved)"),
+    ("A: {}（{}×{}，{} 帧）", "A: {} ({}×{}, {} frames)"),
+    ("B: {}（{}×{}，{} 帧）", "B: {} ({}×{}, {} frames)"),
+    ("上一个有差异的帧", "Previous frame with differences"),
+    ("下一个有差异的帧", "Next frame with differences"),
+    ("第 {} 帧：{} 个像素不同", "Frame {}: {} pixels differ"),
+    ("标出差异", "Highlight differences"),
+    ("B 没有此帧", "B has no such frame"),
+    // 监视文件
+    ("外部修改打开的文件后自动重新加载", "Reload open files when changed by other programs"),
+    ("无法监视文件: {}", "Cannot watch file: {}"),
+    ("PAL 已被外部修改，已重新加载: {}", "PAL changed on disk, reloaded: {}"),
+    ("SHP 已被外部修改，已重新加载: {}", "SHP changed on disk, reloaded: {}"),
+    ("文件已被外部修改", "File changed on disk"),
+    ("{} 在磁盘上被其他程序修改，而编辑器中有未保存的修改。", "{} was modified by another program, but the editor has unsaved changes."),
+    ("重新加载（放弃编辑器中的修改）", "Reload (discard editor changes)"),
+    ("保留编辑器中的版本", "Keep the editor version"),
+    // 导入预缩小
+    ("预缩小倍数", "Pre-downscale"),
+    ("按整数倍面积平均缩小后再量化，适合 2×/4× 渲染的素材", "Area-average down by an integer factor before quantizing; suited to 2×/4× renders"),
+    ("锐化", "Sharpen"),
+    // 导入抠背景
+    ("抠除背景色", "Key out background"),
+    ("品红", "Magenta"),
+    ("绿", "Green"),
+    ("取左上角", "Top-left"),
+    ("使用图片左上角像素的颜色", "Use the color of the image's top-left pixel"),
+    ("容差", "Tolerance"),
+    // 保存编码报告
+    ("未压缩", "Uncompressed"),
+    ("全部设为", "Set all to"),
+    ("较小者", "Smaller"),
+    ("每帧选体积更小的编码", "Pick the smaller encoding for each frame"),
+    ("预计文件大小: {} 字节", "Projected file size: {} bytes"),
+    ("全部未压缩 {} / 全部 RLE0 {} 字节", "All uncompressed {} / all RLE0 {} bytes"),
+    ("未压缩块解码最快；RLE0 对大片透明的帧体积更小", "Uncompressed blocks decode fastest; RLE0 is smaller for frames with large transparent areas"),
+    ("编码", "Encoding"),
+    ("以后保存时不再显示", "Don't show again when saving"),
+    ("保存 SHP 前显示编码与体积", "Show encoding and size report before saving SHP"),
+    ("RLE0 行数据超过 65535 字节，请改用未压缩", "RLE0 row data exceeds 65535 bytes; use uncompressed instead"),
+    // SHP 读取校验
+    ("第 {} 帧: {}", "Frame {}: {}"),
+    ("数据偏移 {} 超出文件长度 {}", "data offset {} is past the end of the file ({} bytes)"),
+    ("第 {} 行: 数据在行长字段处截断", "row {}: data ends inside the row length field"),
+    ("第 {} 行: 行长 {} 无效（至少为 2）", "row {}: invalid row length {} (must be at least 2)"),
+    ("第 {} 行: 声明 {} 字节，但剩余数据只有 {} 字节", "row {}: declares {} bytes but only {} bytes remain"),
+    ("第 {} 行: 行尾缺少透明像素个数", "row {}: missing transparent run length at end of row"),
+    ("未压缩数据需要 {} 字节，但剩余数据只有 {} 字节", "uncompressed data needs {} bytes but only {} bytes remain"),
+    ("单帧 {}x{} 解码后超过 {} MB 上限", "A single {}x{} frame exceeds the {} MB decode limit"),
+    ("全部 {} 帧解码后需要 {} MB，超过 {} MB 上限", "Decoding all {} frames needs {} MB, over the {} MB limit"),
+    ("帧头表不完整：{} 帧需要 {} 字节，文件只有 {} 字节", "Incomplete frame header table: {} frames need {} bytes, file has only {} bytes"),
+    // 压感
+    ("压感控制大小", "Pressure controls size"),
+    ("数位板笔压按比例缩放画笔，满压为上面设定的大小；需要驱动以触控力度上报笔压", "Scales the brush by pen pressure, full pressure being the size set above; requires the tablet driver to report pressure as touch force"),
+    // 导入帧对应方式
+    ("全部追加为新帧", "Append all as new frames"),
+    ("从起始帧覆盖，超出部分追加", "Overwrite from start frame, append extras"),
+    ("从起始帧覆盖，超出部分丢弃", "Overwrite from start frame, skip extras"),
+    ("循环填满起始帧之后的所有帧", "Loop to fill every frame from the start frame on"),
+    ("{}：{} 帧；当前 SHP：{} 帧", "{}: {} frames; current SHP: {} frames"),
+    ("覆盖 {} 帧，新增 {} 帧，未使用的源帧 {} 个", "Overwrites {} frames, adds {} frames, {} source frames unused"),
+    ("导入", "Import"),
+    // 副颜色
+    ("交换主/副颜色", "Swap primary/secondary color"),
+    ("左键选主颜色，右键选副颜色（右键绘制时使用）", "Left-click picks the primary color, right-click the secondary (used when drawing with the right button)"),
+    ("副 索引 {}", "Secondary index {}"),
+    // 画布右键菜单
+    ("取此处颜色", "Pick color here"),
+    ("取此处颜色为副颜色", "Pick color here as secondary"),
+    ("在调色板中定位此索引", "Locate this index in the palette"),
+    ("从此处填充（索引 {}）", "Fill from here (index {})"),
+    ("把导入图片居中到此处", "Center import image here"),
+    ("复制坐标", "Copy coordinates"),
+    ("已复制坐标 {}, {}", "Copied coordinates {}, {}"),
+    // 平移 / 居中
+    ("平移全部帧...", "Shift All Frames..."),
+    ("平移全部帧", "Shift All Frames"),
+    ("内容居中", "Center Content"),
+    ("限制在画布内", "Keep inside canvas"),
+    ("位移量不超过内容到画布边缘的距离，不会裁掉像素", "Limit the shift to the distance between the content and the canvas edge so no pixels are cut off"),
+    ("环绕", "Wrap"),
+    ("移出画布的像素从对边移入", "Pixels shifted off the canvas come back in from the opposite edge"),
+    ("居中所需位移", "Offset to center"),
+    ("填入使全部帧内容的包围盒居中的位移量", "Fill in the shift that centers the combined bounding box of all frames"),
+    ("平移", "Shift"),
+    ("位移为 0，未改动", "Shift is 0, nothing changed"),
+    ("已平移全部帧: {}, {}", "Shifted all frames by {}, {}"),
+    ("所有帧都是空的，无法居中", "All frames are empty, nothing to center"),
+    ("内容已经居中", "Content is already centered"),
+    // 残影叠加导出
+    ("导出残影叠加 PNG...", "Export Ghosting PNG..."),
+    ("把全部帧以递增的不透明度叠到一张图上，便于检查走路循环、枪口火焰的轨迹；限定了播放范围（序列）时只叠加该范围", "Composite all frames onto one image with increasing opacity, handy for checking walk cycles and muzzle-flash travel; only the playback range is used when a sequence is selected"),
+    ("导出残影叠加", "Export Ghosting"),
+    ("首帧不透明度", "First frame opacity"),
+    ("末帧不透明度", "Last frame opacity"),
+    ("每隔几帧取一帧", "Frame step"),
+    ("叠加第 {}–{} 帧中的 {} 帧", "Frames {}–{}, {} composited"),
+    ("已导出 {} 帧的残影叠加: {}", "Exported ghosting of {} frames: {}"),
+    // 切换调色板方式
+    ("每次询问", "Ask every time"),
+    ("仅换调色板（索引不变）", "Reassign palette only (keep indices)"),
+    ("改写索引以保持外观", "Remap indices to preserve appearance"),
+    ("选择内置PAL时", "When choosing a built-in PAL"),
+    ("切换调色板", "Switch Palette"),
+    ("像素索引不动，每个像素改用新调色板中同一索引的颜色显示，外观会随之变化。适合同一套索引规划的调色板之间切换，例如单位与建筑 PAL。", "Pixel indices stay the same and each pixel shows the new palette's color at that index, so the look changes. Suited to palettes sharing one index layout, such as unit and building PALs."),
+    ("为每个使用中的索引在新调色板中找最接近的颜色并改写所有帧，画面尽量保持原样。阴影与阵营色区间按角色对应。", "Finds the closest color in the new palette for every used index and rewrites all frames so the image looks as close as possible. Shadow and remap ranges are mapped by role."),
+    ("记住选择，以后不再询问", "Remember my choice and don't ask again"),
+    ("可在 设置 → 选择内置PAL时 中改回", "Can be changed back under Settings → When choosing a built-in PAL"),
+    // 整理调色板顺序
+    ("按色相分组", "Group by hue"),
+    ("按亮度", "By luminance"),
+    ("整理调色板顺序...", "Reorder Palette..."),
+    ("整理调色板顺序", "Reorder Palette"),
+    ("排序", "Sort"),
+    ("还原", "Revert"),
+    ("阴影、阵营色、循环色保持原位", "Keep shadow, remap and cycling entries in place"),
+    ("拖动格子可手动调整位置；索引 0 固定不动", "Drag cells to move them by hand; index 0 stays fixed"),
+    ("位置 {} ← 原索引 {}", "Slot {} ← original index {}"),
+    ("{} 个索引换了位置；所有帧的像素会同步改写，画面不变", "{} indices moved; pixels in every frame are rewritten so the art looks the same"),
+    ("已整理调色板顺序并改写像素索引", "Reordered the palette and rewrote pixel indices"),
+    // 调色板格子
+    ("当前帧 {} 像素", "{} pixels in current frame"),
+    ("显示编号", "Show numbers"),
+    ("标出当前帧用到的", "Highlight used in frame"),
+    // 选区限制绘制
+    ("绘制、填充、文字与贴入的图片只影响选区内的像素", "Drawing, fills, text and pasted images only affect pixels inside the selection"),
+    // 8 连通填充
+    ("对角连通 (8 连通)", "Diagonal connectivity (8-connected)"),
+    ("对角相邻的同色像素也算连通：可一次填满或选中单像素宽的斜线；注意填充也会从斜线描边的对角缝隙漏出", "Diagonally adjacent pixels of the same color count as connected: one-pixel-wide diagonal lines fill or select in one go; note that fills also leak through the diagonal gaps of a diagonal outline"),
+    // 游戏比例预览
+    ("游戏比例预览", "In-game Scale Preview"),
+    ("1× 原始", "1× native"),
+    ("2× 最近邻", "2× nearest"),
+    ("CRT 扫描线", "CRT scanlines"),
+    ("第 {} 帧 · {}×", "Frame {} · {}×"),
+    // 批量转换
+    ("PNG 序列（每个 SHP 一个子文件夹）", "PNG sequence (one subfolder per SHP)"),
+    ("GIF 动画（每个 SHP 一个文件）", "GIF animation (one file per SHP)"),
+    ("批量转换 SHP 文件夹...", "Batch Convert SHP Folder..."),
+    ("批量转换 SHP", "Batch Convert SHP"),
+    ("输入文件夹", "Input folder"),
+    ("输出文件夹", "Output folder"),
+    ("选择...", "Choose..."),
+    ("{}（{} 个 SHP）", "{} ({} SHPs)"),
+    ("当前调色板 ({})", "Current palette ({})"),
+    ("当前调色板", "Current palette"),
+    ("格式", "Format"),
+    ("帧间隔 {} ms（预览 → 间隔ms）", "Frame interval {} ms (Preview → interval ms)"),
+    ("开始转换", "Start"),
+    ("{} 失败: {}", "{} failed: {}"),
+    ("批量转换已取消：完成 {} 个，失败 {} 个", "Batch conversion cancelled: {} done, {} failed"),
+    ("批量转换完成：{} 个成功，{} 个失败", "Batch conversion finished: {} succeeded, {} failed"),
+    // 后台文件读写
+    ("后台任务意外中止", "Background task stopped unexpectedly"),
+    ("正在打开 {}", "Opening {}"),
+    ("另一个文件操作尚未完成", "Another file operation is still running"),
+    ("已用时 {} 秒", "Elapsed {} s"),
+    ("已取消", "Cancelled"),
+    ("正在保存 {}", "Saving {}"),
+    ("正在读取 {}", "Reading {}"),
+    // 帧属性检查器
+    ("帧属性检查器", "Frame inspector"),
+    ("打开文件时", "As loaded from file"),
+    ("该帧不是从文件读取的（新建、导入或生成）", "This frame was not loaded from a file (new, imported or generated)"),
+    ("存储格式", "Storage format"),
+    ("帧头矩形", "Header rectangle"),
+    ("数据偏移", "Data offset"),
+    ("解码大小", "Decoded size"),
+    ("{} 字节", "{} bytes"),
+    ("是", "Yes"),
+    ("否", "No"),
+    ("帧头矩形超出画布，超出部分读取时已丢弃", "Header rectangle exceeds the canvas; the excess was discarded on load"),
+    ("帧头有尺寸但数据偏移为 0，游戏中按空帧处理", "Header has a size but data offset 0; the game treats it as empty"),
+    ("逐行格式保存时按当前编码方式重新写入", "Line-length format is rewritten with the current encoding on save"),
+    ("当前", "Current"),
+    ("内容范围", "Content bounds"),
+    ("保存时帧头", "Header on save"),
+    ("空帧（数据偏移写 0）", "Empty frame (data offset written as 0)"),
+    ("数据块大小", "Data block size"),
+    ("XOR 关键帧 (Format40)", "XOR keyframe (Format40)"),
+    ("XOR 上一帧 (Format40)", "XOR previous frame (Format40)"),
+    ("未知", "Unknown"),
+    ("逐行（带行长）", "Line-length rows"),
+    // 重定画布
+    ("重定画布", "Retarget canvas"),
+    ("重定画布...", "Retarget Canvas..."),
+    ("按美术类型选择对齐方式，整段动画统一换到新画布尺寸", "Move the whole animation to a new canvas size, aligned the way each art type expects"),
+    ("对齐方式", "Alignment"),
+    ("内容底部中心（步兵）", "Content bottom-center (infantry)"),
+    ("画布中心（载具、飞行器）", "Canvas center (vehicles, aircraft)"),
+    ("画布底部中心（建筑）", "Canvas bottom-center (buildings)"),
+    ("内容中心（特效动画）", "Content center (effects)"),
+    ("底边留白", "Bottom margin"),
+    ("所有帧都是空的，无法按内容对齐", "All frames are empty; cannot align by content"),
+    ("原内容平移 ({}, {})", "Content moves by ({}, {})"),
+    ("新画布放不下全部内容，超出部分会被裁掉", "The new canvas cannot hold all content; the excess will be cropped"),
+    // 用户调色板文件夹
+    ("添加调色板文件夹...", "Add Palette Folder..."),
+    ("扫描文件夹及其子文件夹中的 .pal，加入「User」分组，下次启动自动加载", "Scan the folder and its subfolders for .pal files, add them to the \"User\" group and load them on every start"),
+    ("调色板文件夹", "Palette folder"),
+    ("重新扫描", "Rescan"),
+    ("移除", "Remove"),
+    ("已从调色板文件夹加载 {} 个调色板", "Loaded {} palettes from the palette folder"),
+    ("{} 中没有找到调色板", "No palettes found in {}"),
+    // 固定调色板
+    ("右键固定到菜单顶部", "Right-click to pin to the top of the menu"),
+    ("取消固定", "Unpin"),
+    ("固定到顶部", "Pin to top"),
+    ("已取消固定: {}", "Unpinned: {}"),
+    ("已固定: {}（快捷键 {}）", "Pinned: {} (shortcut {})"),
+    ("已固定: {}", "Pinned: {}"),
+    // 最近使用的颜色
+    ("最近使用", "Recent"),
+    // 图案填充
+    ("单色", "Solid"),
+    ("两色抖动", "Dither"),
+    ("笔刷图案", "Brush pattern"),
+    ("另一颜色占比", "Other color share"),
+    ("主颜色与副颜色按 4×4 有序抖动混合；右键填充时两者对调", "Mixes primary and secondary colors with a 4×4 ordered dither; right-click fill swaps them"),
+    ("先加载笔刷 PNG 或从选区创建笔刷；没有笔刷时按单色填充", "Load a brush PNG or create a brush from the selection first; without one the fill is solid"),
+    ("自定义笔刷从画布左上角起平铺；笔刷空白处保持原样", "The custom brush tiles from the canvas top-left; blank brush pixels are left unchanged"),
+    // 自动明暗滤镜
+    ("自动明暗", "Auto shade"),
+    ("边缘宽度", "Edge width"),
+    ("最多移动格数", "Max ramp steps"),
+    ("色阶", "Ramps"),
+    ("添加", "Add"),
+    ("按 16 格分段", "Split by 16"),
+    ("清空", "Clear"),
+    // 生成朝向
+    ("生成朝向...", "Generate Facings..."),
+    ("把帧旋转成 8/16/32 个朝向并追加到末尾，用于炮塔、抛射体", "Rotate frames into 8/16/32 facings appended at the end, for turrets and projectiles"),
+    ("生成朝向", "Generate facings"),
+    ("朝向数", "Facings"),
+    ("当前帧 ({})", "Current frame ({})"),
+    ("全部 {} 帧", "All {} frames"),
+    ("源帧朝向", "Source faces"),
+    ("北（上）", "North (up)"),
+    ("东（右）", "East (right)"),
+    ("南（下）", "South (down)"),
+    ("西（左）", "West (left)"),
+    ("顺时针排列", "Clockwise order"),
+    ("游戏中的朝向从北开始逆时针排列", "In-game facings start at north and go counter-clockwise"),
+    ("平滑旋转", "Smooth rotation"),
+    ("双线性插值后重新匹配调色板；默认最近邻，只搬运原有索引", "Bilinear interpolation re-matched to the palette; the default nearest-neighbor only moves existing indices"),
+    ("将追加 {} 帧：每个朝向依次包含全部源帧", "{} frames will be appended: each facing holds all source frames in order"),
+    ("将追加 {} 帧", "{} frames will be appended"),
+    ("已生成 {} 个朝向，追加 {} 帧（从第 {} 帧开始）", "Generated {} facings, appended {} frames (from frame {})"),
+    // 重复帧分析
+    ("重复帧分析...", "Duplicate Frames..."),
+    ("重复帧分析", "Duplicate frames"),
+    ("没有内容相同的帧", "No identical frames"),
+    ("{} 组相同帧，共 {} 帧可省去", "{} groups of identical frames, {} frames redundant"),
+    ("第 {} 帧 =", "Frame {} ="),
+    ("像素与对齐偏移都相同才算相同；修改文档后请重新分析", "Frames match only if pixels and alignment offset are equal; re-analyze after editing"),
+    ("保存时相同帧共用数据", "Share data of identical frames on save"),
+    ("相同帧的帧头指向同一份数据，帧数与动画不变，只减小文件", "Identical frames point to one data block; frame count and animation stay the same, only the file shrinks"),
+    ("可减小约 {} 字节", "Saves about {} bytes"),
+    ("重新分析", "Re-analyze"),
+    ("只保留唯一帧", "Keep unique frames only"),
+    ("删除每组中首帧之外的帧；帧序号与动画节奏会随之改变", "Delete all but the first frame of each group; frame numbers and timing change accordingly"),
+    ("已删除 {} 个重复帧", "Deleted {} duplicate frames"),
+    // 空帧写法
+    ("0 偏移", "Zero offset"),
+    ("1×1 透明块", "1×1 transparent block"),
+    ("空帧写法", "Empty frames"),
+    ("保存时没有像素的帧：帧头写 0 偏移，或写一个 1×1 透明块；有的程序把 0 偏移的帧当作缺失（仅对当前文档有效）", "Frames with no pixels on save: write a zero data offset or a 1×1 transparent block; some programs treat zero-offset frames as missing (current document only)"),
+    ("空帧：保存时写为{}", "Empty frame: saved as {}"),
+    // 工程文件
+    ("打开工程...", "Open Project..."),
+    ("打开 .lvshp 工程：帧、调色板、对齐偏移、序列与编辑状态", "Open a .lvshp project: frames, palette, offsets, sequences and editor state"),
+    ("保存工程...", "Save Project..."),
+    ("保存工程", "Save project"),
+    ("工程", "Project"),
+    ("已保存工程: {}", "Project saved: {}"),
+    ("保存工程失败: {}", "Failed to save project: {}"),
+    ("打开工程失败: {}", "Failed to open project: {}"),
+    ("已打开工程: {}", "Project opened: {}"),
+    ("工程文件版本 {} 过新", "Project file version {} is newer than supported"),
+    ("工程画布尺寸无效", "Invalid project canvas size"),
+    ("工程第 {} 帧数据损坏", "Project frame {} data is corrupt"),
+    ("工程缺少条目 {}", "Project is missing entry {}"),
+    // 恢复上次会话
+    ("已恢复上次的文档: {}", "Restored last document: {}"),
+    ("上次的文档已不存在: {}", "Last document no longer exists: {}"),
+    ("启动时重新打开上次的文档", "Reopen last document on startup"),
+    ("恢复文件、帧位置、缩放与调色板；通过命令行或文件关联打开文件时不恢复", "Restores the file, frame, zoom and palette; skipped when a file is opened from the command line or a file association"),
+    // 导入量化预览
+    ("原图", "Original"),
+    ("量化结果", "Quantized"),
+    ("量化结果即固定后写入帧的颜色；有选区时只写入选区内的部分", "Quantized shows the colors that will be written on commit; with a selection only the part inside it is written"),
+    // 双调色板对比
+    ("双调色板对比", "Two-Palette Comparison"),
+    ("调色板 B", "Palette B"),
+    ("分割线", "Split"),
+    ("并排", "Side by side"),
+    ("选择调色板 B 后显示对比", "Choose palette B to compare"),
+    // 帧导航快捷键
+    ("第一帧", "First frame"),
+    ("最后一帧", "Last frame"),
+    ("后退 10 帧", "Back 10 frames"),
+    ("前进 10 帧", "Forward 10 frames"),
+    ("播放/暂停", "Play/Pause"),
+    ("十六进制 ID，或带扩展名的文件名（按所选算法计算 ID）", "Hex ID, or a file name with extension (ID computed with the selected scheme)"),
+    ("该索引在预览、PNG 导出、橡皮和导入中视为透明（仅对当前文档有效）", "This index is treated as transparent in preview, PNG export, eraser and import (current document only)"),
+    ("已从 {} 导入 {} 帧（第 {} 帧起）", "Imported from {}: {} frames (starting at frame {})"),
+    // 导出钩子
+    ("导出后执行命令", "Post-export command"),
+    ("保存/导出成功后执行", "Run after a successful save/export"),
+    ("占位符: {file} 完整路径, {dir} 目录, {name} 文件名(无扩展名), {ext} 扩展名；路径含空格时请加引号", "Placeholders: {file} full path, {dir} directory, {name} file name without extension, {ext} extension; quote paths containing spaces"),
+    ("示例: copy \"{file}\" \"D:\\Mod\\\" && D:\\Mod\\gamemd.exe", "Example: copy \"{file}\" \"D:\\Mod\\\" && D:\\Mod\\gamemd.exe"),
+    ("当前展开: {}", "Expands to: {}"),
+    ("{}；已执行: {}", "{}; ran: {}"),
+    ("执行导出命令失败: {}", "Failed to run post-export command: {}"),
+    // 使用统计
+    ("使用统计", "Usage statistics"),
+    ("(未命名)", "(untitled)"),
+    ("记录本地使用统计（仅保存在本机）", "Record local usage statistics (stored on this machine only)"),
+    ("统计未开启。", "Statistics are disabled."),
+    ("本次会话", "This session"),
+    ("文档: {}", "Document: {}"),
+    ("编辑时间: {}", "Editing time: {}"),
+    ("操作次数: {}，编辑过的帧: {}", "Operations: {}, frames edited: {}"),
+    ("累计", "All time"),
+    ("文档", "Document"),
+    ("时间", "Time"),
+    ("操作", "Operations"),
+    ("清空累计统计", "Reset statistics"),
+    ("铅笔", "Pencil"),
+    ("橡皮", "Eraser"),
+    ("直线", "Line"),
+    ("矩形", "Rectangle"),
+    ("圆", "Circle"),
+    ("填充", "Fill"),
+    ("保存", "Save"),
+    ("清空帧", "Clear frames"),
+    ("复制帧", "Duplicate frames"),
+    ("删除帧", "Delete frames"),
+    ("预览", "Preview"),
+    ("暂停", "Pause"),
+    ("播放", "Play"),
+    ("间隔ms", "Interval ms"),
+    ("调色板预览墙", "Palette gallery"),
+    ("HUD 遮挡区域", "HUD safe area"),
+    ("色盲模拟", "Color-blind simulation"),
+    ("正常视觉", "Normal vision"),
+    ("红色盲 (Protanopia)", "Protanopia"),
+    ("绿色盲 (Deuteranopia)", "Deuteranopia"),
+    ("蓝色盲 (Tritanopia)", "Tritanopia"),
+    ("显示血条/选择框遮挡区域", "Show health bar / selection bracket area"),
+    ("步兵", "Infantry"),
+    ("载具", "Vehicle"),
+    ("血条Y(相对中心)", "Bar Y (from center)"),
+    ("血条宽", "Bar width"),
+    ("血条高", "Bar height"),
+    ("选择框宽", "Bracket width"),
+    ("选择框高", "Bracket height"),
+    // 帧与时间轴
+    ("当前没有SHP", "No SHP loaded"),
+    ("清空选中帧", "Clear selected frames"),
+    ("复制选中帧到末尾", "Duplicate selected frames to end"),
+    ("删除选中帧", "Delete selected frames"),
+    ("已清空 {} 帧", "Cleared {} frame(s)"),
+    ("已复制 {} 帧到末尾", "Duplicated {} frame(s) to the end"),
+    ("至少需要保留一帧", "At least one frame must remain"),
+    ("已删除 {} 帧", "Deleted {} frame(s)"),
+    ("当前帧", "Current frame"),
+    ("选中帧", "Selected frames"),
+    ("所有帧", "All frames"),
+    ("← 上一帧", "← Prev"),
+    ("下一帧 →", "Next →"),
+    ("第 {} 帧 / 共 {} 帧", "Frame {} / {} total"),
+    ("已选 {} 帧", "{} selected"),
+    // 替换颜色
+    ("替换颜色", "Replace color"),
+    ("原索引 A = {}", "From index A = {}"),
+    ("新索引 B = {}", "To index B = {}"),
+    ("替换", "Replace"),
+    ("交换 A/B", "Swap A/B"),
+    ("已将索引 {} 替换为 {}：{} 帧，{} 个像素", "Replaced index {} with {}: {} frame(s), {} pixel(s)"),
+    // 快捷键
+    ("快捷键", "Shortcuts"),
+    ("请按下组合键… (Esc 取消)", "Press a key combination… (Esc to cancel)"),
+    ("与 {} 冲突", "Conflicts with {}"),
+    ("、", ", "),
+    ("修改", "Change"),
+    ("恢复默认", "Restore defaults"),
+    ("配置文件: {}", "Config file: {}"),
+    ("保存设置失败: {}", "Failed to save settings: {}"),
+    ("新建 SHP", "New SHP"),
+    ("打开 SHP", "Open SHP"),
+    ("保存 SHP", "Save SHP"),
+    ("撤销", "Undo"),
+    ("重做", "Redo"),
+    ("上一帧", "Previous frame"),
+    ("下一帧", "Next frame"),
+    ("退出", "Quit"),
+    // 文件操作
+    ("已新建 SHP: {}x{}, 帧数 {}", "New SHP created: {}x{}, {} frame(s)"),
+    ("已加载 SHP: {}", "Loaded SHP: {}"),
+    ("加载SHP失败: {}", "Failed to load SHP: {}"),
+    ("读取文件失败: {}", "Failed to read file: {}"),
+    ("保存失败: {}", "Save failed: {}"),
+    ("已保存: {}", "Saved: {}"),
+    ("导出SHP失败: {}", "Failed to encode SHP: {}"),
+    ("已加载 PAL: {}", "Loaded PAL: {}"),
+    ("加载PAL失败: {}", "Failed to load PAL: {}"),
+    ("保存PAL失败: {}", "Failed to save PAL: {}"),
+    ("已保存 PAL: {}", "Saved PAL: {}"),
+    ("请先新建或打开SHP", "Create or open an SHP first"),
+    ("图片", "Images"),
+    ("已载入 {}，请在画布上拖动/缩放/固定。", "Loaded {}; drag/scale it on the canvas, then fix it."),
+    ("导入失败: {}", "Import failed: {}"),
+    ("已加载笔刷: {} ({}x{})", "Loaded brush: {} ({}x{})"),
+    ("加载笔刷失败: {}", "Failed to load brush: {}"),
+    ("导出帧差异", "Export frame deltas"),
+    ("文件名前缀", "File name prefix"),
+    ("示例: {}0000.png", "Example: {}0000.png"),
+    ("选择文件夹并导出...", "Choose folder and export..."),
+    ("导出失败(帧 {}): {}", "Export failed (frame {}): {}"),
+    ("已导出 {} 帧差异到 {}，共 {} 个变化像素，{} 帧无变化", "Exported {} frame delta(s) to {}: {} changed pixel(s), {} unchanged frame(s)"),
+    ("已导出: {}", "Exported: {}"),
+    ("导出失败: {}", "Export failed: {}"),
+    ("约占内存 {} 字节", "About {} bytes of memory"),
+    // 撤销
+    ("已撤销", "Undone"),
+    ("已重做", "Redone"),
+    ("撤销 ({})", "Undo ({})"),
+    ("重做 ({})", "Redo ({})"),
+    // 工具面板
+    ("工具", "Tools"),
+    ("✏️ 铅笔", "✏️ Pencil"),
+    ("🧽 橡皮", "🧽 Eraser"),
+    ("🪣 填充", "🪣 Fill"),
+    ("📏 直线", "📏 Line"),
+    ("⬛ 矩形", "⬛ Rectangle"),
+    ("⚪ 圆", "⚪ Circle"),
+    ("画笔大小", "Brush size"),
+    ("笔刷形状", "Brush shape"),
+    ("● 圆", "● Circle"),
+    ("■ 方", "■ Square"),
+    ("╱ 斜线", "╱ Diagonal"),
+    ("★ 自定义", "★ Custom"),
+    ("加载自定义笔刷 PNG...", "Load custom brush PNG..."),
+    ("笔刷尺寸需在 1..=256 之间", "Brush size must be within 1..=256"),
+    ("笔刷图片中没有有效像素（需不透明的深色像素）", "Brush image has no usable pixels (needs opaque dark pixels)"),
+    ("填充形状", "Filled shape"),
+    ("调色板", "Palette"),
+    ("索引 {}", "Index {}"),
+    ("预览亮度", "Preview brightness"),
+    ("缩放", "Zoom"),
+    // 画布与导入
+    ("导入图变换", "Import transform"),
+    ("固定到帧", "Fix to frame"),
+    ("取消", "Cancel"),
+    ("新建或打开一个 SHP 开始绘制", "Create or open an SHP to start drawing"),
+    // 对话框
+    ("⚠️ 确认退出", "⚠️ Confirm exit"),
+    ("有未保存的更改", "You have unsaved changes"),
+    ("退出程序", "Exit"),
+    ("建议先保存再退出，避免丢失编辑。", "Save before exiting to avoid losing your edits."),
+    ("💾 保存并退出", "💾 Save and exit"),
+    ("🗙 不保存退出", "🗙 Exit without saving"),
+    ("请输入尺寸与帧数：", "Enter size and frame count:"),
+    ("宽", "W"),
+    ("高", "H"),
+    ("帧数", "Frames"),
+    ("确定", "OK"),
+    // 文件格式错误
+    ("SHP头不足", "SHP header too short"),
+    ("不是有效的SHP文件", "Not a valid SHP file"),
+    ("无效SHP尺寸/帧数", "Invalid SHP size / frame count"),
+    ("SHP数据偏移越界", "SHP data offset out of bounds"),
+    ("没有帧", "No frames"),
+    ("帧索引超界", "Frame index out of range"),
+    ("PAL字节数不足", "PAL file too short"),
+    ("GIF帧解码失败", "Failed to decode GIF frame"),
+    ("GIF没有帧", "GIF has no frames"),
+    ("不支持的图片扩展名", "Unsupported image extension"),
+    // 更新发布日期
+    ("发布日期: {}", "Released: {}"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_follow_language() {
+        assert_eq!(Language::ZhCn.format_date(2026, 10, 16), "2026年10月16日");
+        assert_eq!(Language::EnUs.format_date(2026, 10, 16), "Oct 16, 2026");
+        // 测试中界面语言保持默认（中文）
+        assert_eq!(format_date("2024-05-01T12:00:00Z").as_deref(), Some("2024年5月1日"));
+        assert_eq!(format_date("2024-13-01"), None);
+        assert_eq!(format_date("soon"), None);
+        assert_eq!(format_number(1_234_567), "1,234,567");
+        assert_eq!(format_number(999), "999");
+    }
+}
@@ -2,37 +2,322 @@ use std::path::Path;
 
 use image;
 
-/// 从磁盘加载图片为 RGBA8 帧列表
-/// - png/jpg/jpeg：返回单帧
-/// - gif：返回所有帧（已转换为 RGBA），若无帧报错
-/// - apng：为简化，仅取首帧
+use crate::i18n::tr;
+
+/// 从磁盘加载图片为 RGBA8 帧列表（丢弃帧延时）
 pub fn load_rgba_frames(path: &Path) -> Result<Vec<image::RgbaImage>, String> {
+    Ok(load_animation(path)?.into_iter().map(|(img, _)| img).collect())
+}
+
+/// 从磁盘加载图片为 (RGBA8 帧, 延时毫秒) 列表；静态图延时为 0
+/// - jpg/jpeg：返回单帧
+/// - png/apng：APNG 返回所有帧（按 fcTL 的混合与处置方式合成为完整画面），普通 PNG 返回单帧
+/// - gif：返回所有帧（按帧矩形与处置方式合成为完整画面），若无帧报错
+pub fn load_animation(path: &Path) -> Result<Vec<(image::RgbaImage, u32)>, String> {
     let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
     match ext.as_str() {
-        "png" | "jpg" | "jpeg" => {
+        "jpg" | "jpeg" => {
             let img = image::open(path).map_err(|e| e.to_string())?;
-            Ok(vec![img.to_rgba8()])
+            Ok(vec![(img.to_rgba8(), 0)])
         }
+        "png" | "apng" => load_png_frames(path),
         "gif" => {
             let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
             let mut decoder = gif::DecodeOptions::new();
             decoder.set_color_output(gif::ColorOutput::RGBA);
             let mut decoder = decoder.read_info(file).map_err(|e| e.to_string())?;
+            let (w, h) = (decoder.width() as u32, decoder.height() as u32);
+            let mut canvas = image::RgbaImage::new(w, h);
             let mut frames = Vec::new();
             while let Some(frame) = decoder.read_next_frame().map_err(|e| e.to_string())? {
-                let buf = frame.buffer.clone().into_owned();
-                frames.push(image::RgbaImage::from_raw(decoder.width() as u32, decoder.height() as u32, buf).ok_or("GIF帧解码失败")?);
+                let part = image::RgbaImage::from_raw(frame.width as u32, frame.height as u32, frame.buffer.to_vec()).ok_or(tr("GIF帧解码失败"))?;
+                let restore = (frame.dispose == gif::DisposalMethod::Previous).then(|| canvas.clone());
+                for (x, y, px) in part.enumerate_pixels() {
+                    let (cx, cy) = (frame.left as u32 + x, frame.top as u32 + y);
+                    if px[3] > 0 && cx < w && cy < h { canvas.put_pixel(cx, cy, *px); }
+                }
+                // GIF 延时单位为 1/100 秒
+                frames.push((canvas.clone(), frame.delay as u32 * 10));
+                match frame.dispose {
+                    gif::DisposalMethod::Background => {
+                        for y in frame.top as u32..(frame.top + frame.height) as u32 {
+                            for x in frame.left as u32..(frame.left + frame.width) as u32 {
+                                if x < w && y < h { canvas.put_pixel(x, y, image::Rgba([0, 0, 0, 0])); }
+                            }
+                        }
+                    }
+                    gif::DisposalMethod::Previous => { if let Some(prev) = restore { canvas = prev; } }
+                    _ => {}
+                }
             }
-            if frames.is_empty() { return Err("GIF没有帧".into()); }
+            if frames.is_empty() { return Err(tr("GIF没有帧").into()); }
             Ok(frames)
         }
-        "apng" => {
-            // 简化：暂用首帧作为静态图导入
-            let img = image::open(path).map_err(|e| e.to_string())?;
-            Ok(vec![img.to_rgba8()])
+        _ => Err(tr("不支持的图片扩展名").into()),
+    }
+}
+
+// PNG/APNG：由 image 的 APNG 解码器处理 fcTL/fdAT，并按 blend_op/dispose_op 合成每帧
+fn load_png_frames(path: &Path) -> Result<Vec<(image::RgbaImage, u32)>, String> {
+    use image::AnimationDecoder;
+    let file = std::io::BufReader::new(std::fs::File::open(path).map_err(|e| e.to_string())?);
+    let decoder = image::codecs::png::PngDecoder::new(file).map_err(|e| e.to_string())?;
+    if !decoder.is_apng().map_err(|e| e.to_string())? {
+        let img = image::DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())?;
+        return Ok(vec![(img.to_rgba8(), 0)]);
+    }
+    let frames = decoder.apng().map_err(|e| e.to_string())?.into_frames().collect_frames().map_err(|e| e.to_string())?;
+    if frames.is_empty() { return Err(tr("APNG没有帧").into()); }
+    Ok(frames.into_iter().map(|f| {
+        let (num, den) = f.delay().numer_denom_ms();
+        (f.into_buffer(), num / den.max(1))
+    }).collect())
+}
+
+
+
+/// 把 RGBA 图像写入系统剪贴板（各平台由 arboard 转为 PNG/DIB 等图像格式）
+pub fn copy_to_clipboard(clipboard: &mut arboard::Clipboard, img: &image::RgbaImage) -> Result<(), String> {
+    let data = arboard::ImageData { width: img.width() as usize, height: img.height() as usize, bytes: img.as_raw().into() };
+    clipboard.set_image(data).map_err(|e| e.to_string())
+}
+
+/// 读取系统剪贴板中的图像；剪贴板里没有图像时报错
+pub fn paste_from_clipboard(clipboard: &mut arboard::Clipboard) -> Result<image::RgbaImage, String> {
+    let data = clipboard.get_image().map_err(|e| match e {
+        arboard::Error::ContentNotAvailable => tr("剪贴板中没有图像").to_string(),
+        e => e.to_string(),
+    })?;
+    image::RgbaImage::from_raw(data.width as u32, data.height as u32, data.bytes.into_owned()).ok_or_else(|| tr("剪贴板图像数据无效").into())
+}
+
+/// 编码 8-bit 单平面 PCX（RLE 压缩，文件末尾附 256 色调色板），即 XCC Mixer 等旧工具使用的格式
+pub fn encode_pcx(width: u32, height: u32, pixels: &[u8], pal: &crate::palette::Palette) -> Vec<u8> {
+    // 每行字节数须为偶数
+    let stride = (width as usize).div_ceil(2) * 2;
+    let mut out = vec![0u8; 128];
+    out[..4].copy_from_slice(&[0x0A, 5, 1, 8]);
+    out[8..10].copy_from_slice(&(width.max(1) as u16 - 1).to_le_bytes());
+    out[10..12].copy_from_slice(&(height.max(1) as u16 - 1).to_le_bytes());
+    out[12..14].copy_from_slice(&72u16.to_le_bytes());
+    out[14..16].copy_from_slice(&72u16.to_le_bytes());
+    out[65] = 1;
+    out[66..68].copy_from_slice(&(stride as u16).to_le_bytes());
+    out[68] = 1;
+    for row in pixels.chunks(width.max(1) as usize) {
+        let mut line = row.to_vec();
+        line.resize(stride, 0);
+        let mut i = 0;
+        while i < line.len() {
+            let v = line[i];
+            let run = line[i..].iter().take(63).take_while(|&&b| b == v).count();
+            // 游程或高两位为 1 的字节必须写成 计数+值
+            if run > 1 || v >= 0xC0 { out.push(0xC0 | run as u8); }
+            out.push(v);
+            i += run;
+        }
+    }
+    out.push(0x0C);
+    for c in &pal.colors { out.extend([c.r(), c.g(), c.b()]); }
+    out
+}
+
+/// 导入图缩放/旋转时使用的重采样方式
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resample {
+    Nearest,
+    Triangle,
+    CatmullRom,
+}
+
+impl Resample {
+    pub const ALL: [Resample; 3] = [Resample::Nearest, Resample::Triangle, Resample::CatmullRom];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Resample::Nearest => tr("最近邻 (Nearest)"),
+            Resample::Triangle => tr("双线性 (Triangle)"),
+            Resample::CatmullRom => tr("双三次 (CatmullRom)"),
+        }
+    }
+
+    pub fn filter(self) -> image::imageops::FilterType {
+        match self {
+            Resample::Nearest => image::imageops::Nearest,
+            Resample::Triangle => image::imageops::Triangle,
+            Resample::CatmullRom => image::imageops::CatmullRom,
+        }
+    }
+}
+
+/// 绕中心旋转（角度，顺时针为正），画布扩展到能容纳整张旋转后的图，空白处透明。
+/// 返回新图以及中心位置相对原图左上角的偏移量（新图左上角 = 原左上角 - 偏移）
+pub fn rotate_expand(img: &image::RgbaImage, deg: f32, resample: Resample) -> (image::RgbaImage, (i32, i32)) {
+    let (w, h) = (img.width() as f32, img.height() as f32);
+    let (s, c) = deg.to_radians().sin_cos();
+    if s.abs() < 1e-6 && c > 0.0 { return (img.clone(), (0, 0)); }
+    let nw = (w * c.abs() + h * s.abs()).ceil().max(1.0) as u32;
+    let nh = (w * s.abs() + h * c.abs()).ceil().max(1.0) as u32;
+    let (cx, cy) = (w / 2.0, h / 2.0);
+    let (ncx, ncy) = (nw as f32 / 2.0, nh as f32 / 2.0);
+    let mut out = image::RgbaImage::new(nw, nh);
+    for y in 0..nh {
+        for x in 0..nw {
+            // 目标像素中心逆变换回源图坐标
+            let dx = x as f32 + 0.5 - ncx;
+            let dy = y as f32 + 0.5 - ncy;
+            let sx = c * dx + s * dy + cx;
+            let sy = -s * dx + c * dy + cy;
+            out.put_pixel(x, y, image::Rgba(sample(img, sx, sy, resample)));
         }
-        _ => Err("不支持的图片扩展名".into()),
     }
+    (out, (((nw as f32 - w) / 2.0).round() as i32, ((nh as f32 - h) / 2.0).round() as i32))
+}
+
+// 在源图坐标 (x, y)（像素中心在 .5 处）取样；越界为透明
+fn sample(img: &image::RgbaImage, x: f32, y: f32, resample: Resample) -> [u8; 4] {
+    let px = |ix: i32, iy: i32| -> [f32; 4] {
+        if ix < 0 || iy < 0 || ix >= img.width() as i32 || iy >= img.height() as i32 { return [0.0; 4]; }
+        img.get_pixel(ix as u32, iy as u32).0.map(|v| v as f32)
+    };
+    let (fx, fy) = (x - 0.5, y - 0.5);
+    let acc = match resample {
+        Resample::Nearest => px(x.floor() as i32, y.floor() as i32),
+        Resample::Triangle => {
+            let (x0, y0) = (fx.floor(), fy.floor());
+            let (tx, ty) = (fx - x0, fy - y0);
+            let mut a = [0.0f32; 4];
+            for (ox, oy, wgt) in [(0, 0, (1.0 - tx) * (1.0 - ty)), (1, 0, tx * (1.0 - ty)), (0, 1, (1.0 - tx) * ty), (1, 1, tx * ty)] {
+                let p = px(x0 as i32 + ox, y0 as i32 + oy);
+                for (ak, pk) in a.iter_mut().zip(p) { *ak += pk * wgt; }
+            }
+            a
+        }
+        Resample::CatmullRom => {
+            fn k(t: f32) -> f32 {
+                let t = t.abs();
+                if t < 1.0 { 1.5 * t * t * t - 2.5 * t * t + 1.0 }
+                else if t < 2.0 { -0.5 * t * t * t + 2.5 * t * t - 4.0 * t + 2.0 }
+                else { 0.0 }
+            }
+            let (x0, y0) = (fx.floor() as i32, fy.floor() as i32);
+            let mut a = [0.0f32; 4];
+            for oy in -1..=2 {
+                let wy = k(fy - (y0 + oy) as f32);
+                for ox in -1..=2 {
+                    let wgt = wy * k(fx - (x0 + ox) as f32);
+                    let p = px(x0 + ox, y0 + oy);
+                    for (ak, pk) in a.iter_mut().zip(p) { *ak += pk * wgt; }
+                }
+            }
+            a
+        }
+    };
+    acc.map(|v| v.round().clamp(0.0, 255.0) as u8)
 }
 
+/// 按整数倍面积平均缩小（每个 factor×factor 块取一个像素）。颜色按 alpha 加权，
+/// 透明背景不会把轮廓染黑；右下不足一块的余量按实际像素数平均
+pub fn downscale_area(img: &image::RgbaImage, factor: u32) -> image::RgbaImage {
+    let factor = factor.max(1);
+    if factor == 1 { return img.clone(); }
+    let (w, h) = (img.width(), img.height());
+    let (nw, nh) = (w.div_ceil(factor).max(1), h.div_ceil(factor).max(1));
+    let mut out = image::RgbaImage::new(nw, nh);
+    for by in 0..nh {
+        for bx in 0..nw {
+            let (mut rgb, mut alpha, mut n) = ([0.0f32; 3], 0.0f32, 0.0f32);
+            for y in by * factor..((by + 1) * factor).min(h) {
+                for x in bx * factor..((bx + 1) * factor).min(w) {
+                    let p = img.get_pixel(x, y).0;
+                    let a = p[3] as f32;
+                    for k in 0..3 { rgb[k] += p[k] as f32 * a; }
+                    alpha += a;
+                    n += 1.0;
+                }
+            }
+            let px = if alpha > 0.0 {
+                let c = rgb.map(|v| (v / alpha).round().clamp(0.0, 255.0) as u8);
+                [c[0], c[1], c[2], (alpha / n).round() as u8]
+            } else { [0; 4] };
+            out.put_pixel(bx, by, image::Rgba(px));
+        }
+    }
+    out
+}
 
+/// 反锐化掩模：原色 + amount × (原色 − 3×3 邻域均值)。只处理不透明像素，
+/// 邻域中的透明像素不参与平均，避免轮廓出现亮边
+pub fn sharpen(img: &image::RgbaImage, amount: f32) -> image::RgbaImage {
+    if amount <= 0.0 { return img.clone(); }
+    let (w, h) = (img.width() as i32, img.height() as i32);
+    let mut out = img.clone();
+    for y in 0..h {
+        for x in 0..w {
+            let p = img.get_pixel(x as u32, y as u32).0;
+            if p[3] == 0 { continue; }
+            let (mut sum, mut n) = ([0.0f32; 3], 0.0f32);
+            for ny in (y - 1).max(0)..=(y + 1).min(h - 1) {
+                for nx in (x - 1).max(0)..=(x + 1).min(w - 1) {
+                    let q = img.get_pixel(nx as u32, ny as u32).0;
+                    if q[3] == 0 { continue; }
+                    for k in 0..3 { sum[k] += q[k] as f32; }
+                    n += 1.0;
+                }
+            }
+            let mut s = p;
+            for k in 0..3 {
+                let v = p[k] as f32;
+                s[k] = (v + amount * (v - sum[k] / n)).round().clamp(0.0, 255.0) as u8;
+            }
+            out.put_pixel(x as u32, y as u32, image::Rgba(s));
+        }
+    }
+    out
+}
+
+/// 抠除背景色：与 `key` 的 RGB 距离不超过 `tolerance` 的像素改为全透明，
+/// 用于导入没有 alpha 通道的渲染图（品红/绿幕背景）
+pub fn chroma_key(img: &image::RgbaImage, key: [u8; 3], tolerance: u8) -> image::RgbaImage {
+    let tol2 = (tolerance as i32).pow(2);
+    let mut out = img.clone();
+    for p in out.pixels_mut() {
+        let d2: i32 = (0..3).map(|k| (p.0[k] as i32 - key[k] as i32).pow(2)).sum();
+        if d2 <= tol2 { p.0 = [0; 4]; }
+    }
+    out
+}
+
+/// 游戏比例预览：铺到黑底后按整数倍最近邻放大；`scanline` 为 CRT 效果强度（0..1），
+/// 相邻像素横向渗色，并压暗每个源像素行的下半部分形成扫描线
+pub fn game_preview(img: &image::RgbaImage, zoom: u32, scanline: Option<f32>) -> image::RgbaImage {
+    let k = zoom.max(1);
+    let (w, h) = (img.width(), img.height());
+    let mut out = image::RgbaImage::new(w * k, h * k);
+    let over_black = |x: u32, y: u32| -> [f32; 3] {
+        let p = img.get_pixel(x, y).0;
+        let a = p[3] as f32 / 255.0;
+        [p[0] as f32 * a, p[1] as f32 * a, p[2] as f32 * a]
+    };
+    for y in 0..h {
+        for x in 0..w {
+            let c = over_black(x, y);
+            let c = match scanline {
+                Some(s) => {
+                    let (l, r) = (over_black(x.saturating_sub(1), y), over_black((x + 1).min(w - 1), y));
+                    let bleed = 0.25 * s;
+                    std::array::from_fn(|i| c[i] * (1.0 - 2.0 * bleed) + (l[i] + r[i]) * bleed)
+                }
+                None => c,
+            };
+            for dy in 0..k {
+                // 1 倍时隔行压暗，放大后每个源像素行的下半部分为扫描线
+                let dark = scanline.filter(|_| if k == 1 { y % 2 == 1 } else { dy >= k.div_ceil(2) }).map_or(1.0, |s| 1.0 - 0.6 * s);
+                let v = c.map(|v| (v * dark).round().clamp(0.0, 255.0) as u8);
+                let px = image::Rgba([v[0], v[1], v[2], 255]);
+                for dx in 0..k { out.put_pixel(x * k + dx, y * k + dy, px); }
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,62 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::Instant;
+
+use eframe::egui;
+
+use crate::i18n::tr;
+use crate::shp::{LazyShp, SHP};
+
+/// 打开 SHP 的结果：查看模式下的超大文件为按需解码
+pub enum LoadedShp {
+    Full(SHP),
+    Lazy(LazyShp),
+}
+
+/// 后台任务完成后交回界面线程的结果
+pub enum IoDone {
+    /// `reload` 为外部修改后重新加载时要停留的帧
+    OpenShp { path: PathBuf, result: Result<LoadedShp, String>, reload: Option<usize> },
+    /// 成功时带回写入的字节，供文件监视记录；`doc_hash` 为开始保存时的文档摘要
+    SaveShp { path: PathBuf, result: Result<Vec<u8>, String>, doc_hash: u64 },
+    Image { path: PathBuf, result: Result<Vec<image::RgbaImage>, String> },
+    Animation { path: PathBuf, result: Result<Vec<(image::RgbaImage, u32)>, String> },
+}
+
+/// 在后台线程执行的文件读写与解码；界面线程每帧 `poll`，期间显示进度遮罩
+pub struct IoTask {
+    pub label: String,
+    pub started: Instant,
+    /// 读取类任务取消时直接丢弃结果；保存不可取消，避免写出一半的文件
+    pub cancellable: bool,
+    rx: Receiver<IoDone>,
+}
+
+impl IoTask {
+    pub fn spawn(label: String, cancellable: bool, ctx: &egui::Context, job: impl FnOnce() -> IoDone + Send + 'static) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send(job());
+            ctx.request_repaint();
+        });
+        Self { label, started: Instant::now(), cancellable, rx }
+    }
+
+    /// 完成时返回结果；线程意外退出时返回 Err
+    pub fn poll(&self) -> Option<Result<IoDone, String>> {
+        match self.rx.try_recv() {
+            Ok(done) => Some(Ok(done)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(tr("后台任务意外中止").into())),
+        }
+    }
+}
+
+/// 读取并解码 SHP；`lazy_threshold` 为 Some 时解码后超过该体积的文件改为按需解码
+pub fn read_shp(path: &Path, lazy_threshold: Option<u64>) -> Result<LoadedShp, String> {
+    let bytes = std::fs::read(path).map_err(|e| trf!("读取文件失败: {}", e))?;
+    let lazy = lazy_threshold.is_some_and(|t| LazyShp::decoded_size(&bytes).is_ok_and(|n| n > t));
+    let loaded = if lazy { LazyShp::open(bytes).map(LoadedShp::Lazy) } else { SHP::load(&bytes).map(LoadedShp::Full) };
+    loaded.map_err(|e| trf!("加载SHP失败: {}", e))
+}
@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+
+use eframe::egui::{Event, InputState, Key, Modifiers};
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::tr;
+
+/// 可绑定快捷键的操作
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum Action {
+    NewShp,
+    OpenShp,
+    SaveShp,
+    Undo,
+    Redo,
+    PrevFrame,
+    NextFrame,
+    FirstFrame,
+    LastFrame,
+    BackTenFrames,
+    ForwardTenFrames,
+    PlayPause,
+    Quit,
+    SelectAll,
+    Deselect,
+    DeleteSelection,
+    CopyFrameImage,
+    PasteImage,
+    PrevColor,
+    NextColor,
+    CyclePalette,
+    SwapColors,
+}
+
+impl Action {
+    pub const ALL: [Action; 22] = [
+        Action::NewShp, Action::OpenShp, Action::SaveShp,
+        Action::Undo, Action::Redo,
+        Action::PrevFrame, Action::NextFrame,
+        Action::FirstFrame, Action::LastFrame, Action::BackTenFrames, Action::ForwardTenFrames,
+        Action::PlayPause,
+        Action::SelectAll, Action::Deselect, Action::DeleteSelection,
+        Action::CopyFrameImage, Action::PasteImage,
+        Action::PrevColor, Action::NextColor, Action::SwapColors,
+        Action::CyclePalette,
+        Action::Quit,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::NewShp => tr("新建 SHP"),
+            Action::OpenShp => tr("打开 SHP"),
+            Action::SaveShp => tr("保存 SHP"),
+            Action::Undo => tr("撤销"),
+            Action::Redo => tr("重做"),
+            Action::PrevFrame => tr("上一帧"),
+            Action::NextFrame => tr("下一帧"),
+            Action::FirstFrame => tr("第一帧"),
+            Action::LastFrame => tr("最后一帧"),
+            Action::BackTenFrames => tr("后退 10 帧"),
+            Action::ForwardTenFrames => tr("前进 10 帧"),
+            Action::PlayPause => tr("播放/暂停"),
+            Action::Quit => tr("退出"),
+            Action::SelectAll => tr("全选"),
+            Action::Deselect => tr("取消选择"),
+            Action::DeleteSelection => tr("删除选区内容"),
+            Action::CopyFrameImage => tr("复制帧为图像"),
+            Action::PasteImage => tr("从剪贴板粘贴图像"),
+            Action::PrevColor => tr("上一个颜色索引"),
+            Action::NextColor => tr("下一个颜色索引"),
+            Action::CyclePalette => tr("轮换文档调色板"),
+            Action::SwapColors => tr("交换主/副颜色"),
+        }
+    }
+}
+
+/// 组合键：修饰键 + 按键名（按键名使用 egui 的 `Key::name`，便于写入配置文件）
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct KeyChord {
+    #[serde(default)]
+    pub ctrl: bool,
+    #[serde(default)]
+    pub shift: bool,
+    #[serde(default)]
+    pub alt: bool,
+    pub key: String,
+}
+
+impl KeyChord {
+    pub fn new(modifiers: Modifiers, key: Key) -> Self {
+        Self { ctrl: modifiers.command || modifiers.ctrl, shift: modifiers.shift, alt: modifiers.alt, key: key.name().to_owned() }
+    }
+
+    pub fn key(&self) -> Option<Key> {
+        Key::from_name(&self.key)
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        Modifiers { alt: self.alt, ctrl: self.ctrl, shift: self.shift, mac_cmd: false, command: self.ctrl }
+    }
+
+    /// 本帧是否按下了该组合键（修饰键需完全一致）
+    /// Ctrl+X/C/V 会被 egui 转成剪切/复制/粘贴事件而不产生按键事件，需按事件判断
+    pub fn pressed(&self, i: &InputState) -> bool {
+        let Some(key) = self.key() else { return false; };
+        if !i.modifiers.matches_exact(self.modifiers()) { return false; }
+        match key {
+            Key::X if self.ctrl => i.events.iter().any(|e| matches!(e, Event::Cut)),
+            Key::C if self.ctrl => i.events.iter().any(|e| matches!(e, Event::Copy)),
+            Key::V if self.ctrl => i.events.iter().any(|e| matches!(e, Event::Paste(_))),
+            _ => i.key_pressed(key),
+        }
+    }
+
+    pub fn display(&self) -> String {
+        let mut s = String::new();
+        if self.ctrl { s.push_str("Ctrl+"); }
+        if self.shift { s.push_str("Shift+"); }
+        if self.alt { s.push_str("Alt+"); }
+        s.push_str(&self.key);
+        s
+    }
+}
+
+/// 操作 → 组合键 的映射，随设置一起持久化
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keymap {
+    pub bindings: BTreeMap<Action, KeyChord>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let ctrl = Modifiers::COMMAND;
+        let none = Modifiers::NONE;
+        let mut bindings = BTreeMap::new();
+        bindings.insert(Action::NewShp, KeyChord::new(ctrl, Key::N));
+        bindings.insert(Action::OpenShp, KeyChord::new(ctrl, Key::O));
+        bindings.insert(Action::SaveShp, KeyChord::new(ctrl, Key::S));
+        bindings.insert(Action::Undo, KeyChord::new(ctrl, Key::Z));
+        bindings.insert(Action::Redo, KeyChord::new(ctrl, Key::Y));
+        bindings.insert(Action::PrevFrame, KeyChord::new(none, Key::ArrowLeft));
+        bindings.insert(Action::NextFrame, KeyChord::new(none, Key::ArrowRight));
+        bindings.insert(Action::FirstFrame, KeyChord::new(none, Key::Home));
+        bindings.insert(Action::LastFrame, KeyChord::new(none, Key::End));
+        bindings.insert(Action::BackTenFrames, KeyChord::new(none, Key::PageUp));
+        bindings.insert(Action::ForwardTenFrames, KeyChord::new(none, Key::PageDown));
+        bindings.insert(Action::PlayPause, KeyChord::new(none, Key::Space));
+        bindings.insert(Action::Quit, KeyChord::new(ctrl, Key::Q));
+        bindings.insert(Action::SelectAll, KeyChord::new(ctrl, Key::A));
+        bindings.insert(Action::Deselect, KeyChord::new(ctrl, Key::D));
+        bindings.insert(Action::DeleteSelection, KeyChord::new(none, Key::Delete));
+        bindings.insert(Action::CopyFrameImage, KeyChord::new(ctrl | Modifiers::SHIFT, Key::C));
+        bindings.insert(Action::PasteImage, KeyChord::new(ctrl | Modifiers::SHIFT, Key::V));
+        bindings.insert(Action::PrevColor, KeyChord::new(none, Key::OpenBracket));
+        bindings.insert(Action::NextColor, KeyChord::new(none, Key::CloseBracket));
+        bindings.insert(Action::CyclePalette, KeyChord::new(none, Key::P));
+        bindings.insert(Action::SwapColors, KeyChord::new(none, Key::X));
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// 补齐旧配置中缺失的操作（新版本新增的快捷键使用默认值）
+    pub fn fill_defaults(&mut self) {
+        for (a, c) in Keymap::default().bindings {
+            self.bindings.entry(a).or_insert(c);
+        }
+    }
+
+    pub fn pressed(&self, i: &InputState, action: Action) -> bool {
+        self.bindings.get(&action).is_some_and(|c| c.pressed(i))
+    }
+
+    pub fn chord_text(&self, action: Action) -> String {
+        self.bindings.get(&action).map(|c| c.display()).unwrap_or_default()
+    }
+
+    /// 与 `action` 使用相同组合键的其他操作
+    pub fn conflicts(&self, action: Action) -> Vec<Action> {
+        let Some(c) = self.bindings.get(&action) else { return Vec::new(); };
+        self.bindings.iter().filter(|(a, o)| **a != action && *o == c).map(|(a, _)| *a).collect()
+    }
+}
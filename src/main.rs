@@ -1,19 +1,48 @@
-#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+// 仅 Windows 发布版隐藏控制台窗口；其他平台无此概念
+#![cfg_attr(all(target_os = "windows", not(debug_assertions)), windows_subsystem = "windows")]
 use eframe::NativeOptions;
 
+#[macro_use]
+mod i18n;
 mod app;
+mod batch;
+mod cameo;
 mod palette;
+mod pixel_font;
 mod color_match;
+mod contact_sheet;
+mod filters;
 mod shp;
+mod hook;
 mod image_io;
+mod io_task;
+mod keymap;
+mod mix;
+mod platform;
+mod project;
+mod selection;
+mod sequences;
+mod script;
+mod settings;
+mod stats;
+mod texture_cache;
+mod undo;
+mod update;
+mod watch;
 
 /// 程序入口：基于 eframe/egui 的桌面应用
+/// 命令行：`lvjiaShpEdit [--view] [文件...]`，`--view` 为只读快速查看模式；
+/// 文件按扩展名打开（SHP/PAL/MIX/图片），供“打开方式”与文件关联使用
 fn main() -> eframe::Result<()> {
+    i18n::set_language(settings::Settings::load().language);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let view_only = args.iter().any(|a| a == "--view");
+    let open_paths: Vec<_> = args.iter().filter(|a| !a.starts_with("--")).map(std::path::PathBuf::from).collect();
     let native_options = NativeOptions::default();
     eframe::run_native(
-        "SHP 编辑器",
+        if view_only { i18n::tr("SHP 查看器") } else { i18n::tr("SHP 编辑器") },
         native_options,
-        Box::new(|cc| Box::new(app::MixApp::new(cc))),
+        Box::new(move |cc| Box::new(app::MixApp::new(cc, view_only, open_paths))),
     )
 }
 
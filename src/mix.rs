@@ -0,0 +1,404 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::i18n::tr;
+
+// 新格式（RA 起）文件头首 u16 为 0，其后 u16 为标志位；带校验和时文件末尾另有 20 字节 SHA1
+const FLAG_ENCRYPTED: u32 = 0x0002_0000;
+
+/// 文件名 → 条目 ID 的哈希算法：TD/RA 为循环移位累加，TS/RA2 为补齐后的 CRC32
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum IdScheme {
+    Td,
+    Ts,
+}
+
+impl IdScheme {
+    pub const ALL: [IdScheme; 2] = [IdScheme::Td, IdScheme::Ts];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            IdScheme::Td => "TD / RA",
+            IdScheme::Ts => "TS / RA2",
+        }
+    }
+
+    pub fn id(self, name: &str) -> u32 {
+        let upper = name.trim().to_ascii_uppercase().into_bytes();
+        match self {
+            IdScheme::Td => {
+                let mut id = 0u32;
+                for chunk in upper.chunks(4) {
+                    let mut a = 0u32;
+                    for (j, &c) in chunk.iter().enumerate() { a |= (c as u32) << (8 * j); }
+                    id = id.rotate_left(1).wrapping_add(a);
+                }
+                id
+            }
+            IdScheme::Ts => {
+                // 长度不是 4 的倍数时：追加余数，再用最后一组首字节补齐
+                let mut buf = upper;
+                let l = buf.len();
+                let a = l & !3;
+                if l & 3 != 0 {
+                    buf.push((l - a) as u8);
+                    let pad = buf[a];
+                    for _ in 0..3 - (l & 3) { buf.push(pad); }
+                }
+                crc32(&buf)
+            }
+        }
+    }
+
+    /// 新条目输入：含 '.' 视为文件名，按本算法求 ID；否则按十六进制 ID 解析
+    pub fn parse_id(self, input: &str) -> Option<u32> {
+        let s = input.trim();
+        if s.contains('.') { return Some(self.id(s)); }
+        u32::from_str_radix(s.trim_start_matches("0x"), 16).ok()
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 { crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 }; }
+    }
+    !crc
+}
+
+/// 名称库：同一文件名按两种算法各登记一次，查询时无需知道 MIX 属于哪个游戏
+#[derive(Default)]
+pub struct NameDb {
+    names: HashMap<u32, String>,
+}
+
+/// XCC 在 MIX 内保存文件名列表的条目名
+pub const LOCAL_DB_NAME: &str = "local mix database.dat";
+
+impl NameDb {
+    pub fn len(&self) -> usize { self.names.len() }
+
+    pub fn get(&self, id: u32) -> Option<&str> { self.names.get(&id).map(|s| s.as_str()) }
+
+    pub fn add(&mut self, name: &str) {
+        let name = name.trim();
+        if name.is_empty() { return; }
+        for s in IdScheme::ALL { self.names.entry(s.id(name)).or_insert_with(|| name.to_owned()); }
+    }
+
+    /// 读取名称文件，自动识别：XCC global mix database.dat、local mix database.dat 或每行一个文件名的文本。
+    /// 返回新增的名称数
+    pub fn load_bytes(&mut self, bytes: &[u8]) -> usize {
+        let before = self.names.len();
+        if bytes.starts_with(b"XCC by Olaf van der Spek") {
+            // 44 字节文件头 + u32 数量 + 以 \0 结尾的文件名
+            for name in zero_terminated(bytes.get(48..).unwrap_or_default()) { self.add(name); }
+        } else if bytes.contains(&0) {
+            // global mix database：按游戏分段，每段 u32 数量 + 数量 × (文件名\0 描述\0)
+            let mut pos = 0usize;
+            while let Some(c) = bytes.get(pos..pos + 4) {
+                let count = u32::from_le_bytes([c[0], c[1], c[2], c[3]]) as usize;
+                pos += 4;
+                for _ in 0..count {
+                    let Some((name, next)) = read_cstr(bytes, pos) else { return self.names.len() - before; };
+                    let Some((_, next)) = read_cstr(bytes, next) else { return self.names.len() - before; };
+                    self.add(name);
+                    pos = next;
+                }
+            }
+        } else {
+            for line in String::from_utf8_lossy(bytes).lines() {
+                // 允许 "名称,描述" 或 "名称<Tab>描述" 格式，只取第一列
+                let name = line.split([',', '\t']).next().unwrap_or("").trim();
+                if !name.is_empty() && !name.starts_with('#') && !name.starts_with(';') { self.add(name); }
+            }
+        }
+        self.names.len() - before
+    }
+}
+
+fn read_cstr(bytes: &[u8], pos: usize) -> Option<(&str, usize)> {
+    let rest = bytes.get(pos..)?;
+    let end = rest.iter().position(|&b| b == 0)?;
+    Some((std::str::from_utf8(&rest[..end]).unwrap_or(""), pos + end + 1))
+}
+
+fn zero_terminated(bytes: &[u8]) -> impl Iterator<Item = &str> {
+    bytes.split(|&b| b == 0).filter_map(|s| std::str::from_utf8(s).ok()).filter(|s| !s.is_empty())
+}
+
+/// MIX 条目：offset 相对数据区起点；data 为 Some 表示新增或已替换、尚未写盘的内容
+#[derive(Clone)]
+pub struct MixEntry {
+    pub id: u32,
+    pub offset: u32,
+    pub size: u32,
+    pub data: Option<Vec<u8>>,
+}
+
+impl MixEntry {
+    pub fn is_modified(&self) -> bool { self.data.is_some() }
+}
+
+/// 未加密的 TD/RA/TS/RA2 MIX 归档：读取、增删改条目并重新打包
+pub struct MixFile {
+    pub path: PathBuf,
+    pub file_size: u64,
+    pub entries: Vec<MixEntry>,
+    /// 原文件是否带标志头（写回时保持同一格式）
+    new_format: bool,
+    body: Vec<u8>,
+    /// 有删除或改动尚未保存
+    pub dirty: bool,
+}
+
+impl MixFile {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        let mut mix = Self::from_bytes(&bytes)?;
+        mix.path = path.to_path_buf();
+        Ok(mix)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let u16_at = |o: usize| bytes.get(o..o + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+        let u32_at = |o: usize| bytes.get(o..o + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+        let short = || tr("MIX 文件过短").to_string();
+        let mut pos = 0usize;
+        let new_format = u16_at(0).ok_or_else(short)? == 0;
+        if new_format {
+            let flags = u32_at(0).ok_or_else(short)?;
+            if flags & FLAG_ENCRYPTED != 0 { return Err(tr("暂不支持加密的 MIX").into()); }
+            pos = 4;
+        }
+        let count = u16_at(pos).ok_or_else(short)? as usize;
+        let body_size = u32_at(pos + 2).ok_or_else(short)? as usize;
+        pos += 6;
+        let body_start = pos + count * 12;
+        if bytes.len() < body_start + body_size { return Err(tr("MIX 索引与文件大小不符").into()); }
+        let mut entries = Vec::with_capacity(count);
+        for i in 0..count {
+            let o = pos + i * 12;
+            let (id, offset, size) = (u32_at(o).unwrap(), u32_at(o + 4).unwrap(), u32_at(o + 8).unwrap());
+            if offset as u64 + size as u64 > body_size as u64 { return Err(trf!("条目 {} 超出数据区", format!("{:08X}", id))); }
+            entries.push(MixEntry { id, offset, size, data: None });
+        }
+        Ok(Self {
+            path: PathBuf::new(),
+            file_size: bytes.len() as u64,
+            entries,
+            new_format,
+            body: bytes[body_start..body_start + body_size].to_vec(),
+            dirty: false,
+        })
+    }
+
+    pub fn find(&self, id: u32) -> Option<usize> {
+        self.entries.iter().position(|e| e.id == id)
+    }
+
+    /// 条目当前内容（含未保存的替换）
+    pub fn read(&self, id: u32) -> Option<&[u8]> {
+        let e = &self.entries[self.find(id)?];
+        match &e.data {
+            Some(d) => Some(d),
+            None => self.body.get(e.offset as usize..(e.offset + e.size) as usize),
+        }
+    }
+
+    /// 替换或新增条目
+    pub fn put(&mut self, id: u32, data: Vec<u8>) {
+        let size = data.len() as u32;
+        match self.find(id) {
+            Some(i) => { self.entries[i].size = size; self.entries[i].data = Some(data); }
+            None => self.entries.push(MixEntry { id, offset: 0, size, data: Some(data) }),
+        }
+        self.dirty = true;
+    }
+
+    pub fn remove(&mut self, id: u32) -> bool {
+        let Some(i) = self.find(id) else { return false; };
+        self.entries.remove(i);
+        self.dirty = true;
+        true
+    }
+
+    /// 按 ID 十六进制片段或已知文件名（不区分大小写）筛选
+    pub fn search<'a>(&'a self, query: &str, names: &NameDb) -> Vec<&'a MixEntry> {
+        let q = query.trim().to_ascii_uppercase();
+        self.entries.iter().filter(|e| {
+            q.is_empty() || format!("{:08X}", e.id).contains(&q)
+                || names.get(e.id).is_some_and(|n| n.to_ascii_uppercase().contains(&q))
+        }).collect()
+    }
+
+    /// 按格式猜测所属游戏：无标志头的是 TD，其余按 TS/RA2 处理（RA 的 MIX 多为加密，不在支持范围内）
+    pub fn guess_scheme(&self) -> IdScheme {
+        if self.new_format { IdScheme::Ts } else { IdScheme::Td }
+    }
+
+    /// 找出调色板条目：(显示名, 768 字节数据)。已知名称的按 .pal 扩展名判断，
+    /// 未知名称的按大小为 768 且各分量都不超过 63（6 位 VGA 色）猜测
+    pub fn palettes(&self, names: &NameDb) -> Vec<(String, &[u8])> {
+        let mut out: Vec<(String, &[u8])> = self.entries.iter().filter(|e| e.size == 768).filter_map(|e| {
+            let data = self.read(e.id)?;
+            match names.get(e.id) {
+                Some(n) if n.to_ascii_lowercase().ends_with(".pal") => {
+                    Some((n.rsplit_once('.').map_or(n, |(stem, _)| stem).to_owned(), data))
+                }
+                Some(_) => None,
+                None if data.iter().all(|&b| b <= 63) => Some((format!("{:08X}", e.id), data)),
+                None => None,
+            }
+        }).collect();
+        out.sort_by(|a, b| a.0.cmp(&b.0));
+        out
+    }
+
+    /// MIX 内自带的 XCC 名称列表（若有）
+    pub fn local_names(&self) -> Option<&[u8]> {
+        IdScheme::ALL.iter().find_map(|s| self.read(s.id(LOCAL_DB_NAME)))
+    }
+
+    /// 重新打包：索引按 ID（有符号 32 位）排序，偏移与数据区大小重新计算；校验和不再保留
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        if self.entries.len() > u16::MAX as usize { return Err(tr("MIX 条目数超过 65535").into()); }
+        let mut order: Vec<&MixEntry> = self.entries.iter().collect();
+        order.sort_by_key(|e| e.id as i32);
+        let mut index = Vec::with_capacity(order.len() * 12);
+        let mut body = Vec::new();
+        for e in &order {
+            let data = match &e.data {
+                Some(d) => d.as_slice(),
+                None => &self.body[e.offset as usize..(e.offset + e.size) as usize],
+            };
+            let offset = u32::try_from(body.len()).map_err(|_| tr("MIX 数据超过 4GB").to_string())?;
+            index.extend_from_slice(&e.id.to_le_bytes());
+            index.extend_from_slice(&offset.to_le_bytes());
+            index.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            body.extend_from_slice(data);
+        }
+        let mut out = Vec::with_capacity(10 + index.len() + body.len());
+        // 标志位清零：不加密、不附校验和
+        if self.new_format { out.extend_from_slice(&0u32.to_le_bytes()); }
+        out.extend_from_slice(&(order.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        out.extend_from_slice(&index);
+        out.extend_from_slice(&body);
+        Ok(out)
+    }
+
+    /// 写到 `path` 并以新文件内容刷新自身（偏移、大小、未保存改动）
+    pub fn save_as(&mut self, path: &Path) -> Result<(), String> {
+        let bytes = self.to_bytes()?;
+        std::fs::write(path, &bytes).map_err(|e| e.to_string())?;
+        let fresh = Self::from_bytes(&bytes)?;
+        self.entries = fresh.entries;
+        self.body = fresh.body;
+        self.file_size = fresh.file_size;
+        self.path = path.to_path_buf();
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn save(&mut self) -> Result<(), String> {
+        let path = self.path.clone();
+        self.save_as(&path)
+    }
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut v = bytes as f64;
+    let mut u = 0;
+    while v >= 1024.0 && u < UNITS.len() - 1 { v /= 1024.0; u += 1; }
+    if u == 0 { format!("{} B", bytes) } else { format!("{:.1} {}", v, UNITS[u]) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 旧格式（TD，无标志头）MIX：条目按给定顺序依次写入数据区
+    fn build(entries: &[(u32, &[u8])]) -> Vec<u8> {
+        let body: Vec<u8> = entries.iter().flat_map(|(_, d)| d.iter().copied()).collect();
+        let mut out = Vec::new();
+        out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        let mut offset = 0u32;
+        for (id, d) in entries {
+            out.extend_from_slice(&id.to_le_bytes());
+            out.extend_from_slice(&offset.to_le_bytes());
+            out.extend_from_slice(&(d.len() as u32).to_le_bytes());
+            offset += d.len() as u32;
+        }
+        out.extend_from_slice(&body);
+        out
+    }
+
+    #[test]
+    fn repack_roundtrip() {
+        let bytes = build(&[(0x2000_0000, b"abc"), (0x9000_0000, b"negative"), (0x1000_0000, b"xy")]);
+        let mut mix = MixFile::from_bytes(&bytes).unwrap();
+        assert_eq!(mix.read(0x9000_0000), Some(&b"negative"[..]));
+        mix.put(0x0000_0005, b"new entry".to_vec());
+        mix.put(0x1000_0000, b"replaced".to_vec());
+        assert!(mix.remove(0x2000_0000));
+        let out = mix.to_bytes().unwrap();
+        // 3 条 × 12 字节索引 + 数据区
+        let body = 8 + 9 + 8;
+        assert_eq!(u16::from_le_bytes([out[0], out[1]]), 3);
+        assert_eq!(u32::from_le_bytes(out[2..6].try_into().unwrap()), body);
+        assert_eq!(out.len(), 6 + 3 * 12 + body as usize);
+        let back = MixFile::from_bytes(&out).unwrap();
+        // 索引按有符号 ID 排序：0x90000000 为负数排在最前
+        assert_eq!(back.entries.iter().map(|e| e.id).collect::<Vec<_>>(), [0x9000_0000, 0x0000_0005, 0x1000_0000]);
+        assert_eq!(back.read(0x9000_0000), Some(&b"negative"[..]));
+        assert_eq!(back.read(0x0000_0005), Some(&b"new entry"[..]));
+        assert_eq!(back.read(0x1000_0000), Some(&b"replaced"[..]));
+        assert_eq!(back.read(0x2000_0000), None);
+        assert!(back.entries.iter().all(|e| !e.is_modified()));
+        // save_as 写盘后以新内容刷新自身
+        let path = std::env::temp_dir().join(format!("lvshp_mix_test_{}.mix", std::process::id()));
+        mix.save_as(&path).unwrap();
+        let written = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(written, out);
+        assert!(!mix.dirty && mix.entries.iter().all(|e| !e.is_modified()));
+        assert_eq!(mix.file_size, out.len() as u64);
+        assert_eq!(mix.read(0x1000_0000), Some(&b"replaced"[..]));
+    }
+
+    #[test]
+    fn repack_drops_checksum() {
+        // 新格式：标志头带校验和位，文件末尾 20 字节 SHA1
+        let old = build(&[(7, b"data")]);
+        let mut bytes = 0x0001_0000u32.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&old);
+        bytes.extend_from_slice(&[0xAA; 20]);
+        let mix = MixFile::from_bytes(&bytes).unwrap();
+        let out = mix.to_bytes().unwrap();
+        assert_eq!(&out[..4], &[0; 4]);
+        assert_eq!(out.len(), 4 + old.len());
+        assert_eq!(MixFile::from_bytes(&out).unwrap().read(7), Some(&b"data"[..]));
+    }
+
+    #[test]
+    fn known_ids() {
+        // XCC 公布的 ID；22 个字符不是 4 的倍数，TS 走补齐分支
+        assert_eq!(IdScheme::Ts.id(LOCAL_DB_NAME), 0x366E_051F);
+        assert_eq!(IdScheme::Td.id(LOCAL_DB_NAME), 0x54C2_D545);
+        // 不区分大小写、忽略首尾空白
+        assert_eq!(IdScheme::Ts.id(" Local Mix Database.DAT "), 0x366E_051F);
+        // TD：每 4 字节按小端拼成一个数，循环左移 1 位后累加
+        assert_eq!(IdScheme::Td.id("abcde"), 0x4443_4241u32.rotate_left(1).wrapping_add(0x45));
+        // TS：长度是 4 的倍数时不补齐，即普通 CRC32
+        assert_eq!(IdScheme::Ts.id("ABCD"), crc32(b"ABCD"));
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        // 长度 5：追加余数 1，再用最后一组首字节 'E' 补齐两字节
+        assert_eq!(IdScheme::Ts.id("abcde"), crc32(b"ABCDE\x01EE"));
+        assert_eq!(IdScheme::Ts.parse_id("local mix database.dat"), Some(0x366E_051F));
+        assert_eq!(IdScheme::Td.parse_id("0x366E051F"), Some(0x366E_051F));
+    }
+}
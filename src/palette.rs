@@ -1,9 +1,70 @@
 use eframe::egui::Color32;
 use rust_embed::RustEmbed;
 
+use crate::i18n::tr;
+
 #[derive(Clone)]
 pub struct Palette {
     pub colors: [Color32; 256],
+    pub roles: PaletteRoles,
+}
+
+/// 调色板索引的特殊用途
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PaletteRole {
+    Normal,
+    Transparent,
+    Shadow,
+    Remap,
+    Cycling,
+}
+
+impl PaletteRole {
+    pub fn label(self) -> &'static str {
+        match self {
+            PaletteRole::Normal => tr("普通"),
+            PaletteRole::Transparent => tr("透明"),
+            PaletteRole::Shadow => tr("阴影"),
+            PaletteRole::Remap => tr("阵营色 (Remap)"),
+            PaletteRole::Cycling => tr("循环动画色"),
+        }
+    }
+}
+
+/// 每个调色板的索引角色元数据：按所属游戏给出默认值（参照各游戏引擎的约定）
+#[derive(Clone)]
+pub struct PaletteRoles {
+    pub shadow: Option<u8>,
+    pub remap: Option<(u8, u8)>,
+    pub cycling: Vec<(u8, u8)>,
+}
+
+impl Default for PaletteRoles {
+    /// 默认按 TS/RA2 约定：1 为阴影，16-31 为阵营色
+    fn default() -> Self {
+        Self { shadow: Some(1), remap: Some((16, 31)), cycling: Vec::new() }
+    }
+}
+
+impl PaletteRoles {
+    /// 按内置调色板目录名（TD/RA1/TS/RA2/YR）选择约定
+    pub fn for_game(folder: &str) -> Self {
+        match folder.to_ascii_uppercase().as_str() {
+            // TD：阴影 4，阵营色 176-191，水面循环 32-38
+            "TD" => Self { shadow: Some(4), remap: Some((176, 191)), cycling: vec![(32, 38)] },
+            // RA1：阴影 4，阵营色 80-95，水面循环 96-102
+            "RA1" => Self { shadow: Some(4), remap: Some((80, 95)), cycling: vec![(96, 102)] },
+            _ => Self::default(),
+        }
+    }
+
+    pub fn role(&self, idx: u8) -> PaletteRole {
+        if idx == 0 { return PaletteRole::Transparent; }
+        if self.shadow == Some(idx) { return PaletteRole::Shadow; }
+        if self.remap.is_some_and(|(a, b)| (a..=b).contains(&idx)) { return PaletteRole::Remap; }
+        if self.cycling.iter().any(|&(a, b)| (a..=b).contains(&idx)) { return PaletteRole::Cycling; }
+        PaletteRole::Normal
+    }
 }
 
 impl Palette {
@@ -14,12 +75,12 @@ impl Palette {
             let v = i as u8;
             arr[i as usize] = Color32::from_rgb(v, v, v);
         }
-        Self { colors: arr }
+        Self { colors: arr, roles: PaletteRoles::default() }
     }
 
     /// 从 `.pal` 的 768 字节（RGB*256）构建调色板
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
-        if bytes.len() < 256 * 3 { return Err("PAL字节数不足".into()); }
+        if bytes.len() < 256 * 3 { return Err(tr("PAL字节数不足").into()); }
         let mut arr = [Color32::BLACK; 256];
         for i in 0..256usize {
             let r = bytes[i * 3];
@@ -27,7 +88,7 @@ impl Palette {
             let b = bytes[i * 3 + 2];
             arr[i] = Color32::from_rgb(r, g, b);
         }
-        Ok(Self { colors: arr })
+        Ok(Self { colors: arr, roles: PaletteRoles::default() })
     }
 
     /// 转为 `.pal` 字节序列（RGB*256）
@@ -41,7 +102,6 @@ impl Palette {
         out
     }
 
-    #[allow(dead_code)]
     pub fn from_directory(dir: &std::path::Path) -> Vec<(String, Self)> {
         let mut v = Vec::new();
         if let Ok(rd) = std::fs::read_dir(dir) {
@@ -66,6 +126,187 @@ impl Palette {
     }
 }
 
+/// 色盲模拟（Machado 2009，严重程度 1.0），仅用于预览
+#[derive(Copy, Clone, PartialEq, Eq, Default)]
+pub enum ColorBlindMode {
+    #[default]
+    None,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl ColorBlindMode {
+    pub const ALL: [ColorBlindMode; 4] = [ColorBlindMode::None, ColorBlindMode::Protanopia, ColorBlindMode::Deuteranopia, ColorBlindMode::Tritanopia];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ColorBlindMode::None => tr("正常视觉"),
+            ColorBlindMode::Protanopia => tr("红色盲 (Protanopia)"),
+            ColorBlindMode::Deuteranopia => tr("绿色盲 (Deuteranopia)"),
+            ColorBlindMode::Tritanopia => tr("蓝色盲 (Tritanopia)"),
+        }
+    }
+
+    fn matrix(self) -> Option<[[f32; 3]; 3]> {
+        match self {
+            ColorBlindMode::None => None,
+            ColorBlindMode::Protanopia => Some([[0.152286, 1.052583, -0.204868], [0.114503, 0.786281, 0.099216], [-0.003882, -0.048116, 1.051998]]),
+            ColorBlindMode::Deuteranopia => Some([[0.367322, 0.860646, -0.227968], [0.280085, 0.672501, 0.047413], [-0.011820, 0.042940, 0.968881]]),
+            ColorBlindMode::Tritanopia => Some([[1.255528, -0.076749, -0.178779], [-0.078411, 0.930809, 0.147602], [0.004733, 0.691367, 0.303900]]),
+        }
+    }
+}
+
+impl Palette {
+    /// 返回按色盲模式变换后的调色板（在线性 RGB 空间中做矩阵变换）
+    pub fn simulated(&self, mode: ColorBlindMode) -> Palette {
+        let Some(m) = mode.matrix() else { return self.clone(); };
+        fn to_lin(v: u8) -> f32 { let c = v as f32 / 255.0; if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) } }
+        fn to_srgb(c: f32) -> u8 { let c = c.clamp(0.0, 1.0); let v = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }; (v * 255.0).round() as u8 }
+        let mut out = self.clone();
+        for c in out.colors.iter_mut() {
+            let l = [to_lin(c.r()), to_lin(c.g()), to_lin(c.b())];
+            let t = |row: [f32; 3]| to_srgb(row[0] * l[0] + row[1] * l[1] + row[2] * l[2]);
+            *c = Color32::from_rgb(t(m[0]), t(m[1]), t(m[2]));
+        }
+        out
+    }
+}
+
+/// 调色板色彩调整参数；各项为 0 时不改变颜色
+#[derive(Clone, Copy, PartialEq)]
+pub struct PaletteAdjust {
+    /// 亮度倍率偏移（-1..1），按比例缩放，6 位与 8 位调色板表现一致
+    pub brightness: f32,
+    /// 对比度（-1..1），以所选范围的平均亮度为支点
+    pub contrast: f32,
+    /// 饱和度（-1..1）
+    pub saturation: f32,
+    /// 色相旋转角度
+    pub hue: f32,
+    /// 只调整该闭区间内的索引
+    pub range: (u8, u8),
+}
+
+impl Default for PaletteAdjust {
+    fn default() -> Self {
+        Self { brightness: 0.0, contrast: 0.0, saturation: 0.0, hue: 0.0, range: (0, 255) }
+    }
+}
+
+impl PaletteAdjust {
+    pub fn is_identity(&self) -> bool {
+        self.brightness == 0.0 && self.contrast == 0.0 && self.saturation == 0.0 && self.hue == 0.0
+    }
+
+    pub fn apply(&self, pal: &Palette) -> Palette {
+        let mut out = pal.clone();
+        if self.is_identity() { return out; }
+        // 原始 .pal 多为 6 位 VGA 色（0-63），结果不超出原有取值范围
+        let limit = if pal.colors.iter().all(|c| c.r() <= 63 && c.g() <= 63 && c.b() <= 63) { 63.0 } else { 255.0 };
+        let (lo, hi) = (self.range.0.min(self.range.1) as usize, self.range.0.max(self.range.1) as usize);
+        let sel = &pal.colors[lo..=hi];
+        let pivot = sel.iter().map(|c| (c.r() as f32 + c.g() as f32 + c.b() as f32) / 3.0).sum::<f32>() / sel.len() as f32;
+        for c in &mut out.colors[lo..=hi] {
+            let (h, s, v) = rgb_to_hsv(c.r() as f32, c.g() as f32, c.b() as f32);
+            let (h, s) = ((h + self.hue).rem_euclid(360.0), (s * (1.0 + self.saturation)).clamp(0.0, 1.0));
+            let (r, g, b) = hsv_to_rgb(h, s, v);
+            let f = |x: f32| {
+                let x = x * (1.0 + self.brightness);
+                ((x - pivot) * (1.0 + self.contrast) + pivot).round().clamp(0.0, limit) as u8
+            };
+            *c = Color32::from_rgb(f(r), f(g), f(b));
+        }
+        out
+    }
+}
+
+/// 整理调色板顺序时的排序依据
+#[derive(Clone, Copy, PartialEq)]
+pub enum PaletteSort {
+    /// 灰色在前按明度排，其余按 12 段色相分组、组内由暗到亮，得到一条条色阶
+    Hue,
+    Luminance,
+}
+
+impl PaletteSort {
+    pub const ALL: [PaletteSort; 2] = [PaletteSort::Hue, PaletteSort::Luminance];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PaletteSort::Hue => tr("按色相分组"),
+            PaletteSort::Luminance => tr("按亮度"),
+        }
+    }
+
+    fn key(self, c: Color32) -> (f32, f32, f32) {
+        let (r, g, b) = (c.r() as f32, c.g() as f32, c.b() as f32);
+        let luma = 0.299 * r + 0.587 * g + 0.114 * b;
+        let (h, s, _) = rgb_to_hsv(r, g, b);
+        match self {
+            PaletteSort::Hue if s < 0.15 => (0.0, luma, 0.0),
+            PaletteSort::Hue => (1.0 + (h / 30.0).floor(), luma, h),
+            PaletteSort::Luminance => (luma, h, 0.0),
+        }
+    }
+}
+
+impl Palette {
+    /// 排序后的索引顺序：新的第 k 号放原来的 `order[k]` 号颜色。索引 0 不动；
+    /// `keep_special` 时阴影/阵营色/循环色也留在原位，只在普通索引之间重排
+    pub fn sorted_order(&self, by: PaletteSort, keep_special: bool) -> Vec<u8> {
+        let slots: Vec<u8> = (1..=255u8).filter(|&i| !keep_special || self.roles.role(i) == PaletteRole::Normal).collect();
+        let mut sorted = slots.clone();
+        sorted.sort_by(|&a, &b| {
+            by.key(self.colors[a as usize]).partial_cmp(&by.key(self.colors[b as usize])).unwrap_or(std::cmp::Ordering::Equal).then(a.cmp(&b))
+        });
+        let mut order: Vec<u8> = (0..=255u8).collect();
+        for (&slot, &src) in slots.iter().zip(&sorted) { order[slot as usize] = src; }
+        order
+    }
+
+    /// 按 `order` 重排颜色；角色元数据按位置保留
+    pub fn reordered(&self, order: &[u8]) -> Palette {
+        let mut out = self.clone();
+        for (c, &src) in out.colors.iter_mut().zip(order) { *c = self.colors[src as usize]; }
+        out
+    }
+}
+
+/// 重排顺序的逆映射（原索引 → 新索引），用于改写像素使画面不变
+pub fn inverse_order(order: &[u8]) -> [u8; 256] {
+    let mut table: [u8; 256] = std::array::from_fn(|i| i as u8);
+    for (k, &src) in order.iter().enumerate() { table[src as usize] = k as u8; }
+    table
+}
+
+// h 为角度，s 为 0..1，v 与输入同量纲
+fn rgb_to_hsv(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let d = max - r.min(g).min(b);
+    let h = if d == 0.0 { 0.0 }
+        else if max == r { 60.0 * ((g - b) / d).rem_euclid(6.0) }
+        else if max == g { 60.0 * ((b - r) / d + 2.0) }
+        else { 60.0 * ((r - g) / d + 4.0) };
+    (h, if max == 0.0 { 0.0 } else { d / max }, max)
+}
+
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> (f32, f32, f32) {
+    let c = v * s;
+    let x = c * (1.0 - ((h / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match (h / 60.0) as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    (r + m, g + m, b + m)
+}
+
 #[derive(RustEmbed)]
 #[folder = "Palettes"]
 pub struct EmbeddedPalettes;
@@ -78,9 +319,10 @@ impl EmbeddedPalettes {
             let path = f.as_ref();
             if !path.to_ascii_lowercase().ends_with(".pal") { continue; }
             if let Some(file) = EmbeddedPalettes::get(path) {
-                if let Ok(pal) = Palette::from_bytes(file.data.as_ref()) {
+                if let Ok(mut pal) = Palette::from_bytes(file.data.as_ref()) {
                     let p = std::path::Path::new(path);
                     let folder = p.parent().and_then(|s| s.to_str()).unwrap_or("").to_string();
+                    pal.roles = PaletteRoles::for_game(&folder);
                     let name = p.file_stem().and_then(|s| s.to_str()).unwrap_or("PAL").to_string();
                     groups.entry(folder).or_default().push((name, pal));
                 }
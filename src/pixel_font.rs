@@ -0,0 +1,119 @@
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+
+use crate::i18n::tr;
+
+/// 文字工具的字体：内置 5×7 点阵（仅 ASCII）或内嵌 TTF（按覆盖率阈值二值化，支持中文）
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextFont {
+    Bitmap,
+    Ttf,
+}
+
+impl TextFont {
+    pub const ALL: [TextFont; 2] = [TextFont::Bitmap, TextFont::Ttf];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TextFont::Bitmap => tr("点阵 5×7"),
+            TextFont::Ttf => tr("TTF 字体"),
+        }
+    }
+}
+
+/// 文字参数：点阵按整数倍放大；TTF 按像素字号，覆盖率达到阈值的像素着色
+#[derive(Clone, PartialEq, Hash)]
+pub struct TextParams {
+    pub text: String,
+    pub font: TextFont,
+    pub scale: u32,
+    pub size: u32,
+    pub threshold: u8,
+}
+
+impl Default for TextParams {
+    fn default() -> Self {
+        Self { text: "TEXT".into(), font: TextFont::Bitmap, scale: 1, size: 12, threshold: 128 }
+    }
+}
+
+/// 把文字栅格化为掩码 (宽, 高, true 为着色)；多行按换行分隔。没有可见像素时返回 None
+pub fn rasterize(p: &TextParams, ttf: &[u8]) -> Option<(u32, u32, Vec<bool>)> {
+    let lines: Vec<&str> = p.text.lines().collect();
+    let mut dots: Vec<(u32, u32)> = Vec::new();
+    match p.font {
+        TextFont::Bitmap => {
+            let k = p.scale.max(1);
+            for (row, line) in lines.iter().enumerate() {
+                for (col, ch) in line.chars().enumerate() {
+                    let (ox, oy) = (col as u32 * 6 * k, row as u32 * 8 * k);
+                    for (cx, bits) in glyph(ch).iter().enumerate() {
+                        for cy in (0..7).filter(|b| bits >> b & 1 == 1) {
+                            for (dx, dy) in (0..k).flat_map(|dx| (0..k).map(move |dy| (dx, dy))) {
+                                dots.push((ox + cx as u32 * k + dx, oy + cy * k + dy));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        TextFont::Ttf => {
+            let font = FontRef::try_from_slice(ttf).ok()?;
+            let scale = PxScale::from(p.size.max(4) as f32);
+            let sf = font.as_scaled(scale);
+            let line_h = sf.height() + sf.line_gap();
+            for (row, line) in lines.iter().enumerate() {
+                let mut pen = 0.0f32;
+                let baseline = row as f32 * line_h + sf.ascent();
+                for ch in line.chars() {
+                    let id = sf.glyph_id(ch);
+                    let glyph = id.with_scale_and_position(scale, ab_glyph::point(pen, baseline));
+                    pen += sf.h_advance(id);
+                    let Some(outlined) = font.outline_glyph(glyph) else { continue; };
+                    let b = outlined.px_bounds();
+                    outlined.draw(|gx, gy, cov| {
+                        let (x, y) = (b.min.x as i32 + gx as i32, b.min.y as i32 + gy as i32);
+                        if x >= 0 && y >= 0 && cov * 255.0 >= p.threshold as f32 { dots.push((x as u32, y as u32)); }
+                    });
+                }
+            }
+        }
+    }
+    let w = dots.iter().map(|d| d.0 + 1).max()?;
+    let h = dots.iter().map(|d| d.1 + 1).max()?;
+    let mut mask = vec![false; (w * h) as usize];
+    for (x, y) in dots { mask[(y * w + x) as usize] = true; }
+    Some((w, h, mask))
+}
+
+// 5×7 点阵：每字 5 列，每列低位在上；ASCII 以外的字符显示为 '?'
+fn glyph(ch: char) -> &'static [u8; 5] {
+    let i = ch as usize;
+    if (0x20..0x7F).contains(&i) { &FONT_5X7[i - 0x20] } else { &FONT_5X7[('?' as usize) - 0x20] }
+}
+
+const FONT_5X7: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5F, 0x00, 0x00], [0x00, 0x07, 0x00, 0x07, 0x00], [0x14, 0x7F, 0x14, 0x7F, 0x14],
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62], [0x36, 0x49, 0x56, 0x20, 0x50], [0x00, 0x05, 0x03, 0x00, 0x00],
+    [0x00, 0x1C, 0x22, 0x41, 0x00], [0x00, 0x41, 0x22, 0x1C, 0x00], [0x08, 0x2A, 0x1C, 0x2A, 0x08], [0x08, 0x08, 0x3E, 0x08, 0x08],
+    [0x00, 0x50, 0x30, 0x00, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08], [0x00, 0x60, 0x60, 0x00, 0x00], [0x20, 0x10, 0x08, 0x04, 0x02],
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], [0x00, 0x42, 0x7F, 0x40, 0x00], [0x42, 0x61, 0x51, 0x49, 0x46], [0x21, 0x41, 0x45, 0x4B, 0x31],
+    [0x18, 0x14, 0x12, 0x7F, 0x10], [0x27, 0x45, 0x45, 0x45, 0x39], [0x3C, 0x4A, 0x49, 0x49, 0x30], [0x01, 0x71, 0x09, 0x05, 0x03],
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x06, 0x49, 0x49, 0x29, 0x1E], [0x00, 0x36, 0x36, 0x00, 0x00], [0x00, 0x56, 0x36, 0x00, 0x00],
+    [0x08, 0x14, 0x22, 0x41, 0x00], [0x14, 0x14, 0x14, 0x14, 0x14], [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x51, 0x09, 0x06],
+    [0x32, 0x49, 0x79, 0x41, 0x3E], [0x7E, 0x11, 0x11, 0x11, 0x7E], [0x7F, 0x49, 0x49, 0x49, 0x36], [0x3E, 0x41, 0x41, 0x41, 0x22],
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], [0x7F, 0x49, 0x49, 0x49, 0x41], [0x7F, 0x09, 0x09, 0x09, 0x01], [0x3E, 0x41, 0x49, 0x49, 0x7A],
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], [0x00, 0x41, 0x7F, 0x41, 0x00], [0x20, 0x40, 0x41, 0x3F, 0x01], [0x7F, 0x08, 0x14, 0x22, 0x41],
+    [0x7F, 0x40, 0x40, 0x40, 0x40], [0x7F, 0x02, 0x0C, 0x02, 0x7F], [0x7F, 0x04, 0x08, 0x10, 0x7F], [0x3E, 0x41, 0x41, 0x41, 0x3E],
+    [0x7F, 0x09, 0x09, 0x09, 0x06], [0x3E, 0x41, 0x51, 0x21, 0x5E], [0x7F, 0x09, 0x19, 0x29, 0x46], [0x46, 0x49, 0x49, 0x49, 0x31],
+    [0x01, 0x01, 0x7F, 0x01, 0x01], [0x3F, 0x40, 0x40, 0x40, 0x3F], [0x1F, 0x20, 0x40, 0x20, 0x1F], [0x3F, 0x40, 0x38, 0x40, 0x3F],
+    [0x63, 0x14, 0x08, 0x14, 0x63], [0x07, 0x08, 0x70, 0x08, 0x07], [0x61, 0x51, 0x49, 0x45, 0x43], [0x00, 0x7F, 0x41, 0x41, 0x00],
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x7F, 0x00], [0x04, 0x02, 0x01, 0x02, 0x04], [0x40, 0x40, 0x40, 0x40, 0x40],
+    [0x00, 0x01, 0x02, 0x04, 0x00], [0x20, 0x54, 0x54, 0x54, 0x78], [0x7F, 0x48, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x20],
+    [0x38, 0x44, 0x44, 0x48, 0x7F], [0x38, 0x54, 0x54, 0x54, 0x18], [0x08, 0x7E, 0x09, 0x01, 0x02], [0x0C, 0x52, 0x52, 0x52, 0x3E],
+    [0x7F, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7D, 0x40, 0x00], [0x20, 0x40, 0x44, 0x3D, 0x00], [0x7F, 0x10, 0x28, 0x44, 0x00],
+    [0x00, 0x41, 0x7F, 0x40, 0x00], [0x7C, 0x04, 0x18, 0x04, 0x78], [0x7C, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38],
+    [0x7C, 0x14, 0x14, 0x14, 0x08], [0x08, 0x14, 0x14, 0x18, 0x7C], [0x7C, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x20],
+    [0x04, 0x3F, 0x44, 0x40, 0x20], [0x3C, 0x40, 0x40, 0x20, 0x7C], [0x1C, 0x20, 0x40, 0x20, 0x1C], [0x3C, 0x40, 0x30, 0x40, 0x3C],
+    [0x44, 0x28, 0x10, 0x28, 0x44], [0x0C, 0x50, 0x50, 0x50, 0x3C], [0x44, 0x64, 0x54, 0x4C, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00],
+    [0x00, 0x00, 0x7F, 0x00, 0x00], [0x00, 0x41, 0x36, 0x08, 0x00], [0x10, 0x08, 0x08, 0x10, 0x08],
+];
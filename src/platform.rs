@@ -0,0 +1,20 @@
+use std::path::PathBuf;
+
+// 平台相关路径
+
+/// 应用配置目录（不保证已存在）：
+/// - Windows: %APPDATA%\lvSHP
+/// - macOS: ~/Library/Application Support/lvSHP
+/// - 其他 Unix: $XDG_CONFIG_HOME/lvSHP 或 ~/.config/lvSHP
+pub fn config_dir() -> Option<PathBuf> {
+    let env = |k: &str| std::env::var_os(k).filter(|v| !v.is_empty()).map(PathBuf::from);
+    let base = if cfg!(target_os = "windows") {
+        env("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env("HOME").map(|h| h.join("Library").join("Application Support"))
+    } else {
+        env("XDG_CONFIG_HOME").or_else(|| env("HOME").map(|h| h.join(".config")))
+    };
+    base.map(|b| b.join("lvSHP"))
+}
+
@@ -0,0 +1,178 @@
+use std::io::{Cursor, Read, Write};
+
+use serde::{Deserialize, Serialize};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::i18n::tr;
+use crate::palette::Palette;
+use crate::sequences::Sequence;
+use crate::shp::{Compression, EmptyFrames, Frame, SHP};
+
+// 工程文件格式版本；读取时拒绝更新的版本
+const PROJECT_VERSION: u32 = 1;
+
+// zip 内的条目：元数据为 JSON，调色板与帧像素为原始字节，均以 deflate 压缩
+const META_ENTRY: &str = "project.json";
+const PALETTE_ENTRY: &str = "palette.pal";
+
+fn frame_entry(i: usize) -> String { format!("frames/{:05}.bin", i) }
+
+/// `.lvshp` 工程：索引帧、调色板、对齐偏移、序列与编辑状态一并打包为 zip，
+/// 重新打开时原样还原，不必从导出的 SHP 重建
+#[derive(Serialize, Deserialize)]
+pub struct Project {
+    pub version: u32,
+    pub width: u32,
+    pub height: u32,
+    pub transparent: u8,
+    /// 空帧写为 1×1 透明块（否则写 0 偏移）
+    pub minimal_empty_frames: bool,
+    pub frames: Vec<ProjectFrame>,
+    pub palette_name: String,
+    /// 调色板 RGB*256，单独存为 zip 条目
+    #[serde(skip)]
+    pub palette: Vec<u8>,
+    pub sequences: Vec<Sequence>,
+    pub editor: EditorState,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ProjectFrame {
+    pub offset: (i32, i32),
+    pub rle0: bool,
+    /// 索引像素，按行排列；单独存为 zip 条目
+    #[serde(skip)]
+    pub pixels: Vec<u8>,
+}
+
+/// 打开工程时恢复的编辑状态
+#[derive(Default, Serialize, Deserialize)]
+pub struct EditorState {
+    pub current_frame: usize,
+    pub scale: f32,
+    pub selected_frames: Vec<usize>,
+    pub brush_index: u8,
+    pub brush_index2: u8,
+    /// 上次导出的 SHP 路径
+    pub export_path: Option<String>,
+}
+
+impl Project {
+    pub fn capture(shp: &SHP, palette_name: &str, palette: &Palette, sequences: &[Sequence], editor: EditorState) -> Self {
+        let frames = shp.frames.iter().map(|f| ProjectFrame {
+            offset: f.offset,
+            rle0: f.compression == Compression::Rle0,
+            pixels: f.pixels.clone(),
+        }).collect();
+        Self {
+            version: PROJECT_VERSION,
+            width: shp.width,
+            height: shp.height,
+            transparent: shp.transparent,
+            minimal_empty_frames: shp.empty_frames == EmptyFrames::MinimalBlock,
+            frames,
+            palette_name: palette_name.to_string(),
+            palette: palette.to_bytes(),
+            sequences: sequences.to_vec(),
+            editor,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        let opts = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let meta = serde_json::to_vec(self).map_err(|e| e.to_string())?;
+        let entries = [(META_ENTRY.to_string(), meta.as_slice()), (PALETTE_ENTRY.to_string(), self.palette.as_slice())].into_iter()
+            .chain(self.frames.iter().enumerate().map(|(i, f)| (frame_entry(i), f.pixels.as_slice())));
+        for (name, data) in entries {
+            zip.start_file(name, opts).map_err(|e| e.to_string())?;
+            zip.write_all(data).map_err(|e| e.to_string())?;
+        }
+        Ok(zip.finish().map_err(|e| e.to_string())?.into_inner())
+    }
+
+    pub fn load(bytes: &[u8]) -> Result<Self, String> {
+        let mut zip = ZipArchive::new(Cursor::new(bytes)).map_err(|e| e.to_string())?;
+        let mut p: Project = serde_json::from_slice(&read_entry(&mut zip, META_ENTRY)?).map_err(|e| e.to_string())?;
+        if p.version > PROJECT_VERSION { return Err(trf!("工程文件版本 {} 过新", p.version)); }
+        p.palette = read_entry(&mut zip, PALETTE_ENTRY)?;
+        for (i, f) in p.frames.iter_mut().enumerate() {
+            f.pixels = read_entry(&mut zip, &frame_entry(i))?;
+        }
+        Ok(p)
+    }
+
+    /// 还原文档；帧像素数与画布尺寸不符时报错
+    pub fn to_shp(&self) -> Result<SHP, String> {
+        if self.width == 0 || self.height == 0 { return Err(tr("工程画布尺寸无效").into()); }
+        let n = self.width as usize * self.height as usize;
+        let frames = self.frames.iter().enumerate().map(|(i, f)| {
+            if f.pixels.len() != n { return Err(trf!("工程第 {} 帧数据损坏", i)); }
+            let compression = if f.rle0 { Compression::Rle0 } else { Compression::Uncompressed };
+            Ok(Frame { offset: f.offset, compression, ..Frame::new(f.pixels.clone()) })
+        }).collect::<Result<Vec<_>, String>>()?;
+        let mut shp = SHP::new(self.width, self.height, 0);
+        shp.frames = frames;
+        shp.transparent = self.transparent;
+        shp.empty_frames = if self.minimal_empty_frames { EmptyFrames::MinimalBlock } else { EmptyFrames::NullOffset };
+        Ok(shp)
+    }
+
+    pub fn to_palette(&self) -> Result<Palette, String> {
+        Palette::from_bytes(&self.palette)
+    }
+}
+
+/// 读取整个条目；缺失时报告条目名
+fn read_entry(zip: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Vec<u8>, String> {
+    let mut entry = zip.by_name(name).map_err(|_| trf!("工程缺少条目 {}", name))?;
+    let mut out = Vec::new();
+    entry.read_to_end(&mut out).map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Project {
+        let mut shp = SHP::new(12, 8, 3);
+        shp.transparent = 5;
+        shp.empty_frames = EmptyFrames::MinimalBlock;
+        shp.frames[0].pixels.fill(5);
+        shp.frames[0].pixels[20] = 9;
+        shp.frames[1].compression = Compression::Rle0;
+        shp.frames[1].offset = (3, -2);
+        for p in &mut shp.frames[1].pixels[30..50] { *p = 7; }
+        let mut pal = Palette::default_grayscale();
+        pal.colors[7] = eframe::egui::Color32::from_rgb(252, 0, 4);
+        let editor = EditorState { current_frame: 1, scale: 3.0, selected_frames: vec![0, 2], brush_index: 7, brush_index2: 9, export_path: None };
+        Project::capture(&shp, "temperat", &pal, &[], editor)
+    }
+
+    #[test]
+    fn roundtrip() {
+        let p = sample();
+        let back = Project::load(&p.to_bytes().unwrap()).unwrap();
+        let shp = back.to_shp().unwrap();
+        let orig = p.to_shp().unwrap();
+        assert_eq!((shp.width, shp.height, shp.transparent), (12, 8, 5));
+        assert!(shp.empty_frames == EmptyFrames::MinimalBlock);
+        assert!(shp.frames[1].compression == Compression::Rle0 && shp.frames[0].compression == Compression::Uncompressed);
+        assert_eq!(shp.frames[1].offset, (3, -2));
+        for (a, b) in shp.frames.iter().zip(&orig.frames) { assert_eq!(a.pixels, b.pixels); }
+        assert_eq!(back.to_palette().unwrap().to_bytes(), p.to_palette().unwrap().to_bytes());
+        assert_eq!(back.palette_name, "temperat");
+        assert_eq!((back.editor.current_frame, back.editor.selected_frames, back.editor.brush_index2), (1, vec![0, 2], 9));
+    }
+
+    #[test]
+    fn truncated_frame_entry() {
+        let mut p = sample();
+        p.frames[2].pixels.pop();
+        let back = Project::load(&p.to_bytes().unwrap()).unwrap();
+        let e = back.to_shp().err().unwrap();
+        assert!(e.contains("工程第 2 帧数据损坏"), "{e}");
+    }
+}
@@ -0,0 +1,180 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use eframe::egui::Color32;
+use rhai::{Array, Dynamic, Engine, EvalAltResult};
+
+use crate::i18n::tr;
+use crate::palette::Palette;
+use crate::shp::{Frame, SHP};
+
+// 单次运行的操作数上限，防止死循环卡住界面
+const MAX_OPERATIONS: u64 = 200_000_000;
+
+/// 脚本可用函数一览（控制台中显示）
+pub fn api_help() -> String {
+    [
+        "frame_count()  width()  height()  current_frame()".to_owned(),
+        "get_pixel(f, x, y)  set_pixel(f, x, y, idx)".to_owned(),
+        format!("remap(f, from, to) -> n    // {}", tr("返回替换的像素数；f 为 -1 时作用于全部帧")),
+        "get_color(i) -> [r, g, b]  set_color(i, r, g, b)".to_owned(),
+        "get_offset(f) -> [x, y]  set_offset(f, x, y)".to_owned(),
+        format!("add_frame() -> f    import_png(path) -> f    // {}", tr("返回新帧序号")),
+        "export_png(f, path)  print(...)".to_owned(),
+    ].join("\n")
+}
+
+/// 控制台的初始示例脚本
+pub fn example() -> String {
+    format!("\
+// {}
+let f = current_frame();
+for y in 0..height() {{
+    for x in 0..width() {{
+        let p = get_pixel(f, x, y);
+        if p >= 16 && p < 24 {{ set_pixel(f, x, y, p + 8); }}
+    }}
+}}
+print(`frame ${{f}} done`);
+", tr("示例：把当前帧中索引 16-23 的像素改为 24-31"))
+}
+
+/// 运行结果：成功时为修改后的文档与调色板
+pub struct ScriptOutcome {
+    pub result: Result<(SHP, Palette), String>,
+    pub log: Vec<String>,
+}
+
+struct Doc {
+    shp: SHP,
+    palette: Palette,
+}
+
+type Fallible<T> = Result<T, Box<EvalAltResult>>;
+
+/// 在文档副本上运行 Rhai 脚本；出错时副本整体丢弃，文档保持原样
+pub fn run(source: &str, shp: &SHP, palette: &Palette, current_frame: usize) -> ScriptOutcome {
+    let doc = Rc::new(RefCell::new(Doc { shp: shp.clone(), palette: palette.clone() }));
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    let sink = log.clone();
+    engine.on_print(move |s| sink.borrow_mut().push(s.to_owned()));
+    let sink = log.clone();
+    engine.on_debug(move |s, _, pos| sink.borrow_mut().push(format!("[{}] {}", pos, s)));
+    register_api(&mut engine, &doc, current_frame as i64);
+
+    let result = engine.run(source).map_err(|e| e.to_string());
+    drop(engine);
+    let log = Rc::try_unwrap(log).map(RefCell::into_inner).unwrap_or_default();
+    let result = result.map(|()| {
+        let Doc { shp, palette } = Rc::try_unwrap(doc).ok().expect("engine dropped").into_inner();
+        (shp, palette)
+    });
+    ScriptOutcome { result, log }
+}
+
+fn register_api(engine: &mut Engine, doc: &Rc<RefCell<Doc>>, current: i64) {
+    let d = doc.clone();
+    engine.register_fn("frame_count", move || d.borrow().shp.frames.len() as i64);
+    let d = doc.clone();
+    engine.register_fn("width", move || d.borrow().shp.width as i64);
+    let d = doc.clone();
+    engine.register_fn("height", move || d.borrow().shp.height as i64);
+    engine.register_fn("current_frame", move || current);
+
+    let d = doc.clone();
+    engine.register_fn("get_pixel", move |f: i64, x: i64, y: i64| -> Fallible<i64> {
+        let doc = d.borrow();
+        let i = pixel_at(&doc.shp, f, x, y)?;
+        Ok(doc.shp.frames[f as usize].pixels[i] as i64)
+    });
+    let d = doc.clone();
+    engine.register_fn("set_pixel", move |f: i64, x: i64, y: i64, idx: i64| -> Fallible<()> {
+        let mut doc = d.borrow_mut();
+        let i = pixel_at(&doc.shp, f, x, y)?;
+        doc.shp.frames[f as usize].pixels[i] = color_index(idx)?;
+        Ok(())
+    });
+    let d = doc.clone();
+    engine.register_fn("remap", move |f: i64, from: i64, to: i64| -> Fallible<i64> {
+        let mut doc = d.borrow_mut();
+        let (from, to) = (color_index(from)?, color_index(to)?);
+        let frames: Vec<usize> = if f < 0 { (0..doc.shp.frames.len()).collect() } else { vec![frame_at(&doc.shp, f)?] };
+        Ok(frames.into_iter().map(|fi| doc.shp.replace_index(fi, from, to)).sum::<usize>() as i64)
+    });
+
+    let d = doc.clone();
+    engine.register_fn("get_color", move |i: i64| -> Fallible<Array> {
+        let c = d.borrow().palette.colors[color_index(i)? as usize];
+        Ok(vec![Dynamic::from(c.r() as i64), Dynamic::from(c.g() as i64), Dynamic::from(c.b() as i64)])
+    });
+    let d = doc.clone();
+    engine.register_fn("set_color", move |i: i64, r: i64, g: i64, b: i64| -> Fallible<()> {
+        let i = color_index(i)? as usize;
+        d.borrow_mut().palette.colors[i] = Color32::from_rgb(r.clamp(0, 255) as u8, g.clamp(0, 255) as u8, b.clamp(0, 255) as u8);
+        Ok(())
+    });
+
+    let d = doc.clone();
+    engine.register_fn("get_offset", move |f: i64| -> Fallible<Array> {
+        let doc = d.borrow();
+        let (x, y) = doc.shp.frames[frame_at(&doc.shp, f)?].offset;
+        Ok(vec![Dynamic::from(x as i64), Dynamic::from(y as i64)])
+    });
+    let d = doc.clone();
+    engine.register_fn("set_offset", move |f: i64, x: i64, y: i64| -> Fallible<()> {
+        let mut doc = d.borrow_mut();
+        let fi = frame_at(&doc.shp, f)?;
+        doc.shp.frames[fi].offset = (x as i32, y as i32);
+        Ok(())
+    });
+
+    let d = doc.clone();
+    engine.register_fn("add_frame", move || -> Fallible<i64> {
+        let mut doc = d.borrow_mut();
+        push_frame(&mut doc.shp, None)
+    });
+    let d = doc.clone();
+    engine.register_fn("import_png", move |path: &str| -> Fallible<i64> {
+        let img = image::open(path).map_err(|e| e.to_string())?.to_rgba8();
+        let mut doc = d.borrow_mut();
+        if (img.width(), img.height()) != (doc.shp.width, doc.shp.height) {
+            return Err(trf!("图片尺寸 {}x{} 与文档 {}x{} 不一致", img.width(), img.height(), doc.shp.width, doc.shp.height).into());
+        }
+        let t = doc.shp.transparent;
+        let pixels = crate::color_match::quantize_rgba(&img, &doc.palette.colors, 128, Some(t))
+            .into_iter().map(|p| p.unwrap_or(t)).collect();
+        push_frame(&mut doc.shp, Some(pixels))
+    });
+    let d = doc.clone();
+    engine.register_fn("export_png", move |f: i64, path: &str| -> Fallible<()> {
+        let doc = d.borrow();
+        let fi = frame_at(&doc.shp, f)?;
+        Ok(doc.shp.export_frame_png(fi, &doc.palette, path.into())?)
+    });
+}
+
+fn frame_at(shp: &SHP, f: i64) -> Fallible<usize> {
+    if f < 0 || f as usize >= shp.frames.len() { return Err(trf!("帧序号 {} 超出范围 0..{}", f, shp.frames.len()).into()); }
+    Ok(f as usize)
+}
+
+fn pixel_at(shp: &SHP, f: i64, x: i64, y: i64) -> Fallible<usize> {
+    frame_at(shp, f)?;
+    if x < 0 || y < 0 || x >= shp.width as i64 || y >= shp.height as i64 {
+        return Err(trf!("坐标 ({}, {}) 超出画布", x, y).into());
+    }
+    Ok((y * shp.width as i64 + x) as usize)
+}
+
+fn color_index(i: i64) -> Fallible<u8> {
+    u8::try_from(i).map_err(|_| trf!("颜色索引 {} 超出 0..255", i).into())
+}
+
+fn push_frame(shp: &mut SHP, pixels: Option<Vec<u8>>) -> Fallible<i64> {
+    SHP::check_limits(shp.width, shp.height, shp.frames.len() + 1)?;
+    let pixels = pixels.unwrap_or_else(|| vec![shp.transparent; (shp.width * shp.height) as usize]);
+    shp.frames.push(Frame::new(pixels));
+    Ok(shp.frames.len() as i64 - 1)
+}
@@ -0,0 +1,163 @@
+/// 像素选区：与画布同尺寸的掩码，true 表示选中
+#[derive(Clone, PartialEq)]
+pub struct Selection {
+    pub width: u32,
+    pub height: u32,
+    pub mask: Vec<bool>,
+}
+
+/// 新选区与已有选区的合并方式（Shift 加选、Alt 减选）
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SelectOp {
+    Replace,
+    Add,
+    Subtract,
+}
+
+impl SelectOp {
+    pub fn from_modifiers(shift: bool, alt: bool) -> Self {
+        if alt { SelectOp::Subtract } else if shift { SelectOp::Add } else { SelectOp::Replace }
+    }
+}
+
+impl Selection {
+    pub fn empty(width: u32, height: u32) -> Self {
+        Self { width, height, mask: vec![false; (width * height) as usize] }
+    }
+
+    pub fn all(width: u32, height: u32) -> Self {
+        Self { width, height, mask: vec![true; (width * height) as usize] }
+    }
+
+    /// 矩形选区（两角点闭区间，可超出画布）
+    pub fn rect(width: u32, height: u32, x0: i32, y0: i32, x1: i32, y1: i32) -> Self {
+        let mut s = Self::empty(width, height);
+        let (lx, rx) = (x0.min(x1).max(0), x0.max(x1).min(width as i32 - 1));
+        let (ty, by) = (y0.min(y1).max(0), y0.max(y1).min(height as i32 - 1));
+        for y in ty..=by {
+            for x in lx..=rx { s.mask[(y as u32 * width + x as u32) as usize] = true; }
+        }
+        s
+    }
+
+    /// 自由套索：按奇偶规则填充闭合多边形，以像素中心判定是否在内
+    pub fn lasso(width: u32, height: u32, points: &[(f32, f32)]) -> Self {
+        let mut s = Self::empty(width, height);
+        if points.len() < 3 { return s; }
+        let mut xs = Vec::new();
+        for y in 0..height {
+            let cy = y as f32 + 0.5;
+            xs.clear();
+            for (i, &(ax, ay)) in points.iter().enumerate() {
+                let (bx, by) = points[(i + 1) % points.len()];
+                if (ay <= cy) != (by <= cy) { xs.push(ax + (cy - ay) / (by - ay) * (bx - ax)); }
+            }
+            xs.sort_by(|a, b| a.total_cmp(b));
+            for pair in xs.chunks_exact(2) {
+                let x0 = (pair[0] - 0.5).ceil().max(0.0) as u32;
+                let x1 = ((pair[1] - 0.5).floor() + 1.0).clamp(0.0, width as f32) as u32;
+                for x in x0..x1 { s.mask[(y * width + x) as usize] = true; }
+            }
+        }
+        s
+    }
+
+    /// 魔棒：与起点索引相同的连通区域（与填充工具共用连通规则）
+    pub fn wand(pixels: &[u8], width: u32, height: u32, x: i32, y: i32, diagonal: bool) -> Self {
+        Self { width, height, mask: flood_region(pixels, width, height, x, y, false, diagonal) }
+    }
+
+    /// 把新选区按 `op` 合并进当前选区；结果为空时取消选区
+    pub fn merge_into(cur: &mut Option<Selection>, new: Selection, op: SelectOp) {
+        let merged = match cur.take() {
+            Some(mut c) if op != SelectOp::Replace && c.mask.len() == new.mask.len() => { c.combine(&new, op); c }
+            _ if op == SelectOp::Subtract => return,
+            _ => new,
+        };
+        *cur = (!merged.is_empty()).then_some(merged);
+    }
+
+    pub fn combine(&mut self, other: &Selection, op: SelectOp) {
+        match op {
+            SelectOp::Replace => self.mask.clone_from(&other.mask),
+            SelectOp::Add => for (a, &b) in self.mask.iter_mut().zip(&other.mask) { *a |= b; },
+            SelectOp::Subtract => for (a, &b) in self.mask.iter_mut().zip(&other.mask) { *a &= !b; },
+        }
+    }
+
+    pub fn invert(&mut self) {
+        for m in &mut self.mask { *m = !*m; }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.mask.contains(&true)
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height
+            && self.mask[(y as u32 * self.width + x as u32) as usize]
+    }
+
+    pub fn count(&self) -> usize {
+        self.mask.iter().filter(|&&m| m).count()
+    }
+
+    /// 把选区外被改动的像素还原为 `base`（操作前的帧），使绘制只作用于选区内
+    pub fn clip(&self, pixels: &mut [u8], base: &[u8]) {
+        if pixels.len() != self.mask.len() || base.len() != self.mask.len() { return; }
+        for ((p, &b), &m) in pixels.iter_mut().zip(base).zip(&self.mask) { if !m { *p = b; } }
+    }
+
+    /// 整体平移，移出画布的部分丢弃
+    pub fn shifted(&self, dx: i32, dy: i32) -> Self {
+        let mut s = Self::empty(self.width, self.height);
+        for (i, _) in self.mask.iter().enumerate().filter(|(_, m)| **m) {
+            let (x, y) = ((i as u32 % self.width) as i32 + dx, (i as u32 / self.width) as i32 + dy);
+            if x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height {
+                s.mask[(y as u32 * self.width + x as u32) as usize] = true;
+            }
+        }
+        s
+    }
+
+    /// 选区边界线段（像素坐标），用于在画布上描边
+    pub fn outline(&self) -> Vec<[(u32, u32); 2]> {
+        let (w, h) = (self.width, self.height);
+        let mut segs = Vec::new();
+        for y in 0..h {
+            for x in 0..w {
+                if !self.mask[(y * w + x) as usize] { continue; }
+                let (xi, yi) = (x as i32, y as i32);
+                if !self.contains(xi, yi - 1) { segs.push([(x, y), (x + 1, y)]); }
+                if !self.contains(xi, yi + 1) { segs.push([(x, y + 1), (x + 1, y + 1)]); }
+                if !self.contains(xi - 1, yi) { segs.push([(x, y), (x, y + 1)]); }
+                if !self.contains(xi + 1, yi) { segs.push([(x + 1, y), (x + 1, y + 1)]); }
+            }
+        }
+        segs
+    }
+}
+
+/// 与 (x, y) 处索引相同的连通区域掩码；起点在画布外时为空。`wrap` 时越过边缘从对边继续；
+/// `diagonal` 时按 8 连通，对角相邻也算连通（单像素宽的斜线能一次填满），否则 4 连通
+pub fn flood_region(pixels: &[u8], width: u32, height: u32, x: i32, y: i32, wrap: bool, diagonal: bool) -> Vec<bool> {
+    let (w, h) = (width as i32, height as i32);
+    let mut mask = vec![false; (width * height) as usize];
+    if x < 0 || y < 0 || x >= w || y >= h { return mask; }
+    let target = pixels[(y * w + x) as usize];
+    let mut stack = vec![(x, y)];
+    while let Some((px, py)) = stack.pop() {
+        let (px, py) = if wrap { (px.rem_euclid(w), py.rem_euclid(h)) } else { (px, py) };
+        if px < 0 || py < 0 || px >= w || py >= h { continue; }
+        let i = (py * w + px) as usize;
+        if mask[i] || pixels[i] != target { continue; }
+        mask[i] = true;
+        stack.push((px - 1, py)); stack.push((px + 1, py));
+        stack.push((px, py - 1)); stack.push((px, py + 1));
+        if diagonal {
+            stack.push((px - 1, py - 1)); stack.push((px + 1, py - 1));
+            stack.push((px - 1, py + 1)); stack.push((px + 1, py + 1));
+        }
+    }
+    mask
+}
@@ -0,0 +1,55 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// 命名动画序列（闭区间），对应 art.ini 中按起始帧与帧数引用的动作
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sequence {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Sequence {
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start) + 1
+    }
+
+    /// 夹到帧数以内的闭区间
+    pub fn bounds(&self, frame_count: usize) -> (usize, usize) {
+        let last = frame_count.saturating_sub(1);
+        let (a, b) = (self.start.min(last), self.end.min(last));
+        (a.min(b), a.max(b))
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SidecarFile {
+    sequences: Vec<Sequence>,
+}
+
+/// 旁注文件路径：与 SHP 同目录，`unit.shp` → `unit.shp.seq.json`
+pub fn sidecar_path(shp_path: &Path) -> PathBuf {
+    let mut name = shp_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".seq.json");
+    shp_path.with_file_name(name)
+}
+
+/// 读取 SHP 旁的序列定义；文件不存在时为空
+pub fn load(shp_path: &Path) -> Result<Vec<Sequence>, String> {
+    let path = sidecar_path(shp_path);
+    if !path.exists() { return Ok(Vec::new()); }
+    let bytes = std::fs::read(&path).map_err(|e| e.to_string())?;
+    serde_json::from_slice::<SidecarFile>(&bytes).map(|f| f.sequences).map_err(|e| e.to_string())
+}
+
+/// 写入序列定义；没有序列时删除旧的旁注文件
+pub fn save(shp_path: &Path, sequences: &[Sequence]) -> Result<(), String> {
+    let path = sidecar_path(shp_path);
+    if sequences.is_empty() {
+        if path.exists() { std::fs::remove_file(&path).map_err(|e| e.to_string())?; }
+        return Ok(());
+    }
+    let json = serde_json::to_vec_pretty(&SidecarFile { sequences: sequences.to_vec() }).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| e.to_string())
+}
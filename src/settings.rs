@@ -0,0 +1,124 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::i18n::{tr, Language};
+use crate::keymap::Keymap;
+use crate::stats::UsageStats;
+
+const SETTINGS_FILE: &str = "lvshp_settings.json";
+
+/// 持久化设置：JSON 格式；读取失败时使用默认值
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub keymap: Keymap,
+    pub language: Language,
+    // 本地使用统计：默认关闭，需用户主动开启
+    pub stats_enabled: bool,
+    pub stats: UsageStats,
+    // 保存/导出后执行的命令模板（见 hook.rs 的占位符说明）
+    pub export_hook_enabled: bool,
+    pub export_hook: String,
+    // 启动时检查 GitHub 新版本：默认关闭，需要联网
+    pub check_updates: bool,
+    // MIX 文件名列表（XCC 数据库或纯文本）路径，启动时加载
+    pub mix_name_lists: Vec<String>,
+    pub canvas_bg: CanvasBackground,
+    // 打开的 SHP/PAL 被外部程序修改后自动重新加载
+    pub watch_files: bool,
+    // 保存 SHP 前不再弹出各帧编码与体积报告
+    pub skip_save_report: bool,
+    // 从菜单选择内置调色板时如何处理已有像素
+    pub pal_switch: PaletteSwitch,
+    // 用户调色板文件夹，启动时扫描并加入「User」分组
+    pub palette_folder: Option<String>,
+    // 固定在「选择内置PAL」菜单顶部的调色板 (分组, 名称)；前 9 个可用数字键 1–9 切换
+    pub pinned_palettes: Vec<(String, String)>,
+    // 保存 SHP 时内容相同的帧共用同一份数据
+    pub share_duplicate_frames: bool,
+    // 启动时重新打开上次编辑的文档（文件仍存在时）；退出时记录到 last_session
+    pub restore_session: bool,
+    pub last_session: Option<LastSession>,
+}
+
+/// 退出时的文档状态，供下次启动恢复
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LastSession {
+    /// SHP 或 .lvshp 工程路径
+    pub path: String,
+    pub frame: usize,
+    pub scale: f32,
+    pub palette: String,
+    /// 调色板来自 .pal 文件时的路径；否则按名称在内置/用户调色板中查找
+    pub palette_path: Option<String>,
+}
+
+/// 切换调色板的方式：只换颜色（索引不变，外观随之改变），或改写索引使外观尽量不变
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum PaletteSwitch {
+    /// 每次弹出确认框询问
+    #[default]
+    Ask,
+    Reassign,
+    Remap,
+}
+
+impl PaletteSwitch {
+    pub const ALL: [PaletteSwitch; 3] = [PaletteSwitch::Ask, PaletteSwitch::Reassign, PaletteSwitch::Remap];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PaletteSwitch::Ask => tr("每次询问"),
+            PaletteSwitch::Reassign => tr("仅换调色板（索引不变）"),
+            PaletteSwitch::Remap => tr("改写索引以保持外观"),
+        }
+    }
+}
+
+/// 画布透明区域的背景：棋盘格（颜色、格子大小可调）或纯色；游戏黑底预览优先于两者
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CanvasBackground {
+    pub solid: bool,
+    pub color: [u8; 3],
+    pub checker_light: [u8; 3],
+    pub checker_dark: [u8; 3],
+    /// 格子边长（屏幕像素）；放大后不小于一个画布像素
+    pub checker_size: f32,
+    pub game_black: bool,
+}
+
+impl Default for CanvasBackground {
+    fn default() -> Self {
+        Self { solid: false, color: [0, 0, 0], checker_light: [90, 90, 90], checker_dark: [60, 60, 60], checker_size: 8.0, game_black: false }
+    }
+}
+
+impl Settings {
+    /// 便携模式：可执行文件旁已有配置文件时优先使用；否则放在平台配置目录
+    /// （安装到 /usr/bin 或 macOS .app 包内时可执行文件目录通常不可写）
+    pub fn path() -> PathBuf {
+        let portable = std::env::current_exe().ok().and_then(|p| p.parent().map(|d| d.join(SETTINGS_FILE)));
+        if let Some(p) = portable.as_ref().filter(|p| p.exists()) { return p.clone(); }
+        crate::platform::config_dir().map(|d| d.join(SETTINGS_FILE))
+            .or(portable)
+            .unwrap_or_else(|| PathBuf::from(SETTINGS_FILE))
+    }
+
+    pub fn load() -> Self {
+        let mut s: Settings = std::fs::read(Self::path()).ok()
+            .and_then(|b| serde_json::from_slice(&b).ok())
+            .unwrap_or_default();
+        s.keymap.fill_defaults();
+        s
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_vec_pretty(self).map_err(|e| e.to_string())?;
+        let path = Self::path();
+        if let Some(dir) = path.parent() { std::fs::create_dir_all(dir).map_err(|e| e.to_string())?; }
+        std::fs::write(path, json).map_err(|e| e.to_string())
+    }
+}
@@ -1,12 +1,34 @@
-use eframe::egui::{self, Color32, TextureHandle};
+use eframe::egui::{self, TextureHandle};
 use std::io::{Cursor, Read};
 
-use crate::color_match::best_index_rgb;
+use rayon::prelude::*;
+
+use crate::i18n::tr;
 use crate::palette::Palette;
 
 #[derive(Clone)]
 pub struct Frame {
     pub pixels: Vec<u8>,
+    /// 对齐偏移：保存时帧内容整体按此平移后写入帧头 x/y（编辑时像素不动）
+    pub offset: (i32, i32),
+    /// 保存时的编码方式；读取文件时按帧头 flags 还原
+    pub compression: Compression,
+    /// 打开文件时该帧在文件中的存储信息；新建/导入的帧为 None
+    pub source: Option<FrameSource>,
+}
+
+impl Frame {
+    pub fn new(pixels: Vec<u8>) -> Self {
+        Self { pixels, offset: (0, 0), compression: Compression::default(), source: None }
+    }
+
+    /// 像素内容哈希：用于缩略图/预览等缓存判断内容是否变化
+    pub fn content_hash(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut h = std::collections::hash_map::DefaultHasher::new();
+        self.pixels.hash(&mut h);
+        h.finish()
+    }
 }
 
 #[derive(Clone)]
@@ -14,153 +36,282 @@ pub struct SHP {
     pub width: u32,
     pub height: u32,
     pub frames: Vec<Frame>,
+    /// 透明色索引（按文档设置，默认 0）：影响预览、PNG 导出、橡皮与导入
+    pub transparent: u8,
+    /// 空帧的写法（按文档设置）；读取时按文件中已有的写法还原
+    pub empty_frames: EmptyFrames,
+}
+
+/// 保存时没有非 0 像素的帧如何写入
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyFrames {
+    /// 帧头尺寸与数据偏移都写 0
+    #[default]
+    NullOffset,
+    /// 写一个 1×1 的透明数据块，帧头带有效偏移；供不接受 0 偏移帧的程序使用
+    MinimalBlock,
+}
+
+impl EmptyFrames {
+    pub const ALL: [EmptyFrames; 2] = [EmptyFrames::NullOffset, EmptyFrames::MinimalBlock];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            EmptyFrames::NullOffset => tr("0 偏移"),
+            EmptyFrames::MinimalBlock => tr("1×1 透明块"),
+        }
+    }
+}
+
+/// 帧数据编码：未压缩块解码最快；RLE0 把成串的透明像素压成两个字节，适合轮廓稀疏的帧
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Compression {
+    #[default]
+    Uncompressed,
+    Rle0,
+}
+
+impl Compression {
+    pub const ALL: [Compression; 2] = [Compression::Uncompressed, Compression::Rle0];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Compression::Uncompressed => tr("未压缩"),
+            Compression::Rle0 => "RLE0",
+        }
+    }
+
+    // 帧头 flags：未压缩沿用原先写入的 0；RLE0 为 3
+    fn flags(self) -> u32 {
+        match self {
+            Compression::Uncompressed => 0,
+            Compression::Rle0 => 3,
+        }
+    }
+}
+
+/// 帧在原文件中的存储信息，仅供检查；保存时按当前内容重新计算
+#[derive(Clone, Copy)]
+pub struct FrameSource {
+    /// 帧头记录的数据块矩形 (x, y, w, h)；TD/RA1 格式为整幅画布
+    pub rect: (u16, u16, u16, u16),
+    /// 帧头 flags；TD/RA1 格式为格式字节
+    pub flags: u32,
+    pub data_offset: u32,
+    /// 是否为 TD/RA1 旧格式
+    pub td: bool,
+}
+
+impl FrameSource {
+    /// 存储格式的说明
+    pub fn format_label(&self) -> &'static str {
+        if self.td {
+            return match self.flags as u8 {
+                TD_LCW => "LCW (Format80)",
+                TD_XOR_LCW => tr("XOR 关键帧 (Format40)"),
+                TD_XOR_PREV => tr("XOR 上一帧 (Format40)"),
+                _ => tr("未知"),
+            };
+        }
+        match self.flags & 3 {
+            3 => "RLE0",
+            2 => tr("逐行（带行长）"),
+            _ => tr("未压缩"),
+        }
+    }
+
+    /// 帧头矩形解码后的字节数
+    pub fn decoded_len(&self) -> usize {
+        self.rect.2 as usize * self.rect.3 as usize
+    }
+}
+
+/// 阴影帧生成参数：以帧内容底边为地面，把剪影压扁并沿 x 方向错切
+#[derive(Clone, Copy)]
+pub struct ShadowParams {
+    pub index: u8,
+    /// 每高出地面 1 像素，阴影向右偏移的像素数（负数向左）
+    pub shear: f32,
+    /// 垂直压缩比例（1 为不压缩）
+    pub squash: f32,
+    pub offset_x: i32,
+    pub offset_y: i32,
+}
+
+/// 整体缩放算法。Scale2x/Scale3x 只在已有索引间挑选，不产生新颜色，适合调色板图像
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PixelScaler {
+    Nearest,
+    Scale2x,
+    Scale3x,
+}
+
+impl PixelScaler {
+    pub const ALL: [PixelScaler; 3] = [PixelScaler::Nearest, PixelScaler::Scale2x, PixelScaler::Scale3x];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PixelScaler::Nearest => tr("最近邻 (Nearest)"),
+            PixelScaler::Scale2x => "Scale2x (2×)",
+            PixelScaler::Scale3x => "Scale3x (3×)",
+        }
+    }
+
+    /// 固定倍率算法的倍数；最近邻可任意尺寸，返回 None
+    pub fn factor(self) -> Option<u32> {
+        match self {
+            PixelScaler::Nearest => None,
+            PixelScaler::Scale2x => Some(2),
+            PixelScaler::Scale3x => Some(3),
+        }
+    }
+}
+
+/// 补间方式：RGB 交叉淡化后重新量化到调色板，或把内容按包围盒位置线性移动
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TweenMode {
+    CrossFade,
+    Motion,
+}
+
+impl TweenMode {
+    pub const ALL: [TweenMode; 2] = [TweenMode::CrossFade, TweenMode::Motion];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            TweenMode::CrossFade => tr("交叉淡化"),
+            TweenMode::Motion => tr("位置移动"),
+        }
+    }
+}
+
+/// 重定画布尺寸时新旧画布的对齐方式；所有帧按同一位移平移，帧间相对位置不变
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RetargetAnchor {
+    /// 全部帧内容的底边中点对齐到新画布底边（可留边距）：步兵脚底
+    ContentBottom,
+    /// 画布中心对齐：载具、飞行器等以画布中心为落点的单位
+    CanvasCenter,
+    /// 画布底边中点对齐：建筑地基贴底
+    CanvasBottom,
+    /// 全部帧内容的中心对齐到新画布中心：特效、爆炸等动画
+    ContentCenter,
+}
+
+impl RetargetAnchor {
+    pub const ALL: [RetargetAnchor; 4] = [RetargetAnchor::ContentBottom, RetargetAnchor::CanvasCenter, RetargetAnchor::CanvasBottom, RetargetAnchor::ContentCenter];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RetargetAnchor::ContentBottom => tr("内容底部中心（步兵）"),
+            RetargetAnchor::CanvasCenter => tr("画布中心（载具、飞行器）"),
+            RetargetAnchor::CanvasBottom => tr("画布底部中心（建筑）"),
+            RetargetAnchor::ContentCenter => tr("内容中心（特效动画）"),
+        }
+    }
+}
+
+/// 文件头中的宽、高与帧数都是 u16
+pub const MAX_SIDE: u32 = u16::MAX as u32;
+pub const MAX_FRAMES: usize = u16::MAX as usize;
+
+/// 读取时的内存上限：单帧解码后的字节数，以及一次性全部解码的总字节数
+/// （损坏或恶意构造的文件头可能声明远超实际数据的尺寸）
+const MAX_FRAME_BYTES: u64 = 256 * 1024 * 1024;
+const MAX_DECODED_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+fn check_decoded_size(w: u32, h: u32, frames: usize) -> Result<(), String> {
+    let frame = w as u64 * h as u64;
+    if frame > MAX_FRAME_BYTES { return Err(trf!("单帧 {}x{} 解码后超过 {} MB 上限", w, h, MAX_FRAME_BYTES >> 20)); }
+    let total = frame * frames as u64;
+    if total > MAX_DECODED_BYTES { return Err(trf!("全部 {} 帧解码后需要 {} MB，超过 {} MB 上限", frames, total >> 20, MAX_DECODED_BYTES >> 20)); }
+    Ok(())
 }
 
 impl SHP {
+    /// 不做范围检查，供内部临时画布使用；新建文档用 `try_new`
     pub fn new(width: u32, height: u32, frames: usize) -> Self {
         let mut f = Vec::with_capacity(frames);
-        for _ in 0..frames { f.push(Frame { pixels: vec![0u8; (width * height) as usize] }); }
-        Self { width, height, frames: f }
+        for _ in 0..frames { f.push(Frame::new(vec![0u8; (width * height) as usize])); }
+        Self { width, height, frames: f, transparent: 0, empty_frames: EmptyFrames::default() }
     }
 
-    pub fn load(bytes: &[u8]) -> Result<Self, String> {
-        // 兼容 RA2/YR SHP：
-        // Header: u16 zero, u16 width, u16 height, u16 frame_count
-        // Per-frame header (24 bytes): x,y,w,h (u16*4), flags(u32), frameColor[4], zero(i32), dataOffset(u32)
-        #[derive(Clone, Copy)]
-        struct FHeader { x:u16, y:u16, w:u16, h:u16, flags:u32, data_off:u32 }
-
-        fn read_u16(r:&mut Cursor<&[u8]>) -> Result<u16,String>{ let mut b=[0u8;2]; r.read_exact(&mut b).map_err(|e|e.to_string())?; Ok(u16::from_le_bytes(b)) }
-        fn read_u32(r:&mut Cursor<&[u8]>) -> Result<u32,String>{ let mut b=[0u8;4]; r.read_exact(&mut b).map_err(|e|e.to_string())?; Ok(u32::from_le_bytes(b)) }
-        fn read_i32(r:&mut Cursor<&[u8]>) -> Result<i32,String>{ let mut b=[0u8;4]; r.read_exact(&mut b).map_err(|e|e.to_string())?; Ok(i32::from_le_bytes(b)) }
-
-        if bytes.len() < 8 { return Err("SHP头不足".into()); }
-        let mut cur = Cursor::new(bytes);
-        let zero = read_u16(&mut cur)?; if zero != 0 { return Err("不是有效的SHP文件".into()); }
-        let w = read_u16(&mut cur)? as u32;
-        let h = read_u16(&mut cur)? as u32;
-        let n = read_u16(&mut cur)? as usize;
-        if w == 0 || h == 0 || n == 0 { return Err("无效SHP尺寸/帧数".into()); }
-
-        // 读取帧头
-        let mut fhs: Vec<FHeader> = Vec::with_capacity(n);
-        for _ in 0..n {
-            let x = read_u16(&mut cur)?;
-            let y = read_u16(&mut cur)?;
-            let ww = read_u16(&mut cur)?;
-            let hh = read_u16(&mut cur)?;
-            let flags = read_u32(&mut cur)?;
-            let mut color_rgba = [0u8;4]; cur.read_exact(&mut color_rgba).map_err(|e|e.to_string())?;
-            let _zero2 = read_i32(&mut cur)?; // 忽略
-            let data_off = read_u32(&mut cur)?;
-            fhs.push(FHeader { x, y, w: ww, h: hh, flags, data_off });
-        }
-
-        // 解码帧数据
-        let mut frames: Vec<Frame> = Vec::with_capacity(n);
-        for fh in fhs.iter().copied() {
-            let mut pixels = vec![0u8; (w * h) as usize];
-            if fh.data_off == 0 || fh.w == 0 || fh.h == 0 {
-                frames.push(Frame { pixels });
-                continue;
-            }
-            if fh.data_off as usize >= bytes.len() { return Err("SHP数据偏移越界".into()); }
-            let mut r = Cursor::new(&bytes[fh.data_off as usize..]);
-            let is_rle0 = (fh.flags & 3) == 3;
-            let is_scan = (fh.flags & 2) == 2 && (fh.flags & 1) == 0;
-
-            if is_rle0 {
-                // 每行：u16 长度(含2字节)，随后数据；0后跟零的数量
-                let mut y_abs = fh.y as usize;
-                for _ in 0..fh.h as usize {
-                    let len = read_u16(&mut r)? as usize;
-                    let mut remaining = len.saturating_sub(2);
-                    let mut x_abs = fh.x as usize;
-                    while remaining > 0 {
-                        let mut b=[0u8;1]; r.read_exact(&mut b).map_err(|e|e.to_string())?; remaining-=1;
-                        let v = b[0];
-                        if v != 0 {
-                            if x_abs < w as usize && y_abs < h as usize { pixels[y_abs * w as usize + x_abs] = v; }
-                            x_abs += 1;
-                        } else {
-                            let mut c=[0u8;1]; r.read_exact(&mut c).map_err(|e|e.to_string())?; remaining-=1;
-                            let zeros = c[0] as usize;
-                            x_abs = x_abs.saturating_add(zeros);
-                        }
-                    }
-                    y_abs += 1;
-                }
-            } else if is_scan {
-                // 每行：u16 长度(含2字节)，随后按顺序字节
-                let mut y_abs = fh.y as usize;
-                for _ in 0..fh.h as usize {
-                    let len = read_u16(&mut r)? as usize;
-                    let mut remaining = len.saturating_sub(2);
-                    let mut x_abs = fh.x as usize;
-                    while remaining > 0 {
-                        let mut b=[0u8;1]; r.read_exact(&mut b).map_err(|e|e.to_string())?; remaining-=1;
-                        let v = b[0];
-                        if x_abs < w as usize && y_abs < h as usize { pixels[y_abs * w as usize + x_abs] = v; }
-                        x_abs += 1;
-                    }
-                    y_abs += 1;
-                }
-            } else {
-                // 未压缩：w*h 直接字节块
-                let mut y_abs = fh.y as usize;
-                for _ in 0..fh.h as usize {
-                    let mut row = vec![0u8; fh.w as usize];
-                    r.read_exact(&mut row).map_err(|e| e.to_string())?;
-                    let mut x_abs = fh.x as usize;
-                    for v in row {
-                        if x_abs < w as usize && y_abs < h as usize { pixels[y_abs * w as usize + x_abs] = v; }
-                        x_abs += 1;
-                    }
-                    y_abs += 1;
-                }
-            }
+    pub fn try_new(width: u32, height: u32, frames: usize) -> Result<Self, String> {
+        Self::check_limits(width, height, frames)?;
+        Ok(Self::new(width, height, frames))
+    }
 
-            frames.push(Frame { pixels });
+    /// 尺寸与帧数能否写入 u16 文件头字段
+    pub fn check_limits(width: u32, height: u32, frames: usize) -> Result<(), String> {
+        if width == 0 || height == 0 || width > MAX_SIDE || height > MAX_SIDE {
+            return Err(trf!("画布尺寸 {}x{} 超出 SHP 范围（1~{}）", width, height, MAX_SIDE));
         }
+        if frames > MAX_FRAMES { return Err(trf!("帧数 {} 超出 SHP 上限 {}", frames, MAX_FRAMES)); }
+        Ok(())
+    }
 
-        Ok(Self { width: w, height: h, frames })
+    /// 读取 TS/RA2 格式；文件头像 TD/RA1 旧格式时改用旧格式解码
+    pub fn load(bytes: &[u8]) -> Result<Self, String> {
+        if is_td_shp(bytes) { return load_td(bytes); }
+        let (w, h, fhs) = read_headers(bytes)?;
+        check_decoded_size(w, h, fhs.len())?;
+        // 解码帧数据：各帧数据块相互独立，并行解码
+        let frames: Vec<Frame> = fhs.par_iter().enumerate().map(|(i, &fh)| decode_frame(bytes, i, fh, w, h)).collect::<Result<_, _>>()?;
+        // 有数据块却全为 0 的帧说明文件按“最小数据块”写空帧，保存时沿用
+        let minimal = frames.iter().any(|f| f.source.is_some_and(|s| s.data_offset != 0) && f.pixels.iter().all(|&p| p == 0));
+        let empty_frames = if minimal { EmptyFrames::MinimalBlock } else { EmptyFrames::NullOffset };
+
+        Ok(Self { width: w, height: h, frames, transparent: 0, empty_frames })
     }
 
     pub fn save(&self) -> Result<Vec<u8>, String> {
+        self.save_with(false)
+    }
+
+    /// 同 `save`；`share_duplicates` 时帧头矩形、编码与数据块都相同的帧共用同一份数据（帧头指向同一偏移）
+    pub fn save_with(&self, share_duplicates: bool) -> Result<Vec<u8>, String> {
         // 保存为 RA2/YR 兼容格式：
-        // 8字节头 + N个24字节帧头 + 帧数据（此处使用未压缩块，大小为画布宽*高，每帧）
-        if self.frames.is_empty() { return Err("没有帧".into()); }
+        // 8字节头 + N个24字节帧头 + 帧数据（只写非 0 内容的包围盒，按各帧的 compression 编码）
+        // 帧头 x/y 为包围盒在画布中的位置加上帧的对齐偏移，超出画布的部分裁掉
+        if self.frames.is_empty() { return Err(tr("没有帧").into()); }
+        Self::check_limits(self.width, self.height, self.frames.len())?;
 
         let n = self.frames.len();
         let header_size: usize = 8 + 24 * n;
 
-        // 预先为每帧准备原始数据块（未压缩，大小=margin.w*margin.h，这里使用整幅画布）
-        let mut frame_blocks: Vec<Vec<u8>> = Vec::with_capacity(n);
-        let mut data_offsets: Vec<u32> = vec![0u32; n];
+        // 每帧：(x, y, w, h, 编码后的数据块)；空帧按 empty_frames 写 0 偏移（None）或 1×1 透明块
+        let mut rects: Vec<Option<Block>> = Vec::with_capacity(n);
+        for fr in &self.frames {
+            let rect = match self.frame_block(fr) {
+                Some((x, y, w, h, raw)) => Some((x, y, w, h, encode_block(raw, w, fr.compression)?)),
+                None if self.empty_frames == EmptyFrames::MinimalBlock => Some((0, 0, 1, 1, encode_block(vec![0], 1, fr.compression)?)),
+                None => None,
+            };
+            rects.push(rect);
+        }
 
-        for fi in 0..n {
-            let mut block = Vec::with_capacity((self.width * self.height) as usize);
-            block.resize((self.width * self.height) as usize, 0);
-            // 复制整幅画布
-            for y in 0..self.height as usize {
-                for x in 0..self.width as usize {
-                    let v = self.frames[fi].pixels[y * self.width as usize + x];
-                    block[y * self.width as usize + x] = v;
-                }
+        // 共用数据：记下与之相同的最早一帧
+        let mut shared_from: Vec<Option<usize>> = vec![None; n];
+        if share_duplicates {
+            let mut seen: std::collections::HashMap<(Compression, &Block), usize> = std::collections::HashMap::new();
+            for (i, r) in rects.iter().enumerate() {
+                let Some(b) = r else { continue; };
+                shared_from[i] = seen.get(&(self.frames[i].compression, b)).copied();
+                seen.entry((self.frames[i].compression, b)).or_insert(i);
             }
-            frame_blocks.push(block);
         }
 
         // 计算每帧数据偏移
+        let mut data_offsets: Vec<u32> = vec![0u32; n];
         let mut cursor: u32 = header_size as u32;
-        for (i, blk) in frame_blocks.iter().enumerate() {
-            // 如果整帧为空（全0），写偏移就保留0以保持兼容
-            let empty = blk.iter().all(|&b| b == 0);
-            if empty {
-                data_offsets[i] = 0;
-            } else {
+        for (i, r) in rects.iter().enumerate() {
+            if let Some(j) = shared_from[i] {
+                data_offsets[i] = data_offsets[j];
+                continue;
+            }
+            if let Some((.., blk)) = r {
                 data_offsets[i] = cursor;
-                cursor = cursor.saturating_add(blk.len() as u32);
+                cursor = u32::try_from(blk.len()).ok().and_then(|len| cursor.checked_add(len)).ok_or_else(|| tr("帧数据超过 4GB，无法保存为 SHP").to_string())?;
             }
         }
 
@@ -172,14 +323,12 @@ impl SHP {
         out.extend_from_slice(&(n as u16).to_le_bytes());
 
         // 写每帧24字节帧头
-        for i in 0..n {
-            // x,y,w,h （整幅）
-            out.extend_from_slice(&0u16.to_le_bytes()); // x
-            out.extend_from_slice(&0u16.to_le_bytes()); // y
-            out.extend_from_slice(&(self.width as u16).to_le_bytes());
-            out.extend_from_slice(&(self.height as u16).to_le_bytes());
-            // flags：未压缩且可透明(或0)。这里用0（Opaque）或1（Transparent）都可，加载分支不依赖flags
-            let flags: u32 = 0; // 0=Opaque（简化）
+        for (i, r) in rects.iter().enumerate() {
+            // x,y,w,h （包围盒）
+            let (x, y, w, h) = r.as_ref().map_or((0, 0, 0, 0), |(x, y, w, h, _)| (*x, *y, *w, *h));
+            for v in [x, y, w, h] { out.extend_from_slice(&(v as u16).to_le_bytes()); }
+            // flags：空帧写 0，其余按编码方式
+            let flags: u32 = if r.is_some() { self.frames[i].compression.flags() } else { 0 };
             out.extend_from_slice(&flags.to_le_bytes());
             // frame color (RGB)+0
             out.extend_from_slice(&[0u8, 0, 0, 0]);
@@ -190,14 +339,94 @@ impl SHP {
         }
 
         // 写数据块
-        for (i, blk) in frame_blocks.into_iter().enumerate() {
-            if data_offsets[i] == 0 { continue; }
-            out.extend_from_slice(&blk);
+        for (r, shared) in rects.iter().zip(&shared_from) {
+            if let (Some((.., blk)), None) = (r, shared) { out.extend_from_slice(blk); }
         }
 
         Ok(out)
     }
 
+    /// 各帧数据块在两种编码下的字节数 (未压缩, RLE0)；空帧为 (0, 0)。不含文件头与帧头
+    pub fn encoded_sizes(&self) -> Vec<(usize, usize)> {
+        self.frames.par_iter().map(|fr| match self.frame_block(fr) {
+            Some((.., w, _, raw)) => block_sizes(raw, w),
+            None => (0, 0),
+        }).collect()
+    }
+
+    /// 单帧保存时写入帧头的矩形 (x, y, w, h) 及两种编码下的字节数；空帧返回 None
+    pub fn frame_save_info(&self, frame: usize) -> Option<SaveInfo> {
+        let (x, y, w, h, raw) = self.frame_block(self.frames.get(frame)?)?;
+        Some(((x, y, w, h), block_sizes(raw, w)))
+    }
+
+    // 帧内容（平移对齐偏移并裁到画布内）的包围盒与逐行原始像素
+    fn frame_block(&self, fr: &Frame) -> Option<Block> {
+        let (bx, by, bw, bh) = content_rect(&fr.pixels, self.width, 0)?;
+        let x0 = (bx as i32 + fr.offset.0).max(0);
+        let y0 = (by as i32 + fr.offset.1).max(0);
+        let x1 = ((bx + bw) as i32 + fr.offset.0).min(self.width as i32);
+        let y1 = ((by + bh) as i32 + fr.offset.1).min(self.height as i32);
+        if x0 >= x1 || y0 >= y1 { return None; }
+        let mut block = Vec::with_capacity(((x1 - x0) * (y1 - y0)) as usize);
+        for y in y0..y1 {
+            let sy = (y - fr.offset.1) as u32;
+            let row = (sy * self.width) as usize;
+            let sx0 = (x0 - fr.offset.0) as usize;
+            block.extend_from_slice(&fr.pixels[row + sx0..row + sx0 + (x1 - x0) as usize]);
+        }
+        Some((x0 as u32, y0 as u32, (x1 - x0) as u32, (y1 - y0) as u32, block))
+    }
+
+    /// 保存时是否为空帧（平移对齐偏移并裁到画布后没有非 0 像素）
+    pub fn saves_as_empty(&self, frame: usize) -> bool {
+        let fr = &self.frames[frame];
+        if fr.offset == (0, 0) { return fr.pixels.iter().all(|&p| p == 0); }
+        self.frame_block(fr).is_none()
+    }
+
+    /// 内容相同（像素与对齐偏移都相同）的帧分组：每组至少两帧、组内按帧序，组按首帧排序
+    pub fn duplicate_groups(&self) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        // 哈希与偏移相同的候选组，再逐字节确认
+        let mut buckets: std::collections::HashMap<(u64, (i32, i32)), Vec<usize>> = std::collections::HashMap::new();
+        let hashes: Vec<u64> = self.frames.par_iter().map(Frame::content_hash).collect();
+        for (i, (fr, h)) in self.frames.iter().zip(hashes).enumerate() {
+            let cands = buckets.entry((h, fr.offset)).or_default();
+            match cands.iter().find(|&&g| self.frames[groups[g][0]].pixels == fr.pixels) {
+                Some(&g) => groups[g].push(i),
+                None => { cands.push(groups.len()); groups.push(vec![i]); }
+            }
+        }
+        groups.retain(|g| g.len() > 1);
+        groups
+    }
+
+    /// 帧内非透明像素的包围盒 (x, y, w, h)；空帧返回 None
+    pub fn frame_bounds(&self, frame: usize) -> Option<(u32, u32, u32, u32)> {
+        content_rect(&self.frames.get(frame)?.pixels, self.width, self.transparent)
+    }
+
+    /// 与另一份 SHP 的同序号帧逐像素比较，返回本文档尺寸的差异掩码与差异像素数。
+    /// 两者左上角对齐；超出对方画布或对方没有该帧时，非透明像素都算不同
+    pub fn diff_frame(&self, frame: usize, other: &SHP) -> (Vec<bool>, usize) {
+        let w = self.width as usize;
+        let mine = &self.frames[frame].pixels;
+        let theirs = other.frames.get(frame).map(|f| &f.pixels);
+        let mut count = 0;
+        let mask = mine.iter().enumerate().map(|(i, &p)| {
+            let (x, y) = ((i % w) as u32, (i / w) as u32);
+            let q = theirs.filter(|_| x < other.width && y < other.height).map(|t| t[(y * other.width + x) as usize]);
+            let differs = match q {
+                Some(q) => (p == self.transparent) != (q == other.transparent) || (p != self.transparent && p != q),
+                None => p != self.transparent,
+            };
+            count += differs as usize;
+            differs
+        }).collect();
+        (mask, count)
+    }
+
     #[allow(dead_code)]
     pub fn set_pixel(&mut self, frame: usize, x: u32, y: u32, index: u8) {
         if frame >= self.frames.len() { return; }
@@ -208,19 +437,46 @@ impl SHP {
 
     #[allow(dead_code)]
     pub fn paste_rgba_into_frame(&mut self, frame: usize, rgba: &image::RgbaImage, pal: &Palette) {
+        // 居中贴入
+        let offx = (self.width as i32 - rgba.width() as i32) / 2;
+        let offy = (self.height as i32 - rgba.height() as i32) / 2;
+        self.paste_rgba_at(frame, rgba, offx, offy, pal);
+    }
+
+    pub fn paste_rgba_at(&mut self, frame: usize, rgba: &image::RgbaImage, dest_x: i32, dest_y: i32, pal: &Palette) {
+        let indices = crate::color_match::quantize_rgba(rgba, &pal.colors, 8, Some(self.transparent));
+        self.paste_indices_at(frame, &indices, rgba.width(), rgba.height(), dest_x, dest_y);
+    }
+
+    /// 半透明边缘处理：把 alpha 介于 [alpha_min, 255) 的像素与帧上 (dest_x, dest_y) 处已有颜色混合为不透明，
+    /// 底下是透明索引时保持原样（由量化阈值决定取舍）
+    pub fn composite_over_frame(&self, frame: usize, rgba: &image::RgbaImage, dest_x: i32, dest_y: i32, pal: &Palette, alpha_min: u8) -> image::RgbaImage {
+        let mut out = rgba.clone();
+        let Some(fr) = self.frames.get(frame) else { return out; };
+        for (x, y, px) in out.enumerate_pixels_mut() {
+            let a = px[3];
+            if a < alpha_min || a == 255 { continue; }
+            let (tx, ty) = (dest_x + x as i32, dest_y + y as i32);
+            if tx < 0 || ty < 0 || tx >= self.width as i32 || ty >= self.height as i32 { continue; }
+            let under = fr.pixels[(ty as u32 * self.width + tx as u32) as usize];
+            if under == self.transparent { continue; }
+            let c = pal.colors[under as usize];
+            let k = a as f32 / 255.0;
+            let mix = |s: u8, d: u8| (s as f32 * k + d as f32 * (1.0 - k)).round() as u8;
+            *px = image::Rgba([mix(px[0], c.r()), mix(px[1], c.g()), mix(px[2], c.b()), 255]);
+        }
+        out
+    }
+
+    /// 将已量化的索引缓冲（None 为透明跳过）贴到帧的 (dest_x, dest_y) 处，超出画布部分裁掉
+    pub fn paste_indices_at(&mut self, frame: usize, indices: &[Option<u8>], iw: u32, ih: u32, dest_x: i32, dest_y: i32) {
         if frame >= self.frames.len() { return; }
         let fw = self.width as i32;
         let fh = self.height as i32;
-        let iw = rgba.width() as i32;
-        let ih = rgba.height() as i32;
-        let offx = (fw - iw) / 2;
-        let offy = (fh - ih) / 2;
-        for y in 0..ih {
-            for x in 0..iw {
-                let px = rgba.get_pixel(x as u32, y as u32);
-                if px[3] < 8 { continue; }
-                let idx = best_index_rgb(Color32::from_rgb(px[0], px[1], px[2]), &pal.colors);
-                let tx = x + offx; let ty = y + offy;
+        for y in 0..ih as i32 {
+            for x in 0..iw as i32 {
+                let Some(idx) = indices[(y * iw as i32 + x) as usize] else { continue; };
+                let tx = x + dest_x; let ty = y + dest_y;
                 if tx >= 0 && ty >= 0 && tx < fw && ty < fh {
                     let i = (ty as u32 * self.width + tx as u32) as usize;
                     self.frames[frame].pixels[i] = idx;
@@ -229,39 +485,359 @@ impl SHP {
         }
     }
 
-    pub fn paste_rgba_at(&mut self, frame: usize, rgba: &image::RgbaImage, dest_x: i32, dest_y: i32, pal: &Palette) {
-        if frame >= self.frames.len() { return; }
-        let fw = self.width as i32;
-        let fh = self.height as i32;
-        let iw = rgba.width() as i32;
-        let ih = rgba.height() as i32;
-        for y in 0..ih {
-            for x in 0..iw {
-                let px = rgba.get_pixel(x as u32, y as u32);
-                if px[3] < 8 { continue; }
-                let idx = best_index_rgb(Color32::from_rgb(px[0], px[1], px[2]), &pal.colors);
-                let tx = x + dest_x; let ty = y + dest_y;
-                if tx >= 0 && ty >= 0 && tx < fw && ty < fh {
-                    let i = (ty as u32 * self.width + tx as u32) as usize;
-                    self.frames[frame].pixels[i] = idx;
+    /// 所有帧非透明内容的并集包围盒 (x, y, w, h)；全部为空返回 None
+    pub fn content_bounds(&self) -> Option<(u32, u32, u32, u32)> {
+        let mut acc: Option<(u32, u32, u32, u32)> = None;
+        for i in 0..self.frames.len() {
+            let Some((x, y, w, h)) = self.frame_bounds(i) else { continue; };
+            let (x1, y1) = (x + w, y + h);
+            acc = Some(match acc {
+                None => (x, y, x1, y1),
+                Some((ax, ay, ax1, ay1)) => (ax.min(x), ay.min(y), ax1.max(x1), ay1.max(y1)),
+            });
+        }
+        acc.map(|(x, y, x1, y1)| (x, y, x1 - x, y1 - y))
+    }
+
+    /// 修改画布尺寸：原内容平移 (off_x, off_y) 后放入新画布，超出部分裁掉，新增区域填透明索引
+    pub fn resize_canvas(&mut self, new_w: u32, new_h: u32, off_x: i32, off_y: i32) {
+        let (ow, oh) = (self.width as i32, self.height as i32);
+        for fr in &mut self.frames {
+            let mut pixels = vec![self.transparent; (new_w * new_h) as usize];
+            for y in 0..oh {
+                let ty = y + off_y;
+                if ty < 0 || ty >= new_h as i32 { continue; }
+                for x in 0..ow {
+                    let tx = x + off_x;
+                    if tx < 0 || tx >= new_w as i32 { continue; }
+                    pixels[(ty * new_w as i32 + tx) as usize] = fr.pixels[(y * ow + x) as usize];
+                }
+            }
+            fr.pixels = pixels;
+        }
+        self.width = new_w;
+        self.height = new_h;
+    }
+
+    /// 重定画布到 new_w x new_h 时原内容的平移量，传给 `resize_canvas`；`margin` 为内容底边到新画布底边的距离。
+    /// 按内容对齐而所有帧为空时返回 None
+    pub fn retarget_offset(&self, anchor: RetargetAnchor, new_w: u32, new_h: u32, margin: u32) -> Option<(i32, i32)> {
+        let center = |old: u32, new: u32| (new as i32 - old as i32) / 2;
+        match anchor {
+            RetargetAnchor::CanvasCenter => Some((center(self.width, new_w), center(self.height, new_h))),
+            RetargetAnchor::CanvasBottom => Some((center(self.width, new_w), new_h as i32 - self.height as i32)),
+            RetargetAnchor::ContentBottom => {
+                let (x, y, w, h) = self.content_bounds()?;
+                Some((center(w, new_w) - x as i32, new_h as i32 - margin as i32 - (y + h) as i32))
+            }
+            RetargetAnchor::ContentCenter => {
+                let (x, y, w, h) = self.content_bounds()?;
+                Some((center(w, new_w) - x as i32, center(h, new_h) - y as i32))
+            }
+        }
+    }
+
+    /// 所有帧像素整体平移 (dx, dy)。`wrap` 时移出画布的部分从对边移入，
+    /// 否则直接裁掉，空出的区域填透明索引
+    pub fn shift_pixels(&mut self, dx: i32, dy: i32, wrap: bool) {
+        let (w, h) = (self.width as i32, self.height as i32);
+        if w == 0 || h == 0 { return; }
+        for fr in &mut self.frames {
+            let mut pixels = vec![self.transparent; fr.pixels.len()];
+            for y in 0..h {
+                let ty = if wrap { (y + dy).rem_euclid(h) } else { y + dy };
+                if ty < 0 || ty >= h { continue; }
+                for x in 0..w {
+                    let tx = if wrap { (x + dx).rem_euclid(w) } else { x + dx };
+                    if tx < 0 || tx >= w { continue; }
+                    pixels[(ty * w + tx) as usize] = fr.pixels[(y * w + x) as usize];
+                }
+            }
+            fr.pixels = pixels;
+        }
+    }
+
+    /// 把 (dx, dy) 限制在使所有帧内容仍留在画布内的范围；全部为空时不限制
+    pub fn clamp_shift(&self, dx: i32, dy: i32) -> (i32, i32) {
+        let Some((x, y, w, h)) = self.content_bounds() else { return (dx, dy); };
+        let (x, y) = (x as i32, y as i32);
+        let max_x = self.width as i32 - w as i32 - x;
+        let max_y = self.height as i32 - h as i32 - y;
+        (dx.clamp(-x, max_x), dy.clamp(-y, max_y))
+    }
+
+    /// 使所有帧内容的并集包围盒居中所需的平移量；全部为空返回 None
+    pub fn center_shift(&self) -> Option<(i32, i32)> {
+        let (x, y, w, h) = self.content_bounds()?;
+        Some(((self.width - w) as i32 / 2 - x as i32, (self.height - h) as i32 / 2 - y as i32))
+    }
+
+    /// 所有帧按最近邻重采样到新尺寸
+    pub fn scale_nearest(&mut self, new_w: u32, new_h: u32) {
+        let (ow, oh) = (self.width, self.height);
+        self.frames.par_iter_mut().for_each(|fr| {
+            fr.offset = (fr.offset.0 * new_w as i32 / ow as i32, fr.offset.1 * new_h as i32 / oh as i32);
+            let mut pixels = Vec::with_capacity((new_w * new_h) as usize);
+            for y in 0..new_h {
+                let sy = (y as u64 * oh as u64 / new_h as u64) as u32;
+                for x in 0..new_w {
+                    let sx = (x as u64 * ow as u64 / new_w as u64) as u32;
+                    pixels.push(fr.pixels[(sy * ow + sx) as usize]);
+                }
+            }
+            fr.pixels = pixels;
+        });
+        self.width = new_w;
+        self.height = new_h;
+    }
+
+    /// 按所选算法缩放所有帧；固定倍率算法忽略 new_w/new_h
+    pub fn scale(&mut self, scaler: PixelScaler, new_w: u32, new_h: u32) {
+        let Some(k) = scaler.factor() else { return self.scale_nearest(new_w, new_h); };
+        let (w, h) = (self.width as usize, self.height as usize);
+        self.frames.par_iter_mut().for_each(|fr| {
+            fr.pixels = if k == 2 { scale2x(&fr.pixels, w, h) } else { scale3x(&fr.pixels, w, h) };
+            fr.offset = (fr.offset.0 * k as i32, fr.offset.1 * k as i32);
+        });
+        self.width *= k;
+        self.height *= k;
+    }
+
+    /// 由第 `frame` 帧生成阴影帧：所有非透明像素投影为阴影索引，其余为透明
+    pub fn shadow_frame(&self, frame: usize, p: &ShadowParams) -> Frame {
+        let mut pixels = vec![self.transparent; (self.width * self.height) as usize];
+        let offset = self.frames[frame].offset;
+        let compression = self.frames[frame].compression;
+        let Some((_, by, _, bh)) = self.frame_bounds(frame) else { return Frame { pixels, offset, compression, source: None }; };
+        let ground = (by + bh - 1) as f32;
+        let src = &self.frames[frame].pixels;
+        let w = self.width as usize;
+        for (i, &v) in src.iter().enumerate() {
+            if v == self.transparent { continue; }
+            let (x, y) = ((i % w) as f32, (i / w) as f32);
+            let h = ground - y;
+            let nx = (x + p.shear * h).round() as i32 + p.offset_x;
+            let ny = (ground - h * p.squash).round() as i32 + p.offset_y;
+            if nx < 0 || ny < 0 || nx >= self.width as i32 || ny >= self.height as i32 { continue; }
+            pixels[ny as usize * w + nx as usize] = p.index;
+        }
+        Frame { pixels, offset, compression, source: None }
+    }
+
+    /// 第 `frame` 帧绕画布中心旋转 `deg` 度（顺时针为正）后的新帧，画布尺寸不变，转出画布的部分裁掉。
+    /// 最近邻直接搬运索引；`smooth` 时在 RGB 中双线性插值、覆盖过半才不透明，再量化回调色板
+    pub fn rotated_frame(&self, frame: usize, deg: f32, smooth: bool, pal: &Palette) -> Frame {
+        let fr = &self.frames[frame];
+        let (w, h) = (self.width as i32, self.height as i32);
+        let (s, c) = deg.to_radians().sin_cos();
+        let (cx, cy) = (self.width as f32 / 2.0, self.height as f32 / 2.0);
+        let at = |x: i32, y: i32| if x < 0 || y < 0 || x >= w || y >= h { self.transparent } else { fr.pixels[(y * w + x) as usize] };
+        let matcher = smooth.then(|| crate::color_match::ColorMatcher::shared(&pal.colors, Some(self.transparent)));
+        let mut memo = std::collections::HashMap::new();
+        let mut pixels = vec![self.transparent; fr.pixels.len()];
+        for y in 0..h {
+            for x in 0..w {
+                // 目标像素中心逆变换回源帧坐标
+                let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+                let (sx, sy) = (c * dx + s * dy + cx, -s * dx + c * dy + cy);
+                let Some(m) = &matcher else {
+                    pixels[(y * w + x) as usize] = at(sx.floor() as i32, sy.floor() as i32);
+                    continue;
+                };
+                let (fx, fy) = (sx - 0.5, sy - 0.5);
+                let (x0, y0) = (fx.floor(), fy.floor());
+                let (tx, ty) = (fx - x0, fy - y0);
+                let (mut rgb, mut cover) = ([0.0f32; 3], 0.0f32);
+                for (ox, oy, wgt) in [(0, 0, (1.0 - tx) * (1.0 - ty)), (1, 0, tx * (1.0 - ty)), (0, 1, (1.0 - tx) * ty), (1, 1, tx * ty)] {
+                    let p = at(x0 as i32 + ox, y0 as i32 + oy);
+                    if p == self.transparent { continue; }
+                    let col = pal.colors[p as usize];
+                    for (acc, v) in rgb.iter_mut().zip([col.r(), col.g(), col.b()]) { *acc += v as f32 * wgt; }
+                    cover += wgt;
+                }
+                if cover < 0.5 { continue; }
+                let rgb = rgb.map(|v| (v / cover).round().clamp(0.0, 255.0) as u8);
+                pixels[(y * w + x) as usize] = *memo.entry(rgb).or_insert_with(|| m.nearest(rgb[0], rgb[1], rgb[2]));
+            }
+        }
+        Frame { compression: fr.compression, ..Frame::new(pixels) }
+    }
+
+    /// 在第 `a`、`b` 帧之间生成 `steps` 个补间帧（不含两端）。
+    /// `mask` 为选区掩码：移动模式下只移动选区内的内容（选区应覆盖运动路径），交叉淡化时只混合选区内像素
+    pub fn tween_frames(&self, a: usize, b: usize, steps: usize, mode: TweenMode, pal: &Palette, mask: Option<&[bool]>) -> Vec<Frame> {
+        let (fa, fb) = (&self.frames[a], &self.frames[b]);
+        let inside = |i: usize| mask.is_none_or(|m| m[i]);
+        let lerp = |x: i32, y: i32, t: f32| (x as f32 + (y - x) as f32 * t).round() as i32;
+        let matcher = (mode == TweenMode::CrossFade).then(|| crate::color_match::ColorMatcher::shared(&pal.colors, Some(self.transparent)));
+        // 移动模式：两帧选区内内容包围盒左上角的位移
+        let masked_bounds = |fr: &Frame| {
+            let px: Vec<u8> = fr.pixels.iter().enumerate().map(|(i, &v)| if inside(i) { v } else { self.transparent }).collect();
+            content_rect(&px, self.width, self.transparent)
+        };
+        let shift = match (masked_bounds(fa), masked_bounds(fb)) {
+            (Some((ax, ay, _, _)), Some((bx, by, _, _))) => (bx as i32 - ax as i32, by as i32 - ay as i32),
+            _ => (0, 0),
+        };
+        let mut memo = std::collections::HashMap::new();
+        (1..=steps).map(|k| {
+            let t = k as f32 / (steps + 1) as f32;
+            let mut pixels = fa.pixels.clone();
+            match &matcher {
+                Some(m) => for (i, p) in pixels.iter_mut().enumerate() {
+                    let (pa, pb) = (fa.pixels[i], fb.pixels[i]);
+                    if !inside(i) || pa == pb { continue; }
+                    let (oa, ob) = (pa != self.transparent, pb != self.transparent);
+                    // 不透明度线性过渡，过半才显示；一端透明时直接取另一端的颜色
+                    let alpha = if oa { 1.0 - t } else { 0.0 } + if ob { t } else { 0.0 };
+                    *p = if alpha < 0.5 { self.transparent } else if !oa { pb } else if !ob { pa } else {
+                        let (ca, cb) = (pal.colors[pa as usize], pal.colors[pb as usize]);
+                        let mix = |x: u8, y: u8| (x as f32 + (y as f32 - x as f32) * t).round() as u8;
+                        let rgb = [mix(ca.r(), cb.r()), mix(ca.g(), cb.g()), mix(ca.b(), cb.b())];
+                        *memo.entry(rgb).or_insert_with(|| m.nearest(rgb[0], rgb[1], rgb[2]))
+                    };
+                },
+                None => {
+                    let (dx, dy) = (lerp(0, shift.0, t), lerp(0, shift.1, t));
+                    let (w, h) = (self.width as i32, self.height as i32);
+                    for (i, p) in pixels.iter_mut().enumerate() { if inside(i) { *p = self.transparent; } }
+                    for (i, &v) in fa.pixels.iter().enumerate() {
+                        if !inside(i) || v == self.transparent { continue; }
+                        let (x, y) = ((i as i32 % w) + dx, (i as i32 / w) + dy);
+                        if x >= 0 && y >= 0 && x < w && y < h { pixels[(y * w + x) as usize] = v; }
+                    }
                 }
             }
+            Frame { pixels, offset: (lerp(fa.offset.0, fb.offset.0, t), lerp(fa.offset.1, fb.offset.1, t)), compression: fa.compression, source: None }
+        }).collect()
+    }
+
+    /// 给帧内非透明区域描 1 像素边：`outside` 为 true 时画在剪影外侧的透明像素上，否则覆盖剪影最外圈；
+    /// `diagonal` 时对角相邻也算贴边（外描边转角更圆满）。返回改写的像素数
+    pub fn outline_frame(&mut self, frame: usize, index: u8, outside: bool, diagonal: bool) -> usize {
+        let (w, h) = (self.width as i32, self.height as i32);
+        let t = self.transparent;
+        let src = self.frames[frame].pixels.clone();
+        let opaque = |x: i32, y: i32| x >= 0 && y >= 0 && x < w && y < h && src[(y * w + x) as usize] != t;
+        let near: &[(i32, i32)] = if diagonal {
+            &[(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (1, -1), (-1, 1), (1, 1)]
+        } else {
+            &[(-1, 0), (1, 0), (0, -1), (0, 1)]
+        };
+        let mut n = 0;
+        for (i, p) in self.frames[frame].pixels.iter_mut().enumerate() {
+            let (x, y) = (i as i32 % w, i as i32 / w);
+            // 外描边：透明且挨着剪影；内描边：不透明且挨着透明或画布边缘
+            let edge = if outside {
+                !opaque(x, y) && near.iter().any(|&(dx, dy)| opaque(x + dx, y + dy))
+            } else {
+                opaque(x, y) && near.iter().any(|&(dx, dy)| !opaque(x + dx, y + dy))
+            };
+            if edge && *p != index { *p = index; n += 1; }
+        }
+        n
+    }
+
+    /// 按 256 项映射表改写所有帧的索引（换调色板时使用）
+    pub fn remap_all(&mut self, table: &[u8; 256]) {
+        for fr in &mut self.frames {
+            for p in fr.pixels.iter_mut() { *p = table[*p as usize]; }
         }
     }
 
+    /// 将帧内所有索引 `from` 替换为 `to`，返回替换的像素数
+    pub fn replace_index(&mut self, frame: usize, from: u8, to: u8) -> usize {
+        if frame >= self.frames.len() || from == to { return 0; }
+        let mut n = 0;
+        for p in self.frames[frame].pixels.iter_mut() {
+            if *p == from { *p = to; n += 1; }
+        }
+        n
+    }
+
     #[allow(dead_code)]
     pub fn export_frame_png(&self, frame: usize, pal: &Palette, path: std::path::PathBuf) -> Result<(), String> {
-        if frame >= self.frames.len() { return Err("帧索引超界".into()); }
+        if frame >= self.frames.len() { return Err(tr("帧索引超界").into()); }
+        image::DynamicImage::ImageRgba8(self.frame_rgba(frame, pal)).save(path).map_err(|e| e.to_string())
+    }
+
+    /// 整帧转为 RGBA 图像，文档的透明索引为 alpha 0
+    pub fn frame_rgba(&self, frame: usize, pal: &Palette) -> image::RgbaImage {
         let mut img = image::RgbaImage::new(self.width, self.height);
         let fr = &self.frames[frame];
-        // 约定：调色板索引0为透明
         for y in 0..self.height { for x in 0..self.width {
-            let idx = fr.pixels[(y * self.width + x) as usize] as usize;
-            let c = pal.colors[idx];
-            let a = if idx == 0 { 0u8 } else { 255u8 };
+            let idx = fr.pixels[(y * self.width + x) as usize];
+            let c = pal.colors[idx as usize];
+            let a = if idx == self.transparent { 0u8 } else { 255u8 };
             img.put_pixel(x, y, image::Rgba([c.r(), c.g(), c.b(), a]));
         }}
-        image::DynamicImage::ImageRgba8(img).save(path).map_err(|e| e.to_string())
+        img
+    }
+
+    /// 残影叠加：按顺序把各帧叠到同一张图上，不透明度从 `first_alpha`（第一帧）线性过渡到 `last_alpha`（最后一帧），
+    /// 后面的帧盖在上面
+    pub fn ghost_composite(&self, frames: &[usize], pal: &Palette, first_alpha: f32, last_alpha: f32) -> image::RgbaImage {
+        let mut out = image::RgbaImage::new(self.width, self.height);
+        let n = frames.len();
+        for (k, &fi) in frames.iter().enumerate() {
+            let Some(fr) = self.frames.get(fi) else { continue; };
+            let t = if n > 1 { k as f32 / (n - 1) as f32 } else { 1.0 };
+            let a = (first_alpha + (last_alpha - first_alpha) * t).clamp(0.0, 1.0);
+            for (px, &idx) in out.pixels_mut().zip(&fr.pixels) {
+                if idx == self.transparent { continue; }
+                let c = pal.colors[idx as usize];
+                // 非预乘 alpha 的 over 合成
+                let da = px[3] as f32 / 255.0;
+                let oa = a + da * (1.0 - a);
+                if oa <= 0.0 { continue; }
+                let mix = |s: u8, d: u8| ((s as f32 * a + d as f32 * da * (1.0 - a)) / oa).round() as u8;
+                *px = image::Rgba([mix(c.r(), px[0]), mix(c.g(), px[1]), mix(c.b(), px[2]), (oa * 255.0).round() as u8]);
+            }
+        }
+        out
+    }
+
+    /// 生成帧的低分辨率缩略图（最近邻采样，最长边不超过 `max_side`）
+    pub fn thumbnail_image(&self, frame: usize, pal: &Palette, brightness: f32, max_side: u32) -> egui::ColorImage {
+        let k = (max_side as f32 / self.width.max(self.height).max(1) as f32).min(1.0);
+        let tw = ((self.width as f32 * k).round() as u32).max(1);
+        let th = ((self.height as f32 * k).round() as u32).max(1);
+        let fr = if frame < self.frames.len() { &self.frames[frame] } else { &self.frames[0] };
+        let b = brightness.clamp(0.2, 3.0);
+        let mut rgba = Vec::with_capacity((tw * th * 4) as usize);
+        for ty in 0..th {
+            let sy = (ty * self.height / th).min(self.height - 1);
+            for tx in 0..tw {
+                let sx = (tx * self.width / tw).min(self.width - 1);
+                let idx = fr.pixels[(sy * self.width + sx) as usize];
+                let c = pal.colors[idx as usize];
+                rgba.push(((c.r() as f32) * b).round().min(255.0) as u8);
+                rgba.push(((c.g() as f32) * b).round().min(255.0) as u8);
+                rgba.push(((c.b() as f32) * b).round().min(255.0) as u8);
+                rgba.push(if idx == self.transparent { 0 } else { 255 });
+            }
+        }
+        egui::ColorImage::from_rgba_unmultiplied([tw as usize, th as usize], &rgba)
+    }
+
+    /// 导出帧差异：仅绘制与上一帧（第0帧对比最后一帧，便于检查循环衔接）索引不同的像素，其余透明
+    /// 变为透明索引的像素同样以不透明颜色绘出，便于发现背景闪烁。返回变化像素数
+    pub fn export_frame_delta_png(&self, frame: usize, pal: &Palette, path: std::path::PathBuf) -> Result<usize, String> {
+        if frame >= self.frames.len() { return Err(tr("帧索引超界").into()); }
+        let prev = if frame == 0 { self.frames.len() - 1 } else { frame - 1 };
+        let cur = &self.frames[frame].pixels;
+        let old = &self.frames[prev].pixels;
+        let mut img = image::RgbaImage::new(self.width, self.height);
+        let mut changed = 0usize;
+        for y in 0..self.height { for x in 0..self.width {
+            let i = (y * self.width + x) as usize;
+            if cur[i] == old[i] { continue; }
+            let c = pal.colors[cur[i] as usize];
+            img.put_pixel(x, y, image::Rgba([c.r(), c.g(), c.b(), 255]));
+            changed += 1;
+        }}
+        image::DynamicImage::ImageRgba8(img).save(path).map_err(|e| e.to_string())?;
+        Ok(changed)
     }
 
     #[allow(dead_code)]
@@ -275,6 +851,18 @@ impl SHP {
         frame: usize,
         pal: &Palette,
         brightness: f32,
+    ) -> TextureHandle {
+        self.egui_texture_named(ctx, "frame_tex", frame, pal, brightness)
+    }
+
+    /// 同 `egui_texture_with_brightness`，但可指定纹理名（用于同时持有多张纹理，如缩略图）
+    pub fn egui_texture_named(
+        &self,
+        ctx: &egui::Context,
+        name: &str,
+        frame: usize,
+        pal: &Palette,
+        brightness: f32,
     ) -> TextureHandle {
         // 安全保护：避免异常尺寸导致巨大内存分配
         let pixels_u64 = (self.width as u64) * (self.height as u64);
@@ -282,22 +870,655 @@ impl SHP {
             let img = egui::ColorImage::from_rgba_unmultiplied([1, 1], &[0u8, 0, 0, 255]);
             return ctx.load_texture("frame_tex_err", img, egui::TextureOptions::NEAREST);
         }
-        let mut rgba = Vec::with_capacity((pixels_u64 * 4) as usize);
+        let img = self.region_image(frame, pal, brightness, 0, 0, self.width, self.height);
+        ctx.load_texture(name, img, egui::TextureOptions::NEAREST)
+    }
+
+    /// 将帧的一个矩形区域转换为 RGBA 图像（带亮度），用于整帧纹理或局部更新
+    #[allow(clippy::too_many_arguments)]
+    pub fn region_image(&self, frame: usize, pal: &Palette, brightness: f32, x0: u32, y0: u32, w: u32, h: u32) -> egui::ColorImage {
         let fr = if frame < self.frames.len() { &self.frames[frame] } else { &self.frames[0] };
         let b = brightness.max(0.2).min(3.0);
-        let total = (self.width * self.height) as usize;
-        for i in 0..total {
-            let idx = fr.pixels[i] as usize;
-            let c = pal.colors[idx];
-            let r = ((c.r() as f32) * b).round().min(255.0) as u8;
-            let g = ((c.g() as f32) * b).round().min(255.0) as u8;
-            let bl = ((c.b() as f32) * b).round().min(255.0) as u8;
-            let a = if idx == 0 { 0u8 } else { 255u8 }; // 预览中索引0透明
-            rgba.push(r); rgba.push(g); rgba.push(bl); rgba.push(a);
+        let mut rgba = Vec::with_capacity((w * h * 4) as usize);
+        for y in y0..y0 + h {
+            let row = (y * self.width) as usize;
+            for x in x0..x0 + w {
+                let idx = fr.pixels[row + x as usize];
+                let c = pal.colors[idx as usize];
+                let r = ((c.r() as f32) * b).round().min(255.0) as u8;
+                let g = ((c.g() as f32) * b).round().min(255.0) as u8;
+                let bl = ((c.b() as f32) * b).round().min(255.0) as u8;
+                let a = if idx == self.transparent { 0u8 } else { 255u8 }; // 预览中透明索引不绘制
+                rgba.push(r); rgba.push(g); rgba.push(bl); rgba.push(a);
+            }
+        }
+        egui::ColorImage::from_rgba_unmultiplied([w as usize, h as usize], &rgba)
+    }
+}
+
+// 不等于 `bg` 的像素的包围盒 (x, y, w, h)
+pub fn content_rect(pixels: &[u8], width: u32, bg: u8) -> Option<(u32, u32, u32, u32)> {
+    let w = width as usize;
+    if w == 0 { return None; }
+    let (mut x0, mut y0, mut x1, mut y1) = (usize::MAX, usize::MAX, 0usize, 0usize);
+    for (y, row) in pixels.chunks_exact(w).enumerate() {
+        let Some(first) = row.iter().position(|&p| p != bg) else { continue; };
+        let last = row.iter().rposition(|&p| p != bg).unwrap_or(first);
+        y0 = y0.min(y); y1 = y;
+        x0 = x0.min(first); x1 = x1.max(last);
+    }
+    if y0 == usize::MAX { return None; }
+    Some((x0 as u32, y0 as u32, (x1 - x0 + 1) as u32, (y1 - y0 + 1) as u32))
+}
+
+// 3x3 邻域（边缘按夹紧处理）：[A B C, D E F, G H I]
+fn neighborhood(src: &[u8], w: usize, h: usize, x: usize, y: usize) -> [u8; 9] {
+    let (xl, xr) = (x.saturating_sub(1), (x + 1).min(w - 1));
+    let (yu, yd) = (y.saturating_sub(1), (y + 1).min(h - 1));
+    let at = |xx: usize, yy: usize| src[yy * w + xx];
+    [at(xl, yu), at(x, yu), at(xr, yu), at(xl, y), at(x, y), at(xr, y), at(xl, yd), at(x, yd), at(xr, yd)]
+}
+
+// Scale2x (AdvMAME2x)：沿对角方向的边缘取邻居索引，避免锯齿被简单放大
+fn scale2x(src: &[u8], w: usize, h: usize) -> Vec<u8> {
+    let ow = w * 2;
+    let mut out = vec![0u8; ow * h * 2];
+    for y in 0..h {
+        for x in 0..w {
+            let [_, b, _, d, e, f, _, hh, _] = neighborhood(src, w, h, x, y);
+            let px = if b != hh && d != f {
+                [if d == b { d } else { e }, if b == f { f } else { e }, if d == hh { d } else { e }, if hh == f { f } else { e }]
+            } else {
+                [e; 4]
+            };
+            let (ox, oy) = (x * 2, y * 2);
+            out[oy * ow + ox] = px[0];
+            out[oy * ow + ox + 1] = px[1];
+            out[(oy + 1) * ow + ox] = px[2];
+            out[(oy + 1) * ow + ox + 1] = px[3];
+        }
+    }
+    out
+}
+
+// Scale3x (AdvMAME3x)
+fn scale3x(src: &[u8], w: usize, h: usize) -> Vec<u8> {
+    let ow = w * 3;
+    let mut out = vec![0u8; ow * h * 3];
+    for y in 0..h {
+        for x in 0..w {
+            let [a, b, c, d, e, f, g, hh, i] = neighborhood(src, w, h, x, y);
+            let px = if b != hh && d != f {
+                [
+                    if d == b { d } else { e },
+                    if (d == b && e != c) || (b == f && e != a) { b } else { e },
+                    if b == f { f } else { e },
+                    if (d == b && e != g) || (d == hh && e != a) { d } else { e },
+                    e,
+                    if (b == f && e != i) || (hh == f && e != c) { f } else { e },
+                    if d == hh { d } else { e },
+                    if (d == hh && e != i) || (hh == f && e != g) { hh } else { e },
+                    if hh == f { f } else { e },
+                ]
+            } else {
+                [e; 9]
+            };
+            for (k, &v) in px.iter().enumerate() {
+                out[(y * 3 + k / 3) * ow + x * 3 + k % 3] = v;
+            }
+        }
+    }
+    out
+}
+
+/// 解码后超过该字节数的 SHP 在查看模式下按需解码
+pub const LAZY_THRESHOLD: u64 = 128 * 1024 * 1024;
+const LAZY_CACHE_FRAMES: usize = 32;
+
+/// 按需解码的只读 SHP：保留原始字节与帧头，只把最近用到的帧解码进 LRU 缓存，内存占用有上限
+pub struct LazyShp {
+    pub width: u32,
+    pub height: u32,
+    bytes: Vec<u8>,
+    headers: Vec<FHeader>,
+    // 最近使用的在前
+    cache: std::collections::VecDeque<(usize, Frame)>,
+}
+
+impl LazyShp {
+    pub fn open(bytes: Vec<u8>) -> Result<Self, String> {
+        let (width, height, headers) = read_headers(&bytes)?;
+        Ok(Self { width, height, bytes, headers, cache: Default::default() })
+    }
+
+    /// 全部帧解码后的总字节数，用于判断是否值得按需解码
+    pub fn decoded_size(bytes: &[u8]) -> Result<u64, String> {
+        let (w, h, fhs) = read_headers(bytes)?;
+        Ok(w as u64 * h as u64 * fhs.len() as u64)
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn frame(&mut self, index: usize) -> Result<&Frame, String> {
+        let Some(&fh) = self.headers.get(index) else { return Err(tr("帧索引超界").into()); };
+        match self.cache.iter().position(|(i, _)| *i == index) {
+            Some(pos) => { let hit = self.cache.remove(pos).unwrap(); self.cache.push_front(hit); }
+            None => {
+                let frame = decode_frame(&self.bytes, index, fh, self.width, self.height)?;
+                self.cache.push_front((index, frame));
+                self.cache.truncate(LAZY_CACHE_FRAMES);
+            }
+        }
+        Ok(&self.cache[0].1)
+    }
+}
+
+// 兼容 RA2/YR SHP：
+// Header: u16 zero, u16 width, u16 height, u16 frame_count
+// Per-frame header (24 bytes): x,y,w,h (u16*4), flags(u32), frameColor[4], zero(i32), dataOffset(u32)
+#[derive(Clone, Copy)]
+struct FHeader { x:u16, y:u16, w:u16, h:u16, flags:u32, data_off:u32 }
+
+fn read_u16(r:&mut Cursor<&[u8]>) -> Result<u16,String>{ let mut b=[0u8;2]; r.read_exact(&mut b).map_err(|e|e.to_string())?; Ok(u16::from_le_bytes(b)) }
+fn read_u32(r:&mut Cursor<&[u8]>) -> Result<u32,String>{ let mut b=[0u8;4]; r.read_exact(&mut b).map_err(|e|e.to_string())?; Ok(u32::from_le_bytes(b)) }
+fn read_i32(r:&mut Cursor<&[u8]>) -> Result<i32,String>{ let mut b=[0u8;4]; r.read_exact(&mut b).map_err(|e|e.to_string())?; Ok(i32::from_le_bytes(b)) }
+
+/// 解析文件头与全部帧头，返回 (宽, 高, 帧头)
+fn read_headers(bytes: &[u8]) -> Result<(u32, u32, Vec<FHeader>), String> {
+    if bytes.len() < 8 { return Err(tr("SHP头不足").into()); }
+    let mut cur = Cursor::new(bytes);
+    let zero = read_u16(&mut cur)?; if zero != 0 { return Err(tr("不是有效的SHP文件").into()); }
+    let w = read_u16(&mut cur)? as u32;
+    let h = read_u16(&mut cur)? as u32;
+    let n = read_u16(&mut cur)? as usize;
+    if w == 0 || h == 0 || n == 0 { return Err(tr("无效SHP尺寸/帧数").into()); }
+    if bytes.len() < 8 + 24 * n { return Err(trf!("帧头表不完整：{} 帧需要 {} 字节，文件只有 {} 字节", n, 8 + 24 * n, bytes.len())); }
+    // 单帧尺寸上限在此检查，按需解码时也受约束；总量上限只在一次性全部解码时检查
+    check_decoded_size(w, h, 1)?;
+
+    // 读取帧头
+    let mut fhs: Vec<FHeader> = Vec::with_capacity(n);
+    for _ in 0..n {
+        let x = read_u16(&mut cur)?;
+        let y = read_u16(&mut cur)?;
+        let ww = read_u16(&mut cur)?;
+        let hh = read_u16(&mut cur)?;
+        let flags = read_u32(&mut cur)?;
+        let mut color_rgba = [0u8;4]; cur.read_exact(&mut color_rgba).map_err(|e|e.to_string())?;
+        let _zero2 = read_i32(&mut cur)?; // 忽略
+        let data_off = read_u32(&mut cur)?;
+        fhs.push(FHeader { x, y, w: ww, h: hh, flags, data_off });
+    }
+    Ok((w, h, fhs))
+}
+
+// 帧块：(x, y, w, h, 数据)
+type Block = (u32, u32, u32, u32, Vec<u8>);
+/// 保存时的帧头矩形 (x, y, w, h) 与两种编码下的字节数 (未压缩, RLE0)
+pub type SaveInfo = ((u32, u32, u32, u32), (usize, usize));
+
+// 按编码方式写出逐行像素块。RLE0 每行：u16 行长（含这 2 字节），非 0 字节原样写，
+// 成串的 0 写为 0 + 个数（最多 255）
+fn encode_block(raw: Vec<u8>, width: u32, compression: Compression) -> Result<Vec<u8>, String> {
+    if compression == Compression::Uncompressed { return Ok(raw); }
+    let mut out = Vec::with_capacity(raw.len() / 2);
+    for row in raw.chunks(width as usize) {
+        let start = out.len();
+        out.extend_from_slice(&[0, 0]);
+        let mut i = 0;
+        while i < row.len() {
+            if row[i] != 0 { out.push(row[i]); i += 1; continue; }
+            let run = row[i..].iter().take(255).take_while(|&&v| v == 0).count();
+            out.extend_from_slice(&[0, run as u8]);
+            i += run;
+        }
+        let len = u16::try_from(out.len() - start).map_err(|_| tr("RLE0 行数据超过 65535 字节，请改用未压缩").to_string())?;
+        out[start..start + 2].copy_from_slice(&len.to_le_bytes());
+    }
+    Ok(out)
+}
+
+// 数据块在两种编码下的字节数 (未压缩, RLE0)；单行超出 u16 长度时 RLE0 无法写入，按 usize::MAX 表示不可用
+fn block_sizes(raw: Vec<u8>, w: u32) -> (usize, usize) {
+    let len = raw.len();
+    (len, encode_block(raw, w, Compression::Rle0).map_or(usize::MAX, |b| b.len()))
+}
+
+/// 解码一帧；错误信息带上帧序号
+fn decode_frame(bytes: &[u8], index: usize, fh: FHeader, w: u32, h: u32) -> Result<Frame, String> {
+    decode_frame_data(bytes, fh, w, h).map_err(|e| trf!("第 {} 帧: {}", index, e))
+}
+
+fn decode_frame_data(bytes: &[u8], fh: FHeader, w: u32, h: u32) -> Result<Frame, String> {
+    let mut pixels = vec![0u8; (w * h) as usize];
+    let source = Some(FrameSource { rect: (fh.x, fh.y, fh.w, fh.h), flags: fh.flags, data_offset: fh.data_off, td: false });
+    if fh.data_off == 0 || fh.w == 0 || fh.h == 0 {
+        return Ok(Frame { source, ..Frame::new(pixels) });
+    }
+    let compression = if (fh.flags & 3) == 3 { Compression::Rle0 } else { Compression::Uncompressed };
+    let Some(data) = bytes.get(fh.data_off as usize..).filter(|d| !d.is_empty()) else {
+        return Err(trf!("数据偏移 {} 超出文件长度 {}", fh.data_off, bytes.len()));
+    };
+    let is_rle0 = (fh.flags & 3) == 3;
+    let is_scan = (fh.flags & 2) == 2 && (fh.flags & 1) == 0;
+    // 帧块超出画布的部分直接丢弃
+    let mut put = |x: usize, y: usize, v: u8| {
+        if x < w as usize && y < h as usize { pixels[y * w as usize + x] = v; }
+    };
+
+    if is_rle0 || is_scan {
+        // 每行：u16 行长（含这 2 字节），随后行数据；RLE0 中 0 后跟透明像素个数
+        let mut pos = 0usize;
+        for row in 0..fh.h as usize {
+            let Some(len) = data.get(pos..pos + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize) else {
+                return Err(trf!("第 {} 行: 数据在行长字段处截断", row));
+            };
+            if len < 2 { return Err(trf!("第 {} 行: 行长 {} 无效（至少为 2）", row, len)); }
+            let Some(line) = data.get(pos + 2..pos + len) else {
+                return Err(trf!("第 {} 行: 声明 {} 字节，但剩余数据只有 {} 字节", row, len, data.len() - pos));
+            };
+            pos += len;
+            let y = fh.y as usize + row;
+            let mut x = fh.x as usize;
+            let mut i = 0;
+            while i < line.len() {
+                let v = line[i];
+                i += 1;
+                if is_rle0 && v == 0 {
+                    let Some(&zeros) = line.get(i) else { return Err(trf!("第 {} 行: 行尾缺少透明像素个数", row)); };
+                    i += 1;
+                    x += zeros as usize;
+                } else {
+                    put(x, y, v);
+                    x += 1;
+                }
+            }
+        }
+    } else {
+        // 未压缩：w*h 直接字节块
+        let need = fh.w as usize * fh.h as usize;
+        let Some(block) = data.get(..need) else {
+            return Err(trf!("未压缩数据需要 {} 字节，但剩余数据只有 {} 字节", need, data.len()));
+        };
+        for (row, line) in block.chunks(fh.w as usize).enumerate() {
+            for (k, &v) in line.iter().enumerate() { put(fh.x as usize + k, fh.y as usize + row, v); }
+        }
+    }
+
+    Ok(Frame { compression, source, ..Frame::new(pixels) })
+}
+
+// TD/RA1 旧格式 SHP：
+// Header: u16 frame_count, u16 x, u16 y, u16 width, u16 height, u16 delta_size, u16 flags
+// 随后 frame_count+2 个 8 字节索引：u32 (低 24 位数据偏移 | 高 8 位格式)，u32 (低 24 位参考偏移 | 高 8 位参考格式)
+// 格式 0x80 为整帧 Format80(LCW)；0x40 为与参考偏移处的 0x80 帧做 Format40 异或；0x20 为与上一帧做 Format40 异或
+const TD_LCW: u8 = 0x80;
+const TD_XOR_LCW: u8 = 0x40;
+const TD_XOR_PREV: u8 = 0x20;
+
+fn td_u16(bytes: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn td_u32(bytes: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+/// 文件头启发式：帧数非 0（TS 格式此处为 0）、尺寸有效、首帧偏移恰在索引表之后且格式字节合法
+fn is_td_shp(bytes: &[u8]) -> bool {
+    let (Some(n), Some(w), Some(h)) = (td_u16(bytes, 0), td_u16(bytes, 6), td_u16(bytes, 8)) else { return false; };
+    if n == 0 || w == 0 || h == 0 { return false; }
+    let table_end = 14 + (n as usize + 2) * 8;
+    let Some(first) = td_u32(bytes, 14) else { return false; };
+    (first & 0xFF_FFFF) as usize == table_end && bytes.len() >= table_end
+        && matches!((first >> 24) as u8, TD_LCW | TD_XOR_LCW | TD_XOR_PREV)
+}
+
+fn load_td(bytes: &[u8]) -> Result<SHP, String> {
+    let corrupt = || tr("TD/RA1 SHP 数据损坏").to_string();
+    let n = td_u16(bytes, 0).ok_or_else(corrupt)? as usize;
+    let w = td_u16(bytes, 6).ok_or_else(corrupt)? as u32;
+    let h = td_u16(bytes, 8).ok_or_else(corrupt)? as u32;
+    check_decoded_size(w, h, n)?;
+    let mut frames: Vec<Frame> = Vec::with_capacity(n);
+    // 0x40 帧按参考偏移找到之前解码的 0x80 帧
+    let mut lcw_frames: std::collections::HashMap<u32, usize> = std::collections::HashMap::new();
+    for i in 0..n {
+        let entry = td_u32(bytes, 14 + i * 8).ok_or_else(corrupt)?;
+        let ref_entry = td_u32(bytes, 18 + i * 8).ok_or_else(corrupt)?;
+        let (offset, format) = (entry & 0xFF_FFFF, (entry >> 24) as u8);
+        let src = bytes.get(offset as usize..).ok_or_else(corrupt)?;
+        let pixels = match format {
+            TD_LCW => {
+                let mut px = vec![0u8; (w * h) as usize];
+                decode_format80(src, &mut px);
+                lcw_frames.insert(offset, i);
+                px
+            }
+            TD_XOR_LCW | TD_XOR_PREV => {
+                let base = if format == TD_XOR_PREV { i.checked_sub(1) } else { lcw_frames.get(&(ref_entry & 0xFF_FFFF)).copied() };
+                let mut px = frames[base.ok_or_else(|| trf!("TD/RA1 SHP 第 {} 帧的参考帧无效", i))?].pixels.clone();
+                decode_format40(src, &mut px);
+                px
+            }
+            _ => return Err(trf!("TD/RA1 SHP 第 {} 帧格式未知: {}", i, format)),
+        };
+        let source = FrameSource { rect: (0, 0, w as u16, h as u16), flags: format as u32, data_offset: offset, td: true };
+        frames.push(Frame { source: Some(source), ..Frame::new(pixels) });
+    }
+    Ok(SHP { width: w, height: h, frames, transparent: 0, empty_frames: EmptyFrames::default() })
+}
+
+/// Format80（LCW）解压；源数据不足或越界时就地停止
+fn decode_format80(src: &[u8], dest: &mut [u8]) {
+    let (mut s, mut d) = (0usize, 0usize);
+    let byte = |s: &mut usize| { let v = src.get(*s).copied(); *s += 1; v };
+    let word = |s: &mut usize| { let v = td_u16(src, *s); *s += 2; v.map(|v| v as usize) };
+    // 从已解出的数据复制（允许重叠，逐字节）
+    let copy = |dest: &mut [u8], d: &mut usize, from: usize, count: usize| {
+        for k in 0..count {
+            let (Some(&v), true) = (dest.get(from + k), *d < dest.len()) else { return; };
+            dest[*d] = v;
+            *d += 1;
+        }
+    };
+    while d < dest.len() {
+        let Some(cmd) = byte(&mut s) else { return; };
+        if cmd & 0x80 == 0 {
+            // 0cccpppp pppppppp：从当前位置往回 p 处复制 c+3 字节
+            let Some(lo) = byte(&mut s) else { return; };
+            let count = ((cmd & 0x70) >> 4) as usize + 3;
+            let back = (((cmd & 0x0F) as usize) << 8) | lo as usize;
+            let Some(from) = d.checked_sub(back) else { return; };
+            copy(dest, &mut d, from, count);
+        } else if cmd & 0x40 == 0 {
+            // 10cccccc：原样复制 c 字节，c 为 0 表示结束
+            let count = (cmd & 0x3F) as usize;
+            if count == 0 { return; }
+            for _ in 0..count {
+                let Some(v) = byte(&mut s) else { return; };
+                if d >= dest.len() { return; }
+                dest[d] = v;
+                d += 1;
+            }
+        } else {
+            let count = (cmd & 0x3F) as usize;
+            let (count, from) = match count {
+                // 11111110 cccc vv：用 v 填充 c 字节
+                0x3E => {
+                    let (Some(count), Some(v)) = (word(&mut s), byte(&mut s)) else { return; };
+                    let end = (d + count).min(dest.len());
+                    dest[d..end].fill(v);
+                    d = end;
+                    continue;
+                }
+                // 11111111 cccc pppp：从绝对位置 p 复制 c 字节
+                0x3F => { let (Some(c), Some(p)) = (word(&mut s), word(&mut s)) else { return; }; (c, p) }
+                // 11cccccc pppp：从绝对位置 p 复制 c+3 字节
+                c => { let Some(p) = word(&mut s) else { return; }; (c + 3, p) }
+            };
+            copy(dest, &mut d, from, count);
+        }
+    }
+}
+
+/// Format40：把异或增量叠加到参考帧上
+fn decode_format40(src: &[u8], dest: &mut [u8]) {
+    let mut s = 0usize;
+    let mut d = 0usize;
+    let byte = |s: &mut usize| { let v = src.get(*s).copied(); *s += 1; v };
+    let mut xor = |d: &mut usize, v: u8| { if let Some(p) = dest.get_mut(*d) { *p ^= v; } *d += 1; };
+    loop {
+        let Some(cmd) = byte(&mut s) else { return; };
+        let count = (cmd & 0x7F) as usize;
+        if cmd & 0x80 == 0 {
+            if count == 0 {
+                // 00000000 c v：c 字节异或 v
+                let (Some(c), Some(v)) = (byte(&mut s), byte(&mut s)) else { return; };
+                for _ in 0..c { xor(&mut d, v); }
+            } else {
+                // 0ccccccc：随后 c 字节逐个异或
+                for _ in 0..count { let Some(v) = byte(&mut s) else { return; }; xor(&mut d, v); }
+            }
+        } else if count != 0 {
+            // 1ccccccc：跳过 c 字节
+            d += count;
+        } else {
+            let Some(word) = td_u16(src, s) else { return; };
+            s += 2;
+            let c = (word & 0x3FFF) as usize;
+            match word {
+                0 => return,
+                w if w & 0x8000 == 0 => d += (w & 0x7FFF) as usize,
+                w if w & 0x4000 == 0 => for _ in 0..c { let Some(v) = byte(&mut s) else { return; }; xor(&mut d, v); },
+                _ => { let Some(v) = byte(&mut s) else { return; }; for _ in 0..c { xor(&mut d, v); } }
+            }
         }
-        let img = egui::ColorImage::from_rgba_unmultiplied([self.width as usize, self.height as usize], &rgba);
-        ctx.load_texture("frame_tex", img, egui::TextureOptions::NEAREST)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 手工拼一个 TS 格式 SHP：画布 w×h，每帧 (x, y, fw, fh, flags, 数据)；数据紧跟帧头表依次存放
+    type RawFrame = (u16, u16, u16, u16, u32, Vec<u8>);
+
+    fn build(w: u16, h: u16, frames: &[RawFrame]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for v in [0, w, h, frames.len() as u16] { out.extend_from_slice(&v.to_le_bytes()); }
+        let mut off = 8 + 24 * frames.len() as u32;
+        for (x, y, fw, fh, flags, data) in frames {
+            for v in [*x, *y, *fw, *fh] { out.extend_from_slice(&v.to_le_bytes()); }
+            out.extend_from_slice(&flags.to_le_bytes());
+            out.extend_from_slice(&[0; 8]);
+            out.extend_from_slice(&(if data.is_empty() { 0 } else { off }).to_le_bytes());
+            off += data.len() as u32;
+        }
+        for (.., data) in frames { out.extend_from_slice(data); }
+        out
+    }
+
+    fn sample_shp() -> SHP {
+        let mut shp = SHP::new(40, 30, 3);
+        for (i, p) in shp.frames[0].pixels.iter_mut().enumerate() { if i % 3 == 0 { *p = (i % 200) as u8 + 1; } }
+        for p in &mut shp.frames[1].pixels[300..700] { *p = 7; }
+        shp.frames[1].compression = Compression::Rle0;
+        shp
+    }
+
+    fn load_err(bytes: &[u8]) -> String {
+        match SHP::load(bytes) { Ok(_) => panic!("应当读取失败"), Err(e) => e }
+    }
+
+    #[test]
+    fn roundtrip_both_encodings() {
+        let shp = sample_shp();
+        let back = SHP::load(&shp.save().unwrap()).unwrap();
+        assert_eq!((back.width, back.height, back.frames.len()), (40, 30, 3));
+        for (a, b) in shp.frames.iter().zip(&back.frames) { assert_eq!(a.pixels, b.pixels); }
+        assert!(back.frames[1].compression == Compression::Rle0);
+        assert!(back.frames[0].compression == Compression::Uncompressed);
+    }
+
+    #[test]
+    fn shared_duplicate_frames() {
+        let mut shp = sample_shp();
+        shp.frames.push(shp.frames[1].clone());
+        assert_eq!(shp.duplicate_groups(), vec![vec![1, 3]]);
+        let (plain, shared) = (shp.save().unwrap(), shp.save_with(true).unwrap());
+        assert!(shared.len() < plain.len());
+        let data_off = |b: &[u8], i: usize| u32::from_le_bytes(b[8 + 24 * i + 20..8 + 24 * i + 24].try_into().unwrap());
+        assert_eq!(data_off(&shared, 1), data_off(&shared, 3));
+        let back = SHP::load(&shared).unwrap();
+        for (a, b) in shp.frames.iter().zip(&back.frames) { assert_eq!(a.pixels, b.pixels); }
+    }
+
+    #[test]
+    fn empty_frame_modes() {
+        let mut shp = sample_shp();
+        // 第 2 帧为空：帧头 24 字节，前 8 字节是 x/y/w/h，20..24 是数据偏移
+        let head = 8 + 24 * 2;
+        let null = shp.save().unwrap();
+        assert_eq!(&null[head..head + 8], &[0; 8]);
+        assert_eq!(&null[head + 20..head + 24], &[0; 4]);
+        assert!(SHP::load(&null).unwrap().empty_frames == EmptyFrames::NullOffset);
+        shp.empty_frames = EmptyFrames::MinimalBlock;
+        let minimal = shp.save().unwrap();
+        assert_eq!(&minimal[head..head + 8], &[0, 0, 0, 0, 1, 0, 1, 0]);
+        let off = u32::from_le_bytes(minimal[head + 20..head + 24].try_into().unwrap()) as usize;
+        assert_eq!(minimal[off], 0);
+        // 1×1 块是最后写入的数据
+        assert_eq!(off + 1, minimal.len());
+        let back = SHP::load(&minimal).unwrap();
+        assert!(back.empty_frames == EmptyFrames::MinimalBlock);
+        assert!(back.frames[2].pixels.iter().all(|&p| p == 0));
+    }
+
+    #[test]
+    fn truncated_header_table() {
+        let mut bytes = build(4, 4, &[(0, 0, 2, 2, 0, vec![1; 4])]);
+        bytes.truncate(20);
+        assert!(load_err(&bytes).contains("1 帧需要 32 字节，文件只有 20 字节"));
+        assert!(SHP::load(&bytes[..5]).is_err());
+    }
+
+    #[test]
+    fn data_offset_past_eof() {
+        let mut bytes = build(4, 4, &[(0, 0, 2, 2, 0, vec![1; 4])]);
+        bytes[8 + 20..8 + 24].copy_from_slice(&1000u32.to_le_bytes());
+        let e = load_err(&bytes);
+        assert!(e.contains("第 0 帧") && e.contains("数据偏移 1000 超出文件长度 36"), "{e}");
+    }
+
+    #[test]
+    fn uncompressed_block_cut_short() {
+        let mut bytes = build(4, 4, &[(0, 0, 1, 1, 0, vec![1]), (0, 0, 4, 4, 0, vec![2; 16])]);
+        bytes.truncate(bytes.len() - 5);
+        let e = load_err(&bytes);
+        assert!(e.contains("第 1 帧") && e.contains("需要 16 字节，但剩余数据只有 11 字节"), "{e}");
+    }
+
+    #[test]
+    fn rle_row_longer_than_data() {
+        // 行长声明 50 字节，整行实际只有 5 字节
+        let bytes = build(4, 4, &[(0, 0, 4, 1, 3, vec![50, 0, 1, 2, 3])]);
+        let e = load_err(&bytes);
+        assert!(e.contains("第 0 行: 声明 50 字节，但剩余数据只有 5 字节"), "{e}");
+    }
+
+    #[test]
+    fn zero_length_rle_row() {
+        let bytes = build(4, 4, &[(0, 0, 4, 2, 3, vec![0, 0, 0, 0])]);
+        let e = load_err(&bytes);
+        assert!(e.contains("第 0 行: 行长 0 无效"), "{e}");
+        // 行长 1 也不合法（小于行长字段本身）
+        let bytes = build(4, 4, &[(0, 0, 4, 1, 3, vec![1, 0])]);
+        let e = load_err(&bytes);
+        assert!(e.contains("第 0 行: 行长 1 无效"), "{e}");
+    }
+
+    #[test]
+    fn rle_zero_run_missing_count() {
+        let bytes = build(4, 4, &[(0, 0, 4, 1, 3, vec![4, 0, 5, 0])]);
+        let e = load_err(&bytes);
+        assert!(e.contains("第 0 行: 行尾缺少透明像素个数"), "{e}");
+    }
+
+    #[test]
+    fn rle_runs_past_canvas_are_clipped() {
+        // 透明串远超帧宽后再写像素：越界部分丢弃而不是出错
+        let bytes = build(4, 2, &[(2, 1, 4, 1, 3, vec![6, 0, 9, 0, 200, 9])]);
+        let shp = SHP::load(&bytes).unwrap();
+        assert_eq!(shp.frames[0].pixels, vec![0, 0, 0, 0, 0, 0, 9, 0]);
+    }
+
+    #[test]
+    fn oversized_dimensions_are_rejected_before_allocating() {
+        let bytes = build(u16::MAX, u16::MAX, &[(0, 0, 1, 1, 0, vec![1])]);
+        assert!(SHP::load(&bytes).is_err());
+        assert!(LazyShp::open(bytes).is_err());
+        // 单帧在上限内但帧数过多
+        let frames: Vec<_> = (0..100).map(|_| (0, 0, 0, 0, 0, Vec::new())).collect();
+        let bytes = build(8000, 8000, &frames);
+        let e = load_err(&bytes);
+        assert!(e.contains("全部 100 帧解码后需要"), "{e}");
+    }
+
+    #[test]
+    fn lazy_frame_errors_name_the_frame() {
+        let bytes = build(4, 4, &[(0, 0, 1, 1, 0, vec![1]), (0, 0, 4, 1, 3, vec![9, 0])]);
+        let mut lazy = LazyShp::open(bytes).unwrap();
+        assert!(lazy.frame(0).is_ok());
+        let e = lazy.frame(1).err().unwrap();
+        assert!(e.starts_with("第 1 帧"), "{e}");
+    }
+
+    // 截断与逐字节扰动：任何输入都只能返回 Ok 或 Err，不能 panic
+    #[test]
+    fn mutated_inputs_never_panic() {
+        let valid = sample_shp().save().unwrap();
+        for len in 0..valid.len() { let _ = SHP::load(&valid[..len]); }
+        let mut state = 0x1234_5678u64;
+        for _ in 0..2000 {
+            let mut bytes = valid.clone();
+            for _ in 0..4 {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                let pos = (state >> 33) as usize % bytes.len();
+                bytes[pos] = (state >> 17) as u8;
+            }
+            let _ = SHP::load(&bytes);
+        }
+    }
 
+    #[test]
+    fn malformed_td_shp_never_panics() {
+        // 合法的 TD 文件头 + 一个 LCW 帧索引，随后数据截断或为垃圾
+        let mut bytes = Vec::new();
+        for v in [1u16, 0, 0, 8, 8, 0, 0] { bytes.extend_from_slice(&v.to_le_bytes()); }
+        let table_end = 14 + 3 * 8;
+        bytes.extend_from_slice(&((TD_LCW as u32) << 24 | table_end as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0; 20]);
+        assert!(is_td_shp(&bytes));
+        for tail in [vec![], vec![0x81], vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF], vec![0x0F, 0xFF, 0xFE, 0x10, 0x00]] {
+            let mut b = bytes.clone();
+            b.extend_from_slice(&tail);
+            let _ = SHP::load(&b);
+        }
+    }
+
+    #[test]
+    fn td_shp_keyframe_and_deltas() {
+        // 4×2，三帧：Format80 关键帧、相对关键帧的 Format40、相对上一帧的 Format40
+        let lcw: &[u8] = &[0x82, 1, 2, 0x00, 0x02, 0xFE, 3, 0, 9, 0x80];
+        let xor_key: &[u8] = &[0x01, 0x05, 0x82, 0x00, 0x02, 0xFF, 0x80, 0, 0];
+        let xor_prev: &[u8] = &[0x80, 0x06, 0x00, 0x01, 0x0F, 0x80, 0, 0];
+        let mut bytes = Vec::new();
+        for v in [3u16, 0, 0, 4, 2, 0, 0] { bytes.extend_from_slice(&v.to_le_bytes()); }
+        let key = (14 + 5 * 8) as u32;
+        let (d1, d2) = (key + lcw.len() as u32, key + (lcw.len() + xor_key.len()) as u32);
+        let end = d2 + xor_prev.len() as u32;
+        let table = [
+            ((TD_LCW as u32) << 24 | key, 0),
+            ((TD_XOR_LCW as u32) << 24 | d1, (TD_LCW as u32) << 24 | key),
+            ((TD_XOR_PREV as u32) << 24 | d2, 0),
+            (end, 0),
+            (0, 0),
+        ];
+        for (a, b) in table { bytes.extend_from_slice(&a.to_le_bytes()); bytes.extend_from_slice(&b.to_le_bytes()); }
+        for d in [lcw, xor_key, xor_prev] { bytes.extend_from_slice(d); }
+        assert!(is_td_shp(&bytes));
+        let shp = SHP::load(&bytes).unwrap();
+        assert_eq!((shp.width, shp.height, shp.frames.len()), (4, 2, 3));
+        // 原样 2 字节，往回 2 处复制 3 字节，再填充 3 个 9
+        assert_eq!(shp.frames[0].pixels, [1, 2, 1, 2, 1, 9, 9, 9]);
+        // 第 0 字节异或 5，跳过 2 字节，随后 2 字节异或 0xFF
+        assert_eq!(shp.frames[1].pixels, [4, 2, 1, 0xFD, 0xFE, 9, 9, 9]);
+        // 长跳过 6 字节，再把第 6 字节异或 0x0F
+        assert_eq!(shp.frames[2].pixels, [4, 2, 1, 0xFD, 0xFE, 9, 6, 9]);
+        let src = shp.frames[1].source.unwrap();
+        assert!(src.td && src.flags == TD_XOR_LCW as u32 && src.data_offset == d1);
+    }
+}
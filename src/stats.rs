@@ -0,0 +1,69 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+/// 单个文档的累计统计
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DocStats {
+    pub seconds: u64,
+    pub frames_edited: u64,
+    pub ops: u64,
+}
+
+/// 本地使用统计（仅在设置中开启后记录，保存在本机设置文件中，不会上传）
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UsageStats {
+    pub ops: BTreeMap<String, u64>,
+    pub documents: BTreeMap<String, DocStats>,
+}
+
+/// 当前会话中、当前文档的统计，切换文档或退出时并入 `UsageStats`
+pub struct SessionStats {
+    pub doc_key: String,
+    pub active_secs: f64,
+    pub ops: BTreeMap<&'static str, u64>,
+    pub frames_touched: BTreeSet<usize>,
+    last_tick: Instant,
+}
+
+impl SessionStats {
+    pub fn new(doc_key: String) -> Self {
+        Self { doc_key, active_secs: 0.0, ops: BTreeMap::new(), frames_touched: BTreeSet::new(), last_tick: Instant::now() }
+    }
+
+    /// 每次界面刷新调用：egui 空闲时不刷新，单次间隔超过 5 秒视为离开，不计入编辑时间
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        let dt = now.saturating_duration_since(self.last_tick).as_secs_f64();
+        self.last_tick = now;
+        if dt < 5.0 { self.active_secs += dt; }
+    }
+
+    pub fn record(&mut self, op: &'static str, frame: Option<usize>) {
+        *self.ops.entry(op).or_default() += 1;
+        if let Some(f) = frame { self.frames_touched.insert(f); }
+    }
+
+    pub fn total_ops(&self) -> u64 {
+        self.ops.values().sum()
+    }
+
+    /// 并入累计统计并清零（保留文档键）
+    pub fn flush_into(&mut self, stats: &mut UsageStats) {
+        for (op, n) in &self.ops { *stats.ops.entry((*op).to_owned()).or_default() += n; }
+        let doc = stats.documents.entry(self.doc_key.clone()).or_default();
+        doc.seconds += self.active_secs.round() as u64;
+        doc.frames_edited += self.frames_touched.len() as u64;
+        doc.ops += self.total_ops();
+        *self = SessionStats::new(std::mem::take(&mut self.doc_key));
+    }
+}
+
+/// 秒数格式化为 “1h 02m 03s”
+pub fn format_duration(secs: u64) -> String {
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 { format!("{}h {:02}m {:02}s", h, m, s) } else { format!("{}m {:02}s", m, s) }
+}
@@ -0,0 +1,97 @@
+use std::collections::{HashMap, VecDeque};
+
+use eframe::egui::{self, Color32, TextureHandle};
+
+use crate::palette::Palette;
+use crate::shp::SHP;
+
+/// 画布纹理缓存：保存上次上传的索引数据，下次仅对发生变化的包围矩形做转换与局部上传；
+/// 无变化时直接复用纹理。帧号、尺寸、调色板、亮度或透明索引变化时整帧重建
+pub struct FrameTexture {
+    tex: TextureHandle,
+    frame: usize,
+    width: u32,
+    height: u32,
+    pal: [Color32; 256],
+    brightness_bits: u32,
+    transparent: u8,
+    pixels: Vec<u8>,
+}
+
+impl FrameTexture {
+    pub fn get(cache: &mut Option<FrameTexture>, ctx: &egui::Context, shp: &SHP, frame: usize, pal: &Palette, brightness: f32) -> TextureHandle {
+        let frame = frame.min(shp.frames.len().saturating_sub(1));
+        let pixels = &shp.frames[frame].pixels;
+        if let Some(c) = cache.as_mut()
+            && c.frame == frame && c.width == shp.width && c.height == shp.height
+            && c.brightness_bits == brightness.to_bits() && c.pal == pal.colors && c.transparent == shp.transparent
+        {
+            if let Some((x0, y0, x1, y1)) = diff_rect(&c.pixels, pixels, shp.width as usize) {
+                let img = shp.region_image(frame, pal, brightness, x0, y0, x1 - x0 + 1, y1 - y0 + 1);
+                c.tex.set_partial([x0 as usize, y0 as usize], img, egui::TextureOptions::NEAREST);
+                c.pixels.copy_from_slice(pixels);
+            }
+            return c.tex.clone();
+        }
+        let tex = shp.egui_texture_with_brightness(ctx, frame, pal, brightness);
+        *cache = Some(FrameTexture {
+            tex: tex.clone(), frame, width: shp.width, height: shp.height,
+            pal: pal.colors, brightness_bits: brightness.to_bits(), transparent: shp.transparent, pixels: pixels.clone(),
+        });
+        tex
+    }
+}
+
+// 播放缓存的纹理总量上限（按 RGBA 字节估算）
+const PLAYBACK_CACHE_BYTES: usize = 256 * 1024 * 1024;
+
+/// 播放用的逐帧纹理缓存：按帧内容摘要索引，循环播放时每帧只在第一次显示时转换上传，
+/// 之后直接复用（内容相同的帧共用一张）；播放中改动过的帧摘要不同，自然重新生成。
+/// 尺寸、调色板、亮度或透明索引变化时全部作废；超出上限时先淘汰最早生成的纹理
+#[derive(Default)]
+pub struct PlaybackTextures {
+    key: Option<(u32, u32, [Color32; 256], u32, u8)>,
+    textures: HashMap<u64, TextureHandle>,
+    order: VecDeque<u64>,
+}
+
+impl PlaybackTextures {
+    pub fn get(&mut self, ctx: &egui::Context, shp: &SHP, frame: usize, pal: &Palette, brightness: f32) -> TextureHandle {
+        let frame = frame.min(shp.frames.len().saturating_sub(1));
+        let key = (shp.width, shp.height, pal.colors, brightness.to_bits(), shp.transparent);
+        if self.key != Some(key) {
+            self.clear();
+            self.key = Some(key);
+        }
+        let hash = shp.frames[frame].content_hash();
+        if let Some(tex) = self.textures.get(&hash) { return tex.clone(); }
+        let tex = shp.egui_texture_with_brightness(ctx, frame, pal, brightness);
+        self.textures.insert(hash, tex.clone());
+        self.order.push_back(hash);
+        let max = (PLAYBACK_CACHE_BYTES / (shp.width as usize * shp.height as usize * 4).max(1)).max(2);
+        while self.order.len() > max {
+            if let Some(old) = self.order.pop_front() { self.textures.remove(&old); }
+        }
+        tex
+    }
+
+    pub fn clear(&mut self) {
+        self.key = None;
+        self.textures.clear();
+        self.order.clear();
+    }
+}
+
+/// 两份索引缓冲的差异包围矩形 (x0, y0, x1, y1)，闭区间；完全相同返回 None
+fn diff_rect(old: &[u8], new: &[u8], width: usize) -> Option<(u32, u32, u32, u32)> {
+    if old == new || width == 0 { return None; }
+    let (mut x0, mut y0, mut x1, mut y1) = (usize::MAX, usize::MAX, 0usize, 0usize);
+    for (y, (a, b)) in old.chunks_exact(width).zip(new.chunks_exact(width)).enumerate() {
+        if a == b { continue; }
+        let first = a.iter().zip(b).position(|(p, q)| p != q).unwrap_or(0);
+        let last = width - 1 - a.iter().rev().zip(b.iter().rev()).position(|(p, q)| p != q).unwrap_or(0);
+        y0 = y0.min(y); y1 = y;
+        x0 = x0.min(first); x1 = x1.max(last);
+    }
+    if y0 == usize::MAX { None } else { Some((x0 as u32, y0 as u32, x1 as u32, y1 as u32)) }
+}
@@ -0,0 +1,307 @@
+use crate::palette::Palette;
+use crate::shp::SHP;
+
+// 撤销栈的内存上限：超出时丢弃最早的记录
+const MAX_HISTORY_BYTES: usize = 256 * 1024 * 1024;
+// 相隔不超过这么多字节的差异区段合并为一段，减少区段数量
+const DELTA_MERGE_GAP: usize = 8;
+// 每个区段的额外开销（起点与 Vec 头），用于估算增量是否划算
+const RUN_OVERHEAD: usize = std::mem::size_of::<(usize, Vec<u8>)>();
+// 每隔这么多条像素记录保留一条整帧快照不压缩，撤销链再长也能借它整帧对齐
+const FULL_SNAPSHOT_EVERY: usize = 32;
+
+/// 一帧操作前的像素。刚记录时是整帧快照；下一次记录（或撤销）时与当时的帧内容比较，
+/// 压缩为只含差异区段的增量。差异过大时仍保留整帧
+pub enum FramePixels {
+    Full(Vec<u8>),
+    /// (起点, 该区段的字节)
+    Delta(Vec<(usize, Vec<u8>)>),
+}
+
+impl FramePixels {
+    // 相对 `current` 的增量；差异区段合计超过整帧一半时不划算，返回 None
+    fn delta_against(full: &[u8], current: &[u8]) -> Option<FramePixels> {
+        if full.len() != current.len() { return None; }
+        let mut runs: Vec<(usize, usize)> = Vec::new();
+        let mut i = 0;
+        while i < full.len() {
+            if full[i] == current[i] { i += 1; continue; }
+            let start = i;
+            while i < full.len() && full[i] != current[i] { i += 1; }
+            match runs.last_mut() {
+                Some(last) if start - last.1 <= DELTA_MERGE_GAP => last.1 = i,
+                _ => runs.push((start, i)),
+            }
+        }
+        let cost: usize = runs.iter().map(|(a, b)| b - a + RUN_OVERHEAD).sum();
+        (cost < full.len() / 2).then(|| FramePixels::Delta(runs.into_iter().map(|(a, b)| (a, full[a..b].to_vec())).collect()))
+    }
+
+    fn bytes(&self) -> usize {
+        match self {
+            FramePixels::Full(px) => px.len(),
+            FramePixels::Delta(runs) => runs.iter().map(|(_, b)| b.len() + RUN_OVERHEAD).sum(),
+        }
+    }
+}
+
+/// 撤销补丁：保存操作前的状态片段。应用时与当前状态互换，换出的内容即反向补丁
+pub enum Patch {
+    /// 若干帧操作前的像素（绘制、替换颜色等）
+    Pixels(Vec<(usize, FramePixels)>),
+    /// 若干帧操作前的对齐偏移
+    Offsets(Vec<(usize, (i32, i32))>),
+    /// 调色板及其名称
+    Palette(Box<Palette>, String),
+    /// 整份文档：帧增删/重排、画布尺寸等结构变化；需要时连同调色板一起
+    Document(Box<SHP>, Option<(Box<Palette>, String)>),
+}
+
+impl Patch {
+    fn swap(self, shp: &mut Option<SHP>, palette: &mut Palette, pal_name: &mut String) -> Patch {
+        match (self, shp.as_mut()) {
+            (Patch::Pixels(frames), Some(doc)) => {
+                let n = doc.frames.len();
+                Patch::Pixels(frames.into_iter().filter(|(i, _)| *i < n).map(|(i, px)| {
+                    let cur = &mut doc.frames[i].pixels;
+                    let px = match px {
+                        FramePixels::Full(px) => FramePixels::Full(std::mem::replace(cur, px)),
+                        // 区段内容与当前帧互换，换出的区段即反向增量
+                        FramePixels::Delta(mut runs) => {
+                            for (start, bytes) in &mut runs {
+                                if let Some(dst) = cur.get_mut(*start..*start + bytes.len()) { dst.swap_with_slice(bytes); }
+                            }
+                            FramePixels::Delta(runs)
+                        }
+                    };
+                    (i, px)
+                }).collect())
+            }
+            (Patch::Offsets(frames), Some(doc)) => {
+                let n = doc.frames.len();
+                Patch::Offsets(frames.into_iter().filter(|(i, _)| *i < n)
+                    .map(|(i, off)| (i, std::mem::replace(&mut doc.frames[i].offset, off))).collect())
+            }
+            (Patch::Palette(pal, name), _) => {
+                Patch::Palette(Box::new(std::mem::replace(palette, *pal)), std::mem::replace(pal_name, name))
+            }
+            (Patch::Document(old, pal), Some(doc)) => {
+                let pal = pal.map(|(p, name)| (Box::new(std::mem::replace(palette, *p)), std::mem::replace(pal_name, name)));
+                Patch::Document(Box::new(std::mem::replace(doc, *old)), pal)
+            }
+            // 没有文档时帧相关补丁无从应用，原样保留
+            (p, None) => p,
+        }
+    }
+
+    /// 把整帧快照压缩为增量。`after(i)` 须给出该操作完成后第 i 帧的像素，
+    /// 之后撤销时文档回到的正是这一状态
+    fn compact<'a>(&mut self, after: impl Fn(usize) -> Option<&'a [u8]>) {
+        let Patch::Pixels(frames) = self else { return; };
+        for (i, px) in frames.iter_mut() {
+            let (FramePixels::Full(full), Some(cur)) = (&*px, after(*i)) else { continue; };
+            if let Some(delta) = FramePixels::delta_against(full, cur) { *px = delta; }
+        }
+    }
+
+    // 下一步操作前第 i 帧的像素：新补丁里记录了该帧时以它为准（画布操作常在改动开始后才记录），
+    // 否则取当前文档
+    fn before_frame<'a>(&'a self, shp: Option<&'a SHP>, i: usize) -> Option<&'a [u8]> {
+        match self {
+            Patch::Document(doc, _) => doc.frames.get(i).map(|f| f.pixels.as_slice()),
+            Patch::Pixels(frames) => match frames.iter().find(|(j, _)| *j == i) {
+                Some((_, FramePixels::Full(px))) => Some(px),
+                Some(_) => None,
+                None => shp?.frames.get(i).map(|f| f.pixels.as_slice()),
+            },
+            _ => shp?.frames.get(i).map(|f| f.pixels.as_slice()),
+        }
+    }
+
+    fn bytes(&self) -> usize {
+        match self {
+            Patch::Pixels(frames) => frames.iter().map(|(_, px)| px.bytes()).sum(),
+            Patch::Offsets(frames) => frames.len() * std::mem::size_of::<(usize, (i32, i32))>(),
+            Patch::Palette(..) => std::mem::size_of::<Palette>(),
+            Patch::Document(doc, pal) => {
+                doc.frames.iter().map(|f| f.pixels.len()).sum::<usize>() + pal.as_ref().map_or(0, |_| std::mem::size_of::<Palette>())
+            }
+        }
+    }
+}
+
+/// 撤销/重做栈；每条记录附带记录时的当前帧，撤销后跳回该帧
+#[derive(Default)]
+pub struct History {
+    undo: Vec<(usize, Patch)>,
+    redo: Vec<(usize, Patch)>,
+    // 上次保留整帧快照以来压缩过的像素记录数
+    since_full: usize,
+}
+
+impl History {
+    /// 记录一步。`shp` 为当前文档：上一条记录此时已完成，借机把它压缩为增量
+    pub fn push(&mut self, frame: usize, patch: Patch, max: usize, shp: Option<&SHP>) {
+        if let Some((_, last @ Patch::Pixels(_))) = self.undo.last_mut() {
+            if self.since_full + 1 >= FULL_SNAPSHOT_EVERY {
+                self.since_full = 0;
+            } else {
+                last.compact(|i| patch.before_frame(shp, i));
+                self.since_full += 1;
+            }
+        }
+        self.undo.push((frame, patch));
+        if self.undo.len() > max { self.undo.remove(0); }
+        // 按内存上限再丢弃最早的记录（至少保留最新一条）
+        let mut total: usize = self.undo.iter().map(|(_, p)| p.bytes()).sum();
+        while total > MAX_HISTORY_BYTES && self.undo.len() > 1 {
+            total -= self.undo.remove(0).1.bytes();
+        }
+        self.redo.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.since_full = 0;
+    }
+
+    pub fn can_undo(&self) -> bool { !self.undo.is_empty() }
+    pub fn can_redo(&self) -> bool { !self.redo.is_empty() }
+
+    /// 撤销一步，返回该步所属的帧
+    pub fn undo(&mut self, shp: &mut Option<SHP>, palette: &mut Palette, pal_name: &mut String) -> Option<usize> {
+        let (frame, mut patch) = self.undo.pop()?;
+        // 最新一条尚未压缩：此时文档正是操作后的状态，压缩后撤销与重做两边都只存增量
+        let doc = shp.as_ref();
+        patch.compact(|i| doc?.frames.get(i).map(|f| f.pixels.as_slice()));
+        self.redo.push((frame, patch.swap(shp, palette, pal_name)));
+        Some(frame)
+    }
+
+    /// 重做一步，返回该步所属的帧
+    pub fn redo(&mut self, shp: &mut Option<SHP>, palette: &mut Palette, pal_name: &mut String) -> Option<usize> {
+        let (frame, patch) = self.redo.pop()?;
+        self.undo.push((frame, patch.swap(shp, palette, pal_name)));
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc() -> Option<SHP> { Some(SHP::new(16, 16, 2)) }
+
+    fn px(shp: &Option<SHP>, i: usize) -> Vec<u8> { shp.as_ref().unwrap().frames[i].pixels.clone() }
+
+    fn set(shp: &mut Option<SHP>, i: usize, at: usize, v: u8) { shp.as_mut().unwrap().frames[i].pixels[at] = v; }
+
+    fn snapshot(shp: &Option<SHP>, i: usize) -> Patch { Patch::Pixels(vec![(i, FramePixels::Full(px(shp, i)))]) }
+
+    fn is_delta(p: &Patch) -> bool { matches!(p, Patch::Pixels(f) if matches!(f[0].1, FramePixels::Delta(_))) }
+
+    #[test]
+    fn undo_redo_restore_every_byte() {
+        let (mut shp, mut pal, mut name) = (doc(), Palette::default_grayscale(), String::new());
+        let mut h = History::default();
+        let s0 = px(&shp, 0);
+        h.push(0, snapshot(&shp, 0), 100, shp.as_ref());
+        for at in 10..20 { set(&mut shp, 0, at, 3); }
+        let s1 = px(&shp, 0);
+        h.push(0, snapshot(&shp, 0), 100, shp.as_ref());
+        set(&mut shp, 0, 200, 7);
+        let s2 = px(&shp, 0);
+        assert!(is_delta(&h.undo[0].1));
+        h.undo(&mut shp, &mut pal, &mut name);
+        assert_eq!(px(&shp, 0), s1);
+        h.undo(&mut shp, &mut pal, &mut name);
+        assert_eq!(px(&shp, 0), s0);
+        assert!(h.redo.iter().all(|(_, p)| is_delta(p)));
+        h.redo(&mut shp, &mut pal, &mut name);
+        assert_eq!(px(&shp, 0), s1);
+        h.redo(&mut shp, &mut pal, &mut name);
+        assert_eq!(px(&shp, 0), s2);
+        assert!(!h.can_redo());
+    }
+
+    #[test]
+    fn snapshot_recorded_after_stroke_started() {
+        let (mut shp, mut pal, mut name) = (doc(), Palette::default_grayscale(), String::new());
+        let mut h = History::default();
+        let s0 = px(&shp, 0);
+        h.push(0, snapshot(&shp, 0), 100, shp.as_ref());
+        set(&mut shp, 0, 0, 1);
+        let s1 = px(&shp, 0);
+        // 笔画先改了帧再记录：第 0 个像素又被擦回 0，文档已不是上一步完成时的样子
+        let stroke = snapshot(&shp, 0);
+        set(&mut shp, 0, 0, 0);
+        set(&mut shp, 0, 5, 2);
+        h.push(0, stroke, 100, shp.as_ref());
+        h.undo(&mut shp, &mut pal, &mut name);
+        assert_eq!(px(&shp, 0), s1);
+        h.undo(&mut shp, &mut pal, &mut name);
+        assert_eq!(px(&shp, 0), s0);
+    }
+
+    #[test]
+    fn document_and_palette_patches_between_pixels() {
+        let (mut shp, mut pal, mut name) = (doc(), Palette::default_grayscale(), "a".to_string());
+        let mut h = History::default();
+        let s0 = px(&shp, 0);
+        h.push(0, snapshot(&shp, 0), 100, shp.as_ref());
+        set(&mut shp, 0, 3, 4);
+        let s1 = px(&shp, 0);
+        let pal0 = pal.to_bytes();
+        h.push(0, Patch::Palette(Box::new(pal.clone()), name.clone()), 100, shp.as_ref());
+        pal.colors[1] = eframe::egui::Color32::RED;
+        name = "b".into();
+        let pal1 = pal.to_bytes();
+        h.push(0, snapshot(&shp, 0), 100, shp.as_ref());
+        set(&mut shp, 0, 40, 9);
+        let s2 = px(&shp, 0);
+        h.push(1, Patch::Document(Box::new(shp.clone().unwrap()), None), 100, shp.as_ref());
+        let d = shp.as_mut().unwrap();
+        d.frames.push(d.frames[1].clone());
+        d.frames[0].pixels.fill(6);
+        let s3 = px(&shp, 0);
+
+        h.undo(&mut shp, &mut pal, &mut name);
+        assert_eq!((px(&shp, 0), shp.as_ref().unwrap().frames.len()), (s2.clone(), 2));
+        h.undo(&mut shp, &mut pal, &mut name);
+        assert_eq!(px(&shp, 0), s1);
+        h.undo(&mut shp, &mut pal, &mut name);
+        assert_eq!((pal.to_bytes(), name.as_str()), (pal0, "a"));
+        h.undo(&mut shp, &mut pal, &mut name);
+        assert_eq!(px(&shp, 0), s0);
+        for _ in 0..4 { h.redo(&mut shp, &mut pal, &mut name); }
+        assert_eq!((px(&shp, 0), shp.as_ref().unwrap().frames.len()), (s3, 3));
+        assert_eq!((pal.to_bytes(), name.as_str()), (pal1, "b"));
+    }
+
+    #[test]
+    fn full_snapshot_kept_periodically() {
+        let mut shp = doc();
+        let mut h = History::default();
+        for k in 0..=FULL_SNAPSHOT_EVERY * 2 {
+            h.push(0, snapshot(&shp, 0), 1000, shp.as_ref());
+            set(&mut shp, 0, k, 1);
+        }
+        let full = h.undo.iter().filter(|(_, p)| !is_delta(p)).count();
+        // 每 FULL_SNAPSHOT_EVERY 条留一条整帧，另加尚未压缩的最新一条
+        assert_eq!(full, 3);
+        let (mut pal, mut name) = (Palette::default_grayscale(), String::new());
+        while h.undo(&mut shp, &mut pal, &mut name).is_some() {}
+        assert!(px(&shp, 0).iter().all(|&p| p == 0));
+    }
+
+    #[test]
+    fn memory_limit_keeps_newest() {
+        // 超过上限一半的整帧：每次记录后只能留下最新一条（零填充的大 Vec 不实际占用内存）
+        let big = |i| Patch::Pixels(vec![(i, FramePixels::Full(vec![0; MAX_HISTORY_BYTES / 2 + 1]))]);
+        let mut h = History::default();
+        for i in 0..3 { h.push(i, big(i), 100, None); }
+        assert_eq!(h.undo.len(), 1);
+        assert_eq!(h.undo[0].0, 2);
+    }
+}
@@ -0,0 +1,61 @@
+use std::sync::mpsc::{self, Receiver};
+
+use serde::Deserialize;
+
+const RELEASES_API: &str = "https://api.github.com/repos/ahasasjeb/lvSHP/releases/latest";
+pub const RELEASES_PAGE: &str = "https://github.com/ahasasjeb/lvSHP/releases";
+
+/// GitHub 最新发布信息（只取需要的字段）
+#[derive(Clone, Deserialize)]
+pub struct ReleaseInfo {
+    pub tag_name: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    pub html_url: String,
+    /// 发布时间（ISO 8601）
+    #[serde(default)]
+    pub published_at: Option<String>,
+}
+
+impl ReleaseInfo {
+    /// 发布版本是否比当前运行的版本新
+    pub fn is_newer(&self) -> bool {
+        parse_version(&self.tag_name) > parse_version(current_version())
+    }
+}
+
+pub fn current_version() -> &'static str {
+    env!("CARGO_PKG_VERSION")
+}
+
+// "v1.2.3" / "1.2" → [1, 2, 3] / [1, 2, 0]；无法解析的部分按 0 处理
+fn parse_version(s: &str) -> [u32; 3] {
+    let mut out = [0u32; 3];
+    let core = s.trim().trim_start_matches(['v', 'V']).split(['-', '+']).next().unwrap_or("");
+    for (slot, part) in out.iter_mut().zip(core.split('.')) {
+        *slot = part.trim().parse().unwrap_or(0);
+    }
+    out
+}
+
+/// 在后台线程查询最新发布，结果通过通道返回，避免网络请求阻塞界面
+pub fn spawn_check() -> Receiver<Result<ReleaseInfo, String>> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(fetch_latest());
+    });
+    rx
+}
+
+fn fetch_latest() -> Result<ReleaseInfo, String> {
+    let resp = ureq::get(RELEASES_API)
+        .set("Accept", "application/vnd.github+json")
+        .set("User-Agent", concat!("lvSHP/", env!("CARGO_PKG_VERSION")))
+        .timeout(std::time::Duration::from_secs(10))
+        .call()
+        .map_err(|e| e.to_string())?;
+    let text = resp.into_string().map_err(|e| e.to_string())?;
+    serde_json::from_str(&text).map_err(|e| e.to_string())
+}
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+use notify::{EventKind, RecursiveMode, Watcher};
+
+// 外部工具往往分几次写完文件：最后一次变动后静默这么久才算写完
+const SETTLE: Duration = Duration::from_millis(300);
+
+/// 监视打开的文件被外部程序修改。监视所在目录而非文件本身，
+/// 这样“写临时文件再改名替换”的保存方式也能捕获
+pub struct FileWatch {
+    _watcher: notify::RecommendedWatcher,
+    rx: Receiver<PathBuf>,
+    /// 被监视的文件 → 最近一次已知内容的哈希（载入或本程序保存时记录）
+    known: HashMap<PathBuf, u64>,
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl FileWatch {
+    pub fn new(paths: &[PathBuf], ctx: &egui::Context) -> Result<Self, String> {
+        let (tx, rx) = channel();
+        let ctx = ctx.clone();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let Ok(ev) = res else { return; };
+            if !matches!(ev.kind, EventKind::Create(_) | EventKind::Modify(_)) { return; }
+            for p in ev.paths { let _ = tx.send(p); }
+            ctx.request_repaint();
+        }).map_err(|e| e.to_string())?;
+        let mut known = HashMap::new();
+        for p in paths {
+            let p = normalize(p);
+            if let Some(dir) = p.parent() { watcher.watch(dir, RecursiveMode::NonRecursive).map_err(|e| e.to_string())?; }
+            let hash = std::fs::read(&p).map(|b| content_hash(&b)).unwrap_or(0);
+            known.insert(p, hash);
+        }
+        Ok(Self { _watcher: watcher, rx, known, pending: HashMap::new() })
+    }
+
+    /// 是否正好监视这些文件（用于判断是否需要重建）
+    pub fn watches(&self, paths: &[PathBuf]) -> bool {
+        paths.len() == self.known.len() && paths.iter().all(|p| self.known.contains_key(&normalize(p)))
+    }
+
+    /// 本程序写入了该文件：记下新内容，避免把自己的保存当作外部修改
+    pub fn mark(&mut self, path: &Path, bytes: &[u8]) {
+        if let Some(h) = self.known.get_mut(&normalize(path)) { *h = content_hash(bytes); }
+    }
+
+    /// 取出已写完且内容确实变化的文件及其新内容；仍有文件在等待静默时返回 `true` 于第二项
+    pub fn poll(&mut self) -> (Vec<(PathBuf, Vec<u8>)>, bool) {
+        let now = Instant::now();
+        while let Ok(p) = self.rx.try_recv() {
+            let p = normalize(&p);
+            if self.known.contains_key(&p) { self.pending.insert(p, now); }
+        }
+        let ready: Vec<PathBuf> = self.pending.iter().filter(|(_, t)| now.duration_since(**t) >= SETTLE).map(|(p, _)| p.clone()).collect();
+        let mut changed = Vec::new();
+        for p in ready {
+            self.pending.remove(&p);
+            let Ok(bytes) = std::fs::read(&p) else { continue; };
+            let h = content_hash(&bytes);
+            if self.known.insert(p.clone(), h) != Some(h) { changed.push((p, bytes)); }
+        }
+        (changed, !self.pending.is_empty())
+    }
+}
+
+// 事件里的路径是监视目录下的绝对路径：统一为规范化的目录 + 文件名再比较
+fn normalize(p: &Path) -> PathBuf {
+    let dir = p.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    dir.join(p.file_name().unwrap_or_default())
+}
+
+fn content_hash(bytes: &[u8]) -> u64 {
+    let mut h = std::collections::hash_map::DefaultHasher::new();
+    bytes.hash(&mut h);
+    h.finish()
+}